@@ -0,0 +1,415 @@
+//! Routing of opportunity alerts to notification channels
+//!
+//! `ALERT_RULES` is a `;`-separated list of `conditions->channels` rules,
+//! checked in order; the first whose conditions all hold decides where the
+//! alert goes. Conditions are joined with `&`: `strategy=basis`,
+//! `pair=WETH/USDC`, `instance=main`, `severity=high` (at least that
+//! severity), `min_roi_bps=20` and `min_profit=5`; `*` matches everything.
+//! Channels are `log`, `telegram` (`TELEGRAM_BOT_TOKEN` + `TELEGRAM_CHAT_ID`)
+//! and `webhook` (`ALERT_WEBHOOK_URL`), comma-separated. Severity is `low`
+//! below the first `ALERT_SEVERITY_PROFITS` threshold, `medium` below the
+//! second and `high` above. Alerts are queued and sent in the background.
+//! Operational notices (e.g. the database becoming unavailable) go to every
+//! channel used by any rule.
+//!
+//! Telegram is rate limited per route (strategy, instance, pair and DEX
+//! pair): a gap that persists over consecutive cycles alerts once per
+//! `TELEGRAM_COOLDOWN_SECS`, and the next message counts the alerts held back.
+
+use crate::api::ApiOpportunity;
+use crate::db::Opportunity;
+use crate::share::ShareLinks;
+use anyhow::Context;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Alerts queued before new ones are dropped.
+const QUEUE: usize = 1_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+impl Severity {
+    fn parse(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "low" => Ok(Severity::Low),
+            "medium" => Ok(Severity::Medium),
+            "high" => Ok(Severity::High),
+            other => anyhow::bail!("unknown severity {:?} (low, medium, high)", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Channel {
+    Log,
+    Telegram,
+    Webhook,
+}
+
+#[derive(Debug)]
+enum Condition {
+    Strategy(String),
+    Pair(String),
+    Instance(String),
+    Severity(Severity),
+    MinRoiBps(f64),
+    MinProfit(f64),
+}
+
+impl Condition {
+    fn parse(s: &str) -> anyhow::Result<Self> {
+        let (key, value) = s
+            .split_once('=')
+            .with_context(|| format!("alert condition {:?} is not key=value", s))?;
+        let value = value.trim();
+        Ok(match key.trim() {
+            "strategy" => Condition::Strategy(value.to_string()),
+            "pair" => Condition::Pair(value.to_string()),
+            "instance" => Condition::Instance(value.to_string()),
+            "severity" => Condition::Severity(Severity::parse(value)?),
+            "min_roi_bps" => Condition::MinRoiBps(value.parse()?),
+            "min_profit" => Condition::MinProfit(value.parse()?),
+            other => anyhow::bail!("unknown alert condition {:?}", other),
+        })
+    }
+
+    fn matches(&self, opp: &Opportunity, severity: Severity) -> bool {
+        match self {
+            Condition::Strategy(s) => opp.strategy == *s,
+            Condition::Pair(p) => opp.pair.as_deref() == Some(p.as_str()),
+            Condition::Instance(i) => opp.instance == *i,
+            Condition::Severity(min) => severity >= *min,
+            Condition::MinRoiBps(min) => opp.roi_bps.is_some_and(|roi| roi >= *min),
+            Condition::MinProfit(min) => opp.profit >= *min,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Rule {
+    conditions: Vec<Condition>,
+    channels: Vec<Channel>,
+}
+
+/// Parses `ALERT_RULES`.
+fn parse_rules(spec: &str) -> anyhow::Result<Vec<Rule>> {
+    spec.split(';')
+        .map(str::trim)
+        .filter(|rule| !rule.is_empty())
+        .map(|rule| {
+            let (conditions, channels) = rule
+                .split_once("->")
+                .with_context(|| format!("alert rule {:?} is not conditions->channels", rule))?;
+            let conditions = match conditions.trim() {
+                "*" => Vec::new(),
+                conditions => conditions
+                    .split('&')
+                    .map(Condition::parse)
+                    .collect::<anyhow::Result<_>>()?,
+            };
+            let channels = channels
+                .split(',')
+                .map(|c| match c.trim() {
+                    "log" => Ok(Channel::Log),
+                    "telegram" => Ok(Channel::Telegram),
+                    "webhook" => Ok(Channel::Webhook),
+                    other => anyhow::bail!("unknown alert channel {:?}", other),
+                })
+                .collect::<anyhow::Result<_>>()?;
+            Ok(Rule {
+                conditions,
+                channels,
+            })
+        })
+        .collect()
+}
+
+pub struct Telegram {
+    pub bot_token: String,
+    pub chat_id: String,
+    /// Minimum gap between two alerts for the same route.
+    pub cooldown: Duration,
+}
+
+/// Per-route rate limit of opportunity alerts.
+struct Cooldown {
+    period: Duration,
+    /// Last alert sent per route, and how many were held back since.
+    routes: HashMap<String, (Instant, u32)>,
+}
+
+impl Cooldown {
+    fn new(period: Duration) -> Self {
+        Self {
+            period,
+            routes: HashMap::new(),
+        }
+    }
+
+    /// `Some(held back)` when an alert for `opp`'s route may go out now.
+    fn admit(&mut self, opp: &Opportunity, now: Instant) -> Option<u32> {
+        let route = format!(
+            "{}|{}|{}|{}|{}",
+            opp.strategy,
+            opp.instance,
+            opp.pair.as_deref().unwrap_or_default(),
+            opp.dex_buy,
+            opp.dex_sell
+        );
+        // Routes are the configured DEX pairs, so the map stays small.
+        match self.routes.get_mut(&route) {
+            Some((sent, held_back)) if now.duration_since(*sent) < self.period => {
+                *held_back += 1;
+                None
+            }
+            Some((sent, held_back)) => {
+                *sent = now;
+                Some(std::mem::take(held_back))
+            }
+            None => {
+                self.routes.insert(route, (now, 0));
+                Some(0)
+            }
+        }
+    }
+}
+
+/// Everything needed to build an [`AlertRouter`].
+pub struct AlertConfig<'a> {
+    pub rules: &'a str,
+    /// Profits separating low from medium and medium from high severity.
+    pub severity_profits: (f64, f64),
+    pub telegram: Option<Telegram>,
+    pub webhook_url: Option<String>,
+}
+
+#[derive(Serialize)]
+struct WebhookAlert<'a> {
+    severity: Severity,
+    permalink: &'a str,
+    opportunity: ApiOpportunity,
+}
+
+#[derive(Serialize)]
+struct WebhookNotice<'a> {
+    severity: Severity,
+    message: &'a str,
+}
+
+enum Alert {
+    Opportunity(Severity, Box<Opportunity>),
+    Notice(Severity, String),
+}
+
+pub struct AlertRouter {
+    rules: Vec<Rule>,
+    severity_profits: (f64, f64),
+    links: Arc<ShareLinks>,
+    queue: mpsc::Sender<(Vec<Channel>, Alert)>,
+}
+
+impl AlertRouter {
+    /// Starts the background sender; must be called inside the Tokio runtime.
+    pub fn spawn(cfg: AlertConfig, links: Arc<ShareLinks>) -> anyhow::Result<Self> {
+        let rules = parse_rules(cfg.rules)?;
+        for channel in rules.iter().flat_map(|r| &r.channels) {
+            match channel {
+                Channel::Telegram if cfg.telegram.is_none() => {
+                    anyhow::bail!(
+                        "alert rules use telegram without TELEGRAM_BOT_TOKEN/TELEGRAM_CHAT_ID"
+                    )
+                }
+                Channel::Webhook if cfg.webhook_url.is_none() => {
+                    anyhow::bail!("alert rules use webhook without ALERT_WEBHOOK_URL")
+                }
+                _ => {}
+            }
+        }
+
+        let (tx, mut rx) = mpsc::channel::<(Vec<Channel>, Alert)>(QUEUE);
+        let (telegram, webhook_url) = (cfg.telegram, cfg.webhook_url);
+        let sender_links = Arc::clone(&links);
+        let mut cooldown = Cooldown::new(telegram.as_ref().map_or(Duration::ZERO, |t| t.cooldown));
+        tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            while let Some((channels, alert)) = rx.recv().await {
+                let (text, body, subject) = match &alert {
+                    Alert::Opportunity(severity, opp) => {
+                        let permalink = sender_links.permalink(opp.id);
+                        let body = serde_json::to_value(WebhookAlert {
+                            severity: *severity,
+                            permalink: &permalink,
+                            opportunity: opp.as_ref().into(),
+                        });
+                        (
+                            message(opp, *severity, &permalink),
+                            body,
+                            format!("opportunity {}", opp.id),
+                        )
+                    }
+                    Alert::Notice(severity, text) => {
+                        let body = serde_json::to_value(WebhookNotice {
+                            severity: *severity,
+                            message: text,
+                        });
+                        (text.clone(), body, "notice".to_string())
+                    }
+                };
+                for channel in channels {
+                    let sent = match channel {
+                        Channel::Log => {
+                            log::info!("[alert] {}", text);
+                            Ok(())
+                        }
+                        Channel::Telegram => {
+                            let telegram = telegram.as_ref().expect("checked at startup");
+                            match &alert {
+                                Alert::Opportunity(_, opp) => {
+                                    match cooldown.admit(opp, Instant::now()) {
+                                        Some(0) => send_telegram(&client, telegram, &text).await,
+                                        Some(held_back) => {
+                                            let text = format!(
+                                                "{} (+{} similar since the last alert)",
+                                                text, held_back
+                                            );
+                                            send_telegram(&client, telegram, &text).await
+                                        }
+                                        None => {
+                                            log::debug!("Telegram cooldown holds back {}", subject);
+                                            Ok(())
+                                        }
+                                    }
+                                }
+                                Alert::Notice(..) => send_telegram(&client, telegram, &text).await,
+                            }
+                        }
+                        Channel::Webhook => {
+                            let url = webhook_url.as_deref().expect("checked at startup");
+                            match &body {
+                                Ok(body) => client
+                                    .post(url)
+                                    .json(body)
+                                    .send()
+                                    .await
+                                    .and_then(|r| r.error_for_status())
+                                    .map(drop)
+                                    .map_err(anyhow::Error::from),
+                                Err(e) => Err(anyhow::anyhow!("{}", e)),
+                            }
+                        }
+                    };
+                    if let Err(e) = sent {
+                        log::warn!("Failed to send alert for {}: {:?}", subject, e);
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            rules,
+            severity_profits: cfg.severity_profits,
+            links,
+            queue: tx,
+        })
+    }
+
+    pub fn severity(&self, opp: &Opportunity) -> Severity {
+        let (medium, high) = self.severity_profits;
+        if opp.profit >= high {
+            Severity::High
+        } else if opp.profit >= medium {
+            Severity::Medium
+        } else {
+            Severity::Low
+        }
+    }
+
+    /// Queues the alert for the channels of the first matching rule.
+    pub fn route(&self, opp: &Opportunity) {
+        let severity = self.severity(opp);
+        let Some(rule) = self
+            .rules
+            .iter()
+            .find(|rule| rule.conditions.iter().all(|c| c.matches(opp, severity)))
+        else {
+            return;
+        };
+        if self
+            .queue
+            .try_send((
+                rule.channels.clone(),
+                Alert::Opportunity(severity, Box::new(opp.clone())),
+            ))
+            .is_err()
+        {
+            log::warn!(
+                "Alert queue full, dropping alert for {}",
+                self.links.permalink(opp.id)
+            );
+        }
+    }
+
+    /// Queues an operational notice for every channel any rule uses.
+    pub fn notify(&self, severity: Severity, text: &str) {
+        let mut channels: Vec<Channel> = Vec::new();
+        for channel in self.rules.iter().flat_map(|r| &r.channels) {
+            if !channels.contains(channel) {
+                channels.push(*channel);
+            }
+        }
+        if self
+            .queue
+            .try_send((channels, Alert::Notice(severity, text.to_string())))
+            .is_err()
+        {
+            log::warn!("Alert queue full, dropping notice: {}", text);
+        }
+    }
+}
+
+fn message(opp: &Opportunity, severity: Severity, permalink: &str) -> String {
+    format!(
+        "{:?} {} opportunity [{}]{}: buy {} / sell {}, profit {:.4}{} {}",
+        severity,
+        opp.strategy,
+        opp.instance,
+        opp.pair
+            .as_deref()
+            .map(|p| format!(" {}", p))
+            .unwrap_or_default(),
+        opp.dex_buy,
+        opp.dex_sell,
+        opp.profit,
+        opp.roi_bps
+            .map(|roi| format!(" ({:.1} bps)", roi))
+            .unwrap_or_default(),
+        permalink
+    )
+}
+
+async fn send_telegram(
+    client: &reqwest::Client,
+    telegram: &Telegram,
+    text: &str,
+) -> anyhow::Result<()> {
+    client
+        .post(format!(
+            "https://api.telegram.org/bot{}/sendMessage",
+            telegram.bot_token
+        ))
+        .json(&serde_json::json!({ "chat_id": telegram.chat_id, "text": text }))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        // The URL carries the bot token.
+        .map_err(|e| e.without_url())?;
+    Ok(())
+}
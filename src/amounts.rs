@@ -0,0 +1,100 @@
+//! Token amounts for logs, storage and the API
+//!
+//! Every raw `U256` amount the bot shows or stores is converted through the
+//! [`TokenInfo`] of its token: decimals and symbol are looked up once per
+//! address (the well-known Polygon tokens without an RPC call, any other
+//! token through its `decimals()`/`symbol()`) and cached for the process, so
+//! no call site assumes a token's decimals or names it in a format string.
+
+use crate::contracts::ERC20;
+use crate::money::Money;
+use crate::tokens::KNOWN_TOKENS;
+use crate::units;
+use anyhow::Context;
+use ethers::providers::Middleware;
+use ethers::types::{Address, U256};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+static TOKENS: Lazy<Mutex<HashMap<Address, TokenInfo>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenInfo {
+    pub address: Address,
+    pub decimals: u32,
+    /// Unset when the token does not report one.
+    pub symbol: Option<String>,
+}
+
+impl TokenInfo {
+    /// `raw` as an exact amount.
+    pub fn money(&self, raw: U256) -> Money {
+        Money::from_units(raw, self.decimals)
+    }
+
+    /// `raw` in whole tokens, for ratios and thresholds.
+    pub fn to_f64(&self, raw: U256) -> f64 {
+        units::to_f64(raw, self.decimals)
+    }
+
+    /// Exact decimal string of `raw`, as stored and served (`"1.5"`).
+    pub fn decimal(&self, raw: U256) -> String {
+        units::format_units(raw, self.decimals)
+    }
+
+    /// `raw` for log lines and messages (`"1.5 USDC"`).
+    pub fn display(&self, raw: U256) -> String {
+        self.label(self.decimal(raw))
+    }
+
+    /// An already converted amount followed by the symbol, if any.
+    pub fn label(&self, amount: impl fmt::Display) -> String {
+        match &self.symbol {
+            Some(symbol) => format!("{} {}", amount, symbol),
+            None => amount.to_string(),
+        }
+    }
+}
+
+/// The cached token info of `address`, if it was loaded already.
+pub fn cached(address: Address) -> Option<TokenInfo> {
+    TOKENS.lock().unwrap().get(&address).cloned()
+}
+
+/// Decimals and symbol of `address`, from the cache, the well-known tokens
+/// or the chain. Fails only when the token does not report its decimals.
+pub async fn load<M: Middleware + 'static>(
+    client: Arc<M>,
+    address: Address,
+) -> anyhow::Result<TokenInfo> {
+    if let Some(info) = cached(address) {
+        return Ok(info);
+    }
+    let known = KNOWN_TOKENS
+        .iter()
+        .find(|t| t.address.parse::<Address>().ok() == Some(address));
+    let info = match known {
+        Some(token) => TokenInfo {
+            address,
+            decimals: token.decimals,
+            symbol: Some(token.symbol.to_string()),
+        },
+        None => {
+            let erc20 = ERC20::new(address, client);
+            let decimals = erc20
+                .decimals()
+                .call()
+                .await
+                .with_context(|| format!("could not read decimals of {:?}", address))?;
+            TokenInfo {
+                address,
+                decimals: decimals.into(),
+                symbol: erc20.symbol().call().await.ok(),
+            }
+        }
+    };
+    TOKENS.lock().unwrap().insert(address, info.clone());
+    Ok(info)
+}
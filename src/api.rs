@@ -0,0 +1,103 @@
+//! JSON contract of stored opportunities
+//!
+//! [`Opportunity`] is the internal record: exact amounts and the block the
+//! quotes were read at. Clients (HTTP API, NDJSON output, webhook alerts and
+//! the export) get [`ApiOpportunity`] instead, with amounts as decimal strings
+//! and the optional `display` block, so the internal record can gain
+//! precision or fields without changing what they receive.
+
+use crate::db::{Opportunity, SizePoint};
+use crate::format::{OpportunityDisplay, Precision};
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ApiOpportunity {
+    pub id: i64,
+    pub timestamp: String,
+    pub strategy: String,
+    pub dex_buy: String,
+    pub dex_sell: String,
+    pub amount_in: String,
+    pub amount_out_buy: String,
+    pub amount_out_sell: String,
+    /// token_in bought back by the buying leg.
+    pub amount_back: Option<String>,
+    pub profit: f64,
+    /// Exact decimal profit in token_out; unset on rows stored before it was kept.
+    pub profit_exact: Option<String>,
+    pub instance: String,
+    /// RPC endpoints that served the quotes, comma-separated.
+    pub rpc_provider: Option<String>,
+    pub contested_profit: Option<f64>,
+    pub profit_usd: Option<f64>,
+    pub usd_source: Option<String>,
+    pub cycle_id: Option<String>,
+    pub pair: Option<String>,
+    pub notional_usd: Option<f64>,
+    pub roi_bps: Option<f64>,
+    pub price_impact_bps: Option<f64>,
+    pub optimal_amount_in: Option<String>,
+    pub optimal_profit: Option<f64>,
+    pub size_curve: Option<Vec<SizePoint>>,
+    /// Basis points off the Chainlink-implied price, with `ORACLE_CHECK`.
+    pub oracle_deviation: Option<f64>,
+    /// `DEX_PATHS` routes of the buying and selling DEX, token_in first.
+    pub path_buy: Option<String>,
+    pub path_sell: Option<String>,
+    pub inclusion_probability: Option<f64>,
+    pub expected_delay_secs: Option<f64>,
+    pub expected_profit: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display: Option<OpportunityDisplay>,
+}
+
+impl From<&Opportunity> for ApiOpportunity {
+    fn from(opp: &Opportunity) -> Self {
+        Self {
+            id: opp.id,
+            timestamp: opp.timestamp.clone(),
+            strategy: opp.strategy.clone(),
+            dex_buy: opp.dex_buy.clone(),
+            dex_sell: opp.dex_sell.clone(),
+            amount_in: opp.amount_in.to_string(),
+            amount_out_buy: opp.amount_out_buy.to_string(),
+            amount_out_sell: opp.amount_out_sell.to_string(),
+            amount_back: opp.amount_back.map(|amount| amount.to_string()),
+            profit: opp.profit,
+            profit_exact: opp.profit_exact.map(|profit| profit.to_string()),
+            instance: opp.instance.clone(),
+            rpc_provider: opp.rpc_provider.clone(),
+            contested_profit: opp.contested_profit,
+            profit_usd: opp.profit_usd,
+            usd_source: opp.usd_source.clone(),
+            cycle_id: opp.cycle_id.clone(),
+            pair: opp.pair.clone(),
+            notional_usd: opp.notional_usd,
+            roi_bps: opp.roi_bps,
+            price_impact_bps: opp.price_impact_bps,
+            optimal_amount_in: opp.optimal_amount_in.map(|amount| amount.to_string()),
+            optimal_profit: opp.optimal_profit,
+            size_curve: opp.size_curve.clone(),
+            oracle_deviation: opp.oracle_deviation,
+            path_buy: opp.path_buy.clone(),
+            path_sell: opp.path_sell.clone(),
+            inclusion_probability: opp.inclusion_probability,
+            expected_delay_secs: opp.expected_delay_secs,
+            expected_profit: opp.expected_profit,
+            display: None,
+        }
+    }
+}
+
+impl ApiOpportunity {
+    pub fn with_display(mut self, precision: &Precision) -> Self {
+        self.display = Some(precision.display(
+            &self.amount_in,
+            &self.amount_out_buy,
+            &self.amount_out_sell,
+            self.profit,
+            self.profit_exact.as_deref(),
+        ));
+        self
+    }
+}
@@ -0,0 +1,73 @@
+//! Audit trail of control and admin API actions
+
+use crate::auth::Caller;
+use crate::correlation;
+use chrono::Utc;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub timestamp: String,
+    pub actor_key_id: Option<i64>,
+    pub actor: String,
+    pub action: String,
+    pub payload: serde_json::Value,
+    /// Request that performed the action.
+    pub request_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AuditFilter {
+    pub action: Option<String>,
+    pub actor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+pub fn record(
+    conn: &Connection,
+    caller: &Caller,
+    action: &str,
+    payload: &serde_json::Value,
+) -> anyhow::Result<()> {
+    conn.execute(
+        "INSERT INTO audit_log (timestamp, actor_key_id, actor, action, payload, request_id)
+         VALUES (?1,?2,?3,?4,?5,?6)",
+        params![
+            Utc::now().to_rfc3339(),
+            caller.key_id,
+            caller.name,
+            action,
+            payload.to_string(),
+            correlation::current()
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn list(conn: &Connection, filter: &AuditFilter) -> anyhow::Result<Vec<AuditEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, timestamp, actor_key_id, actor, action, payload, request_id FROM audit_log
+         WHERE (?1 IS NULL OR action = ?1) AND (?2 IS NULL OR actor = ?2)
+         ORDER BY id DESC LIMIT ?3",
+    )?;
+    let entries = stmt
+        .query_map(
+            params![filter.action, filter.actor, filter.limit.unwrap_or(100)],
+            |row| {
+                let payload: String = row.get(5)?;
+                Ok(AuditEntry {
+                    id: row.get(0)?,
+                    timestamp: row.get(1)?,
+                    actor_key_id: row.get(2)?,
+                    actor: row.get(3)?,
+                    action: row.get(4)?,
+                    payload: serde_json::from_str(&payload).unwrap_or(serde_json::Value::Null),
+                    request_id: row.get(6)?,
+                })
+            },
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(entries)
+}
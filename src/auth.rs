@@ -0,0 +1,193 @@
+//! Scoped API keys
+//!
+//! Keys are random tokens handed out once by the `keys create` command; only
+//! their keccak256 hash is stored. With `API_AUTH` enabled every API handler
+//! asks the `Caller` extractor for the scope it needs.
+
+use actix_web::{dev::Payload, error, web, FromRequest, HttpRequest};
+use chrono::Utc;
+use clap::ValueEnum;
+use ethers::core::rand::{thread_rng, RngCore};
+use ethers::utils::{hex, keccak256};
+use futures::future::{ready, Ready};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+
+/// Scopes are ordered: a key with `Admin` may also do anything `Control` or
+/// `Read` allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Read,
+    Control,
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Control => "control",
+            Scope::Admin => "admin",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "read" => Some(Scope::Read),
+            "control" => Some(Scope::Control),
+            "admin" => Some(Scope::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// Whether handlers enforce API keys; registered as app data.
+pub struct AuthConfig {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiKey {
+    pub id: i64,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: String,
+    pub revoked_at: Option<String>,
+}
+
+fn hash_key(key: &str) -> String {
+    hex::encode(keccak256(key.as_bytes()))
+}
+
+fn parse_scopes(s: &str) -> Vec<Scope> {
+    s.split(',').filter_map(Scope::parse).collect()
+}
+
+/// Creates a key and returns the plaintext token, which is not stored.
+pub fn create_key(conn: &Connection, name: &str, scopes: &[Scope]) -> anyhow::Result<String> {
+    let mut bytes = [0u8; 32];
+    thread_rng().fill_bytes(&mut bytes);
+    let key = format!("arb_{}", hex::encode(bytes));
+    let scopes: Vec<&str> = scopes.iter().map(Scope::as_str).collect();
+    conn.execute(
+        "INSERT INTO api_keys (name, key_hash, scopes, created_at) VALUES (?1,?2,?3,?4)",
+        params![
+            name,
+            hash_key(&key),
+            scopes.join(","),
+            Utc::now().to_rfc3339()
+        ],
+    )?;
+    Ok(key)
+}
+
+/// Revokes every active key called `name`; returns how many were revoked.
+pub fn revoke_key(conn: &Connection, name: &str) -> anyhow::Result<usize> {
+    Ok(conn.execute(
+        "UPDATE api_keys SET revoked_at = ?1 WHERE name = ?2 AND revoked_at IS NULL",
+        params![Utc::now().to_rfc3339(), name],
+    )?)
+}
+
+pub fn list_keys(conn: &Connection) -> anyhow::Result<Vec<ApiKey>> {
+    let mut stmt =
+        conn.prepare("SELECT id, name, scopes, created_at, revoked_at FROM api_keys ORDER BY id")?;
+    let keys = stmt
+        .query_map([], |row| {
+            Ok(ApiKey {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                scopes: parse_scopes(&row.get::<_, String>(2)?),
+                created_at: row.get(3)?,
+                revoked_at: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(keys)
+}
+
+fn find_active_key(
+    conn: &Connection,
+    key: &str,
+) -> rusqlite::Result<Option<(i64, String, String)>> {
+    conn.query_row(
+        "SELECT id, name, scopes FROM api_keys WHERE key_hash = ?1 AND revoked_at IS NULL",
+        params![hash_key(key)],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
+    .optional()
+}
+
+/// Identity of the API caller. Resolving it never fails on a missing or
+/// unknown key; handlers decide with `require` whether that is acceptable.
+pub struct Caller {
+    pub key_id: Option<i64>,
+    pub name: String,
+    scopes: Vec<Scope>,
+    auth_enabled: bool,
+}
+
+impl Caller {
+    pub fn require(&self, scope: Scope) -> Result<(), actix_web::Error> {
+        if !self.auth_enabled || self.scopes.iter().any(|s| *s >= scope) {
+            return Ok(());
+        }
+        if self.key_id.is_none() {
+            return Err(error::ErrorUnauthorized("missing or invalid API key"));
+        }
+        Err(error::ErrorForbidden(format!(
+            "API key '{}' lacks the '{}' scope",
+            self.name,
+            scope.as_str()
+        )))
+    }
+}
+
+fn presented_key(req: &HttpRequest) -> Option<&str> {
+    let headers = req.headers();
+    if let Some(v) = headers.get("X-API-Key").and_then(|v| v.to_str().ok()) {
+        return Some(v);
+    }
+    headers
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+impl FromRequest for Caller {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let auth_enabled = req
+            .app_data::<web::Data<AuthConfig>>()
+            .map(|a| a.enabled)
+            .unwrap_or(false);
+        let anonymous = Caller {
+            key_id: None,
+            name: "anonymous".to_string(),
+            scopes: Vec::new(),
+            auth_enabled,
+        };
+        let (Some(key), Some(conn)) = (
+            presented_key(req),
+            req.app_data::<web::Data<Arc<Mutex<Connection>>>>(),
+        ) else {
+            return ready(Ok(anonymous));
+        };
+
+        let found = find_active_key(&conn.lock().unwrap(), key);
+        ready(match found {
+            Ok(Some((id, name, scopes))) => Ok(Caller {
+                key_id: Some(id),
+                name,
+                scopes: parse_scopes(&scopes),
+                auth_enabled,
+            }),
+            Ok(None) => Ok(anonymous),
+            Err(e) => Err(error::ErrorInternalServerError(e)),
+        })
+    }
+}
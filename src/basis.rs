@@ -0,0 +1,108 @@
+//! Spot vs perp basis detection
+//!
+//! Compares the DEX spot price with the mark price of a Polygon perp market
+//! (`PERP_MARKET`, any market with the Synthetix PerpsV2 `fillPrice` and
+//! `currentFundingRate` views, both 18 decimals). The mark is the market's
+//! fill price for a zero-size order, so it carries the skew premium the index
+//! price lacks. A gap is worth what it pays after perp fees on both sides, the
+//! spot swaps' gas and the funding the perp leg pays over `BASIS_HOLD_HOURS`.
+//! Basis opportunities are stored with the `basis` strategy, once per gap:
+//! while one stays open in the same direction, later cycles don't store it
+//! again.
+
+use crate::contracts::PerpMarket;
+use crate::units;
+use ethers::providers::Middleware;
+use ethers::types::{Address, I256};
+use std::sync::{Arc, Mutex};
+
+pub struct BasisSignal {
+    pub spot: f64,
+    pub mark: f64,
+    /// Mark premium over spot in basis points (negative when perp trades at a discount).
+    pub bps: f64,
+    /// Funding longs pay shorts per day, as a fraction of the position.
+    pub funding_rate: f64,
+}
+
+impl BasisSignal {
+    /// Whether the trade shorts the perp (it trades at a premium).
+    pub fn short_perp(&self) -> bool {
+        self.bps > 0.0
+    }
+
+    /// Funding the perp leg pays on `notional` over `hours`; negative when
+    /// it earns funding.
+    pub fn funding_cost(&self, notional: f64, hours: f64) -> f64 {
+        let paid = self.funding_rate * notional * hours / 24.0;
+        if self.short_perp() {
+            -paid
+        } else {
+            paid
+        }
+    }
+}
+
+pub struct BasisMonitor<M> {
+    market: PerpMarket<M>,
+    min_bps: f64,
+    /// Direction of the gap last stored (`true` when shorting the perp),
+    /// until it closes.
+    open: Mutex<Option<bool>>,
+}
+
+impl<M: Middleware + 'static> BasisMonitor<M> {
+    /// Checks that `market` answers both reads before detection starts.
+    pub async fn new(client: Arc<M>, market: Address, min_bps: f64) -> anyhow::Result<Self> {
+        let monitor = Self {
+            market: PerpMarket::new(market, client),
+            min_bps,
+            open: Mutex::new(None),
+        };
+        monitor.mark_price().await?;
+        monitor.funding_rate().await?;
+        Ok(monitor)
+    }
+
+    pub async fn mark_price(&self) -> anyhow::Result<f64> {
+        let (price, invalid) = self.market.fill_price(I256::zero()).call().await?;
+        if invalid {
+            anyhow::bail!(
+                "perp market {:?} reports an invalid price",
+                self.market.address()
+            );
+        }
+        Ok(units::to_f64(price, 18))
+    }
+
+    pub async fn funding_rate(&self) -> anyhow::Result<f64> {
+        let rate = self.market.current_funding_rate().call().await?;
+        Ok(rate.as_i128() as f64 / 1e18)
+    }
+
+    /// Returns a signal when the basis against `spot` is at least `min_bps` wide.
+    pub async fn check(&self, spot: f64) -> anyhow::Result<Option<BasisSignal>> {
+        let mark = self.mark_price().await?;
+        let bps = (mark - spot) / spot * 10_000.0;
+        if bps.abs() < self.min_bps {
+            return Ok(None);
+        }
+        Ok(Some(BasisSignal {
+            spot,
+            mark,
+            bps,
+            funding_rate: self.funding_rate().await?,
+        }))
+    }
+
+    /// Marks `signal`'s gap open; false when it already was, so it isn't
+    /// stored again.
+    pub fn open(&self, signal: &BasisSignal) -> bool {
+        self.open.lock().unwrap().replace(signal.short_perp()) != Some(signal.short_perp())
+    }
+
+    /// Forgets the open gap, so the next one is stored.
+    pub fn close(&self) {
+        *self.open.lock().unwrap() = None;
+    }
+}
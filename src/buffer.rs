@@ -0,0 +1,227 @@
+//! Batched writes of price samples, opportunities and executions
+//!
+//! At sub-second poll intervals a commit per row becomes the bottleneck, so
+//! the detector queues its rows here and writes them in one transaction once
+//! `DB_FLUSH_RECORDS` rows are pending or the oldest has waited
+//! `DB_FLUSH_SECS`. With the default of 1 record every row is written as soon
+//! as it is queued.
+//!
+//! When a write fails the rows stay queued and detection carries on; the
+//! queue is bounded by `DB_BUFFER_MAX_ROWS`, beyond which the oldest price
+//! samples and then the oldest opportunities are dropped; executed trades are
+//! never dropped. Entering and leaving
+//! that degraded state is reported once each as a [`StorageEvent`].
+
+use crate::db::{
+    insert_opportunity, insert_price_samples, NewOpportunity, Opportunity, PriceSample,
+};
+use crate::execution::{self, ExecutedTrade};
+use crate::metrics::{self, METRICS};
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::Connection;
+use std::fmt;
+
+/// Database writes started failing, or work again after failing.
+#[derive(Debug, Clone)]
+pub enum StorageEvent {
+    Failing {
+        error: String,
+        pending: usize,
+    },
+    Recovered {
+        flushed: usize,
+        dropped: u64,
+        since: DateTime<Utc>,
+    },
+}
+
+impl fmt::Display for StorageEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageEvent::Failing { error, pending } => write!(
+                f,
+                "Database writes failing, buffering {} rows in memory: {}",
+                pending, error
+            ),
+            StorageEvent::Recovered {
+                flushed,
+                dropped,
+                since,
+            } => write!(
+                f,
+                "Database writes recovered (failing since {}): flushed {} rows, dropped {}",
+                since.to_rfc3339(),
+                flushed,
+                dropped
+            ),
+        }
+    }
+}
+
+struct PendingSamples {
+    now: DateTime<Utc>,
+    block_number: u64,
+    samples: Vec<PriceSample>,
+}
+
+pub struct WriteBuffer {
+    instance: String,
+    pair: Option<String>,
+    max_records: usize,
+    max_age: Duration,
+    /// Most rows kept while the database is unavailable.
+    capacity: usize,
+    samples: Vec<PendingSamples>,
+    opportunities: Vec<(DateTime<Utc>, NewOpportunity)>,
+    executions: Vec<(DateTime<Utc>, ExecutedTrade)>,
+    /// When the oldest pending row was queued.
+    oldest: Option<DateTime<Utc>>,
+    /// Set while writes fail: when the first one failed.
+    failing_since: Option<DateTime<Utc>>,
+    /// Rows dropped over capacity during the current outage.
+    dropped: u64,
+}
+
+impl WriteBuffer {
+    pub fn new(
+        instance: &str,
+        pair: Option<String>,
+        max_records: usize,
+        max_age_secs: u64,
+        capacity: usize,
+    ) -> Self {
+        Self {
+            instance: instance.to_string(),
+            pair,
+            max_records: max_records.max(1),
+            max_age: Duration::seconds(max_age_secs as i64),
+            capacity: capacity.max(1),
+            samples: Vec::new(),
+            opportunities: Vec::new(),
+            executions: Vec::new(),
+            oldest: None,
+            failing_since: None,
+            dropped: 0,
+        }
+    }
+
+    pub fn push_price_samples(
+        &mut self,
+        now: DateTime<Utc>,
+        block_number: u64,
+        samples: Vec<PriceSample>,
+    ) {
+        self.oldest.get_or_insert(now);
+        self.samples.push(PendingSamples {
+            now,
+            block_number,
+            samples,
+        });
+        self.enforce_capacity();
+    }
+
+    pub fn push_opportunity(&mut self, now: DateTime<Utc>, opp: NewOpportunity) {
+        self.oldest.get_or_insert(now);
+        self.opportunities.push((now, opp));
+        self.enforce_capacity();
+    }
+
+    /// Queues a trade that was sent; it stays queued until a write succeeds.
+    pub fn push_execution(&mut self, now: DateTime<Utc>, trade: ExecutedTrade) {
+        self.oldest.get_or_insert(now);
+        self.executions.push((now, trade));
+    }
+
+    /// Drops the oldest samples, then the oldest opportunities, until the
+    /// queue fits its capacity.
+    fn enforce_capacity(&mut self) {
+        while self.pending() > self.capacity {
+            let dropped = if !self.samples.is_empty() {
+                self.samples.remove(0).samples.len()
+            } else if !self.opportunities.is_empty() {
+                self.opportunities.remove(0);
+                1
+            } else {
+                break;
+            };
+            self.dropped += dropped as u64;
+            metrics::add(&METRICS.db_rows_dropped, dropped as u64);
+        }
+    }
+
+    fn pending(&self) -> usize {
+        self.samples.iter().map(|s| s.samples.len()).sum::<usize>()
+            + self.opportunities.len()
+            + self.executions.len()
+    }
+
+    /// Whether enough rows are pending, or the oldest has waited long enough.
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        match self.oldest {
+            Some(oldest) => self.pending() >= self.max_records || now - oldest >= self.max_age,
+            None => false,
+        }
+    }
+
+    /// [`WriteBuffer::flush`], tracking outages: the event is set when writes
+    /// start failing or recover.
+    pub fn flush_tracked(
+        &mut self,
+        conn: &mut Connection,
+        now: DateTime<Utc>,
+    ) -> (Vec<Opportunity>, Option<StorageEvent>) {
+        let pending = self.pending();
+        match self.flush(conn) {
+            Ok(stored) => {
+                let event = self
+                    .failing_since
+                    .take()
+                    .map(|since| StorageEvent::Recovered {
+                        flushed: pending,
+                        dropped: std::mem::take(&mut self.dropped),
+                        since,
+                    });
+                (stored, event)
+            }
+            Err(e) => {
+                metrics::inc(&METRICS.db_write_failures);
+                let event = self.failing_since.is_none().then(|| StorageEvent::Failing {
+                    error: format!("{:#}", e),
+                    pending,
+                });
+                self.failing_since.get_or_insert(now);
+                (Vec::new(), event)
+            }
+        }
+    }
+
+    /// Writes everything pending in one transaction and returns the stored
+    /// opportunities. On failure nothing is dropped; the next flush retries.
+    pub fn flush(&mut self, conn: &mut Connection) -> anyhow::Result<Vec<Opportunity>> {
+        let tx = conn.transaction()?;
+        for pending in &self.samples {
+            insert_price_samples(
+                &tx,
+                &self.instance,
+                self.pair.as_deref(),
+                pending.block_number,
+                &pending.samples,
+                pending.now,
+            )?;
+        }
+        let stored = self
+            .opportunities
+            .iter()
+            .map(|(now, opp)| insert_opportunity(&tx, *now, opp))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        for (now, trade) in &self.executions {
+            execution::record(&tx, *now, trade)?;
+        }
+        tx.commit()?;
+        self.samples.clear();
+        self.opportunities.clear();
+        self.executions.clear();
+        self.oldest = None;
+        Ok(stored)
+    }
+}
@@ -0,0 +1,218 @@
+//! OHLC candles of one DEX's price, served at `/candles`
+//!
+//! Candles are folded from the per-cycle `price_samples` and, for periods
+//! that were downsampled (see [`crate::maintenance`]), from the 1-minute and
+//! 1-hour `price_bars`. An interval shorter than an hour can't split 1-hour
+//! bars, so those periods have no candles at such intervals. Candles start
+//! on multiples of the interval since the Unix epoch. One series is one pair
+//! on one DEX of one instance, so `pair` is required, and `instance` too when
+//! several instances recorded the pair in the window.
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+/// Candles of `/candles` without `limit`, and the most one request may get.
+pub const DEFAULT_LIMIT: usize = 500;
+pub const MAX_LIMIT: usize = 5000;
+
+#[derive(Deserialize)]
+pub struct CandleParams {
+    /// Token pair, e.g. `WETH/USDC`.
+    pub pair: String,
+    pub dex: String,
+    /// Required only when several instances recorded the pair.
+    pub instance: Option<String>,
+    /// `1m`, `5m`, `1h`, `1d`, ... (default `5m`), at most a day.
+    pub interval: Option<String>,
+    /// RFC 3339 bounds; `limit` intervals up to now when unset.
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Candle {
+    /// Start of the candle, RFC 3339.
+    pub time: String,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    /// Price samples folded into the candle.
+    pub samples: i64,
+}
+
+impl CandleParams {
+    /// Length of a candle in seconds.
+    pub fn interval_secs(&self) -> Result<i64, String> {
+        let spec = self.interval.as_deref().unwrap_or("5m").trim();
+        let invalid = || format!("Invalid interval {:?}: use e.g. 1m, 5m, 1h or 1d", spec);
+        let last = spec.char_indices().last().map_or(0, |(i, _)| i);
+        let (count, unit) = spec.split_at(last);
+        let count: i64 = count.parse().map_err(|_| invalid())?;
+        let unit = match unit {
+            "m" => 60,
+            "h" => 3600,
+            "d" => 86_400,
+            _ => return Err(invalid()),
+        };
+        let secs = count * unit;
+        if !(60..=86_400).contains(&secs) {
+            return Err(format!("Interval {:?} must be from 1m to 1d", spec));
+        }
+        Ok(secs)
+    }
+
+    /// `from` and `to`, defaulting to `limit` intervals up to `now`.
+    pub fn window(
+        &self,
+        now: DateTime<Utc>,
+        interval_secs: i64,
+        limit: usize,
+    ) -> Result<(DateTime<Utc>, DateTime<Utc>), String> {
+        let bound = |value: &Option<String>, name: &str| {
+            value
+                .as_deref()
+                .map(|v| {
+                    DateTime::parse_from_rfc3339(v)
+                        .map(|t| t.with_timezone(&Utc))
+                        .map_err(|e| format!("Invalid {}: {}", name, e))
+                })
+                .transpose()
+        };
+        let to = bound(&self.to, "to")?.unwrap_or(now);
+        let from = bound(&self.from, "from")?
+            .unwrap_or(to - Duration::seconds(interval_secs * limit as i64));
+        if from > to {
+            return Err("from is after to".to_string());
+        }
+        Ok((from, to))
+    }
+}
+
+/// Instances that recorded `pair` on `dex` between `from` and `to`.
+pub fn instances(
+    conn: &Connection,
+    dex: &str,
+    pair: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> anyhow::Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT instance FROM price_samples
+         WHERE dex = ?1 AND pair = ?2 AND timestamp >= ?3 AND timestamp <= ?4
+         UNION
+         SELECT instance FROM price_bars
+         WHERE dex = ?1 AND pair = ?2 AND bucket >= ?3 AND bucket <= ?4
+         ORDER BY instance",
+    )?;
+    let rows = stmt.query_map(
+        params![dex, pair, from.to_rfc3339(), to.to_rfc3339()],
+        |row| row.get::<_, String>(0),
+    )?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+/// Candles of `dex` between `from` and `to`, oldest first. At most `limit`,
+/// the latest ones when the window holds more.
+#[allow(clippy::too_many_arguments)]
+pub fn load(
+    conn: &Connection,
+    dex: &str,
+    pair: &str,
+    instance: Option<&str>,
+    interval_secs: i64,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    limit: usize,
+) -> anyhow::Result<Vec<Candle>> {
+    let from = start_of(from, interval_secs);
+    let (since, until) = (from.to_rfc3339(), to.to_rfc3339());
+
+    // (start, open, high, low, close, samples) of every sample and bar.
+    let mut parts: Vec<(DateTime<Utc>, f64, f64, f64, f64, i64)> = Vec::new();
+    let mut samples = conn.prepare(
+        "SELECT timestamp, price FROM price_samples
+         WHERE dex = ?1 AND price > 0 AND timestamp >= ?2 AND timestamp <= ?3
+           AND pair = ?4 AND (?5 IS NULL OR instance = ?5)
+         ORDER BY timestamp, id",
+    )?;
+    let rows = samples.query_map(params![dex, since, until, pair, instance], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?))
+    })?;
+    for row in rows {
+        let (timestamp, price) = row?;
+        if let Ok(at) = DateTime::parse_from_rfc3339(&timestamp) {
+            parts.push((at.with_timezone(&Utc), price, price, price, price, 1));
+        }
+    }
+
+    let mut resolutions = vec!["1m"];
+    if interval_secs % 3600 == 0 {
+        resolutions.push("1h");
+    }
+    let mut bars = conn.prepare(
+        "SELECT bucket, open, high, low, close, samples FROM price_bars
+         WHERE resolution = ?1 AND dex = ?2 AND bucket >= ?3 AND bucket <= ?4
+           AND pair = ?5 AND (?6 IS NULL OR instance = ?6)
+         ORDER BY bucket",
+    )?;
+    for resolution in resolutions {
+        let rows = bars.query_map(
+            params![resolution, dex, since, until, pair, instance],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, f64>(2)?,
+                    row.get::<_, f64>(3)?,
+                    row.get::<_, f64>(4)?,
+                    row.get::<_, i64>(5)?,
+                ))
+            },
+        )?;
+        for row in rows {
+            let (bucket, open, high, low, close, count) = row?;
+            if let Ok(at) = DateTime::parse_from_rfc3339(&bucket) {
+                parts.push((at.with_timezone(&Utc), open, high, low, close, count));
+            }
+        }
+    }
+    // Bars and samples cover disjoint periods; ordering them by start keeps
+    // each candle's open and close right.
+    parts.sort_by_key(|part| part.0);
+
+    let mut candles: Vec<Candle> = Vec::new();
+    for (at, open, high, low, close, count) in parts {
+        let time = start_of(at, interval_secs).to_rfc3339();
+        match candles.last_mut() {
+            Some(candle) if candle.time == time => {
+                candle.high = candle.high.max(high);
+                candle.low = candle.low.min(low);
+                candle.close = close;
+                candle.samples += count;
+            }
+            _ => candles.push(Candle {
+                time,
+                open,
+                high,
+                low,
+                close,
+                samples: count,
+            }),
+        }
+    }
+    if candles.len() > limit {
+        candles.drain(..candles.len() - limit);
+    }
+    Ok(candles)
+}
+
+/// Start of the candle `at` falls in.
+fn start_of(at: DateTime<Utc>, interval_secs: i64) -> DateTime<Utc> {
+    let secs = at.timestamp();
+    Utc.timestamp_opt(secs - secs.rem_euclid(interval_secs), 0)
+        .single()
+        .unwrap_or(at)
+}
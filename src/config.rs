@@ -0,0 +1,1377 @@
+//! Bot configuration read from the environment (`.env`)
+
+use crate::dex::{self, stable::PoolType};
+use crate::execution::ExecutionMode;
+use crate::format::Precision;
+use crate::gas;
+use crate::schedule::Cron;
+use crate::{tokens, units};
+use anyhow::Context;
+use dotenv::dotenv;
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, U256};
+use ethers::utils::to_checksum;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::env;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Name of the bot instance when `BOT_INSTANCES` is unset.
+pub const DEFAULT_INSTANCE: &str = "default";
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Config {
+    /// Bot instance this config belongs to; scopes stored rows and API queries.
+    pub instance: String,
+    pub rpc_url: String,
+    /// Named V2 routers from `DEXES`, or `A`/`B` from `DEX_A_ROUTER`/`DEX_B_ROUTER`.
+    pub dexes: Vec<(String, Address)>,
+    /// `DEX_PATHS` routes of `dexes` through intermediate tokens, each from one
+    /// pair token to the other.
+    pub dex_paths: Vec<(String, Vec<Address>)>,
+    pub token_in: Address,
+    pub token_out: Address,
+    /// Pair symbols (`WETH/USDC`) of a `PAIRS` entry; looked up on chain otherwise.
+    pub pair: Option<String>,
+    /// Raw `PAIRS` entries, expanded by [`Config::pair_configs`].
+    pairs: Vec<String>,
+    pub trade_size_wei: U256,
+    /// `trade_size_wei` first, followed by any `EXTRA_TRADE_SIZES_WEI`.
+    pub trade_sizes: Vec<U256>,
+    pub min_profit_usdc: f64,
+    /// Minimum profit over notional in bps, on top of `min_profit_usdc`.
+    pub min_roi_bps: Option<f64>,
+    pub poll_interval_secs: u64,
+    pub simulated_gas_usdc: f64,
+    /// Gas units of a round trip priced at the live gas price when the pair
+    /// has no learned estimate; `simulated_gas_usdc` is used without it.
+    pub swap_gas_limit: Option<u64>,
+    /// Wrapped native token quoted to price gas when `native_price_usdc` is unset.
+    pub native_token: Address,
+    pub database_path: String,
+    /// Pending rows that trigger a batched database write (1 = write immediately).
+    pub db_flush_records: usize,
+    /// Longest a queued row waits for its batch to fill.
+    pub db_flush_secs: u64,
+    /// Most rows held in memory while database writes fail.
+    pub db_buffer_max_rows: usize,
+    /// Extra V2 forks from `V2_FORKS`, enabled where their factory has the pair.
+    pub v2_forks: Vec<String>,
+    pub woofi_router: Option<Address>,
+    pub dodo_pools: Vec<Address>,
+    /// Balancer V2 and Curve pools from `STABLE_POOLS`.
+    pub stable_pools: Vec<(PoolType, Address)>,
+    /// Uniswap V3 fee tiers from `V3_POOLS`, each quoted as its own venue.
+    pub v3_pools: Vec<(String, u32)>,
+    pub v3_quoter: Address,
+    pub v3_factory: Address,
+    /// Cycles between full sweeps of every V3 fee tier; in between only the
+    /// tier that quoted best is asked. 1 sweeps every cycle.
+    pub v3_full_sweep_every: u32,
+    /// LP fee of V2 pools, used to price the trade's impact from reserves.
+    pub v2_fee_bps: u32,
+    /// Router quotes further than this from the pool reserves' output flag
+    /// the DEX on the pair (see `quotecheck`).
+    pub quote_mismatch_bps: f64,
+    /// Search the most profitable round-trip size on V2 pool reserves and
+    /// judge gaps by their profit at that size.
+    pub optimal_trade_size: bool,
+    /// Intermediate tokens of `token_in`→B→C→`token_in` cycles quoted on each
+    /// V2 router; no triangular detection when empty.
+    pub triangular_tokens: Vec<Address>,
+    /// Perp market read for basis detection (see `basis`).
+    pub perp_market: Option<Address>,
+    pub basis_min_bps: f64,
+    /// Perp taker fee, paid on opening and on closing the perp leg.
+    pub perp_fee_bps: f64,
+    /// How long a basis trade is expected to stay open, for its funding.
+    pub basis_hold_hours: f64,
+    pub max_quote_age_secs: u64,
+    pub multicall_batch_size: usize,
+    pub rpc_budget_per_block: usize,
+    /// How long detectors wait to share a multicall batch for the same block.
+    pub multicall_coalesce_ms: u64,
+    pub spread_window: usize,
+    pub spread_anomaly_z: f64,
+    pub public_base_url: String,
+    pub share_link_secret: Option<String>,
+    pub share_link_ttl_secs: i64,
+    pub read_only_api: bool,
+    pub api_auth: bool,
+    /// Built single-page app to serve at `/` instead of the bundled landing page.
+    pub frontend_dir: Option<String>,
+    /// Directory the weekly report is written to; no report job without it.
+    pub report_dir: Option<String>,
+    /// When the report is written (default Mondays 00:00 UTC).
+    pub report_cron: Cron,
+    /// Age in days after which raw samples, rejections and timings are pruned.
+    pub retention_days: Option<u32>,
+    pub retention_cron: Cron,
+    /// Age in hours after which price samples are folded into 1-minute bars;
+    /// no downsampling without it.
+    pub downsample_after_hours: Option<u32>,
+    /// Age in days after which 1-minute bars are folded into 1-hour bars.
+    pub downsample_hourly_after_days: u32,
+    pub downsample_cron: Cron,
+    pub rollup_cron: Cron,
+    /// Uptime monitor pinged on `heartbeat_cron`; no heartbeats without it.
+    pub heartbeat_url: Option<String>,
+    pub heartbeat_cron: Cron,
+    /// Decimal places of the rounded `display` values in API responses.
+    pub precision: Precision,
+    /// Competitor wallet (or bot contract) whose round trips are benchmarked.
+    pub watch_wallet: Option<Address>,
+    /// Where diagnostics bundles are written; none are written without it.
+    pub diagnostics_dir: Option<String>,
+    /// Consecutive failed cycles that trigger a diagnostics bundle.
+    pub diagnostics_after_failures: u32,
+    /// POL price used to value priority-fee bids; no contested profit without it.
+    pub native_price_usdc: Option<f64>,
+    pub contest_blocks: u64,
+    pub contest_fee_percentile: f64,
+    pub contest_gas_units: u64,
+    /// Blocks within which an opportunity must be included; no inclusion model without it.
+    pub inclusion_blocks: Option<u64>,
+    /// Priority fee bid modelled when the contest model is off.
+    pub inclusion_priority_fee_gwei: f64,
+    /// `-` for stdout or a file / named pipe receiving NDJSON lines.
+    pub ndjson_output: Option<String>,
+    /// Analytics endpoint opportunities are pushed to; no export without it.
+    pub export_url: Option<String>,
+    pub export_interval_secs: u64,
+    /// Bearer token for `export_url`.
+    pub export_token: Option<String>,
+    /// GraphQL mutation taking `$opportunities`; plain JSON is posted without it.
+    pub export_graphql_mutation: Option<String>,
+    pub disabled_strategies: Vec<String>,
+    /// `name=HH:MM-HH:MM` UTC windows outside which a strategy is paused.
+    pub strategy_windows: Vec<String>,
+    /// Chainlink token_out/USD aggregator used to value profits in USD.
+    pub usd_price_feed: Option<Address>,
+    /// Tokens after token_out, ending in a stablecoin, along which token_out
+    /// is quoted into USD when the feed is down, stale or unset.
+    pub usd_price_route: Vec<Address>,
+    /// `binance:SYMBOL` or `coinbase:BASE-QUOTE`, used when the feed is down or stale.
+    pub usd_fallback_ticker: Option<String>,
+    pub usd_feed_max_age_secs: u64,
+    /// Compare opportunity prices with the tokens' Chainlink USD feeds.
+    pub oracle_check: bool,
+    /// (token, Chainlink USD feed) pairs overriding or adding to the built-in feeds.
+    pub oracle_feeds: Vec<(Address, Address)>,
+    /// Deviation from the oracle price beyond which an opportunity is flagged.
+    pub oracle_max_deviation_bps: f64,
+    /// InfluxDB v2 base URL receiving price samples and spreads.
+    pub influx_url: Option<String>,
+    pub influx_org: String,
+    pub influx_bucket: String,
+    pub influx_token: Option<String>,
+    /// Keep raw price samples out of SQLite when InfluxDB has them.
+    pub influx_only: bool,
+    /// Tried in order after `rpc_url` when it is down.
+    pub rpc_fallback_urls: Vec<String>,
+    /// Fall back to the built-in public RPC list when every configured endpoint is down.
+    pub rpc_public_failover: bool,
+    pub rpc_health_interval_secs: u64,
+    /// A request without an answer by then fails over to the next endpoint.
+    pub rpc_request_timeout_secs: u64,
+    /// Tries per RPC request, the first included, when it fails transiently.
+    pub rpc_retry_attempts: u32,
+    /// Delay before the first retry, doubled before each further one.
+    pub rpc_retry_base_ms: u64,
+    /// Random extra delay per retry, at most this much.
+    pub rpc_retry_jitter_ms: u64,
+    /// WebSocket endpoint whose new heads trigger detection cycles.
+    pub rpc_ws_url: Option<String>,
+    /// `conditions->channels` rules; no alerts are routed without them.
+    pub alert_rules: Option<String>,
+    /// Profits at which alerts become medium and high severity.
+    pub alert_severity_profits: (f64, f64),
+    pub telegram_bot_token: Option<String>,
+    pub telegram_chat_id: Option<String>,
+    /// Minimum gap between Telegram alerts for the same route.
+    pub telegram_cooldown_secs: u64,
+    pub alert_webhook_url: Option<String>,
+    /// Wallet whose POL balance is kept above `gas_floor_pol`; derived from
+    /// the top-up key when unset. No gas checks without either.
+    pub gas_wallet: Option<Address>,
+    pub gas_floor_pol: f64,
+    /// Stable token suggested (or swapped) into POL, `token_out` by default.
+    pub gas_top_up_token: Address,
+    /// Raw units of `gas_top_up_token` per top-up.
+    pub gas_top_up_amount: Option<U256>,
+    /// Send the swap instead of only suggesting it; needs the private key.
+    pub gas_top_up_auto: bool,
+    pub gas_top_up_private_key: Option<String>,
+    pub gas_check_cron: Cron,
+    /// Pool depth in token_out below which a DEX alerts; unset disables the floor.
+    pub liquidity_floor: Option<f64>,
+    /// Percent drop within an hour that alerts.
+    pub liquidity_drop_pct: f64,
+    pub liquidity_check_cron: Cron,
+    /// A pair without two nonzero quotes (or two liquid pools) for this long
+    /// is suspended; 0 never suspends.
+    pub pair_dead_after_secs: u64,
+    /// Consecutive cycles quoting two DEXes needed after a start or RPC
+    /// failover before opportunities are stored; 0 disables the warm-up.
+    pub warmup_cycles: u32,
+    /// `dry-run` (the default) only detects; `live` executes two-DEX trades.
+    pub execution_mode: ExecutionMode,
+    pub execution_private_key: Option<String>,
+    /// Encrypted JSON keystore, used when no private key is set.
+    pub execution_keystore: Option<String>,
+    pub execution_keystore_password: Option<String>,
+    pub execution_slippage_bps: u32,
+    /// How long a sent approval or swap may stay unconfirmed before the trade
+    /// is recorded as `timeout` and the executor moves on.
+    pub execution_confirm_timeout_secs: u64,
+    /// Simulated `token_in` each instance starts paper trading with; ten
+    /// trade sizes when unset.
+    pub paper_start_balance: Option<U256>,
+    /// Slippage modelled on each leg of a paper fill.
+    pub paper_slippage_bps: u32,
+}
+
+impl Config {
+    /// The unprefixed configuration, which also carries the process-wide
+    /// settings (RPC, database, API).
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::load(DEFAULT_INSTANCE)
+    }
+
+    /// Like [`Config::from_env`], with unset variables taken from a TOML file
+    /// first; see [`apply_file`].
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        apply_file(path.as_ref())?;
+        Self::from_env()
+    }
+
+    /// One config per name in `BOT_INSTANCES`, or just the default one.
+    pub fn instances() -> anyhow::Result<Vec<Self>> {
+        let names = env_list(DEFAULT_INSTANCE, "BOT_INSTANCES");
+        if names.is_empty() {
+            return Ok(vec![Self::from_env()?]);
+        }
+        names
+            .iter()
+            .map(|name| {
+                Self::load(name)
+                    .with_context(|| format!("Failed to read config of instance {}", name))
+            })
+            .collect()
+    }
+
+    /// Reads `{INSTANCE}_{KEY}` before falling back to the shared `KEY`.
+    /// Every problem found is reported at once, by variable name.
+    pub fn load(instance: &str) -> anyhow::Result<Self> {
+        dotenv().ok();
+        let r = Reader {
+            instance,
+            problems: Problems::default(),
+        };
+        let (token_in, token_out) = (r.address("TOKEN_IN"), r.address("TOKEN_OUT"));
+        let trade_size_wei = match r.aliased("TRADE_SIZE_WEI", Some("TRADE_SIZE")) {
+            Some((key, value)) => r.amount(&key, &value, token_in),
+            None => {
+                r.problems.add("TRADE_SIZE_WEI", "required but not set");
+                U256::zero()
+            }
+        };
+        let mut trade_sizes = vec![trade_size_wei];
+        let (extra_key, extra) = match env_list(instance, "EXTRA_TRADE_SIZES_WEI") {
+            extra if extra.is_empty() => {
+                ("EXTRA_TRADE_SIZES", env_list(instance, "EXTRA_TRADE_SIZES"))
+            }
+            extra => ("EXTRA_TRADE_SIZES_WEI", extra),
+        };
+        for size in extra {
+            let size = r.amount(extra_key, &size, token_in);
+            if !trade_sizes.contains(&size) {
+                trade_sizes.push(size);
+            }
+        }
+        let gas_top_up_token = r.optional_address("GAS_TOP_UP_TOKEN").unwrap_or(token_out);
+        let cfg = Self {
+            instance: instance.to_string(),
+            rpc_url: r.required("RPC_URL"),
+            dexes: parse_dexes(&r),
+            dex_paths: parse_dex_paths(&r),
+            token_in,
+            token_out,
+            pair: None,
+            pairs: env_list(instance, "PAIRS"),
+            trade_size_wei,
+            trade_sizes,
+            min_profit_usdc: r.required("MIN_PROFIT_USDC"),
+            min_roi_bps: r.optional("MIN_ROI_BPS"),
+            poll_interval_secs: r.required_secs("POLL_INTERVAL_SECS"),
+            simulated_gas_usdc: r.required("SIMULATED_GAS_USDC"),
+            swap_gas_limit: r.optional("SWAP_GAS_LIMIT"),
+            native_token: r.address_or("NATIVE_TOKEN", gas::WRAPPED_NATIVE),
+            database_path: r.required("DATABASE_PATH"),
+            db_flush_records: r.or("DB_FLUSH_RECORDS", 1),
+            db_flush_secs: r.secs("DB_FLUSH_SECS", 5),
+            db_buffer_max_rows: r.or("DB_BUFFER_MAX_ROWS", 10_000),
+            v2_forks: env_list(instance, "V2_FORKS"),
+            woofi_router: r.optional_address("WOOFI_ROUTER"),
+            dodo_pools: r.address_list("DODO_POOLS"),
+            stable_pools: parse_stable_pools(&r),
+            v3_pools: parse_v3_pools(&r),
+            v3_quoter: r.address_or("V3_QUOTER", dex::v3::QUOTER_V2),
+            v3_factory: r.address_or("V3_FACTORY", dex::v3::FACTORY),
+            v3_full_sweep_every: r.or("V3_FULL_SWEEP_EVERY", 10),
+            v2_fee_bps: r.or("V2_FEE_BPS", 30),
+            quote_mismatch_bps: r.or("QUOTE_MISMATCH_BPS", 50.0),
+            optimal_trade_size: env_flag("OPTIMAL_TRADE_SIZE"),
+            triangular_tokens: r.address_list("TRIANGULAR_TOKENS"),
+            perp_market: r.optional_address("PERP_MARKET"),
+            basis_min_bps: r.or("BASIS_MIN_BPS", 50.0),
+            perp_fee_bps: r.or("PERP_FEE_BPS", 5.0),
+            basis_hold_hours: r.or("BASIS_HOLD_HOURS", 8.0),
+            max_quote_age_secs: r.secs("MAX_QUOTE_AGE_SECS", 30),
+            multicall_batch_size: r.or("MULTICALL_BATCH_SIZE", 50),
+            rpc_budget_per_block: r.or("RPC_BUDGET_PER_BLOCK", 10),
+            multicall_coalesce_ms: r.millis("MULTICALL_COALESCE_MS", 25),
+            spread_window: r.or("SPREAD_WINDOW", 200),
+            spread_anomaly_z: r.or("SPREAD_ANOMALY_Z", 4.0),
+            public_base_url: r
+                .string("PUBLIC_BASE_URL")
+                .unwrap_or_else(|| "http://localhost:8080".to_string()),
+            share_link_secret: r.string("SHARE_LINK_SECRET"),
+            share_link_ttl_secs: r.secs("SHARE_LINK_TTL_SECS", 86_400) as i64,
+            read_only_api: env_flag("READ_ONLY_API"),
+            api_auth: env_flag("API_AUTH"),
+            frontend_dir: r.string("FRONTEND_DIR"),
+            report_dir: r.string("REPORT_DIR"),
+            report_cron: r.cron("REPORT_CRON", "0 0 * * 1"),
+            retention_days: r.optional("RETENTION_DAYS"),
+            retention_cron: r.cron("RETENTION_CRON", "30 3 * * *"),
+            downsample_after_hours: r.optional("DOWNSAMPLE_AFTER_HOURS"),
+            downsample_hourly_after_days: r.or("DOWNSAMPLE_HOURLY_AFTER_DAYS", 7),
+            downsample_cron: r.cron("DOWNSAMPLE_CRON", "15 * * * *"),
+            rollup_cron: r.cron("ROLLUP_CRON", "5 * * * *"),
+            heartbeat_url: r.string("HEARTBEAT_URL"),
+            heartbeat_cron: r.cron("HEARTBEAT_CRON", "*/5 * * * *"),
+            precision: Precision {
+                amount: r.or("AMOUNT_PRECISION", 6),
+                profit: r.or("PROFIT_PRECISION", 2),
+            },
+            watch_wallet: r.optional_address("WATCH_WALLET"),
+            diagnostics_dir: r.string("DIAGNOSTICS_DIR"),
+            diagnostics_after_failures: r.or("DIAGNOSTICS_AFTER_FAILURES", 3),
+            native_price_usdc: r.optional("NATIVE_PRICE_USDC"),
+            contest_blocks: r.or("CONTEST_BLOCKS", 20),
+            contest_fee_percentile: r.or("CONTEST_FEE_PERCENTILE", 90.0),
+            contest_gas_units: r.or("CONTEST_GAS_UNITS", 300_000),
+            inclusion_blocks: r.optional("INCLUSION_BLOCKS"),
+            inclusion_priority_fee_gwei: r.or("INCLUSION_PRIORITY_FEE_GWEI", 30.0),
+            ndjson_output: r.string("NDJSON_OUTPUT"),
+            export_url: r.string("EXPORT_URL"),
+            export_interval_secs: r.secs("EXPORT_INTERVAL_SECS", 300),
+            export_token: r.string("EXPORT_TOKEN"),
+            export_graphql_mutation: r.string("EXPORT_GRAPHQL_MUTATION"),
+            disabled_strategies: env_list(instance, "DISABLED_STRATEGIES"),
+            strategy_windows: env_list(instance, "STRATEGY_WINDOWS"),
+            usd_price_feed: r.optional_address("USD_PRICE_FEED"),
+            usd_price_route: r.token_list("USD_PRICE_ROUTE"),
+            usd_fallback_ticker: r.string("USD_FALLBACK_TICKER"),
+            usd_feed_max_age_secs: r.secs("USD_FEED_MAX_AGE_SECS", 3600),
+            oracle_check: env_flag("ORACLE_CHECK"),
+            oracle_feeds: parse_oracle_feeds(&r),
+            oracle_max_deviation_bps: r.or("ORACLE_MAX_DEVIATION_BPS", 500.0),
+            influx_url: r.string("INFLUX_URL"),
+            influx_org: r.string("INFLUX_ORG").unwrap_or_default(),
+            influx_bucket: r
+                .string("INFLUX_BUCKET")
+                .unwrap_or_else(|| "arb".to_string()),
+            influx_token: r.string("INFLUX_TOKEN"),
+            influx_only: env_flag("INFLUX_ONLY"),
+            rpc_fallback_urls: env_list(DEFAULT_INSTANCE, "RPC_FALLBACK_URLS"),
+            rpc_public_failover: env::var("RPC_PUBLIC_FAILOVER")
+                .map(|_| env_flag("RPC_PUBLIC_FAILOVER"))
+                .unwrap_or(true),
+            rpc_health_interval_secs: r.secs("RPC_HEALTH_INTERVAL_SECS", 30),
+            rpc_request_timeout_secs: r.secs("RPC_REQUEST_TIMEOUT_SECS", 10),
+            rpc_retry_attempts: r.or("RPC_RETRY_ATTEMPTS", 3),
+            rpc_retry_base_ms: r.millis("RPC_RETRY_BASE_MS", 200),
+            rpc_retry_jitter_ms: r.millis("RPC_RETRY_JITTER_MS", 100),
+            rpc_ws_url: r.string("RPC_WS_URL"),
+            alert_rules: r.string("ALERT_RULES"),
+            alert_severity_profits: match env_list(instance, "ALERT_SEVERITY_PROFITS").as_slice() {
+                [] => (1.0, 10.0),
+                [medium, high] => match (medium.parse::<f64>(), high.parse::<f64>()) {
+                    (Ok(medium), Ok(high)) => (medium, high),
+                    _ => {
+                        r.problems
+                            .add("ALERT_SEVERITY_PROFITS", "must be two profits, e.g. 1,10");
+                        (1.0, 10.0)
+                    }
+                },
+                _ => {
+                    r.problems
+                        .add("ALERT_SEVERITY_PROFITS", "must be two profits, e.g. 1,10");
+                    (1.0, 10.0)
+                }
+            },
+            telegram_bot_token: r.string("TELEGRAM_BOT_TOKEN"),
+            telegram_chat_id: r.string("TELEGRAM_CHAT_ID"),
+            telegram_cooldown_secs: r.secs("TELEGRAM_COOLDOWN_SECS", 300),
+            alert_webhook_url: r.string("ALERT_WEBHOOK_URL"),
+            gas_wallet: r.optional_address("GAS_WALLET"),
+            gas_floor_pol: r.or("GAS_FLOOR_POL", 5.0),
+            gas_top_up_token,
+            gas_top_up_amount: r
+                .string("GAS_TOP_UP_AMOUNT")
+                .map(|v| r.amount("GAS_TOP_UP_AMOUNT", &v, gas_top_up_token)),
+            gas_top_up_auto: env_flag("GAS_TOP_UP_AUTO"),
+            gas_top_up_private_key: r.string("GAS_TOP_UP_PRIVATE_KEY"),
+            gas_check_cron: r.cron("GAS_CHECK_CRON", "*/10 * * * *"),
+            liquidity_floor: r.optional("LIQUIDITY_FLOOR"),
+            liquidity_drop_pct: r.or("LIQUIDITY_DROP_PCT", 50.0),
+            liquidity_check_cron: r.cron("LIQUIDITY_CHECK_CRON", "*/5 * * * *"),
+            pair_dead_after_secs: r.secs("PAIR_DEAD_AFTER_SECS", 0),
+            warmup_cycles: r.or("WARMUP_CYCLES", 3),
+            execution_mode: r.or("EXECUTION_MODE", ExecutionMode::DryRun),
+            execution_private_key: r.string("EXECUTION_PRIVATE_KEY"),
+            execution_keystore: r.string("EXECUTION_KEYSTORE"),
+            execution_keystore_password: r.string("EXECUTION_KEYSTORE_PASSWORD"),
+            execution_slippage_bps: r.or("EXECUTION_SLIPPAGE_BPS", 50),
+            execution_confirm_timeout_secs: r.secs("EXECUTION_CONFIRM_TIMEOUT_SECS", 120),
+            paper_start_balance: r
+                .string("PAPER_START_BALANCE")
+                .map(|v| r.amount("PAPER_START_BALANCE", &v, token_in)),
+            paper_slippage_bps: r.or("PAPER_SLIPPAGE_BPS", 10),
+        };
+        cfg.validate(&r.problems);
+        cfg.validate_pair(&r.problems, "");
+        r.problems.finish()?;
+        Ok(cfg)
+    }
+
+    /// Range and consistency checks of values that parsed.
+    fn validate(&self, problems: &Problems) {
+        let check = |ok: bool, field: &str, message: &str| {
+            if !ok {
+                problems.add(field, message);
+            }
+        };
+        check(
+            (1..=3600).contains(&self.poll_interval_secs),
+            "POLL_INTERVAL_SECS",
+            "must be between 1 and 3600",
+        );
+        check(
+            self.simulated_gas_usdc >= 0.0,
+            "SIMULATED_GAS_USDC",
+            "must not be negative",
+        );
+        check(
+            self.db_flush_records > 0,
+            "DB_FLUSH_RECORDS",
+            "must be at least 1",
+        );
+        check(
+            self.db_buffer_max_rows >= self.db_flush_records,
+            "DB_BUFFER_MAX_ROWS",
+            "must be at least DB_FLUSH_RECORDS",
+        );
+        if let Some(hours) = self.downsample_after_hours {
+            check(hours > 0, "DOWNSAMPLE_AFTER_HOURS", "must be at least 1");
+            check(
+                self.retention_days.is_none_or(|days| hours < days * 24),
+                "DOWNSAMPLE_AFTER_HOURS",
+                "must be below RETENTION_DAYS, or samples are pruned before they are downsampled",
+            );
+            check(
+                hours < self.downsample_hourly_after_days * 24,
+                "DOWNSAMPLE_HOURLY_AFTER_DAYS",
+                "must be above DOWNSAMPLE_AFTER_HOURS",
+            );
+        }
+        check(
+            self.multicall_batch_size > 0,
+            "MULTICALL_BATCH_SIZE",
+            "must be at least 1",
+        );
+        check(
+            self.rpc_budget_per_block > 0,
+            "RPC_BUDGET_PER_BLOCK",
+            "must be at least 1",
+        );
+        check(
+            self.multicall_coalesce_ms <= 1000,
+            "MULTICALL_COALESCE_MS",
+            "must be at most 1000",
+        );
+        check(
+            self.v3_full_sweep_every >= 1,
+            "V3_FULL_SWEEP_EVERY",
+            "must be at least 1",
+        );
+        check(
+            self.v2_fee_bps < 10_000,
+            "V2_FEE_BPS",
+            "must be below 10000",
+        );
+        check(
+            self.oracle_max_deviation_bps > 0.0,
+            "ORACLE_MAX_DEVIATION_BPS",
+            "must be positive",
+        );
+        check(
+            self.usd_price_route
+                .last()
+                .is_none_or(|&token| tokens::is_stablecoin(token)),
+            "USD_PRICE_ROUTE",
+            "must end in a stablecoin (USDC, USDC.e, USDT or DAI)",
+        );
+        check(
+            self.quote_mismatch_bps > 0.0,
+            "QUOTE_MISMATCH_BPS",
+            "must be positive",
+        );
+        check(
+            self.spread_window >= 2,
+            "SPREAD_WINDOW",
+            "must be at least 2",
+        );
+        check(
+            self.export_interval_secs > 0,
+            "EXPORT_INTERVAL_SECS",
+            "must be at least 1",
+        );
+        check(
+            self.rpc_health_interval_secs > 0,
+            "RPC_HEALTH_INTERVAL_SECS",
+            "must be at least 1",
+        );
+        check(
+            self.liquidity_floor.is_none_or(|floor| floor > 0.0),
+            "LIQUIDITY_FLOOR",
+            "must be positive",
+        );
+        check(
+            self.liquidity_drop_pct > 0.0 && self.liquidity_drop_pct <= 100.0,
+            "LIQUIDITY_DROP_PCT",
+            "must be above 0 and at most 100",
+        );
+        check(
+            self.rpc_request_timeout_secs > 0,
+            "RPC_REQUEST_TIMEOUT_SECS",
+            "must be at least 1",
+        );
+        check(
+            self.rpc_retry_attempts >= 1,
+            "RPC_RETRY_ATTEMPTS",
+            "must be at least 1",
+        );
+        check(
+            (0.0..=100.0).contains(&self.contest_fee_percentile),
+            "CONTEST_FEE_PERCENTILE",
+            "must be between 0 and 100",
+        );
+        check(
+            self.alert_severity_profits.0 <= self.alert_severity_profits.1,
+            "ALERT_SEVERITY_PROFITS",
+            "medium profit must not exceed the high one",
+        );
+        match self.gas_signer() {
+            Ok(Some(signer)) => check(
+                self.gas_wallet.is_none_or(|w| w == signer.address()),
+                "GAS_WALLET",
+                "does not belong to GAS_TOP_UP_PRIVATE_KEY",
+            ),
+            Ok(None) => check(
+                !self.gas_top_up_auto,
+                "GAS_TOP_UP_AUTO",
+                "needs GAS_TOP_UP_PRIVATE_KEY",
+            ),
+            Err(_) => problems.add("GAS_TOP_UP_PRIVATE_KEY", "is not a valid private key"),
+        }
+        if self.execution_mode == ExecutionMode::Live {
+            match (&self.execution_private_key, &self.execution_keystore) {
+                (Some(key), _) => check(
+                    key.trim_start_matches("0x").parse::<LocalWallet>().is_ok(),
+                    "EXECUTION_PRIVATE_KEY",
+                    "is not a valid private key",
+                ),
+                (None, Some(path)) => check(
+                    Path::new(path).is_file(),
+                    "EXECUTION_KEYSTORE",
+                    "is not a file",
+                ),
+                (None, None) => problems.add(
+                    "EXECUTION_MODE",
+                    "live needs EXECUTION_PRIVATE_KEY or EXECUTION_KEYSTORE",
+                ),
+            }
+        }
+        check(
+            self.execution_slippage_bps < 10_000,
+            "EXECUTION_SLIPPAGE_BPS",
+            "must be below 10000",
+        );
+        check(
+            self.execution_confirm_timeout_secs > 0,
+            "EXECUTION_CONFIRM_TIMEOUT_SECS",
+            "must be positive",
+        );
+        check(
+            self.paper_slippage_bps < 10_000,
+            "PAPER_SLIPPAGE_BPS",
+            "must be below 10000",
+        );
+        let venues = self.dexes.len()
+            + self.v2_forks.len()
+            + self.v3_pools.len()
+            + self.dodo_pools.len()
+            + self.stable_pools.len()
+            + usize::from(self.woofi_router.is_some());
+        check(venues >= 2, "DEXES", "needs at least two venues to compare");
+        for (name, _) in &self.dex_paths {
+            if !self.dexes.iter().any(|(n, _)| n == name) {
+                problems.add("DEX_PATHS", format!("{:?} is not one of DEXES", name));
+            }
+        }
+    }
+
+    /// Custom route of the DEX `name` between this config's tokens, if any.
+    pub fn dex_path(&self, name: &str) -> Option<&[Address]> {
+        self.dex_paths
+            .iter()
+            .find(|(n, path)| n == name && joins(path, self.token_in, self.token_out))
+            .map(|(_, path)| path.as_slice())
+    }
+
+    /// Signer of automatic gas top-ups; `None` unless `GAS_TOP_UP_AUTO` is on.
+    pub fn gas_signer(&self) -> anyhow::Result<Option<LocalWallet>> {
+        match (&self.gas_top_up_private_key, self.gas_top_up_auto) {
+            (Some(key), true) => Ok(Some(key.trim_start_matches("0x").parse::<LocalWallet>()?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Wallet trades are sent from; `None` unless `EXECUTION_MODE=live`.
+    /// Decrypting a keystore is slow, so call this once.
+    pub fn execution_signer(&self) -> anyhow::Result<Option<LocalWallet>> {
+        if self.execution_mode != ExecutionMode::Live {
+            return Ok(None);
+        }
+        if let Some(key) = &self.execution_private_key {
+            return Ok(Some(key.trim_start_matches("0x").parse::<LocalWallet>()?));
+        }
+        let path = self
+            .execution_keystore
+            .as_deref()
+            .context("EXECUTION_MODE=live needs EXECUTION_PRIVATE_KEY or EXECUTION_KEYSTORE")?;
+        let password = self
+            .execution_keystore_password
+            .as_deref()
+            .unwrap_or_default();
+        let wallet = LocalWallet::decrypt_keystore(path, password)
+            .with_context(|| format!("Failed to decrypt keystore {}", path))?;
+        Ok(Some(wallet))
+    }
+
+    /// Checks of the settings a `PAIRS` entry overrides; `scope` prefixes the
+    /// field names.
+    fn validate_pair(&self, problems: &Problems, scope: &str) {
+        let field = |name: &str| format!("{}{}", scope, name);
+        if self.trade_size_wei.is_zero() {
+            problems.add(&field("TRADE_SIZE_WEI"), "must not be zero");
+        }
+        if self.token_in == self.token_out {
+            problems.add(&field("TOKEN_OUT"), "must differ from TOKEN_IN");
+        }
+        for (name, router) in &self.dexes {
+            if *router == self.token_in || *router == self.token_out {
+                problems.add(
+                    "DEXES",
+                    format!("router of {} is the address of a pair token", name),
+                );
+            }
+        }
+        // `PAIRS` entries keep only the paths joining their own tokens.
+        if self.pairs.is_empty() {
+            for (name, path) in &self.dex_paths {
+                if !joins(path, self.token_in, self.token_out) {
+                    problems.add(
+                        "DEX_PATHS",
+                        format!("path of {} does not join TOKEN_IN and TOKEN_OUT", name),
+                    );
+                }
+            }
+        }
+    }
+
+    /// One config per `PAIRS` entry (`SYMBOLS:0xTokenIn:0xTokenOut[:TRADE_SIZE_WEI[:MIN_PROFIT]]`),
+    /// or just this one. Entries inherit everything else; size and threshold
+    /// default to `TRADE_SIZE_WEI` and `MIN_PROFIT_USDC`. The perp and USD
+    /// prices are pair specific, so entries drop the perp market and keep the
+    /// USD sources only when they share `TOKEN_OUT`. Problems of all entries
+    /// are reported together.
+    pub fn pair_configs(&self) -> anyhow::Result<Vec<Self>> {
+        if self.pairs.is_empty() {
+            return Ok(vec![self.clone()]);
+        }
+        let problems = Problems::default();
+        let mut cfgs = Vec::with_capacity(self.pairs.len());
+        for entry in &self.pairs {
+            let fields: Vec<&str> = entry.split(':').map(str::trim).collect();
+            let [symbols, token_in, token_out, rest @ ..] = fields.as_slice() else {
+                problems.add(
+                    "PAIRS",
+                    format!(
+                        "entry {:?} is not SYMBOLS:0xTokenIn:0xTokenOut[:size[:min_profit]]",
+                        entry
+                    ),
+                );
+                continue;
+            };
+            let scope = format!("PAIRS {} ", symbols);
+            let mut cfg = self.clone();
+            cfg.pairs = Vec::new();
+            cfg.pair = Some(symbols.to_string());
+            match (parse_address(token_in), parse_address(token_out)) {
+                (Ok(token_in), Ok(token_out)) => {
+                    cfg.token_in = token_in;
+                    cfg.token_out = token_out;
+                }
+                (token_in, token_out) => {
+                    for e in [token_in.err(), token_out.err()].into_iter().flatten() {
+                        problems.add(&scope, e);
+                    }
+                    continue;
+                }
+            }
+            if let Some(size) = rest.first() {
+                match parse_amount(size, cfg.token_in) {
+                    Ok(size) => cfg.trade_size_wei = size,
+                    Err(e) => problems.add(&format!("{}TRADE_SIZE_WEI", scope), e),
+                }
+            }
+            cfg.trade_sizes = vec![cfg.trade_size_wei];
+            if let Some(min_profit) = rest.get(1) {
+                match min_profit.parse::<f64>() {
+                    Ok(min_profit) => cfg.min_profit_usdc = min_profit,
+                    Err(e) => problems.add(&format!("{}MIN_PROFIT", scope), e),
+                }
+            }
+            cfg.perp_market = None;
+            cfg.dex_paths
+                .retain(|(_, path)| joins(path, cfg.token_in, cfg.token_out));
+            if cfg.token_out != self.token_out {
+                cfg.usd_price_feed = None;
+                cfg.usd_price_route.clear();
+                cfg.usd_fallback_ticker = None;
+            }
+            cfg.validate_pair(&problems, &scope);
+            cfgs.push(cfg);
+        }
+        problems.finish()?;
+        Ok(cfgs)
+    }
+
+    /// Every RPC endpoint in order of preference: those of `RPC_URL`, which
+    /// may list several comma-separated, then `RPC_FALLBACK_URLS`.
+    pub fn rpc_urls(&self) -> Vec<String> {
+        self.rpc_url
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(str::to_string)
+            .chain(self.rpc_fallback_urls.iter().cloned())
+            .collect()
+    }
+
+    /// The detection settings, safe to hand out: the RPC URL is cut down to its
+    /// host (providers put API keys in the path) and secrets are left out.
+    pub fn summary(&self) -> serde_json::Value {
+        let rpc_url = self.rpc_urls().into_iter().next().unwrap_or_default();
+        let rpc_host = rpc_url
+            .split("://")
+            .last()
+            .and_then(|rest| rest.split(['/', '?']).next())
+            .unwrap_or_default();
+        serde_json::json!({
+            "instance": self.instance,
+            "rpc_host": rpc_host,
+            "dexes": self.dexes,
+            "dex_paths": self.dex_paths,
+            "token_in": self.token_in,
+            "token_out": self.token_out,
+            "pair": self.pair,
+            "trade_sizes": self.trade_sizes.iter().map(U256::to_string).collect::<Vec<_>>(),
+            "min_profit_usdc": self.min_profit_usdc,
+            "min_roi_bps": self.min_roi_bps,
+            "poll_interval_secs": self.poll_interval_secs,
+            "simulated_gas_usdc": self.simulated_gas_usdc,
+            "swap_gas_limit": self.swap_gas_limit,
+            "v2_forks": self.v2_forks,
+            "woofi_router": self.woofi_router,
+            "dodo_pools": self.dodo_pools,
+            "stable_pools": self.stable_pools,
+            "v3_pools": self.v3_pools,
+            "v3_full_sweep_every": self.v3_full_sweep_every,
+            "perp_market": self.perp_market,
+            "max_quote_age_secs": self.max_quote_age_secs,
+            "multicall_batch_size": self.multicall_batch_size,
+            "rpc_budget_per_block": self.rpc_budget_per_block,
+            "multicall_coalesce_ms": self.multicall_coalesce_ms,
+        })
+    }
+}
+
+/// Loads a TOML config file into the environment without overriding
+/// variables that are already set (by the shell or `.env`), so the rest of the
+/// configuration keeps reading plain variables:
+///
+/// - top-level keys map to their upper-cased names (`database_path` is
+///   `DATABASE_PATH`); `[thresholds]` keys too (`min_profit_usdc`)
+/// - `[rpc]` keys get an `RPC_` prefix (`url`, `fallback_urls`, ...)
+/// - `[dexes]` is `Name = "0xRouter"` pairs, becoming `DEXES`
+/// - `[dex_paths]` is `Name = ["WETH", "WPOL", "USDC"]` pairs, becoming
+///   `DEX_PATHS`
+/// - `[[pairs]]` tables (`symbols`, `token_in`, `token_out`, optional
+///   `trade_size_wei` (or `trade_size`) and `min_profit`) become `PAIRS`; the
+///   first pair also provides `TOKEN_IN`/`TOKEN_OUT`
+///
+/// Arrays are joined with commas. Durations and trade sizes may be written
+/// with units (`poll_interval = "15s"`, `trade_size = "1000 USDC"`).
+pub fn apply_file(path: &Path) -> anyhow::Result<()> {
+    dotenv().ok();
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    let table: toml::Table = text
+        .parse()
+        .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+
+    let mut vars: Vec<(String, String)> = Vec::new();
+    for (key, value) in &table {
+        match (key.as_str(), value) {
+            ("rpc", toml::Value::Table(rpc)) => {
+                for (key, value) in rpc {
+                    vars.push((
+                        format!("RPC_{}", key.to_ascii_uppercase()),
+                        file_value(value)?,
+                    ));
+                }
+            }
+            ("thresholds", toml::Value::Table(thresholds)) => {
+                for (key, value) in thresholds {
+                    vars.push((key.to_ascii_uppercase(), file_value(value)?));
+                }
+            }
+            ("dexes", toml::Value::Table(dexes)) => {
+                let entries = dexes
+                    .iter()
+                    .map(|(name, router)| Ok(format!("{}:{}", name, file_value(router)?)))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                vars.push(("DEXES".to_string(), entries.join(",")));
+            }
+            ("dex_paths", toml::Value::Table(paths)) => {
+                let mut entries = Vec::with_capacity(paths.len());
+                for (name, path) in paths {
+                    let toml::Value::Array(tokens) = path else {
+                        anyhow::bail!("[dex_paths] {} must be an array of tokens", name);
+                    };
+                    let tokens = tokens
+                        .iter()
+                        .map(file_value)
+                        .collect::<anyhow::Result<Vec<_>>>()?;
+                    entries.push(format!("{}:{}", name, tokens.join(">")));
+                }
+                vars.push(("DEX_PATHS".to_string(), entries.join(",")));
+            }
+            ("pairs", toml::Value::Array(pairs)) => {
+                let mut entries = Vec::with_capacity(pairs.len());
+                for pair in pairs {
+                    let field = |name: &str| pair.get(name).map(file_value).transpose();
+                    let required = |name: &str| {
+                        field(name)?.with_context(|| format!("[[pairs]] entry without {}", name))
+                    };
+                    let (token_in, token_out) = (required("token_in")?, required("token_out")?);
+                    if entries.is_empty() {
+                        vars.push(("TOKEN_IN".to_string(), token_in.clone()));
+                        vars.push(("TOKEN_OUT".to_string(), token_out.clone()));
+                    }
+                    let mut entry = format!("{}:{}:{}", required("symbols")?, token_in, token_out);
+                    let size = match field("trade_size_wei")? {
+                        Some(size) => Some(size),
+                        None => field("trade_size")?,
+                    };
+                    match (size, field("min_profit")?) {
+                        (None, None) => {}
+                        (Some(size), None) => entry += &format!(":{}", size),
+                        (Some(size), Some(min)) => entry += &format!(":{}:{}", size, min),
+                        (None, Some(_)) => {
+                            anyhow::bail!("[[pairs]] min_profit needs trade_size_wei")
+                        }
+                    }
+                    entries.push(entry);
+                }
+                vars.push(("PAIRS".to_string(), entries.join(",")));
+            }
+            (_, toml::Value::Table(_)) => anyhow::bail!("unknown config section [{}]", key),
+            (_, value) => vars.push((key.to_ascii_uppercase(), file_value(value)?)),
+        }
+    }
+    for (key, value) in vars {
+        if env::var_os(&key).is_none() {
+            env::set_var(key, value);
+        }
+    }
+    Ok(())
+}
+
+/// Scalar or array of scalars as the string its variable would hold.
+fn file_value(value: &toml::Value) -> anyhow::Result<String> {
+    Ok(match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Integer(i) => i.to_string(),
+        toml::Value::Float(f) => f.to_string(),
+        toml::Value::Boolean(b) => b.to_string(),
+        toml::Value::Array(items) => items
+            .iter()
+            .map(file_value)
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .join(","),
+        other => anyhow::bail!("unsupported config value {}", other),
+    })
+}
+
+/// `Name:0xRouter` entries, falling back to the two legacy router variables
+/// when `DEXES` is unset.
+fn parse_dexes(r: &Reader) -> Vec<(String, Address)> {
+    let entries = env_list(r.instance, "DEXES");
+    if entries.is_empty() {
+        return vec![
+            ("A".to_string(), r.address("DEX_A_ROUTER")),
+            ("B".to_string(), r.address("DEX_B_ROUTER")),
+        ];
+    }
+    let mut dexes: Vec<(String, Address)> = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        let Some((name, router)) = entry.rsplit_once(':') else {
+            r.problems
+                .add("DEXES", format!("entry {:?} is not Name:0xRouter", entry));
+            continue;
+        };
+        let name = name.trim();
+        if dexes.iter().any(|(n, _)| n == name) {
+            r.problems
+                .add("DEXES", format!("DEX {:?} is listed twice", name));
+            continue;
+        }
+        match parse_address(router) {
+            Ok(router) => dexes.push((name.to_string(), router)),
+            Err(e) => r.problems.add("DEXES", format!("{}: {}", name, e)),
+        }
+    }
+    dexes
+}
+
+/// `type:0xPool` entries, type `balancer`, `curve` or `curve-crypto`.
+fn parse_stable_pools(r: &Reader) -> Vec<(PoolType, Address)> {
+    let mut pools = Vec::new();
+    for entry in env_list(r.instance, "STABLE_POOLS") {
+        let Some((kind, pool)) = entry.split_once(':') else {
+            r.problems.add(
+                "STABLE_POOLS",
+                format!("entry {:?} is not type:0xPool", entry),
+            );
+            continue;
+        };
+        match kind.parse::<PoolType>() {
+            Ok(kind) => pools.push((kind, r.checked_address("STABLE_POOLS", pool))),
+            Err(e) => r.problems.add("STABLE_POOLS", e),
+        }
+    }
+    pools
+}
+
+/// `Name:TOKEN>TOKEN>TOKEN` entries, tokens as known symbols or addresses.
+fn parse_dex_paths(r: &Reader) -> Vec<(String, Vec<Address>)> {
+    let mut paths: Vec<(String, Vec<Address>)> = Vec::new();
+    for entry in env_list(r.instance, "DEX_PATHS") {
+        let Some((name, spec)) = entry.split_once(':') else {
+            r.problems.add(
+                "DEX_PATHS",
+                format!("entry {:?} is not Name:TOKEN>TOKEN>TOKEN", entry),
+            );
+            continue;
+        };
+        let name = name.trim();
+        if paths.iter().any(|(n, _)| n == name) {
+            r.problems
+                .add("DEX_PATHS", format!("DEX {:?} has two paths", name));
+            continue;
+        }
+        let path: Vec<Address> = spec
+            .split('>')
+            .map(|token| match tokens::by_name(token.trim()) {
+                Some(token) => token.address(),
+                None => r.checked_address("DEX_PATHS", token),
+            })
+            .collect();
+        if path.len() < 3 {
+            r.problems.add(
+                "DEX_PATHS",
+                format!("path of {} needs at least one intermediate token", name),
+            );
+            continue;
+        }
+        paths.push((name.to_string(), path));
+    }
+    paths
+}
+
+/// Whether `path` runs from `a` to `b` or from `b` to `a`.
+fn joins(path: &[Address], a: Address, b: Address) -> bool {
+    match (path.first(), path.last()) {
+        (Some(&first), Some(&last)) => (first, last) == (a, b) || (first, last) == (b, a),
+        _ => false,
+    }
+}
+
+/// `fee` or `Name:fee` entries, fee in hundredths of a bip (`500` = 0.05%).
+fn parse_v3_pools(r: &Reader) -> Vec<(String, u32)> {
+    env_list(r.instance, "V3_POOLS")
+        .iter()
+        .filter_map(|entry| {
+            let (name, fee) = match entry.rsplit_once(':') {
+                Some((name, fee)) => (Some(name.trim()), fee),
+                None => (None, entry.as_str()),
+            };
+            let Ok(fee) = fee.trim().parse::<u32>() else {
+                r.problems.add(
+                    "V3_POOLS",
+                    format!("entry {:?} has no valid fee tier", entry),
+                );
+                return None;
+            };
+            let name = name.map_or_else(|| dex::v3::default_name(fee), str::to_string);
+            Some((name, fee))
+        })
+        .collect()
+}
+
+/// `TOKEN:0xFeed` entries, the token as an address or a well-known symbol
+/// (which covers every token going by that name).
+fn parse_oracle_feeds(r: &Reader) -> Vec<(Address, Address)> {
+    let mut feeds = Vec::new();
+    for entry in env_list(r.instance, "ORACLE_FEEDS") {
+        let Some((token, feed)) = entry.rsplit_once(':') else {
+            r.problems.add(
+                "ORACLE_FEEDS",
+                format!("entry {:?} is not TOKEN:0xFeed", entry),
+            );
+            continue;
+        };
+        let feed = match parse_address(feed) {
+            Ok(feed) => feed,
+            Err(e) => {
+                r.problems.add("ORACLE_FEEDS", e);
+                continue;
+            }
+        };
+        let tokens: Vec<Address> = match parse_address(token) {
+            Ok(token) => vec![token],
+            Err(_) => tokens::KNOWN_TOKENS
+                .iter()
+                .filter(|t| t.is_named(token.trim()))
+                .map(tokens::Token::address)
+                .collect(),
+        };
+        if tokens.is_empty() {
+            r.problems
+                .add("ORACLE_FEEDS", format!("unknown token {:?}", token.trim()));
+        }
+        feeds.extend(tokens.into_iter().map(|token| (token, feed)));
+    }
+    feeds
+}
+
+/// Parses an address, rejecting mixed-case input whose EIP-55 checksum does
+/// not match (most likely a typo); all-lowercase or all-uppercase input has
+/// no checksum to check.
+pub fn parse_address(s: &str) -> Result<Address, String> {
+    let s = s.trim();
+    let address = s
+        .parse::<Address>()
+        .map_err(|e| format!("invalid address {:?}: {}", s, e))?;
+    let hex = s.strip_prefix("0x").unwrap_or(s);
+    let mixed_case =
+        hex.chars().any(|c| c.is_ascii_lowercase()) && hex.chars().any(|c| c.is_ascii_uppercase());
+    if mixed_case && to_checksum(&address, None)[2..] != *hex {
+        return Err(format!(
+            "address {:?} has a bad checksum (expected {})",
+            s,
+            to_checksum(&address, None)
+        ));
+    }
+    Ok(address)
+}
+
+/// Raw units (`1000000000000000000`), or an amount in token units followed
+/// by the token's symbol (`1.5 WETH`, `1000 USDC`) when `token` is in the
+/// [`tokens`] registry; the symbol must name `token`.
+pub fn parse_amount(value: &str, token: Address) -> Result<U256, String> {
+    let fields: Vec<&str> = value.split_whitespace().collect();
+    match fields.as_slice() {
+        [raw] => U256::from_dec_str(raw).map_err(|e| format!("{:?}: {}", value, e)),
+        [amount, symbol] => {
+            let Some(known) = tokens::by_address(token) else {
+                return Err(format!(
+                    "{:?}: {:?} is not a known token, give the amount in raw units",
+                    value, token
+                ));
+            };
+            if !known.is_named(symbol) {
+                return Err(format!(
+                    "{:?}: the traded token is {}, not {}",
+                    value, known.symbol, symbol
+                ));
+            }
+            units::parse_units(amount, known.decimals).map_err(|e| format!("{:#}", e))
+        }
+        _ => Err(format!(
+            "{:?} is neither raw units nor an amount and symbol",
+            value
+        )),
+    }
+}
+
+/// Problems found while loading a config, reported together.
+#[derive(Default)]
+struct Problems(RefCell<Vec<String>>);
+
+impl Problems {
+    /// Records a problem, unless `field` already has one: a value that did
+    /// not parse reads as its default and would fail the range checks too.
+    fn add(&self, field: &str, message: impl fmt::Display) {
+        let field = field.trim_end();
+        let mut problems = self.0.borrow_mut();
+        let prefix = format!("{}: ", field);
+        if !problems.iter().any(|p| p.starts_with(&prefix)) {
+            problems.push(format!("{}{}", prefix, message));
+        }
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        let problems = self.0.into_inner();
+        match problems.len() {
+            0 => Ok(()),
+            n => anyhow::bail!(
+                "invalid configuration ({} problem{}):\n  - {}",
+                n,
+                if n == 1 { "" } else { "s" },
+                problems.join("\n  - ")
+            ),
+        }
+    }
+}
+
+/// Reads instance-scoped variables, recording a problem instead of failing
+/// on the first bad one. Bad values read as their default so loading goes on.
+struct Reader<'a> {
+    instance: &'a str,
+    problems: Problems,
+}
+
+impl Reader<'_> {
+    /// Set and non-empty value.
+    fn string(&self, key: &str) -> Option<String> {
+        env_var(self.instance, key).ok().filter(|v| !v.is_empty())
+    }
+
+    fn parse<T>(&self, key: &str, value: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        match value.trim().parse::<T>() {
+            Ok(v) => Some(v),
+            Err(e) => {
+                self.problems.add(key, format!("{:?}: {}", value, e));
+                None
+            }
+        }
+    }
+
+    fn required<T>(&self, key: &str) -> T
+    where
+        T: FromStr + Default,
+        T::Err: fmt::Display,
+    {
+        match self.string(key) {
+            Some(v) => self.parse(key, &v).unwrap_or_default(),
+            None => {
+                self.problems.add(key, "required but not set");
+                T::default()
+            }
+        }
+    }
+
+    fn or<T>(&self, key: &str, default: T) -> T
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        match env_var(self.instance, key) {
+            Ok(v) => self.parse(key, &v).unwrap_or(default),
+            Err(_) => default,
+        }
+    }
+
+    fn optional<T>(&self, key: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.string(key).and_then(|v| self.parse(key, &v))
+    }
+
+    fn checked_address(&self, key: &str, value: &str) -> Address {
+        parse_address(value).unwrap_or_else(|e| {
+            self.problems.add(key, e);
+            Address::zero()
+        })
+    }
+
+    fn address(&self, key: &str) -> Address {
+        match self.string(key) {
+            Some(v) => self.checked_address(key, &v),
+            None => {
+                self.problems.add(key, "required but not set");
+                Address::zero()
+            }
+        }
+    }
+
+    fn address_or(&self, key: &str, default: &str) -> Address {
+        self.checked_address(
+            key,
+            &self.string(key).unwrap_or_else(|| default.to_string()),
+        )
+    }
+
+    fn optional_address(&self, key: &str) -> Option<Address> {
+        self.string(key).map(|v| self.checked_address(key, &v))
+    }
+
+    fn address_list(&self, key: &str) -> Vec<Address> {
+        env_list(self.instance, key)
+            .iter()
+            .map(|v| self.checked_address(key, v))
+            .collect()
+    }
+
+    /// Like [`Self::address_list`], but known symbols (`WETH`, `USDC`) work too.
+    fn token_list(&self, key: &str) -> Vec<Address> {
+        env_list(self.instance, key)
+            .iter()
+            .map(|v| match tokens::by_name(v.trim()) {
+                Some(token) => token.address(),
+                None => self.checked_address(key, v),
+            })
+            .collect()
+    }
+
+    /// Value of `key`, or of `alias` when only that is set, with the name read.
+    fn aliased(&self, key: &str, alias: Option<&str>) -> Option<(String, String)> {
+        std::iter::once(key)
+            .chain(alias)
+            .find_map(|k| self.string(k).map(|v| (k.to_string(), v)))
+    }
+
+    /// Duration from `key` or, for TOML-friendly names, `key` without its
+    /// `_SECS`/`_MS` suffix (`POLL_INTERVAL = "15s"`). `None` when unset.
+    fn duration(&self, key: &str, bare: Duration) -> Option<Duration> {
+        let alias = key
+            .strip_suffix("_SECS")
+            .or_else(|| key.strip_suffix("_MS"));
+        let (used, value) = self.aliased(key, alias)?;
+        match units::parse_duration(&value, bare) {
+            Ok(duration) => Some(duration),
+            Err(e) => {
+                self.problems.add(&used, format!("{:#}", e));
+                None
+            }
+        }
+    }
+
+    /// Whole seconds; bare numbers are seconds.
+    fn secs(&self, key: &str, default: u64) -> u64 {
+        match self.duration(key, Duration::from_secs(1)) {
+            Some(d) if d.subsec_nanos() == 0 => d.as_secs(),
+            Some(d) => {
+                self.problems
+                    .add(key, format!("{:?} is not a whole number of seconds", d));
+                default
+            }
+            None => default,
+        }
+    }
+
+    fn required_secs(&self, key: &str) -> u64 {
+        if self.aliased(key, key.strip_suffix("_SECS")).is_none() {
+            self.problems.add(key, "required but not set");
+        }
+        self.secs(key, 0)
+    }
+
+    /// Whole milliseconds; bare numbers are milliseconds.
+    fn millis(&self, key: &str, default: u64) -> u64 {
+        self.duration(key, Duration::from_millis(1))
+            .map_or(default, |d| d.as_millis() as u64)
+    }
+
+    /// Amount of `token` in raw units; see [`parse_amount`].
+    fn amount(&self, key: &str, value: &str, token: Address) -> U256 {
+        parse_amount(value, token).unwrap_or_else(|e| {
+            self.problems.add(key, e);
+            U256::zero()
+        })
+    }
+
+    /// Cron expression from `key`, or `default` when unset.
+    fn cron(&self, key: &str, default: &str) -> Cron {
+        let expr = env::var(key).unwrap_or_else(|_| default.to_string());
+        Cron::parse(&expr).unwrap_or_else(|e| {
+            self.problems.add(key, format!("{:#}", e));
+            Cron::parse(default).expect("default cron expressions are valid")
+        })
+    }
+}
+
+/// `true`/`1`/`yes` enable a flag; anything else (or unset) leaves it off.
+fn env_flag(key: &str) -> bool {
+    env::var(key)
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "true" | "1" | "yes"))
+        .unwrap_or(false)
+}
+
+/// Instance-scoped variable: `{INSTANCE}_{KEY}` wins over the shared `KEY`.
+fn env_var(instance: &str, key: &str) -> Result<String, env::VarError> {
+    if instance != DEFAULT_INSTANCE {
+        if let Ok(v) = env::var(format!("{}_{}", instance.to_ascii_uppercase(), key)) {
+            return Ok(v);
+        }
+    }
+    env::var(key)
+}
+
+/// Comma-separated optional variable; unset means an empty list.
+fn env_list(instance: &str, key: &str) -> Vec<String> {
+    env_var(instance, key)
+        .map(|v| {
+            v.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,100 @@
+//! Liquidity-weighted consensus price and per-DEX deviation
+//!
+//! With three or more DEXes quoting a pair, each cycle's price samples are
+//! compared with a consensus price: the mean of the DEX prices weighted by
+//! the depth their quotes imply. Depth is read from price impact between two
+//! trade sizes (a constant-product pool of depth R moves its price by about
+//! `size / R`), so DEXes quoted at a single size weigh the average of the
+//! others. Each sample stores its deviation in bps, and
+//! `/api/stats/deviations` averages them per DEX to expose venues that are
+//! systematically off.
+
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+/// Fewest quoting DEXes for which a consensus is meaningful.
+pub const MIN_SOURCES: usize = 3;
+
+/// Depth (in token_in) implied by the price drop from `small` to `large`,
+/// each given as (size, amount_out); `None` without measurable impact.
+pub fn implied_depth(small: (f64, f64), large: (f64, f64)) -> Option<f64> {
+    let (p_small, p_large) = (small.1 / small.0, large.1 / large.0);
+    let impact = (p_small - p_large) / p_small;
+    (large.0 > small.0 && impact > 0.0).then_some((large.0 - small.0) / impact)
+}
+
+/// Depth-weighted mean of `(price, depth)`; a DEX without a depth weighs the
+/// average depth of the others (or all weigh equally when none has one).
+pub fn consensus(prices: &[(f64, Option<f64>)]) -> Option<f64> {
+    if prices.len() < MIN_SOURCES {
+        return None;
+    }
+    let depths: Vec<f64> = prices.iter().filter_map(|p| p.1).collect();
+    let fallback = if depths.is_empty() {
+        1.0
+    } else {
+        depths.iter().sum::<f64>() / depths.len() as f64
+    };
+    let (mut weighted, mut total) = (0.0, 0.0);
+    for &(price, depth) in prices {
+        let weight = depth.unwrap_or(fallback);
+        weighted += price * weight;
+        total += weight;
+    }
+    (total > 0.0).then_some(weighted / total)
+}
+
+pub fn deviation_bps(price: f64, consensus: f64) -> f64 {
+    (price - consensus) / consensus * 10_000.0
+}
+
+#[derive(Deserialize)]
+pub struct DeviationParams {
+    /// Look-back window in hours (default 24).
+    pub hours: Option<u32>,
+    pub instance: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct DexDeviation {
+    pub instance: String,
+    pub pair: Option<String>,
+    pub dex: String,
+    pub samples: i64,
+    /// Signed mean: positive means the DEX pays more token_out than consensus.
+    pub avg_deviation_bps: f64,
+    pub avg_abs_deviation_bps: f64,
+    pub max_abs_deviation_bps: f64,
+}
+
+/// Deviation from consensus per DEX in the window, most off first.
+pub fn load(
+    conn: &Connection,
+    now: DateTime<Utc>,
+    hours: u32,
+    instance: Option<&str>,
+) -> anyhow::Result<Vec<DexDeviation>> {
+    let since = (now - Duration::hours(hours as i64)).to_rfc3339();
+    let mut stmt = conn.prepare(
+        "SELECT instance, pair, dex, COUNT(*), AVG(deviation_bps), AVG(ABS(deviation_bps)),
+                MAX(ABS(deviation_bps))
+         FROM price_samples
+         WHERE timestamp >= ?1 AND (?2 IS NULL OR instance = ?2) AND deviation_bps IS NOT NULL
+         GROUP BY instance, pair, dex ORDER BY AVG(ABS(deviation_bps)) DESC",
+    )?;
+    let deviations = stmt
+        .query_map(params![since, instance], |row| {
+            Ok(DexDeviation {
+                instance: row.get(0)?,
+                pair: row.get(1)?,
+                dex: row.get(2)?,
+                samples: row.get(3)?,
+                avg_deviation_bps: row.get(4)?,
+                avg_abs_deviation_bps: row.get(5)?,
+                max_abs_deviation_bps: row.get(6)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(deviations)
+}
@@ -0,0 +1,39 @@
+//! Priority-fee model for contested opportunities
+//!
+//! A visible spread is usually chased by other bots, so winning it means
+//! outbidding them on priority fee. The bid is modelled as the highest
+//! `CONTEST_FEE_PERCENTILE` priority fee paid in each of the last
+//! `CONTEST_BLOCKS` blocks (`eth_feeHistory`), paid on `CONTEST_GAS_UNITS` gas.
+
+use ethers::providers::Middleware;
+use ethers::types::{BlockNumber, U256};
+
+pub struct ContestModel {
+    pub blocks: u64,
+    pub percentile: f64,
+    pub gas_units: u64,
+    /// Native gas token (POL) price in USDC.
+    pub native_price_usdc: f64,
+}
+
+impl ContestModel {
+    /// Priority fee in wei per gas needed to plausibly be included first.
+    pub async fn priority_fee<M: Middleware + 'static>(&self, client: &M) -> anyhow::Result<U256> {
+        let history = client
+            .fee_history(self.blocks, BlockNumber::Latest, &[self.percentile])
+            .await
+            .map_err(|e| anyhow::anyhow!("eth_feeHistory failed: {}", e))?;
+        Ok(history
+            .reward
+            .iter()
+            .filter_map(|rewards| rewards.first().copied())
+            .max()
+            .unwrap_or_default())
+    }
+
+    /// USDC cost of bidding `priority_fee` on the modelled gas usage.
+    pub fn cost_usdc(&self, priority_fee: U256) -> f64 {
+        let wei = priority_fee.saturating_mul(U256::from(self.gas_units));
+        crate::units::to_f64(wei, 18) * self.native_price_usdc
+    }
+}
@@ -0,0 +1,43 @@
+//! Contract bindings
+//!
+//! Generated from the ABI JSON files checked in under `abi/`, one binding per
+//! contract however many modules call it. To add a contract, drop its ABI
+//! (only the functions the bot calls are needed) into `abi/` and list it
+//! below. Each binding lives in its own submodule (`contracts::erc20`, …);
+//! the generated `*Call`/`*Return` types are reached through it when two
+//! contracts share a function name.
+
+use ethers::prelude::abigen;
+
+abigen!(
+    UniswapV2Router,
+    "abi/UniswapV2Router.json";
+    UniswapV2Factory,
+    "abi/UniswapV2Factory.json";
+    UniswapV2Pair,
+    "abi/UniswapV2Pair.json";
+    QuoterV2,
+    "abi/QuoterV2.json";
+    UniswapV3Factory,
+    "abi/UniswapV3Factory.json";
+    ERC20,
+    "abi/ERC20.json";
+    Multicall3,
+    "abi/Multicall3.json";
+    AggregatorV3,
+    "abi/AggregatorV3.json";
+    WooRouter,
+    "abi/WooRouter.json";
+    DodoPool,
+    "abi/DodoPool.json";
+    BalancerVault,
+    "abi/BalancerVault.json";
+    BalancerPool,
+    "abi/BalancerPool.json";
+    CurvePool,
+    "abi/CurvePool.json";
+    CurveCryptoPool,
+    "abi/CurveCryptoPool.json";
+    PerpMarket,
+    "abi/PerpMarket.json"
+);
@@ -0,0 +1,53 @@
+//! Request and cycle IDs for tracing an alert back to its origin
+//!
+//! Every API call runs under a request ID (the client's `X-Request-Id` when it
+//! sends a usable one, echoed in the response) and every detection cycle under
+//! a cycle ID. While one is in scope, log lines are prefixed with it and the
+//! rows written on its behalf (opportunities, rejections, cycle timings,
+//! audit entries) store it, so an opportunity's `cycle_id` leads to the log
+//! lines of the cycle and the RPC calls that found it.
+
+use ethers::core::rand::{thread_rng, RngCore};
+use ethers::utils::hex;
+use std::future::Future;
+
+tokio::task_local! {
+    static CURRENT: String;
+}
+
+/// Longest client-supplied request ID that is accepted as is.
+const MAX_ID_LEN: usize = 64;
+
+/// Fresh ID such as `cyc-1f2e3d4c5b6a`.
+pub fn new_id(prefix: &str) -> String {
+    let mut bytes = [0u8; 6];
+    thread_rng().fill_bytes(&mut bytes);
+    format!("{}-{}", prefix, hex::encode(bytes))
+}
+
+/// The client's request ID if it is short and plain enough to log and echo,
+/// a new one otherwise.
+pub fn request_id(supplied: Option<&str>) -> String {
+    match supplied {
+        Some(id)
+            if !id.is_empty()
+                && id.len() <= MAX_ID_LEN
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') =>
+        {
+            id.to_string()
+        }
+        _ => new_id("req"),
+    }
+}
+
+/// Runs `f` with `id` as the current request or cycle ID.
+pub async fn scope<F: Future>(id: String, f: F) -> F::Output {
+    CURRENT.scope(id, f).await
+}
+
+/// ID of the request or cycle the calling task is working on.
+pub fn current() -> Option<String> {
+    CURRENT.try_with(|id| id.clone()).ok()
+}
@@ -0,0 +1,701 @@
+//! SQLite schema and opportunity/price-sample persistence
+
+use crate::money::Money;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+/// Stored opportunity as read back from SQLite and passed to
+/// `ArbDetector::subscribe`, alerts and hooks. Amounts are exact; the JSON
+/// shape served to clients is [`crate::api::ApiOpportunity`].
+#[derive(Clone, Debug)]
+pub struct Opportunity {
+    pub id: i64,
+    pub timestamp: String,
+    pub strategy: String,
+    pub dex_buy: String,
+    pub dex_sell: String,
+    pub amount_in: Money,
+    /// token_out value of `amount_in` at the buying leg's price.
+    pub amount_out_buy: Money,
+    pub amount_out_sell: Money,
+    /// token_in bought back by the buying leg; unset for basis rows and rows
+    /// stored before it was kept.
+    pub amount_back: Option<Money>,
+    pub profit: f64,
+    /// Exact profit in token_out; unset on rows stored before it was kept.
+    pub profit_exact: Option<Money>,
+    pub instance: String,
+    /// Block the quotes were read at; unset on rows stored before it was kept.
+    pub block_number: Option<u64>,
+    /// RPC endpoints (scheme and host) that answered the cycle's requests up
+    /// to this opportunity, comma-separated; several when one failed over.
+    pub rpc_provider: Option<String>,
+    /// Profit after outbidding competing bots on priority fee, when modelled.
+    pub contested_profit: Option<f64>,
+    pub profit_usd: Option<f64>,
+    /// Where the USD price came from (`chainlink`, `binance`, `coinbase`).
+    pub usd_source: Option<String>,
+    /// Detection cycle that found it; its log lines carry the same ID.
+    pub cycle_id: Option<String>,
+    /// Token symbols of the pair, e.g. `WETH/USDC`.
+    pub pair: Option<String>,
+    /// USD value of `amount_in` (token_out taken at par without a USD price).
+    pub notional_usd: Option<f64>,
+    /// Profit over notional in basis points.
+    pub roi_bps: Option<f64>,
+    /// Output lost to the trade's own price impact on both legs, from V2 pool
+    /// reserves; unset unless both legs trade on V2 pools.
+    pub price_impact_bps: Option<f64>,
+    /// Round-trip size in token_in with the largest profit on the two pools'
+    /// reserves, with `OPTIMAL_TRADE_SIZE`; unset unless both legs are V2.
+    pub optimal_amount_in: Option<Money>,
+    /// Projected profit in token_out at `optimal_amount_in`, net of gas.
+    pub optimal_profit: Option<f64>,
+    /// Profit at fractions and multiples of the quoted size, from the V2 pool
+    /// reserves; unset unless both legs are V2.
+    pub size_curve: Option<Vec<SizePoint>>,
+    /// Price of the leg furthest from the Chainlink-implied price, relative
+    /// to it in basis points; unset without `ORACLE_CHECK` or a feed answer.
+    pub oracle_deviation: Option<f64>,
+    /// `DEX_PATHS` route of the buying and selling DEX (`WETH>WPOL>USDC`);
+    /// unset when the DEX swaps the pair directly.
+    pub path_buy: Option<String>,
+    pub path_sell: Option<String>,
+    /// Chance of landing within `INCLUSION_BLOCKS` blocks, when modelled.
+    pub inclusion_probability: Option<f64>,
+    pub expected_delay_secs: Option<f64>,
+    /// Profit weighted by the inclusion probability.
+    pub expected_profit: Option<f64>,
+}
+
+/// Price (token_out per token_in) one DEX quoted in a cycle.
+#[derive(Clone, Debug, Serialize)]
+pub struct PriceSample {
+    pub dex: String,
+    pub price: f64,
+    /// Deviation from the cycle's consensus price, when enough DEXes quoted.
+    pub deviation_bps: Option<f64>,
+}
+
+/// Projected round-trip profit at `size_pct` percent of the quoted size.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SizePoint {
+    pub size_pct: u32,
+    /// Decimal amount in token_in.
+    pub amount_in: String,
+    /// In token_out, net of the cycle's gas cost.
+    pub profit: f64,
+}
+
+/// Row written to the `opportunities` table.
+pub struct NewOpportunity {
+    pub instance: String,
+    pub strategy: &'static str,
+    pub dex_buy: String,
+    pub dex_sell: String,
+    pub amount_in: Money,
+    pub amount_out_buy: Money,
+    pub amount_out_sell: Money,
+    pub amount_back: Option<Money>,
+    pub profit: Money,
+    pub block_number: Option<u64>,
+    pub rpc_provider: Option<String>,
+    pub contested_profit: Option<f64>,
+    pub profit_usd: Option<f64>,
+    pub usd_source: Option<&'static str>,
+    pub cycle_id: Option<String>,
+    pub pair: Option<String>,
+    pub inclusion_probability: Option<f64>,
+    pub expected_delay_secs: Option<f64>,
+    pub expected_profit: Option<f64>,
+    pub notional_usd: f64,
+    pub roi_bps: f64,
+    pub price_impact_bps: Option<f64>,
+    pub optimal_amount_in: Option<Money>,
+    pub optimal_profit: Option<Money>,
+    pub size_curve: Option<Vec<SizePoint>>,
+    pub oracle_deviation: Option<f64>,
+    pub path_buy: Option<String>,
+    pub path_sell: Option<String>,
+}
+
+pub fn init_db(conn: &Connection) -> anyhow::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS opportunities (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            dex_buy TEXT NOT NULL,
+            dex_sell TEXT NOT NULL,
+            amount_in TEXT NOT NULL,
+            amount_out_buy TEXT NOT NULL,
+            amount_out_sell TEXT NOT NULL,
+            profit REAL NOT NULL,
+            strategy TEXT NOT NULL DEFAULT 'two_dex',
+            instance TEXT NOT NULL DEFAULT 'default',
+            contested_profit REAL,
+            profit_usd REAL,
+            usd_source TEXT
+        )",
+        [],
+    )?;
+    ensure_column(
+        conn,
+        "opportunities",
+        "strategy",
+        "TEXT NOT NULL DEFAULT 'two_dex'",
+    )?;
+    ensure_column(
+        conn,
+        "opportunities",
+        "instance",
+        "TEXT NOT NULL DEFAULT 'default'",
+    )?;
+    ensure_column(conn, "opportunities", "contested_profit", "REAL")?;
+    ensure_column(conn, "opportunities", "profit_usd", "REAL")?;
+    ensure_column(conn, "opportunities", "usd_source", "TEXT")?;
+    ensure_column(conn, "opportunities", "cycle_id", "TEXT")?;
+    ensure_column(conn, "opportunities", "inclusion_probability", "REAL")?;
+    ensure_column(conn, "opportunities", "expected_delay_secs", "REAL")?;
+    ensure_column(conn, "opportunities", "expected_profit", "REAL")?;
+    ensure_column(conn, "opportunities", "pair", "TEXT")?;
+    ensure_column(conn, "opportunities", "notional_usd", "REAL")?;
+    ensure_column(conn, "opportunities", "roi_bps", "REAL")?;
+    ensure_column(conn, "opportunities", "profit_exact", "TEXT")?;
+    ensure_column(conn, "opportunities", "price_impact_bps", "REAL")?;
+    ensure_column(conn, "opportunities", "optimal_amount_in", "TEXT")?;
+    ensure_column(conn, "opportunities", "optimal_profit", "REAL")?;
+    // Token decimals of the stored amounts, so they read back exactly.
+    ensure_column(conn, "opportunities", "decimals_in", "INTEGER")?;
+    ensure_column(conn, "opportunities", "decimals_out", "INTEGER")?;
+    ensure_column(conn, "opportunities", "block_number", "INTEGER")?;
+    ensure_column(conn, "opportunities", "rpc_provider", "TEXT")?;
+    // JSON array of `SizePoint`s.
+    ensure_column(conn, "opportunities", "size_curve", "TEXT")?;
+    ensure_column(conn, "opportunities", "oracle_deviation", "REAL")?;
+    ensure_column(conn, "opportunities", "path_buy", "TEXT")?;
+    ensure_column(conn, "opportunities", "path_sell", "TEXT")?;
+    ensure_column(conn, "opportunities", "amount_back", "TEXT")?;
+    // `/opportunities` filters by time and pair and pages newest first.
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_opportunities_timestamp ON opportunities (timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_opportunities_pair ON opportunities (pair, id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS price_samples (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            block_number INTEGER NOT NULL,
+            dex TEXT NOT NULL,
+            price REAL NOT NULL,
+            instance TEXT NOT NULL DEFAULT 'default'
+        )",
+        [],
+    )?;
+    ensure_column(
+        conn,
+        "price_samples",
+        "instance",
+        "TEXT NOT NULL DEFAULT 'default'",
+    )?;
+    ensure_column(conn, "price_samples", "deviation_bps", "REAL")?;
+    ensure_column(conn, "price_samples", "pair", "TEXT")?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_price_samples_block ON price_samples (block_number)",
+        [],
+    )?;
+    // `/spreads` and retention pruning scan by time.
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_price_samples_timestamp ON price_samples (timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS api_keys (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            key_hash TEXT NOT NULL UNIQUE,
+            scopes TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            revoked_at TEXT
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            actor_key_id INTEGER,
+            actor TEXT NOT NULL,
+            action TEXT NOT NULL,
+            payload TEXT NOT NULL
+        )",
+        [],
+    )?;
+    ensure_column(conn, "audit_log", "request_id", "TEXT")?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS watched_swaps (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            block_number INTEGER NOT NULL,
+            wallet TEXT NOT NULL,
+            tx_hash TEXT NOT NULL UNIQUE,
+            sent TEXT NOT NULL,
+            received TEXT NOT NULL,
+            touched_out INTEGER NOT NULL,
+            spread_bps REAL NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS cycle_timings (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            latency_ms REAL NOT NULL,
+            ok INTEGER NOT NULL,
+            instance TEXT NOT NULL DEFAULT 'default'
+        )",
+        [],
+    )?;
+    ensure_column(
+        conn,
+        "cycle_timings",
+        "instance",
+        "TEXT NOT NULL DEFAULT 'default'",
+    )?;
+    ensure_column(conn, "cycle_timings", "cycle_id", "TEXT")?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS rejections (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            instance TEXT NOT NULL,
+            reason TEXT NOT NULL,
+            dex_buy TEXT,
+            dex_sell TEXT,
+            amount_in REAL,
+            gross_profit REAL
+        )",
+        [],
+    )?;
+    ensure_column(conn, "rejections", "cycle_id", "TEXT")?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_rejections_timestamp ON rejections (timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS pair_gas (
+            token_in TEXT NOT NULL,
+            token_out TEXT NOT NULL,
+            gas_units REAL NOT NULL,
+            samples INTEGER NOT NULL,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (token_in, token_out)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS gas_top_ups (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            wallet TEXT NOT NULL,
+            balance_pol REAL NOT NULL,
+            amount_in TEXT NOT NULL,
+            expected_pol TEXT NOT NULL,
+            status TEXT NOT NULL,
+            tx_hash TEXT
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS liquidity_samples (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            instance TEXT NOT NULL,
+            pair TEXT,
+            dex TEXT NOT NULL,
+            block_number INTEGER NOT NULL,
+            reserve_in TEXT NOT NULL,
+            reserve_out TEXT NOT NULL,
+            liquidity REAL NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_liquidity_samples_pool
+         ON liquidity_samples (instance, pair, dex, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS executions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            instance TEXT NOT NULL,
+            pair TEXT,
+            cycle_id TEXT,
+            dex_sell TEXT NOT NULL,
+            dex_buy TEXT NOT NULL,
+            amount_in TEXT NOT NULL,
+            expected_in TEXT NOT NULL,
+            sell_tx TEXT,
+            buy_tx TEXT,
+            received_out TEXT,
+            realized_in TEXT,
+            status TEXT NOT NULL,
+            error TEXT
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS paper_balances (
+            instance TEXT NOT NULL,
+            token TEXT NOT NULL,
+            balance TEXT NOT NULL,
+            PRIMARY KEY (instance, token)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS paper_trades (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            instance TEXT NOT NULL,
+            pair TEXT,
+            cycle_id TEXT,
+            dex_sell TEXT NOT NULL,
+            dex_buy TEXT NOT NULL,
+            amount_in TEXT NOT NULL,
+            amount_back TEXT NOT NULL,
+            gas TEXT NOT NULL,
+            pnl REAL NOT NULL,
+            cumulative_pnl REAL NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS export_cursor (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            last_id INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS daily_rollups (
+            day TEXT NOT NULL,
+            instance TEXT NOT NULL,
+            pair TEXT NOT NULL DEFAULT '',
+            strategy TEXT NOT NULL,
+            opportunities INTEGER NOT NULL,
+            total_profit REAL NOT NULL,
+            max_profit REAL NOT NULL,
+            total_profit_usd REAL,
+            PRIMARY KEY (day, instance, pair, strategy)
+        )",
+        [],
+    )?;
+    // Downsampled price samples; `bucket` is the RFC 3339 start of the bar.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS price_bars (
+            resolution TEXT NOT NULL,
+            bucket TEXT NOT NULL,
+            instance TEXT NOT NULL,
+            pair TEXT NOT NULL DEFAULT '',
+            dex TEXT NOT NULL,
+            open REAL NOT NULL,
+            high REAL NOT NULL,
+            low REAL NOT NULL,
+            close REAL NOT NULL,
+            samples INTEGER NOT NULL,
+            PRIMARY KEY (resolution, bucket, instance, pair, dex)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS spread_bars (
+            resolution TEXT NOT NULL,
+            bucket TEXT NOT NULL,
+            instance TEXT NOT NULL,
+            pair TEXT NOT NULL DEFAULT '',
+            open REAL NOT NULL,
+            high REAL NOT NULL,
+            low REAL NOT NULL,
+            close REAL NOT NULL,
+            samples INTEGER NOT NULL,
+            PRIMARY KEY (resolution, bucket, instance, pair)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Adds `column` to `table` on databases created before the column existed.
+fn ensure_column(conn: &Connection, table: &str, column: &str, decl: &str) -> anyhow::Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .any(|name| name.map(|n| n == column).unwrap_or(false));
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+pub const OPPORTUNITY_COLUMNS: &str =
+    "id, timestamp, strategy, dex_buy, dex_sell, amount_in, amount_out_buy, amount_out_sell, profit, instance, contested_profit, profit_usd, usd_source, cycle_id, inclusion_probability, expected_delay_secs, expected_profit, pair, notional_usd, roi_bps, profit_exact, price_impact_bps, optimal_amount_in, optimal_profit, decimals_in, decimals_out, block_number, rpc_provider, size_curve, oracle_deviation, path_buy, path_sell, amount_back";
+
+/// A stored decimal amount at the row's token decimals, or at the digits it
+/// has when those are more (rows stored before the decimals were kept, and
+/// basis quotes in the other token), which reads back exactly either way.
+fn stored_amount(
+    row: &rusqlite::Row,
+    idx: usize,
+    decimals: Option<u32>,
+) -> rusqlite::Result<Money> {
+    let text: String = row.get(idx)?;
+    let digits = text
+        .split_once('.')
+        .map_or(0, |(_, frac)| frac.len() as u32);
+    let decimals = decimals.unwrap_or(0).max(digits);
+    Money::parse(&text, decimals).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, e.into())
+    })
+}
+
+pub fn opportunity_from_row(row: &rusqlite::Row) -> rusqlite::Result<Opportunity> {
+    let decimals_in: Option<u32> = row.get(24)?;
+    let decimals_out: Option<u32> = row.get(25)?;
+    let profit_exact = match row.get_ref(20)? {
+        rusqlite::types::ValueRef::Null => None,
+        _ => Some(stored_amount(row, 20, decimals_out)?),
+    };
+    let optimal_amount_in = match row.get_ref(22)? {
+        rusqlite::types::ValueRef::Null => None,
+        _ => Some(stored_amount(row, 22, decimals_in)?),
+    };
+    let amount_back = match row.get_ref(32)? {
+        rusqlite::types::ValueRef::Null => None,
+        _ => Some(stored_amount(row, 32, decimals_in)?),
+    };
+    Ok(Opportunity {
+        id: row.get(0)?,
+        timestamp: row.get(1)?,
+        strategy: row.get(2)?,
+        dex_buy: row.get(3)?,
+        dex_sell: row.get(4)?,
+        amount_in: stored_amount(row, 5, decimals_in)?,
+        amount_out_buy: stored_amount(row, 6, decimals_out)?,
+        amount_out_sell: stored_amount(row, 7, decimals_out)?,
+        amount_back,
+        profit: row.get(8)?,
+        instance: row.get(9)?,
+        block_number: row.get(26)?,
+        rpc_provider: row.get(27)?,
+        size_curve: row
+            .get::<_, Option<String>>(28)?
+            .and_then(|curve| serde_json::from_str(&curve).ok()),
+        oracle_deviation: row.get(29)?,
+        path_buy: row.get(30)?,
+        path_sell: row.get(31)?,
+        contested_profit: row.get(10)?,
+        profit_usd: row.get(11)?,
+        usd_source: row.get(12)?,
+        cycle_id: row.get(13)?,
+        inclusion_probability: row.get(14)?,
+        expected_delay_secs: row.get(15)?,
+        expected_profit: row.get(16)?,
+        pair: row.get(17)?,
+        notional_usd: row.get(18)?,
+        roi_bps: row.get(19)?,
+        profit_exact,
+        price_impact_bps: row.get(21)?,
+        optimal_amount_in,
+        optimal_profit: row.get(23)?,
+    })
+}
+
+/// Inserts an opportunity and returns it as stored.
+pub fn insert_opportunity(
+    conn: &Connection,
+    now: DateTime<Utc>,
+    opp: &NewOpportunity,
+) -> anyhow::Result<Opportunity> {
+    let ts = now.to_rfc3339();
+    conn.execute(
+        "INSERT INTO opportunities (timestamp, strategy, dex_buy, dex_sell, amount_in, amount_out_buy, amount_out_sell, profit, instance, contested_profit, profit_usd, usd_source, cycle_id, inclusion_probability, expected_delay_secs, expected_profit, pair, notional_usd, roi_bps, profit_exact, price_impact_bps, optimal_amount_in, optimal_profit, decimals_in, decimals_out, block_number, rpc_provider, size_curve, oracle_deviation, path_buy, path_sell, amount_back) 
+         VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15,?16,?17,?18,?19,?20,?21,?22,?23,?24,?25,?26,?27,?28,?29,?30,?31,?32)",
+        params![
+            ts,
+            opp.strategy,
+            opp.dex_buy,
+            opp.dex_sell,
+            opp.amount_in.to_string(),
+            opp.amount_out_buy.to_string(),
+            opp.amount_out_sell.to_string(),
+            opp.profit.to_f64(),
+            opp.instance,
+            opp.contested_profit,
+            opp.profit_usd,
+            opp.usd_source,
+            opp.cycle_id,
+            opp.inclusion_probability,
+            opp.expected_delay_secs,
+            opp.expected_profit,
+            opp.pair,
+            opp.notional_usd,
+            opp.roi_bps,
+            opp.profit.to_string(),
+            opp.price_impact_bps,
+            opp.optimal_amount_in.map(|amount| amount.to_string()),
+            opp.optimal_profit.map(|profit| profit.to_f64()),
+            opp.amount_in.decimals(),
+            opp.profit.decimals(),
+            opp.block_number,
+            opp.rpc_provider,
+            opp.size_curve
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()?,
+            opp.oracle_deviation,
+            opp.path_buy,
+            opp.path_sell,
+            opp.amount_back.map(|amount| amount.to_string())
+        ],
+    )?;
+    Ok(Opportunity {
+        id: conn.last_insert_rowid(),
+        timestamp: ts,
+        strategy: opp.strategy.to_string(),
+        dex_buy: opp.dex_buy.clone(),
+        dex_sell: opp.dex_sell.clone(),
+        amount_in: opp.amount_in,
+        amount_out_buy: opp.amount_out_buy,
+        amount_out_sell: opp.amount_out_sell,
+        amount_back: opp.amount_back,
+        profit: opp.profit.to_f64(),
+        profit_exact: Some(opp.profit),
+        instance: opp.instance.clone(),
+        block_number: opp.block_number,
+        rpc_provider: opp.rpc_provider.clone(),
+        contested_profit: opp.contested_profit,
+        profit_usd: opp.profit_usd,
+        usd_source: opp.usd_source.map(str::to_string),
+        cycle_id: opp.cycle_id.clone(),
+        inclusion_probability: opp.inclusion_probability,
+        expected_delay_secs: opp.expected_delay_secs,
+        expected_profit: opp.expected_profit,
+        pair: opp.pair.clone(),
+        notional_usd: Some(opp.notional_usd),
+        roi_bps: Some(opp.roi_bps),
+        price_impact_bps: opp.price_impact_bps,
+        optimal_amount_in: opp.optimal_amount_in,
+        optimal_profit: opp.optimal_profit.map(|profit| profit.to_f64()),
+        size_curve: opp.size_curve.clone(),
+        oracle_deviation: opp.oracle_deviation,
+        path_buy: opp.path_buy.clone(),
+        path_sell: opp.path_sell.clone(),
+    })
+}
+
+/// Stores one price sample per DEX for a cycle.
+pub fn insert_price_samples(
+    conn: &Connection,
+    instance: &str,
+    pair: Option<&str>,
+    block_number: u64,
+    samples: &[PriceSample],
+    now: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let ts = now.to_rfc3339();
+    let mut stmt = conn.prepare(
+        "INSERT INTO price_samples (timestamp, block_number, dex, price, instance, deviation_bps, pair)
+         VALUES (?1,?2,?3,?4,?5,?6,?7)",
+    )?;
+    for sample in samples {
+        stmt.execute(params![
+            ts,
+            block_number,
+            sample.dex,
+            sample.price,
+            instance,
+            sample.deviation_bps,
+            pair
+        ])?;
+    }
+    Ok(())
+}
+
+/// What `/opportunities/top` ranks by.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TopBy {
+    #[default]
+    Profit,
+    Roi,
+    ProfitUsd,
+    ExpectedProfit,
+}
+
+impl TopBy {
+    fn column(self) -> &'static str {
+        match self {
+            TopBy::Profit => "profit",
+            TopBy::Roi => "roi_bps",
+            TopBy::ProfitUsd => "profit_usd",
+            TopBy::ExpectedProfit => "expected_profit",
+        }
+    }
+}
+
+/// The `n` opportunities since `since` ranking highest by `by`; rows without
+/// a value to rank by are left out.
+pub fn top_opportunities(
+    conn: &Connection,
+    since: DateTime<Utc>,
+    by: TopBy,
+    n: usize,
+    instance: Option<&str>,
+    pair: Option<&str>,
+) -> anyhow::Result<Vec<Opportunity>> {
+    let column = by.column();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM opportunities WHERE timestamp >= ?1 AND {} IS NOT NULL
+         AND (?2 IS NULL OR instance = ?2) AND (?3 IS NULL OR pair = ?3)
+         ORDER BY {} DESC, id DESC LIMIT ?4",
+        OPPORTUNITY_COLUMNS, column, column
+    ))?;
+    let rows = stmt
+        .query_map(
+            params![since.to_rfc3339(), instance, pair, n as i64],
+            opportunity_from_row,
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Cross-DEX spread in bps of the pair's last `limit` sampled blocks in the
+/// instance, oldest first.
+pub fn load_recent_spreads(
+    conn: &Connection,
+    instance: &str,
+    pair: Option<&str>,
+    limit: usize,
+) -> anyhow::Result<Vec<f64>> {
+    let mut stmt = conn.prepare(
+        "SELECT (MAX(price) - MIN(price)) / MIN(price) * 10000.0 FROM price_samples
+         WHERE instance = ?1 AND pair IS ?2 GROUP BY block_number
+         HAVING COUNT(*) >= 2 AND MIN(price) > 0
+         ORDER BY block_number DESC LIMIT ?3",
+    )?;
+    let mut spreads = stmt
+        .query_map(params![instance, pair, limit as i64], |row| {
+            row.get::<_, f64>(0)
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    spreads.reverse();
+    Ok(spreads)
+}
@@ -0,0 +1,1889 @@
+//! Detection cycle: quotes every configured DEX, simulates round trips and
+//! stores the opportunities that clear the profit threshold.
+
+use crate::amounts::{self, TokenInfo};
+use crate::api::ApiOpportunity;
+use crate::basis::BasisMonitor;
+use crate::buffer::{StorageEvent, WriteBuffer};
+use crate::clock::{Clock, SystemClock};
+use crate::config::Config;
+use crate::consensus;
+use crate::contest::ContestModel;
+use crate::correlation;
+use crate::db::{load_recent_spreads, NewOpportunity, Opportunity, PriceSample, SizePoint};
+use crate::dex::pmm::{Dodo, WooFi};
+use crate::dex::reserves::{self, ReserveReader, Reserves};
+use crate::dex::v2::V2Router;
+use crate::dex::v3::TierCache;
+use crate::dex::{self, BlockRef, DexQuoter, Quote};
+use crate::diagnostics::{self, CycleTrace, QuoteTrace};
+use crate::execution::{ExecutedTrade, ExecutionMode, Executor, TradePlan};
+use crate::gas;
+use crate::heads::{self, NewHeads};
+use crate::heatmap;
+use crate::hooks::Hooks;
+use crate::inclusion::{Inclusion, InclusionModel};
+use crate::influx::InfluxSink;
+use crate::jobs::JOBS;
+use crate::metrics::{self, METRICS};
+use crate::money::Money;
+use crate::oracle::{self, Oracle};
+use crate::pair_symbols;
+use crate::paper::{self, PaperOrder};
+use crate::planner::{Coalescer, Planner, QuoteRequest};
+use crate::prices::{LivePrices, PriceSnapshot};
+use crate::quotecheck::{self, QUOTE_CHECKS};
+use crate::rejections::{self, Candidate, Reason};
+use crate::rpc;
+use crate::share::ShareLinks;
+use crate::sink::NdjsonSink;
+use crate::stats::SpreadStats;
+use crate::strategy::Strategies;
+use crate::suspension::SUSPENSIONS;
+use crate::tokens;
+use crate::triangular;
+use crate::units;
+use crate::usd::{CexTicker, DexRoute, UsdPrice, UsdPricer};
+use crate::warmup::Warmup;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use ethers::providers::Middleware;
+use ethers::types::U256;
+use futures::stream::{self, Stream};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+/// Stored opportunities buffered per subscriber before it starts lagging.
+const SUBSCRIBER_BUFFER: usize = 256;
+/// Sizes, in percent of the quoted one, an opportunity's profit is projected at.
+const SIZE_CURVE_PCTS: &[u32] = &[25, 50, 100, 200];
+
+tokio::task_local! {
+    /// Set while a cycle runs through [`ArbDetector::run_once`].
+    static ON_REQUEST: ();
+}
+
+/// Whether the current cycle was requested rather than scheduled.
+fn on_request() -> bool {
+    ON_REQUEST.try_with(|_| ()).is_ok()
+}
+
+/// What a requested cycle ([`ArbDetector::run_once`]) quoted and stored.
+#[derive(Serialize)]
+pub struct CycleReport {
+    pub instance: String,
+    pub pair: Option<String>,
+    pub cycle_id: String,
+    pub latency_ms: f64,
+    /// Why the cycle failed, if it did.
+    pub error: Option<String>,
+    pub quotes: Vec<QuoteTrace>,
+    pub opportunities: Vec<ApiOpportunity>,
+}
+
+/// Everything a detection cycle needs, built once at startup.
+pub struct ArbDetector<M> {
+    cfg: Config,
+    conn: Arc<Mutex<Connection>>,
+    client: Arc<M>,
+    planner: Planner<M>,
+    reserves: ReserveReader<M>,
+    dexes: Vec<Box<dyn DexQuoter>>,
+    /// Multi-hop cycles quoted by the triangular strategy.
+    triangles: Vec<Box<dyn DexQuoter>>,
+    basis: Option<BasisMonitor<M>>,
+    spread_stats: Mutex<SpreadStats>,
+    v3_tiers: Mutex<TierCache>,
+    warmup: Mutex<Warmup>,
+    links: Arc<ShareLinks>,
+    token_in: TokenInfo,
+    token_out: TokenInfo,
+    /// Pair symbols stored with every row, e.g. `WETH/USDC`.
+    pair: Option<String>,
+    opportunities: broadcast::Sender<Opportunity>,
+    hooks: Hooks,
+    clock: Arc<dyn Clock>,
+    trace: Mutex<CycleTrace>,
+    contest: Option<ContestModel>,
+    inclusion: Option<InclusionModel>,
+    sink: Option<Arc<NdjsonSink>>,
+    buffer: Mutex<WriteBuffer>,
+    strategies: Arc<Strategies>,
+    usd: Option<UsdPricer<M>>,
+    /// Chainlink feeds of both tokens, with `ORACLE_CHECK`.
+    oracle: Option<Oracle<M>>,
+    influx: Option<Arc<InfluxSink>>,
+    heads: Option<NewHeads>,
+    live_prices: Option<Arc<LivePrices>>,
+    shutdown: CancellationToken,
+    executor: Option<Arc<Executor<M>>>,
+}
+
+impl<M: Middleware + 'static> ArbDetector<M> {
+    /// Loads the DEXes, feeds and rolling statistics of one bot instance.
+    pub async fn new(
+        cfg: Config,
+        provider: &Arc<M>,
+        conn: &Arc<Mutex<Connection>>,
+        links: &Arc<ShareLinks>,
+    ) -> anyhow::Result<Self> {
+        log::info!(
+            "Starting Polygon Arb Bot [{}] | Poll every {}s | Min profit {} USDC",
+            cfg.instance,
+            cfg.poll_interval_secs,
+            cfg.min_profit_usdc
+        );
+
+        let pair = match &cfg.pair {
+            Some(pair) => Some(pair.clone()),
+            None => pair_symbols(Arc::clone(provider), cfg.token_in, cfg.token_out).await,
+        };
+
+        // Seed the spread statistics from recent history instead of warming up from scratch.
+        let mut spread_stats = SpreadStats::new(cfg.spread_window);
+        for spread in load_recent_spreads(
+            &conn.lock().unwrap(),
+            &cfg.instance,
+            pair.as_deref(),
+            cfg.spread_window,
+        )? {
+            spread_stats.push(spread);
+        }
+        log::info!(
+            "[{}] Seeded {} spread statistics with {} samples from the database",
+            cfg.instance,
+            pair.as_deref().unwrap_or("pair"),
+            spread_stats.sample_count()
+        );
+
+        let mut dexes: Vec<Box<dyn DexQuoter>> = cfg
+            .dexes
+            .iter()
+            .map(|(name, router)| match cfg.dex_path(name) {
+                Some(path) => {
+                    let mut label: Vec<String> = path.iter().map(|&t| tokens::label(t)).collect();
+                    if path.first() != Some(&cfg.token_in) {
+                        label.reverse();
+                    }
+                    log::info!(
+                        "[{}] {} quotes along {}",
+                        cfg.instance,
+                        name,
+                        label.join(" → ")
+                    );
+                    Box::new(V2Router::with_path(
+                        name,
+                        *router,
+                        path.to_vec(),
+                        label.join(">"),
+                    )) as Box<dyn DexQuoter>
+                }
+                None => Box::new(V2Router::new(name, *router)) as Box<dyn DexQuoter>,
+            })
+            .collect();
+        for fork in dex::v2::load_forks(
+            Arc::clone(provider),
+            &cfg.v2_forks,
+            cfg.token_in,
+            cfg.token_out,
+        )
+        .await?
+        {
+            dexes.push(Box::new(fork));
+        }
+        for pool in dex::v3::load_pools(
+            Arc::clone(provider),
+            cfg.v3_quoter,
+            cfg.v3_factory,
+            &cfg.v3_pools,
+            cfg.token_in,
+            cfg.token_out,
+        )
+        .await?
+        {
+            dexes.push(Box::new(pool));
+        }
+        if let Some(router) = cfg.woofi_router {
+            dexes.push(Box::new(WooFi::new(router)));
+        }
+        for pool in &cfg.dodo_pools {
+            match Dodo::load(Arc::clone(provider), *pool, cfg.token_in, cfg.token_out).await {
+                Ok(Some(dodo)) => dexes.push(Box::new(dodo)),
+                Ok(None) => log::warn!(
+                    "DODO pool {:?} does not trade the configured pair, skipping",
+                    pool
+                ),
+                Err(e) => log::warn!("Could not load DODO pool {:?}: {:?}", pool, e),
+            }
+        }
+        dexes.extend(
+            dex::stable::load_pools(
+                Arc::clone(provider),
+                &cfg.stable_pools,
+                cfg.token_in,
+                cfg.token_out,
+            )
+            .await,
+        );
+
+        let triangles: Vec<Box<dyn DexQuoter>> =
+            triangular::routes(&dexes, cfg.token_in, &cfg.triangular_tokens)
+                .into_iter()
+                .map(|route| Box::new(route) as Box<dyn DexQuoter>)
+                .collect();
+        if !cfg.triangular_tokens.is_empty() {
+            log::info!("Quoting {} triangular cycles", triangles.len());
+        }
+
+        let basis = match cfg.perp_market {
+            Some(market) => Some(
+                BasisMonitor::new(Arc::clone(provider), market, cfg.basis_min_bps)
+                    .await
+                    .context("Failed to read the perp market")?,
+            ),
+            None => None,
+        };
+
+        // Read from the tokens rather than assumed: a wrong guess would scale
+        // every quote and profit.
+        let token_in = amounts::load(Arc::clone(provider), cfg.token_in).await?;
+        let token_out = amounts::load(Arc::clone(provider), cfg.token_out).await?;
+
+        let contest = cfg.native_price_usdc.map(|native_price_usdc| ContestModel {
+            blocks: cfg.contest_blocks,
+            percentile: cfg.contest_fee_percentile,
+            gas_units: cfg.contest_gas_units,
+            native_price_usdc,
+        });
+        let inclusion = cfg.inclusion_blocks.map(|blocks| InclusionModel {
+            blocks,
+            priority_fee: U256::from((cfg.inclusion_priority_fee_gwei * 1e9) as u128),
+        });
+
+        let usd = if cfg.usd_price_feed.is_some()
+            || !cfg.usd_price_route.is_empty()
+            || cfg.usd_fallback_ticker.is_some()
+        {
+            let ticker = cfg
+                .usd_fallback_ticker
+                .as_deref()
+                .map(CexTicker::parse)
+                .transpose()?;
+            let route = match (cfg.usd_price_route.is_empty(), cfg.dexes.first()) {
+                (false, Some((_, router))) => Some(DexRoute::new(
+                    Arc::clone(provider),
+                    *router,
+                    &token_out,
+                    &cfg.usd_price_route,
+                )),
+                _ => None,
+            };
+            Some(
+                UsdPricer::new(
+                    Arc::clone(provider),
+                    cfg.usd_price_feed,
+                    route,
+                    ticker,
+                    cfg.usd_feed_max_age_secs,
+                )
+                .await?,
+            )
+        } else {
+            if !tokens::is_stablecoin(cfg.token_out) {
+                log::warn!(
+                    "[{}] {} is not a USD stablecoin and no USD price source is set: MIN_PROFIT_USDC and gas are taken as token_out amounts; set USD_PRICE_ROUTE or USD_PRICE_FEED",
+                    cfg.instance,
+                    token_out.label("token_out")
+                );
+            }
+            None
+        };
+
+        let oracle = if cfg.oracle_check {
+            let oracle = Oracle::new(
+                Arc::clone(provider),
+                cfg.token_in,
+                cfg.token_out,
+                &cfg.oracle_feeds,
+                cfg.usd_feed_max_age_secs,
+            )
+            .await?;
+            if oracle.is_none() {
+                log::warn!(
+                    "[{}] No Chainlink USD feed for both tokens of {}; set ORACLE_FEEDS to check its prices",
+                    cfg.instance,
+                    pair.as_deref().unwrap_or("the pair")
+                );
+            }
+            oracle
+        } else {
+            None
+        };
+
+        let buffer = WriteBuffer::new(
+            &cfg.instance,
+            pair.clone(),
+            cfg.db_flush_records,
+            cfg.db_flush_secs,
+            cfg.db_buffer_max_rows,
+        );
+        let planner = Planner::new(
+            Arc::clone(provider),
+            cfg.multicall_batch_size,
+            cfg.rpc_budget_per_block,
+        )?;
+        let v3_tiers = Mutex::new(TierCache::new(cfg.v3_full_sweep_every));
+        let warmup = Mutex::new(Warmup::new(cfg.warmup_cycles));
+        Ok(Self {
+            cfg,
+            conn: Arc::clone(conn),
+            client: Arc::clone(provider),
+            planner,
+            reserves: ReserveReader::new(Arc::clone(provider)),
+            dexes,
+            triangles,
+            basis,
+            spread_stats: Mutex::new(spread_stats),
+            v3_tiers,
+            warmup,
+            links: Arc::clone(links),
+            token_in,
+            token_out,
+            pair,
+            opportunities: broadcast::channel(SUBSCRIBER_BUFFER).0,
+            hooks: Hooks::default(),
+            clock: Arc::new(SystemClock),
+            trace: Mutex::new(CycleTrace::default()),
+            contest,
+            inclusion,
+            sink: None,
+            buffer: Mutex::new(buffer),
+            strategies: Arc::new(Strategies::default()),
+            usd,
+            oracle,
+            influx: None,
+            heads: None,
+            live_prices: None,
+            shutdown: CancellationToken::new(),
+            executor: None,
+        })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.cfg
+    }
+
+    /// Names of the DEXes this detector quotes, in configuration order.
+    pub fn dex_names(&self) -> Vec<&str> {
+        self.dexes.iter().map(|dex| dex.name()).collect()
+    }
+
+    /// Label of the pair this detector quotes, when it runs one of several.
+    pub fn pair(&self) -> Option<&str> {
+        self.pair.as_deref()
+    }
+
+    /// Replaces the wall clock, e.g. with a `MockClock` to step cycles deterministically.
+    pub fn with_clock(&mut self, clock: Arc<dyn Clock>) -> &mut Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Also writes every stored opportunity and price sample to `sink`.
+    pub fn with_sink(&mut self, sink: Arc<NdjsonSink>) -> &mut Self {
+        self.sink = Some(sink);
+        self
+    }
+
+    /// Also writes every price sample and spread to InfluxDB.
+    pub fn with_influx(&mut self, influx: Arc<InfluxSink>) -> &mut Self {
+        self.influx = Some(influx);
+        self
+    }
+
+    /// Also publishes each cycle's per-DEX prices, e.g. for `/ws/prices`.
+    pub fn with_live_prices(&mut self, prices: Arc<LivePrices>) -> &mut Self {
+        self.live_prices = Some(prices);
+        self
+    }
+
+    /// Stops [`ArbDetector::run_cycles`] once `token` is cancelled: the cycle
+    /// in flight finishes and the buffered rows are written before it returns.
+    pub fn with_shutdown(&mut self, token: CancellationToken) -> &mut Self {
+        self.shutdown = token;
+        self
+    }
+
+    /// Runs a cycle per new block instead of every `poll_interval_secs`,
+    /// which stays the fallback while no heads arrive.
+    pub fn with_new_heads(&mut self, heads: NewHeads) -> &mut Self {
+        self.heads = Some(heads);
+        self
+    }
+
+    /// Merges this detector's multicall batches with those of the other
+    /// detectors quoting the same block.
+    pub fn with_coalescer(&mut self, coalescer: Arc<Coalescer<M>>) -> &mut Self {
+        self.planner.with_coalescer(coalescer);
+        self
+    }
+
+    /// Executes two-DEX opportunities on V2 routers (`EXECUTION_MODE=live`).
+    pub fn with_executor(&mut self, executor: Arc<Executor<M>>) -> &mut Self {
+        self.executor = Some(executor);
+        self
+    }
+
+    /// Shares strategy switches and windows, e.g. with the control API.
+    pub fn with_strategies(&mut self, strategies: Arc<Strategies>) -> &mut Self {
+        self.strategies = strategies;
+        self
+    }
+
+    /// Called with every stored opportunity, before it reaches subscribers.
+    pub fn on_opportunity(
+        &mut self,
+        hook: impl Fn(&Opportunity) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.hooks.on_opportunity(hook);
+        self
+    }
+
+    /// Called with every trade sent in live execution, filled or not, before
+    /// it is recorded.
+    pub fn on_execution(
+        &mut self,
+        hook: impl Fn(&ExecutedTrade) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.hooks.on_execution(hook);
+        self
+    }
+
+    /// Called with the error of every failed detection cycle.
+    pub fn on_error(&mut self, hook: impl Fn(&anyhow::Error) + Send + Sync + 'static) -> &mut Self {
+        self.hooks.on_error(hook);
+        self
+    }
+
+    /// Called when database writes start failing and again once they recover.
+    pub fn on_storage(
+        &mut self,
+        hook: impl Fn(&StorageEvent) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.hooks.on_storage(hook);
+        self
+    }
+
+    /// Every opportunity stored from now on. A subscriber that falls more than
+    /// `SUBSCRIBER_BUFFER` behind skips the ones it missed.
+    pub fn subscribe(&self) -> impl Stream<Item = Opportunity> {
+        stream::unfold(self.opportunities.subscribe(), |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(opp) => return Some((opp, rx)),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        log::warn!("Opportunity subscriber lagged, skipped {}", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    /// Runs detection cycles every `poll_interval_secs` (or every new block, see
+    /// [`ArbDetector::with_new_heads`]) until the task is dropped or the
+    /// [`ArbDetector::with_shutdown`] token is cancelled.
+    /// Each cycle runs under its own cycle ID (see [`correlation`]), which is
+    /// also current while the hooks are called.
+    pub async fn run(self) {
+        self.run_cycles().await
+    }
+
+    /// [`ArbDetector::run`] on a shared detector, so a supervisor can restart
+    /// the loop after a panic.
+    pub async fn run_cycles(&self) {
+        let job = format!(
+            "detector:{}:{}",
+            self.cfg.instance,
+            self.pair.as_deref().unwrap_or("pair")
+        );
+        let poll_interval = Duration::from_secs(self.cfg.poll_interval_secs);
+        let mut heads = self.heads.as_ref().map(NewHeads::subscribe);
+        JOBS.register(
+            &job,
+            match heads {
+                Some(_) => "every block".to_string(),
+                None => format!("every {}s", poll_interval.as_secs()),
+            },
+        );
+        let mut failure_streak = 0u32;
+        loop {
+            // A suspended pair idles until it is reactivated.
+            if !SUSPENSIONS.is_suspended(&self.cfg.instance, self.pair.as_deref()) {
+                let cycle_id = correlation::new_id("cyc");
+                correlation::scope(cycle_id.clone(), async {
+                    let (started, _, result) = self.timed_cycle(&cycle_id).await;
+                    if let Err(e) = &result {
+                        failure_streak += 1;
+                        if failure_streak == self.cfg.diagnostics_after_failures {
+                            self.dump_diagnostics(e);
+                        }
+                    } else {
+                        failure_streak = 0;
+                    }
+                    JOBS.finished(&job, started, &result);
+                })
+                .await;
+            }
+            let next = async {
+                match &mut heads {
+                    Some(rx) => heads::next(rx, poll_interval).await,
+                    None => {
+                        JOBS.scheduled(
+                            &job,
+                            self.clock.now()
+                                + chrono::Duration::seconds(poll_interval.as_secs() as i64),
+                        );
+                        self.clock.sleep(poll_interval).await
+                    }
+                }
+            };
+            tokio::select! {
+                biased;
+                _ = self.shutdown.cancelled() => break,
+                _ = next => {}
+            }
+        }
+        // Buffered rows would otherwise go down with the process.
+        self.flush(self.clock.now(), true);
+        log::info!("[{}] Detector stopped", self.cfg.instance);
+    }
+
+    /// Runs one detection cycle now, outside the schedule, and reports what
+    /// it quoted and stored. Its opportunities are written and published as
+    /// usual, but nothing is traded, live or on paper.
+    pub async fn run_once(&self) -> CycleReport {
+        let cycle_id = correlation::new_id("cyc");
+        let mut stored = self.opportunities.subscribe();
+        let (_, latency_ms, result) = correlation::scope(
+            cycle_id.clone(),
+            ON_REQUEST.scope((), self.timed_cycle(&cycle_id)),
+        )
+        .await;
+        // Written now rather than with the next batch, so they can be returned.
+        self.flush(self.clock.now(), true);
+        let mut opportunities = Vec::new();
+        loop {
+            match stored.try_recv() {
+                Ok(opp) if opp.cycle_id.as_deref() == Some(cycle_id.as_str()) => {
+                    opportunities.push(ApiOpportunity::from(&opp))
+                }
+                Ok(_) | Err(broadcast::error::TryRecvError::Lagged(_)) => {}
+                Err(_) => break,
+            }
+        }
+        CycleReport {
+            instance: self.cfg.instance.clone(),
+            pair: self.pair.clone(),
+            cycle_id,
+            latency_ms,
+            error: result.err().map(|e| format!("{:#}", e)),
+            quotes: self.trace.lock().unwrap().quotes.clone(),
+            opportunities,
+        }
+    }
+
+    /// One cycle with its metrics, flush and timing row; returns when it
+    /// started, how long it took in ms and how it ended.
+    async fn timed_cycle(&self, cycle_id: &str) -> (DateTime<Utc>, f64, anyhow::Result<()>) {
+        metrics::inc(&METRICS.cycles_total);
+        let started = self.clock.now();
+        let timer = std::time::Instant::now();
+        let result = rpc::track(self.run_cycle()).await;
+        self.flush_if_due(self.clock.now());
+        let latency_ms = timer.elapsed().as_secs_f64() * 1000.0;
+        if let Err(e) = &result {
+            metrics::inc(&METRICS.cycles_failed);
+            self.warmup.lock().unwrap().reset();
+            log::error!("[{}] Error in arbitrage loop: {:?}", self.cfg.instance, e);
+            self.hooks.error(e);
+        }
+        if let Err(e) = heatmap::record_cycle(
+            &self.conn.lock().unwrap(),
+            &self.cfg.instance,
+            started,
+            latency_ms,
+            result.is_ok(),
+            cycle_id,
+        ) {
+            log::error!("Failed to record cycle timing: {:?}", e);
+        }
+        (started, latency_ms, result)
+    }
+
+    /// Quotes every (DEX, trade size) combination at the latest block and
+    /// simulates the round trip leg by leg: sell token_in on the DEX paying the
+    /// most token_out, then buy token_in back with exactly those proceeds on
+    /// every other DEX. Profit is the token_in surplus valued at the first
+    /// leg's price, so price impact on both legs is included.
+    async fn run_cycle(&self) -> anyhow::Result<()> {
+        let cfg = &self.cfg;
+        let block = dex::latest_block(self.client.as_ref()).await?;
+
+        let mut requests = Vec::with_capacity(cfg.trade_sizes.len() * self.dexes.len());
+        for &size in &cfg.trade_sizes {
+            for dex in 0..self.dexes.len() {
+                requests.push(QuoteRequest {
+                    dex,
+                    token_in: cfg.token_in,
+                    token_out: cfg.token_out,
+                    amount_in: size,
+                });
+            }
+        }
+        let tiers: Vec<usize> = (0..self.dexes.len())
+            .filter(|&i| self.dexes[i].v3_fee().is_some())
+            .collect();
+        let skipped = self.v3_tiers.lock().unwrap().skipped(&tiers);
+        let forward = self.timed_execute(&requests, &skipped, block).await;
+        self.trace.lock().unwrap().quotes = requests
+            .iter()
+            .zip(&forward)
+            .map(|(req, q)| QuoteTrace {
+                dex: self.dexes[req.dex].name().to_string(),
+                block: block.number,
+                amount_in: req.amount_in.to_string(),
+                amount_out: q.map(|q| q.amount_out.to_string()),
+            })
+            .collect();
+
+        // Per trade size: (size, [(dex index, token_out received)])
+        let by_size: Vec<(U256, Vec<(usize, Quote)>)> = cfg
+            .trade_sizes
+            .iter()
+            .zip(forward.chunks(self.dexes.len()))
+            .map(|(&size, quotes)| {
+                let quotes = quotes
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, q)| q.as_ref().map(|q| (i, *q)))
+                    .collect();
+                (size, quotes)
+            })
+            .collect();
+
+        for (size, quotes) in &by_size {
+            let prices: Vec<String> = quotes
+                .iter()
+                .map(|(i, q)| {
+                    format!(
+                        "{} = {:.4}",
+                        self.dexes[*i].name(),
+                        self.token_out.to_f64(q.amount_out)
+                    )
+                })
+                .collect();
+            log::info!(
+                "Prices for {:.4}: {}",
+                self.token_in.to_f64(*size),
+                prices.join(" | ")
+            );
+        }
+
+        let primary = &by_size[0].1;
+        self.v3_tiers.lock().unwrap().observe(
+            primary
+                .iter()
+                .filter(|(i, _)| tiers.contains(i))
+                .max_by_key(|(_, q)| q.amount_out)
+                .map(|(i, _)| *i),
+        );
+        let quoted = primary
+            .iter()
+            .filter(|(_, q)| !q.amount_out.is_zero())
+            .count()
+            >= 2;
+        let warm = self.warmup.lock().unwrap().observe(&cfg.instance, quoted);
+        if let Some(reason) = SUSPENSIONS.quoted(
+            &cfg.instance,
+            self.pair.as_deref(),
+            quoted,
+            self.clock.now(),
+            chrono::Duration::seconds(cfg.pair_dead_after_secs as i64),
+        ) {
+            log::warn!(
+                "[{}] Suspending {}: {}; reactivate it with POST /api/control/pairs/reactivate",
+                cfg.instance,
+                self.pair.as_deref().unwrap_or("the pair"),
+                reason
+            );
+        }
+        if primary.len() < 2 {
+            anyhow::bail!("need quotes from at least two DEXes, got {}", primary.len());
+        }
+
+        // Leg 1 per size: (size, dex index, quote) of the best token_out
+        // proceeds; none while the two-DEX strategy is paused.
+        let two_dex = self.strategies.is_active("two_dex", self.clock.now());
+        let legs: Vec<(U256, usize, Quote)> = by_size
+            .iter()
+            .filter(|(_, quotes)| two_dex && quotes.len() >= 2)
+            .map(|(size, quotes)| {
+                let (i, q) = *quotes.iter().max_by_key(|(_, q)| q.amount_out).unwrap();
+                (*size, i, q)
+            })
+            .collect();
+
+        // Leg 2: every other DEX quotes buying token_in back with those proceeds.
+        let per_leg = self.dexes.len() - 1;
+        let mut back_requests = Vec::with_capacity(legs.len() * per_leg);
+        for &(_, sell_idx, sell) in &legs {
+            for dex in (0..self.dexes.len()).filter(|&i| i != sell_idx) {
+                back_requests.push(QuoteRequest {
+                    dex,
+                    token_in: cfg.token_out,
+                    token_out: cfg.token_in,
+                    amount_in: sell.amount_out,
+                });
+            }
+        }
+        let backs = self.timed_execute(&back_requests, &skipped, block).await;
+
+        // Quotes must still hold by the time a transaction would land.
+        let contest_fee = self.contest_fee().await;
+        let inclusion = self.inclusion(contest_fee).await;
+        let delay = inclusion.map_or(0.0, |i| i.expected_delay_secs);
+        if discard_if_stale(
+            cfg,
+            self.clock.now() + chrono::Duration::milliseconds((delay * 1000.0) as i64),
+            forward.iter().chain(&backs).flatten(),
+        ) {
+            self.reject(Reason::StaleQuotes, None);
+            return Ok(());
+        }
+        self.record_spread(block, &by_size)?;
+        let usd = self.usd_price().await;
+        let triangular =
+            !self.triangles.is_empty() && self.strategies.is_active("triangular", self.clock.now());
+        let pricing = CyclePricing {
+            gas_out: if two_dex || triangular {
+                self.gas_cost(block, usd).await
+            } else {
+                usdc_to_out(cfg.simulated_gas_usdc, usd)
+            },
+            contest_out: self
+                .contest
+                .as_ref()
+                .zip(contest_fee)
+                .map(|(contest, fee)| usdc_to_out(contest.cost_usdc(fee), usd)),
+            inclusion,
+            usd,
+            warm,
+            oracle: if legs.is_empty() {
+                None
+            } else {
+                self.oracle_price().await
+            },
+        };
+        if two_dex {
+            for _ in by_size.iter().filter(|(_, quotes)| quotes.len() < 2) {
+                self.reject(Reason::ThinLiquidity, None);
+            }
+
+            for (li, &(size, sell_idx, sell)) in legs.iter().enumerate() {
+                let range = li * per_leg..(li + 1) * per_leg;
+                let best_back = back_requests[range.clone()]
+                    .iter()
+                    .zip(&backs[range])
+                    .filter_map(|(req, q)| q.as_ref().map(|q| (req.dex, *q)))
+                    .max_by_key(|(_, q)| q.amount_out);
+                match best_back {
+                    Some((buy_idx, back)) => {
+                        self.record_two_dex(size, sell_idx, sell, buy_idx, back, pricing)
+                            .await?
+                    }
+                    None => self.reject(Reason::ThinLiquidity, None),
+                }
+            }
+        }
+
+        if triangular {
+            self.check_triangular(primary, block, pricing).await?;
+        }
+
+        if let Some(basis) = &self.basis {
+            if self.strategies.is_active("basis", self.clock.now()) {
+                self.check_basis(basis, primary, pricing).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stores the per-DEX prices of the primary trade size, with their
+    /// deviation from the depth-weighted consensus, and feeds the cycle's
+    /// spread into the rolling statistics.
+    fn record_spread(
+        &self,
+        block: BlockRef,
+        by_size: &[(U256, Vec<(usize, Quote)>)],
+    ) -> anyhow::Result<()> {
+        let (primary_size, quotes) = &by_size[0];
+        // (size, amount_out) in whole tokens.
+        let point = |size: U256, q: &Quote| {
+            (
+                self.token_in.to_f64(size),
+                self.token_out.to_f64(q.amount_out),
+            )
+        };
+        let priced: Vec<(usize, f64, Option<f64>)> = quotes
+            .iter()
+            .map(|(i, q)| {
+                let primary = point(*primary_size, q);
+                let depth = by_size[1..]
+                    .iter()
+                    .filter_map(|(size, quotes)| {
+                        let (_, q) = quotes.iter().find(|(j, _)| j == i)?;
+                        Some(point(*size, q))
+                    })
+                    .max_by(|a, b| a.0.total_cmp(&b.0))
+                    .and_then(|other| {
+                        if other.0 > primary.0 {
+                            consensus::implied_depth(primary, other)
+                        } else {
+                            consensus::implied_depth(other, primary)
+                        }
+                    });
+                (*i, primary.1 / primary.0, depth)
+            })
+            .collect();
+        let reference = consensus::consensus(
+            &priced
+                .iter()
+                .map(|&(_, price, depth)| (price, depth))
+                .collect::<Vec<_>>(),
+        );
+        let samples: Vec<PriceSample> = priced
+            .iter()
+            .map(|&(i, price, _)| PriceSample {
+                dex: self.dexes[i].name().to_string(),
+                price,
+                deviation_bps: reference.map(|c| consensus::deviation_bps(price, c)),
+            })
+            .collect();
+        let now = self.clock.now();
+        if let Some(sink) = &self.sink {
+            sink.price_samples(
+                now,
+                &self.cfg.instance,
+                self.pair.as_deref(),
+                block.number,
+                &samples,
+            );
+        }
+
+        if let Some(influx) = &self.influx {
+            influx.price_samples(
+                now,
+                &self.cfg.instance,
+                self.pair.as_deref(),
+                block.number,
+                &samples,
+            );
+        }
+
+        let max = samples.iter().map(|s| s.price).fold(f64::MIN, f64::max);
+        let min = samples.iter().map(|s| s.price).fold(f64::MAX, f64::min);
+        if let Some(live) = &self.live_prices {
+            live.publish(PriceSnapshot {
+                instance: self.cfg.instance.clone(),
+                pair: self.pair.clone(),
+                timestamp: now.to_rfc3339(),
+                block: block.number,
+                cycle_id: correlation::current(),
+                prices: samples.clone(),
+                spread_bps: (min > 0.0).then(|| (max - min) / min * 10_000.0),
+            });
+        }
+        if !self.cfg.influx_only {
+            self.buffer
+                .lock()
+                .unwrap()
+                .push_price_samples(now, block.number, samples);
+        }
+        if min <= 0.0 {
+            return Ok(());
+        }
+        let spread_bps = (max - min) / min * 10_000.0;
+        if let Some(influx) = &self.influx {
+            influx.spread(
+                now,
+                &self.cfg.instance,
+                self.pair.as_deref(),
+                block.number,
+                spread_bps,
+            );
+        }
+
+        let mut stats = self.spread_stats.lock().unwrap();
+        if let Some(z) = stats.z_score(spread_bps) {
+            if z.abs() >= self.cfg.spread_anomaly_z {
+                log::warn!(
+                    "Spread anomaly: {:.1} bps (z = {:.1}, EMA {:.1} bps)",
+                    spread_bps,
+                    z,
+                    stats.ema().unwrap_or_default()
+                );
+            }
+        }
+        stats.push(spread_bps);
+        Ok(())
+    }
+
+    async fn record_two_dex(
+        &self,
+        size: U256,
+        sell_idx: usize,
+        sell: Quote,
+        buy_idx: usize,
+        back: Quote,
+        pricing: CyclePricing,
+    ) -> anyhow::Result<()> {
+        // Nothing bought back leaves no buying price to judge.
+        if back.amount_out.is_zero() {
+            self.reject(Reason::ThinLiquidity, None);
+            return Ok(());
+        }
+        let amount_in = self.token_in.money(size);
+        let sell_out = self.token_out.money(sell.amount_out);
+        let buy_back = self.token_in.money(back.amount_out);
+        // token_out the buying leg asks for `amount_in`.
+        let buy_out = amount_in.convert(sell.amount_out, back.amount_out, self.token_out.decimals);
+        let (size_f, sell_out_f) = (amount_in.to_f64(), sell_out.to_f64());
+
+        let sell_price = sell_out_f / size_f;
+        let buy_price = sell_out_f / buy_back.to_f64();
+        // The round trip's token_in gain, valued at the selling rate.
+        let gross_profit =
+            (buy_back - amount_in).convert(sell.amount_out, size, self.token_out.decimals);
+        let profit = gross_profit - Money::from_f64(pricing.gas_out, self.token_out.decimals);
+        let min_profit = Money::from_f64(
+            usdc_to_out(self.cfg.min_profit_usdc, pricing.usd),
+            self.token_out.decimals,
+        );
+        let (buy_dex, sell_dex) = (self.dexes[buy_idx].name(), self.dexes[sell_idx].name());
+        let mut pools = None;
+        let optimal = if self.cfg.optimal_trade_size {
+            pools = self.v2_pools(sell_idx, buy_idx, sell.block).await;
+            pools.and_then(|(sell_pool, buy_pool)| {
+                self.optimal_size(&sell_pool, &buy_pool, pricing.gas_out)
+            })
+        } else {
+            None
+        };
+        // With an optimal size, the gap is judged by its profit at that size.
+        let (judged_in, judged_gross) = match &optimal {
+            Some(o) => (
+                o.amount_in.to_f64(),
+                o.profit + Money::from_f64(pricing.gas_out, self.token_out.decimals),
+            ),
+            None => (size_f, gross_profit),
+        };
+        let judged_profit = optimal.as_ref().map_or(profit, |o| o.profit);
+        let candidate = Candidate {
+            dex_buy: buy_dex,
+            dex_sell: sell_dex,
+            amount_in: judged_in,
+            gross_profit: judged_gross.to_f64(),
+        };
+        if judged_profit <= min_profit {
+            let reason = if judged_gross > min_profit {
+                Reason::GasTooHigh
+            } else {
+                Reason::BelowThreshold
+            };
+            self.reject(reason, Some(&candidate));
+            return Ok(());
+        }
+        // Notional and ROI describe the stored row, which is at the quoted size.
+        let (notional_usd, roi_bps) = notional(sell_out_f, profit.to_f64(), pricing.usd);
+        if self.below_min_roi(roi_bps, &candidate) || self.warming_up(pricing.warm, &candidate) {
+            return Ok(());
+        }
+
+        if !self.cfg.optimal_trade_size {
+            pools = self.v2_pools(sell_idx, buy_idx, sell.block).await;
+        }
+        if let Some((sell_pool, buy_pool)) = &pools {
+            self.check_quotes(size, (sell_idx, sell), (buy_idx, back), sell_pool, buy_pool);
+        }
+        let size_curve = pools.as_ref().map(|(sell_pool, buy_pool)| {
+            self.size_curve(size, sell_pool, buy_pool, pricing.gas_out)
+        });
+        let price_impact_bps = pools
+            .and_then(|(sell_pool, buy_pool)| self.price_impact(size, sell, &sell_pool, &buy_pool));
+        // The leg furthest from the oracle.
+        let oracle_deviation = pricing.oracle.map(|oracle| {
+            let (sell_dev, buy_dev) = (
+                oracle::deviation_bps(sell_price, oracle),
+                oracle::deviation_bps(buy_price, oracle),
+            );
+            if sell_dev.abs() >= buy_dev.abs() {
+                sell_dev
+            } else {
+                buy_dev
+            }
+        });
+        // A leg this far off is more likely a bad pool than a real spread.
+        let oracle_suspect =
+            oracle_deviation.is_some_and(|bps| bps.abs() > self.cfg.oracle_max_deviation_bps);
+        if oracle_suspect {
+            log::warn!(
+                "[{}] Prices of {} → {} are {:+.0} bps off the Chainlink price; possibly a stale pool or a bad token",
+                self.cfg.instance,
+                buy_dex,
+                sell_dex,
+                oracle_deviation.unwrap_or_default()
+            );
+        }
+        log::info!(
+            "Arb Opportunity: Buy on DEX {} @ {:.4}, Sell on DEX {} @ {:.4} → Profit: {}{}{}",
+            buy_dex,
+            buy_price,
+            sell_dex,
+            sell_price,
+            self.token_out.label(profit),
+            price_impact_bps
+                .map(|bps| format!(" (price impact {:.1} bps)", bps))
+                .unwrap_or_default(),
+            optimal
+                .as_ref()
+                .map(|o| {
+                    format!(
+                        ", {} at optimal size {}",
+                        self.token_out.label(o.profit),
+                        self.token_in.label(o.amount_in)
+                    )
+                })
+                .unwrap_or_default()
+        );
+        let profit_f = profit.to_f64();
+        self.store(NewOpportunity {
+            instance: self.cfg.instance.clone(),
+            strategy: "two_dex",
+            dex_buy: buy_dex.to_string(),
+            dex_sell: sell_dex.to_string(),
+            amount_in,
+            amount_out_buy: buy_out,
+            amount_out_sell: sell_out,
+            amount_back: Some(buy_back),
+            profit,
+            block_number: Some(sell.block.number),
+            rpc_provider: rpc::served_by(),
+            contested_profit: pricing.contest_out.map(|cost| profit_f - cost),
+            profit_usd: pricing.usd.map(|usd| profit_f * usd.price),
+            usd_source: pricing.usd.map(|usd| usd.source),
+            cycle_id: correlation::current(),
+            pair: self.pair.clone(),
+            inclusion_probability: pricing.inclusion.map(|i| i.probability),
+            expected_delay_secs: pricing.inclusion.map(|i| i.expected_delay_secs),
+            expected_profit: pricing.inclusion.map(|i| profit_f * i.probability),
+            notional_usd,
+            roi_bps,
+            price_impact_bps,
+            optimal_amount_in: optimal.as_ref().map(|o| o.amount_in),
+            optimal_profit: optimal.map(|o| o.profit),
+            size_curve,
+            oracle_deviation,
+            path_buy: self.dexes[buy_idx].path().map(str::to_string),
+            path_sell: self.dexes[sell_idx].path().map(str::to_string),
+        });
+        // Only the quoted size is executed, and only when it clears the threshold itself.
+        if profit > min_profit && !on_request() {
+            if oracle_suspect && self.cfg.execution_mode != ExecutionMode::DryRun {
+                log::warn!(
+                    "[{}] Not executing: beyond ORACLE_MAX_DEVIATION_BPS",
+                    self.cfg.instance
+                );
+                return Ok(());
+            }
+            match self.cfg.execution_mode {
+                ExecutionMode::DryRun => {}
+                ExecutionMode::Paper => {
+                    self.paper_fill(size, sell_idx, sell, buy_idx, back, pricing.gas_out)?
+                }
+                ExecutionMode::Live => self.execute(size, sell_idx, sell, buy_idx, back).await?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Fills the round trip against the instance's simulated balances.
+    fn paper_fill(
+        &self,
+        size: U256,
+        sell_idx: usize,
+        sell: Quote,
+        buy_idx: usize,
+        back: Quote,
+        gas_out: f64,
+    ) -> anyhow::Result<()> {
+        let order = PaperOrder {
+            instance: self.cfg.instance.clone(),
+            pair: self.pair.clone(),
+            cycle_id: correlation::current(),
+            dex_sell: self.dexes[sell_idx].name().to_string(),
+            dex_buy: self.dexes[buy_idx].name().to_string(),
+            token_in: self.cfg.token_in,
+            token_out: self.cfg.token_out,
+            decimals_in: self.token_in.decimals,
+            decimals_out: self.token_out.decimals,
+            amount_in: size,
+            sell_quote: sell.amount_out,
+            back_quote: back.amount_out,
+            gas: Money::from_f64(gas_out, self.token_out.decimals),
+        };
+        let start_balance = self
+            .cfg
+            .paper_start_balance
+            .unwrap_or(self.cfg.trade_size_wei * 10);
+        let filled = paper::fill(
+            &mut self.conn.lock().unwrap(),
+            self.clock.now(),
+            &order,
+            start_balance,
+            self.cfg.paper_slippage_bps,
+        )?;
+        match filled {
+            Some(fill) => log::info!(
+                "Paper fill: {} -> {}, bought back {}, P&L {} (total {:.4})",
+                order.dex_sell,
+                order.dex_buy,
+                fill.amount_back,
+                fill.pnl,
+                fill.cumulative_pnl
+            ),
+            None => log::info!("Paper fill skipped: simulated balance below the trade size"),
+        }
+        Ok(())
+    }
+
+    /// Executes the round trip live when an executor is set and both legs
+    /// trade on V2 routers, recording the outcome in `executions`.
+    async fn execute(
+        &self,
+        size: U256,
+        sell_idx: usize,
+        sell: Quote,
+        buy_idx: usize,
+        back: Quote,
+    ) -> anyhow::Result<()> {
+        let Some(executor) = &self.executor else {
+            return Ok(());
+        };
+        let (Some(sell_router), Some(buy_router)) = (
+            self.dexes[sell_idx].v2_router(),
+            self.dexes[buy_idx].v2_router(),
+        ) else {
+            log::info!("Not executing: only round trips on V2 routers can be executed");
+            return Ok(());
+        };
+        let plan = TradePlan {
+            instance: self.cfg.instance.clone(),
+            pair: self.pair.clone(),
+            cycle_id: correlation::current(),
+            dex_sell: self.dexes[sell_idx].name().to_string(),
+            dex_buy: self.dexes[buy_idx].name().to_string(),
+            sell_router,
+            buy_router,
+            token_in: self.cfg.token_in,
+            token_out: self.cfg.token_out,
+            decimals_in: self.token_in.decimals,
+            decimals_out: self.token_out.decimals,
+            amount_in: size,
+            sell_quote: sell.amount_out,
+            back_quote: back.amount_out,
+        };
+        let execution = executor.execute(&plan, self.clock.now()).await;
+        match &execution.error {
+            None => log::info!(
+                "Executed round trip: {} -> {}, bought back {} (txs {} / {})",
+                plan.dex_sell,
+                plan.dex_buy,
+                self.token_in
+                    .display(execution.realized_in.unwrap_or_default()),
+                execution.sell_tx.as_deref().unwrap_or_default(),
+                execution.buy_tx.as_deref().unwrap_or_default()
+            ),
+            Some(error) => log::error!("Execution {}: {}", execution.status(), error),
+        }
+        let trade = ExecutedTrade { plan, execution };
+        self.hooks.execution(&trade);
+        // Queued like every other row, so a trade survives a database outage,
+        // but written right away.
+        let now = self.clock.now();
+        self.buffer.lock().unwrap().push_execution(now, trade);
+        self.flush(now, true);
+        Ok(())
+    }
+
+    /// V2 pool reserves of both legs of a round trip at the quoted block;
+    /// `None` unless both legs trade on V2 routers.
+    async fn v2_pools(
+        &self,
+        sell_idx: usize,
+        buy_idx: usize,
+        block: BlockRef,
+    ) -> Option<(Reserves, Reserves)> {
+        let sell_router = self.dexes[sell_idx].v2_router()?;
+        let buy_router = self.dexes[buy_idx].v2_router()?;
+        let (token_in, token_out) = (self.cfg.token_in, self.cfg.token_out);
+        let pools = futures::future::try_join(
+            self.reserves.load(sell_router, token_in, token_out, block),
+            self.reserves.load(buy_router, token_out, token_in, block),
+        )
+        .await;
+        match pools {
+            Ok((Some(sell_pool), Some(buy_pool))) => Some((sell_pool, buy_pool)),
+            Ok(_) => None,
+            Err(e) => {
+                log::warn!("Could not read pool reserves: {:?}", e);
+                None
+            }
+        }
+    }
+
+    /// Round-trip size with the largest gain on the two pools and its profit
+    /// net of `gas_out`; `None` when no size gains.
+    fn optimal_size(
+        &self,
+        sell_pool: &Reserves,
+        buy_pool: &Reserves,
+        gas_out: f64,
+    ) -> Option<OptimalSize> {
+        let trip = reserves::optimal_round_trip(sell_pool, buy_pool, self.cfg.v2_fee_bps);
+        if trip.amount_in.is_zero() {
+            return None;
+        }
+        let amount_in = self.token_in.money(trip.amount_in);
+        let gain = self.token_in.money(trip.buy_back) - amount_in;
+        Some(OptimalSize {
+            amount_in,
+            profit: gain.convert(trip.sell_out, trip.amount_in, self.token_out.decimals)
+                - Money::from_f64(gas_out, self.token_out.decimals),
+        })
+    }
+
+    /// Profit net of `gas_out` at each of [`SIZE_CURVE_PCTS`] of `size`,
+    /// from the V2 pool reserves, to show whether the gap scales with size.
+    fn size_curve(
+        &self,
+        size: U256,
+        sell_pool: &Reserves,
+        buy_pool: &Reserves,
+        gas_out: f64,
+    ) -> Vec<SizePoint> {
+        let fee = self.cfg.v2_fee_bps;
+        let gas = Money::from_f64(gas_out, self.token_out.decimals);
+        SIZE_CURVE_PCTS
+            .iter()
+            .map(|&pct| {
+                let amount_in = size * pct / 100;
+                let sell_out = sell_pool.amount_out(amount_in, fee);
+                let buy_back = buy_pool.amount_out(sell_out, fee);
+                let gain = self.token_in.money(buy_back) - self.token_in.money(amount_in);
+                SizePoint {
+                    size_pct: pct,
+                    amount_in: self.token_in.decimal(amount_in),
+                    profit: (gain.convert(sell_out, amount_in, self.token_out.decimals) - gas)
+                        .to_f64(),
+                }
+            })
+            .collect()
+    }
+
+    /// Price impact of both legs of a round trip from the V2 pool reserves.
+    fn price_impact(
+        &self,
+        size: U256,
+        sell: Quote,
+        sell_pool: &Reserves,
+        buy_pool: &Reserves,
+    ) -> Option<f64> {
+        let fee = self.cfg.v2_fee_bps;
+        Some(
+            sell_pool.price_impact_bps(size, fee)?
+                + buy_pool.price_impact_bps(sell.amount_out, fee)?,
+        )
+    }
+
+    /// Compares both legs' router quotes with what the pools' reserves give
+    /// and flags a DEX quoting more than `QUOTE_MISMATCH_BPS` away.
+    fn check_quotes(
+        &self,
+        size: U256,
+        (sell_idx, sell): (usize, Quote),
+        (buy_idx, back): (usize, Quote),
+        sell_pool: &Reserves,
+        buy_pool: &Reserves,
+    ) {
+        let fee = self.cfg.v2_fee_bps;
+        let legs = [
+            (sell_idx, sell.amount_out, sell_pool.amount_out(size, fee)),
+            (
+                buy_idx,
+                back.amount_out,
+                buy_pool.amount_out(sell.amount_out, fee),
+            ),
+        ];
+        for (idx, quoted, local) in legs {
+            let Some(deviation) =
+                quotecheck::deviation_bps(units::to_f64(quoted, 0), units::to_f64(local, 0))
+            else {
+                continue;
+            };
+            let dex = self.dexes[idx].name();
+            let flagged = QUOTE_CHECKS.record(
+                &self.cfg.instance,
+                self.pair.as_deref(),
+                dex,
+                deviation,
+                self.cfg.quote_mismatch_bps,
+                self.clock.now(),
+            );
+            if flagged {
+                log::warn!(
+                    "[{}] {} quoted {} but its reserves give {} at {} bps fee ({:+.1} bps); check for a fee-on-transfer token or custom router",
+                    self.cfg.instance,
+                    dex,
+                    quoted,
+                    local,
+                    fee,
+                    deviation
+                );
+            }
+        }
+    }
+
+    /// Quotes every triangular cycle at `trade_size_wei` and records the best
+    /// one when it clears the thresholds. Its surplus is valued at the best
+    /// token_out quote of the primary size, reported as `amount_out_sell`.
+    async fn check_triangular(
+        &self,
+        primary: &[(usize, Quote)],
+        block: BlockRef,
+        pricing: CyclePricing,
+    ) -> anyhow::Result<()> {
+        let size = self.cfg.trade_size_wei;
+        let Some((_, rate)) = primary.iter().max_by_key(|(_, q)| q.amount_out) else {
+            return Ok(());
+        };
+        let requests: Vec<QuoteRequest> = (0..self.triangles.len())
+            .map(|dex| QuoteRequest {
+                dex,
+                token_in: self.cfg.token_in,
+                token_out: self.cfg.token_in,
+                amount_in: size,
+            })
+            .collect();
+        let quotes = self
+            .planner
+            .execute(&self.triangles, &requests, block)
+            .await;
+        let Some((idx, back)) = quotes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, q)| q.map(|q| (i, q)))
+            .max_by_key(|(_, q)| q.amount_out)
+        else {
+            self.reject(Reason::ThinLiquidity, None);
+            return Ok(());
+        };
+
+        let amount_in = self.token_in.money(size);
+        let amount_back = self.token_in.money(back.amount_out);
+        let notional_out = self.token_out.money(rate.amount_out);
+        let gross_profit =
+            (amount_back - amount_in).convert(rate.amount_out, size, self.token_out.decimals);
+        let profit = gross_profit - Money::from_f64(pricing.gas_out, self.token_out.decimals);
+        let min_profit = Money::from_f64(
+            usdc_to_out(self.cfg.min_profit_usdc, pricing.usd),
+            self.token_out.decimals,
+        );
+        let route = self.triangles[idx].name();
+        let candidate = Candidate {
+            dex_buy: route,
+            dex_sell: route,
+            amount_in: amount_in.to_f64(),
+            gross_profit: gross_profit.to_f64(),
+        };
+        if profit <= min_profit {
+            let reason = if gross_profit > min_profit {
+                Reason::GasTooHigh
+            } else {
+                Reason::BelowThreshold
+            };
+            self.reject(reason, Some(&candidate));
+            return Ok(());
+        }
+        let (notional_usd, roi_bps) = notional(notional_out.to_f64(), profit.to_f64(), pricing.usd);
+        if self.below_min_roi(roi_bps, &candidate) || self.warming_up(pricing.warm, &candidate) {
+            return Ok(());
+        }
+
+        log::info!(
+            "Triangular Opportunity: {} turns {} into {} → Profit: {}",
+            route,
+            self.token_in.label(amount_in),
+            self.token_in.label(amount_back),
+            self.token_out.label(profit)
+        );
+        let profit_f = profit.to_f64();
+        self.store(NewOpportunity {
+            instance: self.cfg.instance.clone(),
+            strategy: "triangular",
+            dex_buy: route.to_string(),
+            dex_sell: route.to_string(),
+            amount_in,
+            amount_out_buy: amount_in.convert(
+                rate.amount_out,
+                back.amount_out,
+                self.token_out.decimals,
+            ),
+            amount_out_sell: notional_out,
+            amount_back: Some(amount_back),
+            profit,
+            block_number: Some(block.number),
+            rpc_provider: rpc::served_by(),
+            contested_profit: pricing.contest_out.map(|cost| profit_f - cost),
+            profit_usd: pricing.usd.map(|usd| profit_f * usd.price),
+            usd_source: pricing.usd.map(|usd| usd.source),
+            cycle_id: correlation::current(),
+            pair: self.pair.clone(),
+            inclusion_probability: pricing.inclusion.map(|i| i.probability),
+            expected_delay_secs: pricing.inclusion.map(|i| i.expected_delay_secs),
+            expected_profit: pricing.inclusion.map(|i| profit_f * i.probability),
+            notional_usd,
+            roi_bps,
+            price_impact_bps: None,
+            optimal_amount_in: None,
+            optimal_profit: None,
+            size_curve: None,
+            oracle_deviation: None,
+            path_buy: None,
+            path_sell: None,
+        });
+        Ok(())
+    }
+
+    async fn check_basis(
+        &self,
+        basis: &BasisMonitor<M>,
+        quotes: &[(usize, Quote)],
+        pricing: CyclePricing,
+    ) -> anyhow::Result<()> {
+        let trade_size_f = self.token_in.to_f64(self.cfg.trade_size_wei);
+        let spot = quotes
+            .iter()
+            .map(|(_, q)| self.token_out.to_f64(q.amount_out) / trade_size_f)
+            .sum::<f64>()
+            / quotes.len() as f64;
+        let Some(signal) = basis.check(spot).await? else {
+            basis.close();
+            return Ok(());
+        };
+
+        // Positive basis: long spot on the DEXes, short the perp.
+        let ((dex_buy, price_buy), (dex_sell, price_sell)) = if signal.short_perp() {
+            (("spot", signal.spot), ("perp", signal.mark))
+        } else {
+            (("perp", signal.mark), ("spot", signal.spot))
+        };
+        let perp_notional = signal.mark * trade_size_f;
+        let gross_profit = (signal.mark - signal.spot).abs() * trade_size_f;
+        let fees = perp_notional * 2.0 * self.cfg.perp_fee_bps / 10_000.0;
+        let funding = signal.funding_cost(perp_notional, self.cfg.basis_hold_hours);
+        let profit = gross_profit - fees - pricing.gas_out - funding;
+        let min_profit = usdc_to_out(self.cfg.min_profit_usdc, pricing.usd);
+        let candidate = Candidate {
+            dex_buy,
+            dex_sell,
+            amount_in: trade_size_f,
+            gross_profit,
+        };
+        if profit <= min_profit {
+            let reason = if gross_profit - fees - funding > min_profit {
+                Reason::GasTooHigh
+            } else {
+                Reason::BelowThreshold
+            };
+            self.reject(reason, Some(&candidate));
+            basis.close();
+            return Ok(());
+        }
+        let (notional_usd, roi_bps) = notional(signal.spot * trade_size_f, profit, pricing.usd);
+        if self.below_min_roi(roi_bps, &candidate) || self.warming_up(pricing.warm, &candidate) {
+            return Ok(());
+        }
+        if !basis.open(&signal) {
+            log::debug!(
+                "Basis still open: spot {:.4} vs perp mark {:.4} ({:+.1} bps)",
+                signal.spot,
+                signal.mark,
+                signal.bps
+            );
+            return Ok(());
+        }
+        log::info!(
+            "Basis Opportunity: spot {:.4} vs perp mark {:.4} ({:+.1} bps, funding {:+.4}%/day) → Profit: {:.4}",
+            signal.spot,
+            signal.mark,
+            signal.bps,
+            signal.funding_rate * 100.0,
+            profit
+        );
+        self.store(NewOpportunity {
+            instance: self.cfg.instance.clone(),
+            strategy: "basis",
+            dex_buy: dex_buy.to_string(),
+            dex_sell: dex_sell.to_string(),
+            amount_in: self.token_in.money(self.cfg.trade_size_wei),
+            amount_out_buy: Money::from_f64(price_buy * trade_size_f, self.token_out.decimals),
+            amount_out_sell: Money::from_f64(price_sell * trade_size_f, self.token_out.decimals),
+            amount_back: None,
+            profit: Money::from_f64(profit, self.token_out.decimals),
+            block_number: quotes.first().map(|(_, q)| q.block.number),
+            rpc_provider: rpc::served_by(),
+            contested_profit: None,
+            profit_usd: pricing.usd.map(|usd| profit * usd.price),
+            usd_source: pricing.usd.map(|usd| usd.source),
+            cycle_id: correlation::current(),
+            pair: self.pair.clone(),
+            inclusion_probability: None,
+            expected_delay_secs: None,
+            expected_profit: None,
+            notional_usd,
+            roi_bps,
+            price_impact_bps: None,
+            optimal_amount_in: None,
+            optimal_profit: None,
+            size_curve: None,
+            oracle_deviation: None,
+            path_buy: None,
+            path_sell: None,
+        });
+        Ok(())
+    }
+
+    /// Gas cost of a round trip in token_out at the current gas price. Gas
+    /// units are learned for the pair when there are samples,
+    /// `SWAP_GAS_LIMIT` otherwise; the native price is `NATIVE_PRICE_USDC`
+    /// converted at the cycle's USD price or, with `SWAP_GAS_LIMIT` set,
+    /// quoted on the first DEX. `SIMULATED_GAS_USDC` when either is missing.
+    async fn gas_cost(&self, block: BlockRef, usd: Option<UsdPrice>) -> f64 {
+        let fallback = usdc_to_out(self.cfg.simulated_gas_usdc, usd);
+        let swap_gas_limit = self.cfg.swap_gas_limit.map(|limit| limit as f64);
+        if self.cfg.native_price_usdc.is_none() && swap_gas_limit.is_none() {
+            return fallback;
+        }
+        let learned = gas::lookup(
+            &self.conn.lock().unwrap(),
+            self.cfg.token_in,
+            self.cfg.token_out,
+        );
+        let learned = learned.unwrap_or_else(|e| {
+            log::warn!("Could not load pair gas estimate: {:?}", e);
+            None
+        });
+        let Some(gas_units) = learned.or(swap_gas_limit) else {
+            return fallback;
+        };
+        let native_price = match self.cfg.native_price_usdc {
+            Some(price) => usdc_to_out(price, usd),
+            None => match gas::native_price(
+                self.client.as_ref(),
+                self.dexes[0].as_ref(),
+                self.cfg.native_token,
+                &self.token_out,
+                block,
+            )
+            .await
+            {
+                Ok(price) => price,
+                Err(e) => {
+                    log::warn!("Could not quote the native token price: {:?}", e);
+                    return fallback;
+                }
+            },
+        };
+        match self.client.get_gas_price().await {
+            Ok(gas_price) => gas::cost(gas_units, gas_price, native_price),
+            Err(e) => {
+                log::warn!("Could not fetch gas price: {:?}", e);
+                fallback
+            }
+        }
+    }
+
+    async fn usd_price(&self) -> Option<UsdPrice> {
+        let pricer = self.usd.as_ref()?;
+        match pricer.price(self.clock.now()).await {
+            Ok(price) => Some(price),
+            Err(e) => {
+                log::warn!("Could not price profits in USD: {:?}", e);
+                None
+            }
+        }
+    }
+
+    /// token_out per token_in from the Chainlink feeds, if the oracle check
+    /// is on and both feeds answered.
+    async fn oracle_price(&self) -> Option<f64> {
+        let oracle = self.oracle.as_ref()?;
+        match oracle.price(self.clock.now()).await {
+            Ok(price) => Some(price),
+            Err(e) => {
+                log::warn!("Could not read the Chainlink oracle price: {:?}", e);
+                None
+            }
+        }
+    }
+
+    /// Priority fee bid this cycle, if the contest model is on.
+    async fn contest_fee(&self) -> Option<U256> {
+        let contest = self.contest.as_ref()?;
+        match contest.priority_fee(self.client.as_ref()).await {
+            Ok(fee) => Some(fee),
+            Err(e) => {
+                log::warn!("Could not model priority fee: {:?}", e);
+                None
+            }
+        }
+    }
+
+    /// Inclusion odds of this cycle's bid, if the inclusion model is on.
+    async fn inclusion(&self, contest_fee: Option<U256>) -> Option<Inclusion> {
+        let model = self.inclusion.as_ref()?;
+        match model.estimate(self.client.as_ref(), contest_fee).await {
+            Ok(inclusion) => Some(inclusion),
+            Err(e) => {
+                log::warn!("Could not model inclusion probability: {:?}", e);
+                None
+            }
+        }
+    }
+
+    /// Quotes `requests` except those on the `skipped` venues, which come
+    /// back as `None`.
+    async fn timed_execute(
+        &self,
+        requests: &[QuoteRequest],
+        skipped: &[usize],
+        block: BlockRef,
+    ) -> Vec<Option<Quote>> {
+        let wanted: Vec<QuoteRequest> = requests
+            .iter()
+            .filter(|req| !skipped.contains(&req.dex))
+            .copied()
+            .collect();
+        metrics::add(
+            &METRICS.v3_tiers_skipped,
+            (requests.len() - wanted.len()) as u64,
+        );
+        let timer = std::time::Instant::now();
+        let mut quotes = self
+            .planner
+            .execute(&self.dexes, &wanted, block)
+            .await
+            .into_iter();
+        self.trace
+            .lock()
+            .unwrap()
+            .record_latency(timer.elapsed().as_secs_f64() * 1000.0);
+        requests
+            .iter()
+            .map(|req| {
+                if skipped.contains(&req.dex) {
+                    None
+                } else {
+                    quotes.next().flatten()
+                }
+            })
+            .collect()
+    }
+
+    fn dump_diagnostics(&self, error: &anyhow::Error) {
+        let Some(dir) = &self.cfg.diagnostics_dir else {
+            return;
+        };
+        let written = diagnostics::write_bundle(
+            Path::new(dir),
+            &self.cfg.instance,
+            self.clock.now(),
+            error,
+            &self.cfg.summary(),
+            &self.trace.lock().unwrap(),
+        );
+        match written {
+            Ok(path) => log::error!(
+                "[{}] {} cycles failed in a row, diagnostics written to {}",
+                self.cfg.instance,
+                self.cfg.diagnostics_after_failures,
+                path.display()
+            ),
+            Err(e) => log::error!("Failed to write diagnostics bundle: {:?}", e),
+        }
+    }
+
+    fn reject(&self, reason: Reason, candidate: Option<&Candidate>) {
+        if let Err(e) = rejections::record(
+            &self.conn.lock().unwrap(),
+            self.clock.now(),
+            &self.cfg.instance,
+            reason,
+            candidate,
+            correlation::current().as_deref(),
+        ) {
+            log::error!("Failed to record rejection: {:?}", e);
+        }
+    }
+
+    /// Rejects a candidate returning less than `MIN_ROI_BPS`.
+    fn below_min_roi(&self, roi_bps: f64, candidate: &Candidate) -> bool {
+        match self.cfg.min_roi_bps {
+            Some(min) if roi_bps < min => {
+                self.reject(Reason::BelowRoi, Some(candidate));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Rejects a candidate found before the detector warmed up.
+    fn warming_up(&self, warm: bool, candidate: &Candidate) -> bool {
+        if !warm {
+            self.reject(Reason::WarmingUp, Some(candidate));
+        }
+        !warm
+    }
+
+    /// Queues an opportunity and publishes whatever the write stored.
+    fn store(&self, opp: NewOpportunity) {
+        let now = self.clock.now();
+        self.buffer.lock().unwrap().push_opportunity(now, opp);
+        self.flush_if_due(now);
+    }
+
+    /// Writes the queued rows once their batch is full or old enough. A failed
+    /// write keeps the rows queued for the next attempt instead of failing the
+    /// cycle.
+    fn flush_if_due(&self, now: DateTime<Utc>) {
+        self.flush(now, false)
+    }
+
+    /// Writes the buffered opportunities when due, or whenever `force` is set.
+    fn flush(&self, now: DateTime<Utc>, force: bool) {
+        let (stored, event) = {
+            let mut buffer = self.buffer.lock().unwrap();
+            if !force && !buffer.is_due(now) {
+                return;
+            }
+            buffer.flush_tracked(&mut self.conn.lock().unwrap(), now)
+        };
+        if let Some(event) = event {
+            match &event {
+                StorageEvent::Failing { .. } => log::error!("[{}] {}", self.cfg.instance, event),
+                StorageEvent::Recovered { .. } => log::info!("[{}] {}", self.cfg.instance, event),
+            }
+            self.hooks.storage(&event);
+        }
+        for opp in stored {
+            log::info!(
+                "[{}] Stored {} opportunity {}",
+                self.cfg.instance,
+                opp.strategy,
+                self.links.permalink(opp.id)
+            );
+            self.publish(opp);
+        }
+    }
+
+    /// Hands a stored opportunity to the subscribers, if there are any.
+    fn publish(&self, opp: Opportunity) {
+        if let Some(sink) = &self.sink {
+            sink.opportunity(&opp);
+        }
+        self.hooks.opportunity(&opp);
+        // Only fails when nobody is subscribed.
+        let _ = self.opportunities.send(opp);
+    }
+}
+
+/// Per-cycle costs and prices shared by every candidate round trip.
+#[derive(Clone, Copy)]
+struct CyclePricing {
+    /// Gas cost of a round trip, in token_out like every amount below.
+    gas_out: f64,
+    /// Priority fee bid, when the contest model is on.
+    contest_out: Option<f64>,
+    /// Chance and delay of landing the bid, when the inclusion model is on.
+    inclusion: Option<Inclusion>,
+    /// USD per token_out, when a USD price source is configured and answered.
+    usd: Option<UsdPrice>,
+    /// Whether the detector has warmed up, see [`crate::warmup`].
+    warm: bool,
+    /// token_out per token_in from the Chainlink feeds, with `ORACLE_CHECK`.
+    oracle: Option<f64>,
+}
+
+/// Most profitable round-trip size on two V2 pools.
+struct OptimalSize {
+    amount_in: Money,
+    /// Projected profit in token_out, net of gas.
+    profit: Money,
+}
+
+/// `usdc` (a USD amount such as `MIN_PROFIT_USDC`) in token_out at the
+/// cycle's USD price; at par without one.
+fn usdc_to_out(usdc: f64, usd: Option<UsdPrice>) -> f64 {
+    usd.map_or(usdc, |usd| usdc / usd.price)
+}
+
+/// USD notional and ROI in bps of a trade worth `notional_out` token_out.
+/// Without a USD price token_out is taken at par, as for `MIN_PROFIT_USDC`.
+fn notional(notional_out: f64, profit: f64, usd: Option<UsdPrice>) -> (f64, f64) {
+    let roi_bps = if notional_out > 0.0 {
+        profit / notional_out * 10_000.0
+    } else {
+        0.0
+    };
+    (notional_out * usd.map_or(1.0, |usd| usd.price), roi_bps)
+}
+
+/// Drops the cycle when the oldest quote's block is more than
+/// `max_quote_age_secs` behind the wall clock by the time it is compared.
+fn discard_if_stale<'a>(
+    cfg: &Config,
+    now: DateTime<Utc>,
+    quotes: impl IntoIterator<Item = &'a Quote>,
+) -> bool {
+    let now = now.timestamp().max(0) as u64;
+    let Some(age) = quotes.into_iter().map(|q| q.age_secs(now)).max() else {
+        return false;
+    };
+    if age <= cfg.max_quote_age_secs {
+        return false;
+    }
+    metrics::inc(&METRICS.stale_cycles);
+    log::warn!(
+        "Discarding cycle: quotes are {}s old (max {}s)",
+        age,
+        cfg.max_quote_age_secs
+    );
+    true
+}
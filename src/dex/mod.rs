@@ -0,0 +1,110 @@
+//! DEX quoting adapters
+//!
+//! Every venue is reduced to a single `eth_call` that prices `amount_in` of one
+//! token in another, so an adapter only has to describe how that call is
+//! encoded and how its return data is decoded.
+
+pub mod pmm;
+pub mod reserves;
+pub mod stable;
+pub mod v2;
+pub mod v3;
+
+use anyhow::Context;
+use ethers::providers::Middleware;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, BlockId, BlockNumber, Bytes, TransactionRequest, U256};
+
+/// Target contract and calldata of a single quote.
+#[derive(Debug, Clone)]
+pub struct QuoteCall {
+    pub target: Address,
+    pub calldata: Bytes,
+}
+
+/// Block a quote was taken at, as reported by the provider.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockRef {
+    pub number: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Quote {
+    pub amount_out: U256,
+    pub block: BlockRef,
+}
+
+impl Quote {
+    /// Seconds elapsed between the quoted block and `now` (unix seconds).
+    pub fn age_secs(&self, now: u64) -> u64 {
+        now.saturating_sub(self.block.timestamp)
+    }
+}
+
+pub trait DexQuoter: Send + Sync {
+    /// Name recorded as `dex_buy` / `dex_sell` for opportunities on this venue.
+    fn name(&self) -> &str;
+
+    /// Call quoting `amount_in`; an error when the venue can't swap the tokens.
+    fn quote_call(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+    ) -> anyhow::Result<QuoteCall>;
+
+    /// Decodes the raw return data of `quote_call` into the output amount.
+    fn decode_quote(&self, data: &[u8]) -> anyhow::Result<U256>;
+
+    /// Router of a Uniswap V2-style venue, whose pool reserves can be read.
+    fn v2_router(&self) -> Option<Address> {
+        None
+    }
+
+    /// Multi-hop route the venue quotes the pair along, token_in first
+    /// (`WETH>WPOL>USDC`), recorded with opportunities; `None` when it swaps
+    /// the pair directly.
+    fn path(&self) -> Option<&str> {
+        None
+    }
+
+    /// Fee tier of a Uniswap V3 venue, one of several pools of the pair.
+    fn v3_fee(&self) -> Option<u32> {
+        None
+    }
+}
+
+pub async fn latest_block<M: Middleware + 'static>(client: &M) -> anyhow::Result<BlockRef> {
+    let block = client
+        .get_block(BlockNumber::Latest)
+        .await?
+        .context("provider returned no latest block")?;
+    Ok(BlockRef {
+        number: block.number.context("latest block has no number")?.as_u64(),
+        timestamp: block.timestamp.as_u64(),
+    })
+}
+
+/// Quotes `amount_in` on `dex` pinned to `block`, so every quote of a cycle
+/// prices the same chain state.
+pub async fn quote<M: Middleware + 'static>(
+    client: &M,
+    dex: &dyn DexQuoter,
+    token_in: Address,
+    token_out: Address,
+    amount_in: U256,
+    block: BlockRef,
+) -> anyhow::Result<Quote> {
+    let call = dex.quote_call(token_in, token_out, amount_in)?;
+    let tx: TypedTransaction = TransactionRequest::new()
+        .to(call.target)
+        .data(call.calldata)
+        .into();
+    let at = BlockId::Number(BlockNumber::Number(block.number.into()));
+    let data = client.call(&tx, Some(at)).await?;
+    Ok(Quote {
+        amount_out: dex.decode_quote(&data)?,
+        block,
+    })
+}
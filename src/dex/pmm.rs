@@ -0,0 +1,131 @@
+//! Oracle-driven PMM venues (WooFi, DODO V2)
+//!
+//! These don't expose a V2 router, so each adapter maps the common quote
+//! request onto the venue's own query function.
+
+use super::{DexQuoter, QuoteCall};
+use crate::contracts::dodo_pool::{QuerySellBaseCall, QuerySellBaseReturn, QuerySellQuoteCall};
+use crate::contracts::woo_router::{QuerySwapCall, QuerySwapReturn};
+use crate::contracts::DodoPool;
+use ethers::abi::{AbiDecode, AbiEncode};
+use ethers::providers::Middleware;
+use ethers::types::{Address, U256};
+use std::sync::Arc;
+
+pub struct WooFi {
+    router: Address,
+}
+
+impl WooFi {
+    pub fn new(router: Address) -> Self {
+        Self { router }
+    }
+}
+
+impl DexQuoter for WooFi {
+    fn name(&self) -> &str {
+        "WooFi"
+    }
+
+    fn quote_call(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+    ) -> anyhow::Result<QuoteCall> {
+        let call = QuerySwapCall {
+            from_token: token_in,
+            to_token: token_out,
+            from_amount: amount_in,
+        };
+        Ok(QuoteCall {
+            target: self.router,
+            calldata: call.encode().into(),
+        })
+    }
+
+    fn decode_quote(&self, data: &[u8]) -> anyhow::Result<U256> {
+        Ok(QuerySwapReturn::decode(data)?.to_amount)
+    }
+}
+
+/// A single DODO V2 pool (DVM, DPP or DSP). Selling the base token and selling
+/// the quote token are separate query functions, so the pool's tokens are
+/// resolved once when the adapter is created.
+pub struct Dodo {
+    name: String,
+    pool: Address,
+    base_token: Address,
+    quote_token: Address,
+}
+
+impl Dodo {
+    /// `None` when the pool doesn't trade `token_in` against `token_out`.
+    pub async fn load<M: Middleware + 'static>(
+        client: Arc<M>,
+        pool: Address,
+        token_in: Address,
+        token_out: Address,
+    ) -> anyhow::Result<Option<Self>> {
+        let contract = DodoPool::new(pool, client);
+        let base_token = contract.base_token().call().await?;
+        let quote_token = contract.quote_token().call().await?;
+        if !pair_matches(base_token, quote_token, token_in, token_out) {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            name: format!("DODO {:?}", pool),
+            pool,
+            base_token,
+            quote_token,
+        }))
+    }
+}
+
+fn pair_matches(base: Address, quote: Address, a: Address, b: Address) -> bool {
+    (base, quote) == (a, b) || (base, quote) == (b, a)
+}
+
+impl DexQuoter for Dodo {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn quote_call(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+    ) -> anyhow::Result<QuoteCall> {
+        if !pair_matches(self.base_token, self.quote_token, token_in, token_out) {
+            anyhow::bail!(
+                "{} does not trade {:?} for {:?}",
+                self.name,
+                token_in,
+                token_out
+            );
+        }
+        let calldata = if token_in == self.base_token {
+            QuerySellBaseCall {
+                trader: Address::zero(),
+                pay_base_amount: amount_in,
+            }
+            .encode()
+        } else {
+            QuerySellQuoteCall {
+                trader: Address::zero(),
+                pay_quote_amount: amount_in,
+            }
+            .encode()
+        };
+        Ok(QuoteCall {
+            target: self.pool,
+            calldata: calldata.into(),
+        })
+    }
+
+    fn decode_quote(&self, data: &[u8]) -> anyhow::Result<U256> {
+        // Both query functions return (received amount, fee) with the same layout.
+        Ok(QuerySellBaseReturn::decode(data)?.receive_quote_amount)
+    }
+}
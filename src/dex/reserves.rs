@@ -0,0 +1,180 @@
+//! Execution price of V2 legs from pool reserves
+//!
+//! A router quote says what a trade returns but not how much of the spread
+//! the trade itself eats. Reading the pair's `getReserves` at the quoted block
+//! gives both the exact constant-product output for the trade size (with a
+//! `V2_FEE_BPS` LP fee) and the output at the pool's mid price, whose gap is
+//! the trade's price impact.
+//!
+//! The same math finds the round-trip size that maximises the gain of a
+//! price gap between two pools ([`optimal_round_trip`]), instead of judging
+//! it at the fixed `TRADE_SIZE_WEI`.
+
+use super::BlockRef;
+use crate::contracts::{UniswapV2Factory, UniswapV2Pair, UniswapV2Router};
+use crate::units;
+use ethers::providers::Middleware;
+use ethers::types::{Address, BlockId, BlockNumber, I256, U256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Reserves {
+    pub reserve_in: U256,
+    pub reserve_out: U256,
+}
+
+impl Reserves {
+    /// Constant-product output of `amount_in` after a `fee_bps` LP fee, as
+    /// the pair contract computes it.
+    pub fn amount_out(&self, amount_in: U256, fee_bps: u32) -> U256 {
+        let with_fee = amount_in * U256::from(10_000u32.saturating_sub(fee_bps));
+        let denominator = self.reserve_in * U256::from(10_000u32) + with_fee;
+        if denominator.is_zero() {
+            return U256::zero();
+        }
+        with_fee * self.reserve_out / denominator
+    }
+
+    /// Share of the mid-price output (after the fee) lost to the trade's own
+    /// price impact, in basis points.
+    pub fn price_impact_bps(&self, amount_in: U256, fee_bps: u32) -> Option<f64> {
+        let reserve_in = units::to_f64(self.reserve_in, 0);
+        if reserve_in <= 0.0 {
+            return None;
+        }
+        let fee = 1.0 - fee_bps as f64 / 10_000.0;
+        let ideal =
+            units::to_f64(amount_in, 0) * fee * units::to_f64(self.reserve_out, 0) / reserve_in;
+        if ideal <= 0.0 {
+            return None;
+        }
+        let exact = units::to_f64(self.amount_out(amount_in, fee_bps), 0);
+        Some((1.0 - exact / ideal) * 10_000.0)
+    }
+}
+
+/// Round trip of `amount_in` sold on `sell` and bought back on `buy`.
+#[derive(Debug, Clone, Copy)]
+pub struct RoundTrip {
+    pub amount_in: U256,
+    pub sell_out: U256,
+    pub buy_back: U256,
+}
+
+impl RoundTrip {
+    fn at(sell: &Reserves, buy: &Reserves, amount_in: U256, fee_bps: u32) -> Self {
+        let sell_out = sell.amount_out(amount_in, fee_bps);
+        Self {
+            amount_in,
+            sell_out,
+            buy_back: buy.amount_out(sell_out, fee_bps),
+        }
+    }
+
+    /// `token_in` gained, negative when the round trip loses.
+    pub fn gain(&self) -> I256 {
+        I256::from_raw(self.buy_back) - I256::from_raw(self.amount_in)
+    }
+}
+
+/// Round trip whose `token_in` gain is largest, found by ternary search over
+/// sizes up to the selling pool's input reserve; the gain of two
+/// constant-product legs is concave in the size. A size of zero means no
+/// size gains.
+pub fn optimal_round_trip(sell: &Reserves, buy: &Reserves, fee_bps: u32) -> RoundTrip {
+    let trip = |amount_in| RoundTrip::at(sell, buy, amount_in, fee_bps);
+    let (mut lo, mut hi) = (U256::zero(), sell.reserve_in);
+    while hi - lo > U256::from(2u32) {
+        let third = (hi - lo) / 3;
+        let (left, right) = (lo + third, hi - third);
+        if trip(left).gain() < trip(right).gain() {
+            lo = left;
+        } else {
+            hi = right;
+        }
+    }
+    let mut best = trip(U256::zero());
+    let mut size = lo;
+    while size <= hi {
+        let candidate = trip(size);
+        if candidate.gain() > best.gain() {
+            best = candidate;
+        }
+        size += U256::one();
+    }
+    best
+}
+
+/// Reads reserves of the pools behind V2 routers, resolving each router's
+/// pair once.
+pub struct ReserveReader<M> {
+    client: Arc<M>,
+    /// (router, token_a, token_b) → pair, `None` when the router has no pool.
+    pairs: Mutex<HashMap<(Address, Address, Address), Option<Address>>>,
+}
+
+impl<M: Middleware + 'static> ReserveReader<M> {
+    pub fn new(client: Arc<M>) -> Self {
+        Self {
+            client,
+            pairs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn pair(
+        &self,
+        router: Address,
+        token_a: Address,
+        token_b: Address,
+    ) -> anyhow::Result<Option<Address>> {
+        let key = (router, token_a.min(token_b), token_a.max(token_b));
+        if let Some(pair) = self.pairs.lock().unwrap().get(&key) {
+            return Ok(*pair);
+        }
+        let factory = UniswapV2Router::new(router, Arc::clone(&self.client))
+            .factory()
+            .call()
+            .await?;
+        let pair = UniswapV2Factory::new(factory, Arc::clone(&self.client))
+            .get_pair(token_a, token_b)
+            .call()
+            .await?;
+        let pair = (pair != Address::zero()).then_some(pair);
+        self.pairs.lock().unwrap().insert(key, pair);
+        Ok(pair)
+    }
+
+    /// Reserves of the router's `token_in`/`token_out` pool at `block`,
+    /// oriented for a `token_in` → `token_out` trade.
+    pub async fn load(
+        &self,
+        router: Address,
+        token_in: Address,
+        token_out: Address,
+        block: BlockRef,
+    ) -> anyhow::Result<Option<Reserves>> {
+        let Some(pair) = self.pair(router, token_in, token_out).await? else {
+            return Ok(None);
+        };
+        let at = BlockId::Number(BlockNumber::Number(block.number.into()));
+        let (reserve0, reserve1, _) = UniswapV2Pair::new(pair, Arc::clone(&self.client))
+            .get_reserves()
+            .block(at)
+            .call()
+            .await?;
+        let (reserve0, reserve1) = (U256::from(reserve0), U256::from(reserve1));
+        // Pairs order their tokens by address.
+        Ok(Some(if token_in < token_out {
+            Reserves {
+                reserve_in: reserve0,
+                reserve_out: reserve1,
+            }
+        } else {
+            Reserves {
+                reserve_in: reserve1,
+                reserve_out: reserve0,
+            }
+        }))
+    }
+}
@@ -0,0 +1,258 @@
+//! Stable-pool venues (Balancer V2, Curve)
+//!
+//! Configured per pool in `STABLE_POOLS` as `type:0xPool`. Balancer pools are
+//! quoted through the Vault's `queryBatchSwap` with a single swap step, Curve
+//! pools through their own `get_dy`, which takes coin indexes rather than
+//! addresses; `curve` pools index with `int128`, `curve-crypto` pools with
+//! `uint256`. Pools that don't hold both pair tokens are skipped.
+
+use super::{DexQuoter, QuoteCall};
+use crate::contracts::balancer_vault::{
+    BatchSwapStep, FundManagement, QueryBatchSwapCall, QueryBatchSwapReturn,
+};
+use crate::contracts::curve_crypto_pool::GetDyCall as CryptoGetDyCall;
+use crate::contracts::curve_pool::{GetDyCall, GetDyReturn};
+use crate::contracts::{BalancerPool, BalancerVault, CurvePool};
+use anyhow::Context;
+use ethers::abi::{AbiDecode, AbiEncode};
+use ethers::providers::Middleware;
+use ethers::types::{Address, Bytes, U256};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// The Balancer V2 Vault, at the same address on every chain.
+pub const BALANCER_VAULT: &str = "0xBA12222222228d8Ba445958a75a0704d566BF2C8";
+
+/// Most coins a Curve pool is probed for.
+const MAX_CURVE_COINS: u64 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PoolType {
+    Balancer,
+    Curve,
+    CurveCrypto,
+}
+
+impl FromStr for PoolType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "balancer" => Ok(PoolType::Balancer),
+            "curve" => Ok(PoolType::Curve),
+            "curve-crypto" => Ok(PoolType::CurveCrypto),
+            other => Err(format!(
+                "unknown pool type {:?} (balancer, curve, curve-crypto)",
+                other
+            )),
+        }
+    }
+}
+
+/// One Balancer V2 pool, quoted through the Vault.
+pub struct Balancer {
+    name: String,
+    vault: Address,
+    pool_id: [u8; 32],
+}
+
+impl Balancer {
+    /// `None` when the pool doesn't hold both tokens.
+    pub async fn load<M: Middleware + 'static>(
+        client: Arc<M>,
+        pool: Address,
+        token_in: Address,
+        token_out: Address,
+    ) -> anyhow::Result<Option<Self>> {
+        let vault = BALANCER_VAULT.parse::<Address>()?;
+        let pool_id = BalancerPool::new(pool, Arc::clone(&client))
+            .get_pool_id()
+            .call()
+            .await?;
+        let (tokens, _, _) = BalancerVault::new(vault, client)
+            .get_pool_tokens(pool_id)
+            .call()
+            .await?;
+        if !tokens.contains(&token_in) || !tokens.contains(&token_out) {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            name: format!("Balancer {:?}", pool),
+            vault,
+            pool_id,
+        }))
+    }
+}
+
+impl DexQuoter for Balancer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn quote_call(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+    ) -> anyhow::Result<QuoteCall> {
+        let call = QueryBatchSwapCall {
+            // GIVEN_IN: `amount` is what goes in.
+            kind: 0,
+            swaps: vec![BatchSwapStep {
+                pool_id: self.pool_id,
+                asset_in_index: U256::zero(),
+                asset_out_index: U256::one(),
+                amount: amount_in,
+                user_data: Bytes::new(),
+            }],
+            assets: vec![token_in, token_out],
+            funds: FundManagement {
+                sender: Address::zero(),
+                from_internal_balance: false,
+                recipient: Address::zero(),
+                to_internal_balance: false,
+            },
+        };
+        Ok(QuoteCall {
+            target: self.vault,
+            calldata: call.encode().into(),
+        })
+    }
+
+    fn decode_quote(&self, data: &[u8]) -> anyhow::Result<U256> {
+        // Deltas are from the Vault's side: what leaves it is negative.
+        let deltas = QueryBatchSwapReturn::decode(data)?.asset_deltas;
+        let out = deltas.get(1).copied().unwrap_or_default();
+        Ok(if out.is_negative() {
+            out.unsigned_abs()
+        } else {
+            U256::zero()
+        })
+    }
+}
+
+/// One Curve pool, with its coins resolved to indexes up front.
+pub struct Curve {
+    name: String,
+    pool: Address,
+    crypto: bool,
+    coins: Vec<Address>,
+}
+
+impl Curve {
+    /// `None` when the pool doesn't hold both tokens.
+    pub async fn load<M: Middleware + 'static>(
+        client: Arc<M>,
+        pool: Address,
+        crypto: bool,
+        token_in: Address,
+        token_out: Address,
+    ) -> anyhow::Result<Option<Self>> {
+        // Both kinds expose `coins(uint256)`; past the last coin it reverts.
+        let contract = CurvePool::new(pool, client);
+        let mut coins = Vec::new();
+        for i in 0..MAX_CURVE_COINS {
+            match contract.coins(U256::from(i)).call().await {
+                Ok(coin) => coins.push(coin),
+                Err(_) if !coins.is_empty() => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if !coins.contains(&token_in) || !coins.contains(&token_out) {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            name: format!("Curve {:?}", pool),
+            pool,
+            crypto,
+            coins,
+        }))
+    }
+
+    fn index(&self, token: Address) -> anyhow::Result<usize> {
+        self.coins
+            .iter()
+            .position(|&c| c == token)
+            .with_context(|| format!("{} does not hold {:?}", self.name, token))
+    }
+}
+
+impl DexQuoter for Curve {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn quote_call(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+    ) -> anyhow::Result<QuoteCall> {
+        let (i, j) = (self.index(token_in)?, self.index(token_out)?);
+        let calldata = if self.crypto {
+            CryptoGetDyCall {
+                i: U256::from(i),
+                j: U256::from(j),
+                dx: amount_in,
+            }
+            .encode()
+        } else {
+            GetDyCall {
+                i: i as i128,
+                j: j as i128,
+                dx: amount_in,
+            }
+            .encode()
+        };
+        Ok(QuoteCall {
+            target: self.pool,
+            calldata: calldata.into(),
+        })
+    }
+
+    fn decode_quote(&self, data: &[u8]) -> anyhow::Result<U256> {
+        // Both `get_dy` variants return a single uint256.
+        Ok(GetDyReturn::decode(data)?.0)
+    }
+}
+
+/// Loads every `(type, pool)` of `pools` that holds both pair tokens.
+pub async fn load_pools<M: Middleware + 'static>(
+    client: Arc<M>,
+    pools: &[(PoolType, Address)],
+    token_in: Address,
+    token_out: Address,
+) -> Vec<Box<dyn DexQuoter>> {
+    let mut venues: Vec<Box<dyn DexQuoter>> = Vec::new();
+    for &(kind, pool) in pools {
+        let loaded = match kind {
+            PoolType::Balancer => Balancer::load(Arc::clone(&client), pool, token_in, token_out)
+                .await
+                .map(|b| b.map(|b| Box::new(b) as Box<dyn DexQuoter>)),
+            PoolType::Curve | PoolType::CurveCrypto => Curve::load(
+                Arc::clone(&client),
+                pool,
+                kind == PoolType::CurveCrypto,
+                token_in,
+                token_out,
+            )
+            .await
+            .map(|c| c.map(|c| Box::new(c) as Box<dyn DexQuoter>)),
+        };
+        match loaded {
+            Ok(Some(venue)) => {
+                log::info!("Enabled {}", venue.name());
+                venues.push(venue);
+            }
+            Ok(None) => log::warn!(
+                "{:?} pool {:?} does not hold both pair tokens, skipping",
+                kind,
+                pool
+            ),
+            Err(e) => log::warn!("Could not load {:?} pool {:?}: {:?}", kind, pool, e),
+        }
+    }
+    venues
+}
@@ -0,0 +1,219 @@
+//! Uniswap V2-style routers (QuickSwap, SushiSwap and their forks)
+
+use super::{DexQuoter, QuoteCall};
+use crate::contracts::uniswap_v2_router::{GetAmountsOutCall, GetAmountsOutReturn};
+use crate::contracts::UniswapV2Factory;
+use ethers::abi::{AbiDecode, AbiEncode};
+use ethers::providers::Middleware;
+use ethers::types::{Address, U256};
+use std::sync::Arc;
+
+/// A V2 fork deployed on Polygon that can be enabled by name via `V2_FORKS`.
+pub struct KnownFork {
+    pub name: &'static str,
+    pub router: &'static str,
+    pub factory: &'static str,
+}
+
+pub const KNOWN_FORKS: &[KnownFork] = &[
+    KnownFork {
+        name: "Meshswap",
+        router: "0x10f4A785F458Bc144e3706575924889954946639",
+        factory: "0x9F3044f7F9FC8bC9eD615d54845b4577B833282d",
+    },
+    KnownFork {
+        name: "Dfyn",
+        router: "0xA102072A4C07F06EC3B4900FDC4C7B80b6c57429",
+        factory: "0xE7Fb3e833eFE5F9c441105EB65Ef8b261266423B",
+    },
+    KnownFork {
+        name: "ApeSwap",
+        router: "0xC0788A3aD43d79aa53B09c2EaCc313A787d1d607",
+        factory: "0xCf083Be4164828f00cAE704EC15a36D711491284",
+    },
+];
+
+pub struct V2Router {
+    name: String,
+    router: Address,
+    /// Custom route between the pair tokens, in either direction; empty for
+    /// the direct pool.
+    path: Vec<Address>,
+    path_label: Option<String>,
+}
+
+impl V2Router {
+    pub fn new(name: impl Into<String>, router: Address) -> Self {
+        Self {
+            name: name.into(),
+            router,
+            path: Vec::new(),
+            path_label: None,
+        }
+    }
+
+    /// A router quoting the pair along `path` (`DEX_PATHS`), labelled
+    /// `label` in opportunity records.
+    pub fn with_path(
+        name: impl Into<String>,
+        router: Address,
+        path: Vec<Address>,
+        label: String,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            router,
+            path,
+            path_label: Some(label),
+        }
+    }
+
+    /// `path` when it runs from `token_in` to `token_out`, reversed when it
+    /// runs the other way; the direct hop for any other tokens.
+    fn route(&self, token_in: Address, token_out: Address) -> Vec<Address> {
+        match (self.path.first(), self.path.last()) {
+            (Some(&first), Some(&last)) if first == token_in && last == token_out => {
+                self.path.clone()
+            }
+            (Some(&first), Some(&last)) if first == token_out && last == token_in => {
+                self.path.iter().rev().copied().collect()
+            }
+            _ => vec![token_in, token_out],
+        }
+    }
+}
+
+impl DexQuoter for V2Router {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn quote_call(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+    ) -> anyhow::Result<QuoteCall> {
+        let call = GetAmountsOutCall {
+            amount_in,
+            path: self.route(token_in, token_out),
+        };
+        Ok(QuoteCall {
+            target: self.router,
+            calldata: call.encode().into(),
+        })
+    }
+
+    fn decode_quote(&self, data: &[u8]) -> anyhow::Result<U256> {
+        let ret = GetAmountsOutReturn::decode(data)?;
+        Ok(ret.amounts.last().cloned().unwrap_or_else(U256::zero))
+    }
+
+    /// Routed quotes span several pools, so neither pool math nor a direct
+    /// swap applies to them.
+    fn v2_router(&self) -> Option<Address> {
+        self.path.is_empty().then_some(self.router)
+    }
+
+    fn path(&self) -> Option<&str> {
+        self.path_label.as_deref()
+    }
+}
+
+/// A V2 router quoting through fixed intermediate tokens, e.g. the
+/// A→B→C→A cycles of triangular arbitrage.
+pub struct V2Route {
+    name: String,
+    router: Address,
+    hops: Vec<Address>,
+}
+
+impl V2Route {
+    pub fn new(name: impl Into<String>, router: Address, hops: Vec<Address>) -> Self {
+        Self {
+            name: name.into(),
+            router,
+            hops,
+        }
+    }
+}
+
+impl DexQuoter for V2Route {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn quote_call(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+    ) -> anyhow::Result<QuoteCall> {
+        let mut path = Vec::with_capacity(self.hops.len() + 2);
+        path.push(token_in);
+        path.extend(&self.hops);
+        path.push(token_out);
+        let call = GetAmountsOutCall { amount_in, path };
+        Ok(QuoteCall {
+            target: self.router,
+            calldata: call.encode().into(),
+        })
+    }
+
+    fn decode_quote(&self, data: &[u8]) -> anyhow::Result<U256> {
+        let ret = GetAmountsOutReturn::decode(data)?;
+        Ok(ret.amounts.last().cloned().unwrap_or_else(U256::zero))
+    }
+}
+
+pub async fn pair_exists<M: Middleware + 'static>(
+    client: Arc<M>,
+    factory: Address,
+    token_a: Address,
+    token_b: Address,
+) -> anyhow::Result<bool> {
+    let pair = UniswapV2Factory::new(factory, client)
+        .get_pair(token_a, token_b)
+        .call()
+        .await?;
+    Ok(pair != Address::zero())
+}
+
+/// Resolves the requested fork names against `KNOWN_FORKS`, keeping only the
+/// forks whose factory actually has a pool for the pair.
+pub async fn load_forks<M: Middleware + 'static>(
+    client: Arc<M>,
+    names: &[String],
+    token_in: Address,
+    token_out: Address,
+) -> anyhow::Result<Vec<V2Router>> {
+    let mut routers = Vec::new();
+    for name in names {
+        let Some(fork) = KNOWN_FORKS
+            .iter()
+            .find(|f| f.name.eq_ignore_ascii_case(name))
+        else {
+            log::warn!("Unknown V2 fork '{}', skipping", name);
+            continue;
+        };
+        let router = fork.router.parse::<Address>()?;
+        let factory = fork.factory.parse::<Address>()?;
+
+        match pair_exists(Arc::clone(&client), factory, token_in, token_out).await {
+            Ok(true) => {
+                log::info!("Enabled {} router {:?}", fork.name, router);
+                routers.push(V2Router::new(fork.name, router));
+            }
+            Ok(false) => log::warn!(
+                "{} has no pool for the configured pair, skipping",
+                fork.name
+            ),
+            Err(e) => log::warn!(
+                "Could not validate {} against its factory: {:?}",
+                fork.name,
+                e
+            ),
+        }
+    }
+    Ok(routers)
+}
@@ -0,0 +1,150 @@
+//! Uniswap V3 pools, quoted through QuoterV2
+//!
+//! A V3 pair has one pool per fee tier, so each configured tier is its own
+//! venue. QuoterV2 simulates the swap and reverts internally, which is why it
+//! is not `view`; an `eth_call` still returns the result.
+//!
+//! Most of the time one tier quotes best cycle after cycle, so a [`TierCache`]
+//! quotes only that tier and sweeps every tier again every
+//! `V3_FULL_SWEEP_EVERY` cycles, or as soon as the cached tier fails.
+
+use super::{DexQuoter, QuoteCall};
+use crate::contracts::quoter_v2::{
+    QuoteExactInputSingleCall, QuoteExactInputSingleParams, QuoteExactInputSingleReturn,
+};
+use crate::contracts::UniswapV3Factory;
+use ethers::abi::{AbiDecode, AbiEncode};
+use ethers::providers::Middleware;
+use ethers::types::{Address, U256};
+use std::sync::Arc;
+
+/// Uniswap's QuoterV2 deployment on Polygon.
+pub const QUOTER_V2: &str = "0x61fFE014bA17989E743c5F6cB21bF9697530B21e";
+/// Uniswap's V3 factory on Polygon.
+pub const FACTORY: &str = "0x1F98431c8aD98523631AE4a59f267346ea31F984";
+
+pub struct V3Pool {
+    name: String,
+    quoter: Address,
+    /// Fee tier in hundredths of a bip (500 = 0.05%).
+    fee: u32,
+}
+
+impl V3Pool {
+    pub fn new(name: impl Into<String>, quoter: Address, fee: u32) -> Self {
+        Self {
+            name: name.into(),
+            quoter,
+            fee,
+        }
+    }
+}
+
+impl DexQuoter for V3Pool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn quote_call(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+    ) -> anyhow::Result<QuoteCall> {
+        let call = QuoteExactInputSingleCall {
+            params: QuoteExactInputSingleParams {
+                token_in,
+                token_out,
+                amount_in,
+                fee: self.fee,
+                sqrt_price_limit_x96: U256::zero(),
+            },
+        };
+        Ok(QuoteCall {
+            target: self.quoter,
+            calldata: call.encode().into(),
+        })
+    }
+
+    fn decode_quote(&self, data: &[u8]) -> anyhow::Result<U256> {
+        Ok(QuoteExactInputSingleReturn::decode(data)?.amount_out)
+    }
+
+    fn v3_fee(&self) -> Option<u32> {
+        Some(self.fee)
+    }
+}
+
+/// The fee tier of a pair that quoted best last, so the other tiers can be
+/// left out until the next full sweep.
+pub struct TierCache {
+    sweep_every: u32,
+    /// Venue index of the best tier; unset until a sweep found one.
+    best: Option<usize>,
+    /// Cycles since the last full sweep.
+    cycles: u32,
+}
+
+impl TierCache {
+    pub fn new(sweep_every: u32) -> Self {
+        Self {
+            sweep_every,
+            best: None,
+            cycles: 0,
+        }
+    }
+
+    /// Venue indexes of `tiers` to leave out this cycle: every one but the
+    /// best, unless a full sweep is due.
+    pub fn skipped(&mut self, tiers: &[usize]) -> Vec<usize> {
+        let best = match self.best {
+            Some(best) if tiers.len() >= 2 && self.cycles + 1 < self.sweep_every => best,
+            _ => {
+                self.cycles = 0;
+                return Vec::new();
+            }
+        };
+        self.cycles += 1;
+        tiers.iter().copied().filter(|&i| i != best).collect()
+    }
+
+    /// Remembers the tier that quoted the most this cycle; none (e.g. the
+    /// cached tier failed to quote) makes the next cycle a full sweep.
+    pub fn observe(&mut self, best: Option<usize>) {
+        self.best = best;
+    }
+}
+
+/// Name used for a fee tier without an explicit one, e.g. `UniswapV3 0.05%`.
+pub fn default_name(fee: u32) -> String {
+    format!("UniswapV3 {}%", fee as f64 / 10_000.0)
+}
+
+/// Builds a venue per `(name, fee)` tier, keeping only the tiers whose
+/// factory actually has a pool for the pair.
+pub async fn load_pools<M: Middleware + 'static>(
+    client: Arc<M>,
+    quoter: Address,
+    factory: Address,
+    tiers: &[(String, u32)],
+    token_in: Address,
+    token_out: Address,
+) -> anyhow::Result<Vec<V3Pool>> {
+    let factory = UniswapV3Factory::new(factory, client);
+    let mut pools = Vec::new();
+    for (name, fee) in tiers {
+        match factory.get_pool(token_in, token_out, *fee).call().await {
+            Ok(pool) if pool != Address::zero() => {
+                log::info!("Enabled {} pool {:?}", name, pool);
+                pools.push(V3Pool::new(name, quoter, *fee));
+            }
+            Ok(_) => log::warn!("{} has no pool for the configured pair, skipping", name),
+            Err(e) => log::warn!(
+                "Could not validate {} against the V3 factory: {:?}",
+                name,
+                e
+            ),
+        }
+    }
+    Ok(pools)
+}
@@ -0,0 +1,186 @@
+//! Process logger and diagnostics bundles for remote debugging
+//!
+//! The log filter can be swapped at runtime (admin API) and the logger keeps
+//! the last `LOG_LINES` records in memory. When a bot instance fails
+//! `DIAGNOSTICS_AFTER_FAILURES` cycles in a row, those lines are zipped into
+//! `DIAGNOSTICS_DIR` together with a redacted config summary, the last quotes
+//! and recent RPC latencies. Lines logged while a request or detection cycle
+//! is in scope are prefixed with its ID.
+
+use crate::correlation;
+use chrono::{DateTime, Utc};
+use log::{Log, Metadata, Record};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+const LOG_LINES: usize = 500;
+/// RPC round trips kept per detector.
+pub const RPC_SAMPLES: usize = 100;
+
+static RECENT_LOGS: Lazy<Mutex<VecDeque<String>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(LOG_LINES)));
+
+static LOGGER: Lazy<RecordingLogger> = Lazy::new(|| {
+    let spec = std::env::var("RUST_LOG").unwrap_or_default();
+    RecordingLogger {
+        inner: RwLock::new(build_logger(&spec)),
+        spec: RwLock::new(spec),
+    }
+});
+
+/// Forwards to `env_logger` and remembers what it printed.
+struct RecordingLogger {
+    inner: RwLock<env_logger::Logger>,
+    /// `RUST_LOG`-style filter `inner` was built from.
+    spec: RwLock<String>,
+}
+
+fn build_logger(spec: &str) -> env_logger::Logger {
+    env_logger::Builder::new()
+        .filter_level(log::LevelFilter::Error)
+        .parse_filters(spec)
+        .build()
+}
+
+impl Log for RecordingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.read().unwrap().enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        let inner = self.inner.read().unwrap();
+        if !inner.matches(record) {
+            return;
+        }
+        let id = correlation::current();
+        let prefix = id
+            .as_deref()
+            .map(|id| format!("[{}] ", id))
+            .unwrap_or_default();
+        let line = format!(
+            "{} {:<5} {}: {}{}",
+            Utc::now().to_rfc3339(),
+            record.level(),
+            record.target(),
+            prefix,
+            record.args()
+        );
+        let mut logs = RECENT_LOGS.lock().unwrap();
+        if logs.len() == LOG_LINES {
+            logs.pop_front();
+        }
+        logs.push_back(line);
+        drop(logs);
+        match id {
+            Some(_) => inner.log(
+                &Record::builder()
+                    .level(record.level())
+                    .target(record.target())
+                    .module_path(record.module_path())
+                    .file(record.file())
+                    .line(record.line())
+                    .args(format_args!("{}{}", prefix, record.args()))
+                    .build(),
+            ),
+            None => inner.log(record),
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.read().unwrap().flush();
+    }
+}
+
+/// Installs the process logger (`RUST_LOG` filters as with `env_logger::init`).
+pub fn init_logging() {
+    log::set_max_level(LOGGER.inner.read().unwrap().filter());
+    log::set_logger(&*LOGGER).expect("logger already set");
+}
+
+/// Current `RUST_LOG`-style filter.
+pub fn log_filter() -> String {
+    LOGGER.spec.read().unwrap().clone()
+}
+
+/// Replaces the log filter (e.g. `info,polygon_arb_bot::planner=debug`)
+/// without restarting. Unparseable directives are ignored by `env_logger`.
+pub fn set_log_filter(spec: &str) {
+    let logger = build_logger(spec);
+    log::set_max_level(logger.filter());
+    *LOGGER.inner.write().unwrap() = logger;
+    *LOGGER.spec.write().unwrap() = spec.to_string();
+}
+
+/// Last quote of a DEX as seen by the detector.
+#[derive(Serialize, Clone)]
+pub struct QuoteTrace {
+    pub dex: String,
+    pub block: u64,
+    pub amount_in: String,
+    pub amount_out: Option<String>,
+}
+
+/// What the detector remembers between cycles for a bundle.
+#[derive(Default)]
+pub struct CycleTrace {
+    pub quotes: Vec<QuoteTrace>,
+    pub rpc_latencies_ms: VecDeque<f64>,
+}
+
+impl CycleTrace {
+    pub fn record_latency(&mut self, ms: f64) {
+        if self.rpc_latencies_ms.len() == RPC_SAMPLES {
+            self.rpc_latencies_ms.pop_front();
+        }
+        self.rpc_latencies_ms.push_back(ms);
+    }
+}
+
+/// Writes `diagnostics-{instance}-{timestamp}.zip` into `dir`.
+pub fn write_bundle(
+    dir: &Path,
+    instance: &str,
+    now: DateTime<Utc>,
+    error: &anyhow::Error,
+    config: &serde_json::Value,
+    trace: &CycleTrace,
+) -> anyhow::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!(
+        "diagnostics-{}-{}.zip",
+        instance,
+        now.format("%Y%m%dT%H%M%SZ")
+    ));
+    let mut zip = ZipWriter::new(File::create(&path)?);
+    let options = FileOptions::default();
+
+    zip.start_file("error.txt", options)?;
+    if let Some(cycle_id) = correlation::current() {
+        writeln!(zip, "cycle {}", cycle_id)?;
+    }
+    writeln!(zip, "{}\n\n{:?}", now.to_rfc3339(), error)?;
+
+    zip.start_file("logs.txt", options)?;
+    for line in RECENT_LOGS.lock().unwrap().iter() {
+        writeln!(zip, "{}", line)?;
+    }
+
+    zip.start_file("config.json", options)?;
+    zip.write_all(serde_json::to_string_pretty(config)?.as_bytes())?;
+
+    zip.start_file("quotes.json", options)?;
+    zip.write_all(serde_json::to_string_pretty(&trace.quotes)?.as_bytes())?;
+
+    zip.start_file("rpc_latencies_ms.json", options)?;
+    zip.write_all(serde_json::to_string(&trace.rpc_latencies_ms)?.as_bytes())?;
+
+    zip.finish()?;
+    Ok(path)
+}
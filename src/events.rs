@@ -0,0 +1,89 @@
+//! Bursts of opportunities grouped into market events
+//!
+//! One large move usually produces a run of near-identical opportunities over
+//! several cycles and across bot instances. `/api/events` walks the stored
+//! opportunities in time order and starts a new event whenever more than
+//! `gap_secs` pass without one, returning a summary per event instead of
+//! every row.
+
+use crate::db::{opportunity_from_row, OPPORTUNITY_COLUMNS};
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+#[derive(Deserialize)]
+pub struct EventParams {
+    /// Look-back window in hours (default 24).
+    pub hours: Option<u32>,
+    /// Longest quiet period inside one event in seconds (default 60).
+    pub gap_secs: Option<u32>,
+    pub instance: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct MarketEvent {
+    pub start: String,
+    pub end: String,
+    pub opportunities: usize,
+    pub instances: BTreeSet<String>,
+    pub strategies: BTreeSet<String>,
+    pub total_profit: f64,
+    pub max_profit: f64,
+    /// Id of the most profitable opportunity of the event.
+    pub best_id: i64,
+}
+
+/// Events in the window, newest first.
+pub fn load(
+    conn: &Connection,
+    now: DateTime<Utc>,
+    hours: u32,
+    gap_secs: u32,
+    instance: Option<&str>,
+) -> anyhow::Result<Vec<MarketEvent>> {
+    let since = (now - Duration::hours(hours as i64)).to_rfc3339();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM opportunities WHERE timestamp >= ?1 AND (?2 IS NULL OR instance = ?2)
+         ORDER BY timestamp",
+        OPPORTUNITY_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![since, instance], opportunity_from_row)?;
+
+    let gap = Duration::seconds(gap_secs as i64);
+    let mut events: Vec<MarketEvent> = Vec::new();
+    let mut last_seen: Option<DateTime<Utc>> = None;
+    for opp in rows {
+        let opp = opp?;
+        let at = DateTime::parse_from_rfc3339(&opp.timestamp)?.with_timezone(&Utc);
+        let current = events
+            .last_mut()
+            .filter(|_| last_seen.is_some_and(|last| at - last <= gap));
+        match current {
+            Some(event) => {
+                event.end = opp.timestamp.clone();
+                event.opportunities += 1;
+                event.instances.insert(opp.instance);
+                event.strategies.insert(opp.strategy);
+                event.total_profit += opp.profit;
+                if opp.profit > event.max_profit {
+                    event.max_profit = opp.profit;
+                    event.best_id = opp.id;
+                }
+            }
+            None => events.push(MarketEvent {
+                start: opp.timestamp.clone(),
+                end: opp.timestamp,
+                opportunities: 1,
+                instances: BTreeSet::from([opp.instance]),
+                strategies: BTreeSet::from([opp.strategy]),
+                total_profit: opp.profit,
+                max_profit: opp.profit,
+                best_id: opp.id,
+            }),
+        }
+        last_seen = Some(at);
+    }
+    events.reverse();
+    Ok(events)
+}
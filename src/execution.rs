@@ -0,0 +1,342 @@
+//! Live trade execution
+//!
+//! The bot only detects by default (`EXECUTION_MODE=dry-run`);
+//! `EXECUTION_MODE=paper` fills trades against simulated balances (see
+//! [`crate::paper`]). With
+//! `EXECUTION_MODE=live` and a wallet (`EXECUTION_PRIVATE_KEY`, or an
+//! encrypted `EXECUTION_KEYSTORE` file with `EXECUTION_KEYSTORE_PASSWORD`),
+//! every two-DEX opportunity whose legs both trade on V2 routers is executed
+//! as two `swapExactTokensForTokens` calls: `token_in` sold on one router,
+//! the `token_out` received bought back on the other. Each leg's minimum
+//! output is its quote less `EXECUTION_SLIPPAGE_BPS`, the router is approved
+//! first when needed, and one trade runs at a time. A transaction still
+//! unconfirmed after `EXECUTION_CONFIRM_TIMEOUT_SECS` ends the trade as
+//! `timeout`, so a stuck transaction holds up neither other pairs nor
+//! shutdown. Transaction hashes and the realized amounts are recorded in
+//! `executions`, and the gas of a filled round trip is folded into the pair's
+//! estimate (see [`crate::gas`]).
+
+use crate::contracts::{UniswapV2Router, ERC20};
+use crate::{gas, units};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use ethers::middleware::SignerMiddleware;
+use ethers::providers::Middleware;
+use ethers::providers::PendingTransaction;
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, TransactionReceipt, H256, U256};
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Seconds a sent swap stays valid.
+const DEADLINE_SECS: i64 = 120;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExecutionMode {
+    DryRun,
+    Paper,
+    Live,
+}
+
+impl FromStr for ExecutionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dry-run" | "dry_run" | "dryrun" => Ok(Self::DryRun),
+            "paper" => Ok(Self::Paper),
+            "live" => Ok(Self::Live),
+            _ => Err("expected dry-run, paper or live".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ExecutionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::DryRun => "dry-run",
+            Self::Paper => "paper",
+            Self::Live => "live",
+        })
+    }
+}
+
+/// A detected round trip to execute, with the labels it is recorded under.
+pub struct TradePlan {
+    pub instance: String,
+    pub pair: Option<String>,
+    pub cycle_id: Option<String>,
+    pub dex_sell: String,
+    pub dex_buy: String,
+    pub sell_router: Address,
+    pub buy_router: Address,
+    pub token_in: Address,
+    pub token_out: Address,
+    pub decimals_in: u32,
+    pub decimals_out: u32,
+    pub amount_in: U256,
+    /// Quoted token_out of the selling leg.
+    pub sell_quote: U256,
+    /// Quoted token_in bought back with `sell_quote`.
+    pub back_quote: U256,
+}
+
+/// Outcome of a trade; a failed leg leaves the later fields unset.
+#[derive(Debug, Default)]
+pub struct Execution {
+    pub sell_tx: Option<String>,
+    pub buy_tx: Option<String>,
+    /// token_out actually received from the selling leg.
+    pub received_out: Option<U256>,
+    /// token_in actually bought back.
+    pub realized_in: Option<U256>,
+    /// Gas used by the swaps that went through, approvals excluded.
+    pub gas_used: Option<u64>,
+    /// A transaction was still unconfirmed after the timeout; the leg it
+    /// belongs to may yet land.
+    pub timed_out: bool,
+    pub error: Option<String>,
+}
+
+impl Execution {
+    pub fn status(&self) -> &'static str {
+        match (&self.error, &self.sell_tx) {
+            (None, _) => "filled",
+            (Some(_), _) if self.timed_out => "timeout",
+            // Holding token_out: the selling leg went through, the buying one did not.
+            (Some(_), Some(_)) => "partial",
+            (Some(_), None) => "failed",
+        }
+    }
+}
+
+/// A transaction still unconfirmed after `EXECUTION_CONFIRM_TIMEOUT_SECS`.
+#[derive(Debug)]
+struct Unconfirmed {
+    what: &'static str,
+    tx: H256,
+    timeout: Duration,
+}
+
+impl fmt::Display for Unconfirmed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:?} unconfirmed after {}s",
+            self.what,
+            self.tx,
+            self.timeout.as_secs()
+        )
+    }
+}
+
+impl std::error::Error for Unconfirmed {}
+
+/// A trade that was sent, as handed to `on_execution` hooks and recorded.
+pub struct ExecutedTrade {
+    pub plan: TradePlan,
+    pub execution: Execution,
+}
+
+pub struct Executor<M> {
+    client: Arc<SignerMiddleware<M, LocalWallet>>,
+    slippage_bps: u32,
+    confirm_timeout: Duration,
+    /// One trade at a time, so nonces and balances of two trades never mix.
+    busy: tokio::sync::Mutex<()>,
+}
+
+impl<M: Middleware + 'static> Executor<M> {
+    pub async fn new(
+        client: M,
+        signer: LocalWallet,
+        slippage_bps: u32,
+        confirm_timeout: Duration,
+    ) -> anyhow::Result<Self> {
+        let chain_id = client.get_chainid().await?.as_u64();
+        let signer = signer.with_chain_id(chain_id);
+        log::warn!(
+            "Live execution enabled: trades are sent from {:?}",
+            signer.address()
+        );
+        Ok(Self {
+            client: Arc::new(SignerMiddleware::new(client, signer)),
+            slippage_bps,
+            confirm_timeout,
+            busy: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// The wallet trades are sent from.
+    pub fn address(&self) -> Address {
+        self.client.address()
+    }
+
+    /// Executes both legs of `plan`; failures are reported in the result.
+    pub async fn execute(&self, plan: &TradePlan, now: DateTime<Utc>) -> Execution {
+        let _busy = self.busy.lock().await;
+        let mut execution = Execution::default();
+        if let Err(e) = self.run(plan, now, &mut execution).await {
+            execution.timed_out = e.downcast_ref::<Unconfirmed>().is_some();
+            execution.error = Some(format!("{:#}", e));
+        }
+        execution
+    }
+
+    async fn run(
+        &self,
+        plan: &TradePlan,
+        now: DateTime<Utc>,
+        execution: &mut Execution,
+    ) -> anyhow::Result<()> {
+        let (received, sell_tx, sell_gas) = self
+            .swap(
+                plan.sell_router,
+                plan.token_in,
+                plan.token_out,
+                plan.amount_in,
+                self.min_out(plan.sell_quote),
+                now,
+            )
+            .await
+            .inspect_err(|e| execution.sell_tx = unconfirmed_swap(e))
+            .context("selling leg")?;
+        execution.sell_tx = Some(sell_tx);
+        execution.received_out = Some(received);
+        execution.gas_used = sell_gas;
+
+        // The buying leg spends what actually arrived, so its quote is scaled
+        // to the amount received.
+        let back_quote = if plan.sell_quote.is_zero() {
+            U256::zero()
+        } else {
+            plan.back_quote * received / plan.sell_quote
+        };
+        let (realized, buy_tx, buy_gas) = self
+            .swap(
+                plan.buy_router,
+                plan.token_out,
+                plan.token_in,
+                received,
+                self.min_out(back_quote),
+                now,
+            )
+            .await
+            .inspect_err(|e| execution.buy_tx = unconfirmed_swap(e))
+            .context("buying leg")?;
+        execution.buy_tx = Some(buy_tx);
+        execution.realized_in = Some(realized);
+        execution.gas_used = sell_gas.zip(buy_gas).map(|(sell, buy)| sell + buy);
+        Ok(())
+    }
+
+    fn min_out(&self, quote: U256) -> U256 {
+        quote * U256::from(10_000u32.saturating_sub(self.slippage_bps)) / U256::from(10_000u32)
+    }
+
+    /// Swaps `amount_in` on `router` and returns the output actually received
+    /// (by balance difference) with the transaction hash and its gas used.
+    async fn swap(
+        &self,
+        router: Address,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+        min_out: U256,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<(U256, String, Option<u64>)> {
+        let wallet = self.client.address();
+        let input = ERC20::new(token_in, Arc::clone(&self.client));
+        if input.allowance(wallet, router).call().await? < amount_in {
+            // Approved once per router and token rather than before every trade.
+            let approve = input.approve(router, U256::MAX);
+            let pending = approve.send().await?;
+            self.confirmed(pending, "approval").await?;
+        }
+
+        let output = ERC20::new(token_out, Arc::clone(&self.client));
+        let before = output.balance_of(wallet).call().await?;
+        let deadline = U256::from(now.timestamp() + DEADLINE_SECS);
+        let router = UniswapV2Router::new(router, Arc::clone(&self.client));
+        let swap = router.swap_exact_tokens_for_tokens(
+            amount_in,
+            min_out,
+            vec![token_in, token_out],
+            wallet,
+            deadline,
+        );
+        let pending = swap.send().await?;
+        let receipt = self.confirmed(pending, "swap").await?;
+        let after = output.balance_of(wallet).call().await?;
+        Ok((
+            after.saturating_sub(before),
+            format!("{:?}", receipt.transaction_hash),
+            receipt.gas_used.map(|gas| gas.as_u64()),
+        ))
+    }
+
+    /// Waits up to `confirm_timeout` for `pending` to be mined successfully.
+    async fn confirmed(
+        &self,
+        pending: PendingTransaction<'_, M::Provider>,
+        what: &'static str,
+    ) -> anyhow::Result<TransactionReceipt> {
+        let tx = pending.tx_hash();
+        let receipt = tokio::time::timeout(self.confirm_timeout, pending)
+            .await
+            .map_err(|_| Unconfirmed {
+                what,
+                tx,
+                timeout: self.confirm_timeout,
+            })??
+            .with_context(|| format!("{} {:?} was dropped", what, tx))?;
+        if receipt.status != Some(1u64.into()) {
+            anyhow::bail!("{} {:?} reverted", what, receipt.transaction_hash);
+        }
+        Ok(receipt)
+    }
+}
+
+/// Hash of a swap that timed out unconfirmed, recorded as its leg's
+/// transaction since it may still land.
+fn unconfirmed_swap(err: &anyhow::Error) -> Option<String> {
+    err.downcast_ref::<Unconfirmed>()
+        .filter(|u| u.what == "swap")
+        .map(|u| format!("{:?}", u.tx))
+}
+
+/// Stores a trade in `executions`, amounts as decimal strings, and folds the
+/// gas of a filled round trip into the pair's average.
+pub fn record(conn: &Connection, now: DateTime<Utc>, trade: &ExecutedTrade) -> anyhow::Result<()> {
+    let ExecutedTrade { plan, execution } = trade;
+    let amount = |value: U256, decimals: u32| units::format_units(value, decimals);
+    conn.execute(
+        "INSERT INTO executions (timestamp, instance, pair, cycle_id, dex_sell, dex_buy, amount_in, expected_in, sell_tx, buy_tx, received_out, realized_in, status, error)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+        params![
+            now.to_rfc3339(),
+            plan.instance,
+            plan.pair,
+            plan.cycle_id,
+            plan.dex_sell,
+            plan.dex_buy,
+            amount(plan.amount_in, plan.decimals_in),
+            amount(plan.back_quote, plan.decimals_in),
+            execution.sell_tx,
+            execution.buy_tx,
+            execution.received_out.map(|v| amount(v, plan.decimals_out)),
+            execution.realized_in.map(|v| amount(v, plan.decimals_in)),
+            execution.status(),
+            execution.error
+        ],
+    )?;
+    if let (None, Some(gas_used)) = (&execution.error, execution.gas_used) {
+        gas::record(conn, now, plan.token_in, plan.token_out, gas_used)?;
+    }
+    Ok(())
+}
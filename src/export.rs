@@ -0,0 +1,103 @@
+//! Scheduled export of opportunities to an analytics endpoint
+//!
+//! Every `EXPORT_INTERVAL_SECS` the opportunities stored since the last
+//! successful push are POSTed to `EXPORT_URL`: as `{"opportunities": [...]}`,
+//! or with `EXPORT_GRAPHQL_MUTATION` set as a GraphQL request passing them in
+//! the `$opportunities` variable. The last exported id is kept in
+//! `export_cursor`, so a restart resumes where the previous push stopped.
+
+use crate::api::ApiOpportunity;
+use crate::db::{opportunity_from_row, Opportunity, OPPORTUNITY_COLUMNS};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+
+/// Opportunities sent per request.
+const BATCH_SIZE: usize = 500;
+
+pub struct Exporter {
+    client: reqwest::Client,
+    url: String,
+    token: Option<String>,
+    mutation: Option<String>,
+}
+
+impl Exporter {
+    /// `token` is sent as a bearer token when set.
+    pub fn new(url: &str, token: Option<String>, mutation: Option<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+            token,
+            mutation,
+        }
+    }
+
+    /// Pushes everything stored since the last export and returns how many
+    /// opportunities were sent.
+    pub async fn export(&self, conn: &Arc<Mutex<Connection>>) -> anyhow::Result<usize> {
+        let mut exported = 0;
+        loop {
+            let batch = pending(&conn.lock().unwrap(), BATCH_SIZE)?;
+            let Some(last_id) = batch.last().map(|opp| opp.id) else {
+                return Ok(exported);
+            };
+            self.push(&batch).await?;
+            set_cursor(&conn.lock().unwrap(), last_id)?;
+            exported += batch.len();
+            if batch.len() < BATCH_SIZE {
+                return Ok(exported);
+            }
+        }
+    }
+
+    async fn push(&self, batch: &[Opportunity]) -> anyhow::Result<()> {
+        let batch: Vec<ApiOpportunity> = batch.iter().map(ApiOpportunity::from).collect();
+        let body = match &self.mutation {
+            Some(query) => json!({ "query": query, "variables": { "opportunities": batch } }),
+            None => json!({ "opportunities": batch }),
+        };
+        let mut request = self.client.post(&self.url).json(&body);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await?.error_for_status()?;
+        if self.mutation.is_some() {
+            // GraphQL servers report failed mutations in a 200 response.
+            let reply: serde_json::Value = response.json().await?;
+            if let Some(errors) = reply.get("errors") {
+                anyhow::bail!("GraphQL export rejected: {}", errors);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Oldest opportunities not exported yet.
+fn pending(conn: &Connection, limit: usize) -> anyhow::Result<Vec<Opportunity>> {
+    let cursor: i64 = conn
+        .query_row(
+            "SELECT last_id FROM export_cursor WHERE id = 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?
+        .unwrap_or(0);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM opportunities WHERE id > ?1 ORDER BY id LIMIT ?2",
+        OPPORTUNITY_COLUMNS
+    ))?;
+    let batch = stmt
+        .query_map(params![cursor, limit as i64], opportunity_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(batch)
+}
+
+fn set_cursor(conn: &Connection, last_id: i64) -> anyhow::Result<()> {
+    conn.execute(
+        "INSERT INTO export_cursor (id, last_id) VALUES (1, ?1)
+         ON CONFLICT(id) DO UPDATE SET last_id = excluded.last_id",
+        params![last_id],
+    )?;
+    Ok(())
+}
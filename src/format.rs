@@ -0,0 +1,54 @@
+//! Display precision for API responses
+//!
+//! Stored values are returned untouched; alongside them every opportunity
+//! carries a `display` block rounded server-side (`AMOUNT_PRECISION` for
+//! amounts and prices, `PROFIT_PRECISION` for profits) so clients don't each
+//! truncate floats their own way.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct Precision {
+    pub amount: usize,
+    pub profit: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct OpportunityDisplay {
+    pub amount_in: String,
+    pub amount_out_buy: String,
+    pub amount_out_sell: String,
+    pub profit: String,
+    /// Exact stored profit; the shortest `f64` representation on older rows.
+    pub profit_raw: String,
+}
+
+impl Precision {
+    pub fn display(
+        &self,
+        amount_in: &str,
+        amount_out_buy: &str,
+        amount_out_sell: &str,
+        profit: f64,
+        profit_exact: Option<&str>,
+    ) -> OpportunityDisplay {
+        OpportunityDisplay {
+            amount_in: round_str(amount_in, self.amount),
+            amount_out_buy: round_str(amount_out_buy, self.amount),
+            amount_out_sell: round_str(amount_out_sell, self.amount),
+            profit: round(profit, self.profit),
+            profit_raw: profit_exact.map_or_else(|| profit.to_string(), str::to_string),
+        }
+    }
+}
+
+pub fn round(value: f64, places: usize) -> String {
+    format!("{:.*}", places, value)
+}
+
+/// Rounds a stored numeric string; anything that doesn't parse is passed through.
+pub fn round_str(raw: &str, places: usize) -> String {
+    raw.parse::<f64>()
+        .map(|v| round(v, places))
+        .unwrap_or_else(|_| raw.to_string())
+}
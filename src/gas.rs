@@ -0,0 +1,88 @@
+//! Empirical gas usage per token pair
+//!
+//! Tokens with transfer hooks or fee-on-transfer logic make round trips on
+//! some pairs much more expensive than others. Gas used by round trips seen
+//! on-chain (the watched wallet's, and the bot's own filled executions) is
+//! averaged per pair in `pair_gas`, and once a pair has samples the detector prices gas from it at the current
+//! gas price instead of the flat `SIMULATED_GAS_USDC`.
+//!
+//! With `SWAP_GAS_LIMIT` set, pairs without samples are priced from that limit
+//! at the live `eth_gasPrice` too, and the native token is priced by quoting
+//! one `NATIVE_TOKEN` in the pair's output token on the first DEX each cycle
+//! (unless `NATIVE_PRICE_USDC` fixes it).
+
+use crate::amounts::TokenInfo;
+use crate::dex::{self, BlockRef, DexQuoter};
+use chrono::{DateTime, Utc};
+use ethers::providers::Middleware;
+use ethers::types::{Address, U256};
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// Wrapped POL (formerly WMATIC) on Polygon PoS.
+pub const WRAPPED_NATIVE: &str = "0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270";
+
+/// Weight of a new sample in the moving average.
+const ALPHA: f64 = 0.2;
+
+/// Folds one observed round trip's gas into the pair's average.
+pub fn record(
+    conn: &Connection,
+    now: DateTime<Utc>,
+    token_in: Address,
+    token_out: Address,
+    gas_used: u64,
+) -> anyhow::Result<()> {
+    conn.execute(
+        "INSERT INTO pair_gas (token_in, token_out, gas_units, samples, updated_at)
+         VALUES (?1, ?2, ?3, 1, ?4)
+         ON CONFLICT(token_in, token_out) DO UPDATE SET
+            gas_units = gas_units * (1.0 - ?5) + excluded.gas_units * ?5,
+            samples = samples + 1,
+            updated_at = excluded.updated_at",
+        params![
+            format!("{:?}", token_in),
+            format!("{:?}", token_out),
+            gas_used as f64,
+            now.to_rfc3339(),
+            ALPHA
+        ],
+    )?;
+    Ok(())
+}
+
+/// Average gas units of a round trip on the pair, if any were observed.
+pub fn lookup(
+    conn: &Connection,
+    token_in: Address,
+    token_out: Address,
+) -> anyhow::Result<Option<f64>> {
+    Ok(conn
+        .query_row(
+            "SELECT gas_units FROM pair_gas WHERE token_in = ?1 AND token_out = ?2",
+            params![format!("{:?}", token_in), format!("{:?}", token_out)],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+/// Price of one native token in `token_out`, quoted on `dex` at `block`.
+pub async fn native_price<M: Middleware + 'static>(
+    client: &M,
+    dex: &dyn DexQuoter,
+    wrapped_native: Address,
+    token_out: &TokenInfo,
+    block: BlockRef,
+) -> anyhow::Result<f64> {
+    if token_out.address == wrapped_native {
+        return Ok(1.0);
+    }
+    let one = U256::exp10(18);
+    let quote = dex::quote(client, dex, wrapped_native, token_out.address, one, block).await?;
+    Ok(token_out.to_f64(quote.amount_out))
+}
+
+/// Cost of `gas_units` at `gas_price` wei per gas, in the unit
+/// `native_price` is quoted in.
+pub fn cost(gas_units: f64, gas_price: U256, native_price: f64) -> f64 {
+    gas_units * crate::units::to_f64(gas_price, 18) * native_price
+}
@@ -0,0 +1,77 @@
+//! New-block notifications over a WebSocket RPC (`RPC_WS_URL`)
+//!
+//! Prices only move when a block lands, so with a WebSocket endpoint the
+//! detectors run one cycle per new head instead of sleeping
+//! `POLL_INTERVAL_SECS`. One `eth_subscribe("newHeads")` subscription is
+//! shared by every detector; a dropped connection is re-established with
+//! backoff, and detectors fall back to the poll interval while no heads
+//! arrive.
+
+use crate::rpc::url_host;
+use ethers::providers::{Middleware, Provider, Ws};
+use futures::StreamExt;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Heads buffered per detector; a detector that falls behind skips to the latest.
+const BUFFER: usize = 16;
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Clone)]
+pub struct NewHeads {
+    tx: broadcast::Sender<u64>,
+}
+
+impl NewHeads {
+    /// Subscribes in the background; must be called inside the Tokio runtime.
+    pub fn spawn(url: &str) -> Self {
+        let (tx, _) = broadcast::channel(BUFFER);
+        let heads = Self { tx: tx.clone() };
+        let url = url.to_string();
+        tokio::spawn(async move {
+            let mut backoff = Duration::from_secs(1);
+            loop {
+                match forward(&url, &tx).await {
+                    Ok(()) => {
+                        log::warn!("New heads subscription on {} ended", url_host(&url));
+                        backoff = Duration::from_secs(1);
+                    }
+                    Err(e) => log::warn!("New heads subscription failed: {:?}", e),
+                }
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        });
+        heads
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<u64> {
+        self.tx.subscribe()
+    }
+}
+
+/// Forwards block numbers until the subscription ends.
+async fn forward(url: &str, tx: &broadcast::Sender<u64>) -> anyhow::Result<()> {
+    let provider = Provider::<Ws>::connect(url).await?;
+    let mut blocks = provider.subscribe_blocks().await?;
+    log::info!("Subscribed to new heads on {}", url_host(url));
+    while let Some(block) = blocks.next().await {
+        if let Some(number) = block.number {
+            // Only fails while no detector is listening.
+            let _ = tx.send(number.as_u64());
+        }
+    }
+    Ok(())
+}
+
+/// Returns at once when heads arrived during the previous cycle (one cycle
+/// covers them all), otherwise waits for the next head or `fallback`.
+pub async fn next(rx: &mut broadcast::Receiver<u64>, fallback: Duration) {
+    let mut queued = false;
+    while let Ok(_) | Err(broadcast::error::TryRecvError::Lagged(_)) = rx.try_recv() {
+        queued = true;
+    }
+    if !queued {
+        let _ = tokio::time::timeout(fallback, rx.recv()).await;
+    }
+}
@@ -0,0 +1,105 @@
+//! Cycle latency and opportunity frequency by hour of day / day of week
+//!
+//! Every detection cycle is timed into `cycle_timings`; `load` buckets those
+//! and the stored opportunities into a 7×24 UTC grid for `/api/stats/heatmap`.
+
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize)]
+pub struct HeatmapParams {
+    /// Look-back window in days (default 28).
+    pub days: Option<u32>,
+    /// Restrict to one bot instance; all instances when unset.
+    pub instance: Option<String>,
+}
+
+#[derive(Serialize, Default, Clone)]
+pub struct HeatmapCell {
+    /// 0 = Sunday … 6 = Saturday (UTC).
+    pub day_of_week: u8,
+    pub hour: u8,
+    pub cycles: i64,
+    pub failed_cycles: i64,
+    pub avg_latency_ms: Option<f64>,
+    pub max_latency_ms: Option<f64>,
+    pub opportunities: i64,
+}
+
+pub fn record_cycle(
+    conn: &Connection,
+    instance: &str,
+    started: DateTime<Utc>,
+    latency_ms: f64,
+    ok: bool,
+    cycle_id: &str,
+) -> anyhow::Result<()> {
+    conn.execute(
+        "INSERT INTO cycle_timings (timestamp, latency_ms, ok, instance, cycle_id)
+         VALUES (?1,?2,?3,?4,?5)",
+        params![started.to_rfc3339(), latency_ms, ok, instance, cycle_id],
+    )?;
+    Ok(())
+}
+
+/// All 168 cells, ordered by day of week then hour; empty buckets are zeroed.
+pub fn load(
+    conn: &Connection,
+    now: DateTime<Utc>,
+    days: u32,
+    instance: Option<&str>,
+) -> anyhow::Result<Vec<HeatmapCell>> {
+    let since = (now - Duration::days(days as i64)).to_rfc3339();
+    let mut cells: Vec<HeatmapCell> = (0..7 * 24)
+        .map(|i| HeatmapCell {
+            day_of_week: (i / 24) as u8,
+            hour: (i % 24) as u8,
+            ..Default::default()
+        })
+        .collect();
+
+    let mut stmt = conn.prepare(
+        "SELECT CAST(strftime('%w', substr(timestamp, 1, 19)) AS INTEGER) AS dow,
+                CAST(substr(timestamp, 12, 2) AS INTEGER) AS hour,
+                COUNT(*), SUM(ok = 0), AVG(latency_ms), MAX(latency_ms)
+         FROM cycle_timings WHERE timestamp >= ?1 AND (?2 IS NULL OR instance = ?2) GROUP BY dow, hour",
+    )?;
+    let rows = stmt.query_map(params![since, instance], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, i64>(2)?,
+            row.get::<_, i64>(3)?,
+            row.get::<_, f64>(4)?,
+            row.get::<_, f64>(5)?,
+        ))
+    })?;
+    for row in rows {
+        let (dow, hour, cycles, failed, avg, max) = row?;
+        let cell = &mut cells[(dow * 24 + hour) as usize];
+        cell.cycles = cycles;
+        cell.failed_cycles = failed;
+        cell.avg_latency_ms = Some(avg);
+        cell.max_latency_ms = Some(max);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT CAST(strftime('%w', substr(timestamp, 1, 19)) AS INTEGER) AS dow,
+                CAST(substr(timestamp, 12, 2) AS INTEGER) AS hour,
+                COUNT(*)
+         FROM opportunities WHERE timestamp >= ?1 AND (?2 IS NULL OR instance = ?2) GROUP BY dow, hour",
+    )?;
+    let rows = stmt.query_map(params![since, instance], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, i64>(2)?,
+        ))
+    })?;
+    for row in rows {
+        let (dow, hour, count) = row?;
+        cells[(dow * 24 + hour) as usize].opportunities = count;
+    }
+    Ok(cells)
+}
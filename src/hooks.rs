@@ -0,0 +1,61 @@
+//! Callback hooks for embedders
+//!
+//! Registered on an [`ArbDetector`](crate::ArbDetector) before it is run and
+//! called synchronously from the detection loop, so they should hand slow work
+//! off (e.g. to a channel or `tokio::spawn`) instead of blocking the cycle.
+
+use crate::buffer::StorageEvent;
+use crate::db::Opportunity;
+use crate::execution::ExecutedTrade;
+
+type Hook<T> = Box<dyn Fn(&T) + Send + Sync>;
+
+#[derive(Default)]
+pub struct Hooks {
+    opportunity: Vec<Hook<Opportunity>>,
+    execution: Vec<Hook<ExecutedTrade>>,
+    error: Vec<Hook<anyhow::Error>>,
+    storage: Vec<Hook<StorageEvent>>,
+}
+
+impl Hooks {
+    pub fn on_opportunity(&mut self, hook: impl Fn(&Opportunity) + Send + Sync + 'static) {
+        self.opportunity.push(Box::new(hook));
+    }
+
+    pub fn on_execution(&mut self, hook: impl Fn(&ExecutedTrade) + Send + Sync + 'static) {
+        self.execution.push(Box::new(hook));
+    }
+
+    pub fn on_error(&mut self, hook: impl Fn(&anyhow::Error) + Send + Sync + 'static) {
+        self.error.push(Box::new(hook));
+    }
+
+    pub fn on_storage(&mut self, hook: impl Fn(&StorageEvent) + Send + Sync + 'static) {
+        self.storage.push(Box::new(hook));
+    }
+
+    pub(crate) fn opportunity(&self, opp: &Opportunity) {
+        for hook in &self.opportunity {
+            hook(opp);
+        }
+    }
+
+    pub(crate) fn execution(&self, trade: &ExecutedTrade) {
+        for hook in &self.execution {
+            hook(trade);
+        }
+    }
+
+    pub(crate) fn error(&self, err: &anyhow::Error) {
+        for hook in &self.error {
+            hook(err);
+        }
+    }
+
+    pub(crate) fn storage(&self, event: &StorageEvent) {
+        for hook in &self.storage {
+            hook(event);
+        }
+    }
+}
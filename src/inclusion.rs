@@ -0,0 +1,93 @@
+//! Inclusion probability of a priority-fee bid
+//!
+//! Over the last `HISTORY_BLOCKS` blocks (`eth_feeHistory`), a block that was
+//! not full would have taken any tip. In a full block the chance is the share
+//! of its gas that tipped less than the bid, interpolated between sampled
+//! reward percentiles. The mean over the history is the per-block
+//! probability `p`; being included within `INCLUSION_BLOCKS` blocks then has
+//! probability `1 - (1 - p)^K` and the expected wait is `1 / p` blocks.
+//! The wait is added to quote age in the staleness check, and profits are
+//! weighted by the inclusion probability.
+
+use ethers::providers::Middleware;
+use ethers::types::{BlockNumber, U256};
+
+const HISTORY_BLOCKS: u64 = 20;
+const PERCENTILES: [f64; 5] = [10.0, 25.0, 50.0, 75.0, 90.0];
+/// Gas used ratio above which a block is treated as contested.
+const FULL_BLOCK: f64 = 0.95;
+/// Per-block probability of a bid above every sampled percentile.
+const MAX_BLOCK_PROBABILITY: f64 = 0.99;
+const BLOCK_TIME_SECS: f64 = 2.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Inclusion {
+    /// Probability of being included within the configured number of blocks.
+    pub probability: f64,
+    pub expected_delay_secs: f64,
+}
+
+pub struct InclusionModel {
+    /// Blocks (`K`) within which the opportunity must be included.
+    pub blocks: u64,
+    /// Bid used when the contest model does not supply one.
+    pub priority_fee: U256,
+}
+
+impl InclusionModel {
+    pub async fn estimate<M: Middleware + 'static>(
+        &self,
+        client: &M,
+        bid: Option<U256>,
+    ) -> anyhow::Result<Inclusion> {
+        let bid = bid.unwrap_or(self.priority_fee);
+        let history = client
+            .fee_history(HISTORY_BLOCKS, BlockNumber::Latest, &PERCENTILES)
+            .await
+            .map_err(|e| anyhow::anyhow!("eth_feeHistory failed: {}", e))?;
+        let per_block: Vec<f64> = history
+            .reward
+            .iter()
+            .zip(&history.gas_used_ratio)
+            .map(|(rewards, &used)| {
+                if used < FULL_BLOCK {
+                    1.0
+                } else {
+                    block_probability(bid, rewards)
+                }
+            })
+            .collect();
+        if per_block.is_empty() {
+            anyhow::bail!("eth_feeHistory returned no blocks");
+        }
+        let p = per_block.iter().sum::<f64>() / per_block.len() as f64;
+        let delay_blocks = if p > 0.0 {
+            (1.0 / p).min(HISTORY_BLOCKS as f64)
+        } else {
+            HISTORY_BLOCKS as f64
+        };
+        Ok(Inclusion {
+            probability: 1.0 - (1.0 - p).powi(self.blocks as i32),
+            expected_delay_secs: delay_blocks * BLOCK_TIME_SECS,
+        })
+    }
+}
+
+/// Share of a full block's gas that tipped less than `bid`.
+fn block_probability(bid: U256, rewards: &[U256]) -> f64 {
+    let bid = crate::units::to_f64(bid, 9);
+    let mut prev = (0.0, 0.0);
+    for (percentile, reward) in PERCENTILES.iter().zip(rewards) {
+        let point = (percentile / 100.0, crate::units::to_f64(*reward, 9));
+        if bid < point.1 {
+            let span = point.1 - prev.1;
+            return if span > 0.0 {
+                prev.0 + (point.0 - prev.0) * (bid - prev.1) / span
+            } else {
+                prev.0
+            };
+        }
+        prev = point;
+    }
+    MAX_BLOCK_PROBABILITY
+}
@@ -0,0 +1,125 @@
+//! InfluxDB sink for price samples and spreads
+//!
+//! With `INFLUX_URL` set, every price sample and cycle spread is also written
+//! to InfluxDB (v2 `/api/v2/write`, line protocol) so long histories live in a
+//! time-series store. Lines are queued and posted in batches once a second; a
+//! failed post is logged and its batch dropped. With `INFLUX_ONLY` the raw
+//! samples are no longer written to SQLite, which then keeps opportunities
+//! (spread seeding, lead-lag and deviation stats read SQLite samples and go
+//! quiet in that mode).
+
+use crate::db::PriceSample;
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Lines queued before new ones are dropped.
+const QUEUE: usize = 10_000;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct InfluxSink {
+    lines: mpsc::Sender<String>,
+}
+
+impl InfluxSink {
+    /// Starts the background writer; must be called inside the Tokio runtime.
+    pub fn spawn(url: &str, org: &str, bucket: &str, token: Option<String>) -> Self {
+        let (tx, mut rx) = mpsc::channel::<String>(QUEUE);
+        let endpoint = format!(
+            "{}/api/v2/write?org={}&bucket={}&precision=ms",
+            url.trim_end_matches('/'),
+            org,
+            bucket
+        );
+        tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            let mut batch = Vec::new();
+            loop {
+                let closed = tokio::time::timeout(FLUSH_INTERVAL, async {
+                    while let Some(line) = rx.recv().await {
+                        batch.push(line);
+                    }
+                })
+                .await
+                .is_ok();
+                if !batch.is_empty() {
+                    let mut request = client.post(&endpoint).body(batch.join("\n"));
+                    if let Some(token) = &token {
+                        request = request.header("Authorization", format!("Token {}", token));
+                    }
+                    let sent = request.send().await.and_then(|r| r.error_for_status());
+                    if let Err(e) = sent {
+                        log::warn!("Failed to write {} lines to InfluxDB: {}", batch.len(), e);
+                    }
+                    batch.clear();
+                }
+                if closed {
+                    return;
+                }
+            }
+        });
+        Self { lines: tx }
+    }
+
+    pub fn price_samples(
+        &self,
+        now: DateTime<Utc>,
+        instance: &str,
+        pair: Option<&str>,
+        block_number: u64,
+        samples: &[PriceSample],
+    ) {
+        let tags = tags(instance, pair);
+        for sample in samples {
+            let mut fields = format!("price={},block={}i", sample.price, block_number);
+            if let Some(deviation) = sample.deviation_bps {
+                fields.push_str(&format!(",deviation_bps={}", deviation));
+            }
+            self.send(format!(
+                "price_sample,{},dex={} {} {}",
+                tags,
+                escape(&sample.dex),
+                fields,
+                now.timestamp_millis()
+            ));
+        }
+    }
+
+    pub fn spread(
+        &self,
+        now: DateTime<Utc>,
+        instance: &str,
+        pair: Option<&str>,
+        block_number: u64,
+        spread_bps: f64,
+    ) {
+        self.send(format!(
+            "spread,{} spread_bps={},block={}i {}",
+            tags(instance, pair),
+            spread_bps,
+            block_number,
+            now.timestamp_millis()
+        ));
+    }
+
+    fn send(&self, line: String) {
+        if self.lines.try_send(line).is_err() {
+            log::warn!("InfluxDB queue full, dropping a line");
+        }
+    }
+}
+
+fn tags(instance: &str, pair: Option<&str>) -> String {
+    match pair {
+        Some(pair) => format!("instance={},pair={}", escape(instance), escape(pair)),
+        None => format!("instance={}", escape(instance)),
+    }
+}
+
+/// Escapes a tag value for line protocol.
+fn escape(value: &str) -> String {
+    value
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
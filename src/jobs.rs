@@ -0,0 +1,70 @@
+//! Status of background tasks, served at `/api/jobs`
+//!
+//! Every long-running task (a detector per pair, the scheduled jobs, the
+//! exporter, the wallet watcher) registers under a name and reports each run,
+//! so the last run, its outcome and the next planned run are visible without
+//! reading logs.
+
+use crate::rpc;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct JobStatus {
+    pub name: String,
+    /// Cron expression or `every Ns`.
+    pub schedule: String,
+    pub runs: u64,
+    pub failures: u64,
+    pub last_run: Option<String>,
+    /// `ok`, or the error of the last run with URLs cut down to the host.
+    pub last_result: Option<String>,
+    pub next_run: Option<String>,
+}
+
+pub struct Jobs {
+    jobs: Mutex<BTreeMap<String, JobStatus>>,
+}
+
+pub static JOBS: Jobs = Jobs {
+    jobs: Mutex::new(BTreeMap::new()),
+};
+
+impl Jobs {
+    pub fn register(&self, name: &str, schedule: impl Into<String>) {
+        self.jobs.lock().unwrap().insert(
+            name.to_string(),
+            JobStatus {
+                name: name.to_string(),
+                schedule: schedule.into(),
+                ..Default::default()
+            },
+        );
+    }
+
+    pub fn scheduled(&self, name: &str, next: DateTime<Utc>) {
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(name) {
+            job.next_run = Some(next.to_rfc3339());
+        }
+    }
+
+    pub fn finished<T>(&self, name: &str, at: DateTime<Utc>, result: &anyhow::Result<T>) {
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(name) {
+            job.runs += 1;
+            job.last_run = Some(at.to_rfc3339());
+            job.last_result = Some(match result {
+                Ok(_) => "ok".to_string(),
+                Err(e) => {
+                    job.failures += 1;
+                    rpc::redact_urls(&format!("{:#}", e))
+                }
+            });
+        }
+    }
+
+    pub fn list(&self) -> Vec<JobStatus> {
+        self.jobs.lock().unwrap().values().cloned().collect()
+    }
+}
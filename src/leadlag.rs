@@ -0,0 +1,155 @@
+//! Which DEX moves first: lead-lag between per-DEX price samples
+//!
+//! For every pair of DEXes sampled by an instance, the price returns of one
+//! are correlated with the other's returns up to `max_lag` sampled cycles
+//! later, in both directions. The direction with the stronger lagged
+//! correlation names the leader, i.e. the venue whose price the other follows
+//! and the one to trade against first.
+
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Weakest lagged correlation still reported as a leader.
+const MIN_CORRELATION: f64 = 0.1;
+
+#[derive(Deserialize)]
+pub struct LeadLagParams {
+    /// Look-back window in hours (default 24).
+    pub hours: Option<u32>,
+    /// Longest lag tried, in sampled cycles (default 5).
+    pub max_lag: Option<usize>,
+    pub instance: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct LeadLag {
+    pub instance: String,
+    /// Token pair the DEXes were sampled on.
+    pub pair: Option<String>,
+    pub dex_a: String,
+    pub dex_b: String,
+    /// DEX whose moves the other follows; none without a clear signal.
+    pub leader: Option<String>,
+    /// Cycles between the leader's move and the follower's.
+    pub lag_cycles: Option<usize>,
+    /// Correlation of the leader's returns with the follower's lagged ones.
+    pub correlation: Option<f64>,
+    /// Correlation of returns in the same cycle.
+    pub same_cycle_correlation: Option<f64>,
+    /// Cycles in which both DEXes were sampled.
+    pub samples: usize,
+}
+
+pub fn load(
+    conn: &Connection,
+    now: DateTime<Utc>,
+    hours: u32,
+    max_lag: usize,
+    instance: Option<&str>,
+) -> anyhow::Result<Vec<LeadLag>> {
+    let since = (now - Duration::hours(hours as i64)).to_rfc3339();
+    let mut stmt = conn.prepare(
+        "SELECT instance, pair, block_number, dex, price FROM price_samples
+         WHERE timestamp >= ?1 AND (?2 IS NULL OR instance = ?2) AND price > 0",
+    )?;
+    // (instance, pair) -> dex -> block -> price
+    type Series = BTreeMap<String, BTreeMap<i64, f64>>;
+    let mut prices: BTreeMap<(String, Option<String>), Series> = BTreeMap::new();
+    let rows = stmt.query_map(params![since, instance], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, i64>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, f64>(4)?,
+        ))
+    })?;
+    for row in rows {
+        let (instance, pair, block, dex, price) = row?;
+        prices
+            .entry((instance, pair))
+            .or_default()
+            .entry(dex)
+            .or_default()
+            .insert(block, price);
+    }
+
+    let mut results = Vec::new();
+    for ((instance, pair), by_dex) in &prices {
+        let dexes: Vec<&String> = by_dex.keys().collect();
+        for (i, a) in dexes.iter().enumerate() {
+            for b in &dexes[i + 1..] {
+                let (ra, rb) = returns(&by_dex[*a], &by_dex[*b]);
+                let mut result = dex_pair(instance, a, b, &ra, &rb, max_lag);
+                result.pair = pair.clone();
+                results.push(result);
+            }
+        }
+    }
+    Ok(results)
+}
+
+/// Log returns of both DEXes between consecutive blocks they were both sampled in.
+fn returns(a: &BTreeMap<i64, f64>, b: &BTreeMap<i64, f64>) -> (Vec<f64>, Vec<f64>) {
+    let blocks: Vec<i64> = a.keys().filter(|k| b.contains_key(k)).copied().collect();
+    blocks
+        .windows(2)
+        .map(|w| ((a[&w[1]] / a[&w[0]]).ln(), (b[&w[1]] / b[&w[0]]).ln()))
+        .unzip()
+}
+
+fn dex_pair(instance: &str, a: &str, b: &str, ra: &[f64], rb: &[f64], max_lag: usize) -> LeadLag {
+    // Best correlation of `lead` now with `follow` `lag` cycles later.
+    let best = |lead: &[f64], follow: &[f64]| {
+        (1..=max_lag)
+            .filter(|&lag| lag < lead.len())
+            .filter_map(|lag| {
+                correlation(&lead[..lead.len() - lag], &follow[lag..]).map(|c| (lag, c))
+            })
+            .max_by(|x, y| x.1.total_cmp(&y.1))
+    };
+    let a_leads = best(ra, rb);
+    let b_leads = best(rb, ra);
+    let leader = match (a_leads, b_leads) {
+        (Some(x), Some(y)) if x.1 >= y.1 => Some((a, x)),
+        (Some(x), None) => Some((a, x)),
+        (_, Some(y)) => Some((b, y)),
+        (None, None) => None,
+    }
+    .filter(|(_, (_, c))| *c >= MIN_CORRELATION);
+
+    LeadLag {
+        instance: instance.to_string(),
+        pair: None,
+        dex_a: a.to_string(),
+        dex_b: b.to_string(),
+        leader: leader.map(|(dex, _)| dex.to_string()),
+        lag_cycles: leader.map(|(_, (lag, _))| lag),
+        correlation: leader.map(|(_, (_, c))| c),
+        same_cycle_correlation: correlation(ra, rb),
+        samples: ra.len(),
+    }
+}
+
+/// Pearson correlation; `None` when either side is flat or too short.
+fn correlation(x: &[f64], y: &[f64]) -> Option<f64> {
+    let n = x.len().min(y.len());
+    if n < 3 {
+        return None;
+    }
+    let (x, y) = (&x[..n], &y[..n]);
+    let mean_x = x.iter().sum::<f64>() / n as f64;
+    let mean_y = y.iter().sum::<f64>() / n as f64;
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (xi, yi) in x.iter().zip(y) {
+        cov += (xi - mean_x) * (yi - mean_y);
+        var_x += (xi - mean_x).powi(2);
+        var_y += (yi - mean_y).powi(2);
+    }
+    if var_x == 0.0 || var_y == 0.0 {
+        return None;
+    }
+    Some(cov / (var_x * var_y).sqrt())
+}
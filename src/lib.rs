@@ -0,0 +1,127 @@
+//! Polygon arbitrage opportunity detection
+//!
+//! The `polygon_arb_bot` binary wires these modules into a bot with a web
+//! dashboard. Embedders can drive an [`ArbDetector`] themselves and consume
+//! its detections with [`ArbDetector::subscribe`], or plug side effects in
+//! with [`ArbDetector::on_opportunity`], [`ArbDetector::on_execution`] and
+//! [`ArbDetector::on_error`]:
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use ethers::providers::{Http, Provider};
+//! use futures::StreamExt;
+//! use polygon_arb_bot::{db, share::ShareLinks, ArbDetector, Config};
+//! use std::sync::{Arc, Mutex};
+//!
+//! let cfg = Config::from_env()?;
+//! let provider = Arc::new(Provider::<Http>::try_from(cfg.rpc_url.as_str())?);
+//! let conn = rusqlite::Connection::open(&cfg.database_path)?;
+//! db::init_db(&conn)?;
+//! let conn = Arc::new(Mutex::new(conn));
+//! let links = Arc::new(ShareLinks::new(&cfg.public_base_url, None, 0));
+//!
+//! let mut detector = ArbDetector::new(cfg, &provider, &conn, &links).await?;
+//! detector.on_error(|e| eprintln!("cycle failed: {e:?}"));
+//! let mut opportunities = Box::pin(detector.subscribe());
+//! tokio::spawn(detector.run());
+//! while let Some(opp) = opportunities.next().await {
+//!     println!("{} {} -> {}: {:.2}", opp.id, opp.dex_buy, opp.dex_sell, opp.profit);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use ethers::providers::Middleware;
+use ethers::types::Address;
+use std::sync::Arc;
+
+pub mod alerts;
+pub mod amounts;
+pub mod api;
+pub mod audit;
+pub mod auth;
+pub mod basis;
+pub mod buffer;
+pub mod candles;
+pub mod clock;
+pub mod config;
+pub mod consensus;
+pub mod contest;
+pub mod contracts;
+pub mod correlation;
+pub mod db;
+pub mod detector;
+pub mod dex;
+pub mod diagnostics;
+pub mod events;
+pub mod execution;
+pub mod export;
+pub mod format;
+pub mod gas;
+pub mod heads;
+pub mod heatmap;
+pub mod hooks;
+pub mod inclusion;
+pub mod influx;
+pub mod jobs;
+pub mod leadlag;
+pub mod liquidity;
+pub mod maintenance;
+pub mod metrics;
+pub mod migrate;
+pub mod money;
+pub mod oracle;
+pub mod paper;
+pub mod planner;
+pub mod portfolio;
+pub mod prices;
+pub mod query;
+pub mod quotecheck;
+pub mod rejections;
+pub mod report;
+pub mod rpc;
+pub mod schedule;
+pub mod share;
+pub mod sink;
+pub mod spreads;
+pub mod stats;
+pub mod strategy;
+pub mod summary;
+pub mod supervisor;
+pub mod suspension;
+pub mod tokens;
+pub mod topup;
+pub mod triangular;
+pub mod uniswap;
+pub mod units;
+pub mod usd;
+pub mod warmup;
+pub mod watch;
+
+pub use config::Config;
+pub use contracts::ERC20;
+pub use db::Opportunity;
+pub use detector::ArbDetector;
+
+// ----- Helpers -----
+pub async fn get_decimals_cached<M: Middleware + 'static>(
+    provider: Arc<M>,
+    token: Address,
+) -> Option<u8> {
+    let info = amounts::load(provider, token).await.ok()?;
+    u8::try_from(info.decimals).ok()
+}
+
+/// `SYMBOL_IN/SYMBOL_OUT` of a pair, if both tokens report a symbol.
+pub async fn pair_symbols<M: Middleware + 'static>(
+    provider: Arc<M>,
+    token_in: Address,
+    token_out: Address,
+) -> Option<String> {
+    let symbol_in = amounts::load(Arc::clone(&provider), token_in)
+        .await
+        .ok()?
+        .symbol?;
+    let symbol_out = amounts::load(provider, token_out).await.ok()?.symbol?;
+    Some(format!("{}/{}", symbol_in, symbol_out))
+}
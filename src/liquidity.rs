@@ -0,0 +1,199 @@
+//! Liquidity watch over every pair's V2 pools
+//!
+//! On `LIQUIDITY_CHECK_CRON` the reserves of each pair's pool behind every
+//! configured V2 router are read and stored in `liquidity_samples`. A pool's
+//! liquidity is its depth in token_out, twice its token_out reserve. A pool
+//! below `LIQUIDITY_FLOOR`, or more than `LIQUIDITY_DROP_PCT` below its
+//! highest sample of the past hour, stops being worth arbitraging: each such
+//! dip alerts once, and the pool re-arms once it is back above both. A pair
+//! with fewer than two pools above the floor for `PAIR_DEAD_AFTER_SECS` is
+//! suspended (see [`crate::suspension`]).
+
+use crate::amounts::{self, TokenInfo};
+use crate::config::Config;
+use crate::dex::reserves::ReserveReader;
+use crate::dex::BlockRef;
+use crate::suspension::SUSPENSIONS;
+use chrono::{DateTime, Duration, Utc};
+use ethers::providers::Middleware;
+use ethers::types::Address;
+use rusqlite::{params, Connection};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+pub struct LiquidityConfig {
+    /// Depth in token_out below which a pool alerts.
+    pub floor: Option<f64>,
+    /// Drop from the past hour's peak, in percent, that alerts.
+    pub drop_pct: f64,
+    /// How long a pair may lack two pools above the floor before it is
+    /// suspended; zero never suspends.
+    pub dead_after: Duration,
+}
+
+/// One pair's pool behind one router.
+struct Pool {
+    instance: String,
+    pair: Option<String>,
+    dex: String,
+    router: Address,
+    token_in: Address,
+    token_out: TokenInfo,
+}
+
+impl Pool {
+    fn label(&self) -> String {
+        let pair = match &self.pair {
+            Some(pair) => format!("{} ", pair),
+            None => String::new(),
+        };
+        format!("[{}] {}pool on {}", self.instance, pair, self.dex)
+    }
+}
+
+pub struct LiquidityWatch<M> {
+    client: Arc<M>,
+    reserves: ReserveReader<M>,
+    cfg: LiquidityConfig,
+    pools: Vec<Pool>,
+    /// Indexes of pools whose current dip was alerted.
+    alerted: Mutex<HashSet<usize>>,
+}
+
+impl<M: Middleware + 'static> LiquidityWatch<M> {
+    /// Watches the pool of every pair in `pairs` on each of its V2 DEXes.
+    pub async fn new(
+        client: Arc<M>,
+        pairs: &[Config],
+        cfg: LiquidityConfig,
+    ) -> anyhow::Result<Self> {
+        let mut pools = Vec::new();
+        for pair in pairs {
+            let token_out = amounts::load(Arc::clone(&client), pair.token_out).await?;
+            for (dex, router) in &pair.dexes {
+                pools.push(Pool {
+                    instance: pair.instance.clone(),
+                    pair: pair.pair.clone(),
+                    dex: dex.clone(),
+                    router: *router,
+                    token_in: pair.token_in,
+                    token_out: token_out.clone(),
+                });
+            }
+        }
+        Ok(Self {
+            reserves: ReserveReader::new(Arc::clone(&client)),
+            client,
+            cfg,
+            pools,
+            alerted: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Samples every pool and returns the alerts of pools that just dipped.
+    /// Pools that can't be read are skipped until the next check.
+    pub async fn check(
+        &self,
+        conn: &Mutex<Connection>,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<String>> {
+        let block = BlockRef {
+            number: self.client.get_block_number().await?.as_u64(),
+            timestamp: now.timestamp() as u64,
+        };
+        let mut samples = Vec::with_capacity(self.pools.len());
+        for (i, pool) in self.pools.iter().enumerate() {
+            match self
+                .reserves
+                .load(pool.router, pool.token_in, pool.token_out.address, block)
+                .await
+            {
+                Ok(Some(reserves)) => samples.push((i, reserves)),
+                // The DEX lists no pool for the pair.
+                Ok(None) => {}
+                Err(e) => log::warn!("Could not read reserves of the {}: {:#}", pool.label(), e),
+            }
+        }
+
+        let conn = conn.lock().unwrap();
+        let since = (now - Duration::hours(1)).to_rfc3339();
+        let mut alerts = Vec::new();
+        let mut depths = Vec::with_capacity(samples.len());
+        for (i, reserves) in samples {
+            let pool = &self.pools[i];
+            let liquidity = 2.0 * pool.token_out.to_f64(reserves.reserve_out);
+            depths.push((i, liquidity));
+            let peak: Option<f64> = conn.query_row(
+                "SELECT MAX(liquidity) FROM liquidity_samples
+                 WHERE instance = ?1 AND pair IS ?2 AND dex = ?3 AND timestamp >= ?4",
+                params![pool.instance, pool.pair, pool.dex, since],
+                |row| row.get(0),
+            )?;
+            conn.execute(
+                "INSERT INTO liquidity_samples (timestamp, instance, pair, dex, block_number, reserve_in, reserve_out, liquidity)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    now.to_rfc3339(),
+                    pool.instance,
+                    pool.pair,
+                    pool.dex,
+                    block.number,
+                    reserves.reserve_in.to_string(),
+                    reserves.reserve_out.to_string(),
+                    liquidity
+                ],
+            )?;
+
+            let floor = self.cfg.floor.filter(|floor| liquidity < *floor);
+            let drop_pct = peak
+                .filter(|peak| *peak > 0.0)
+                .map(|peak| (peak - liquidity) / peak * 100.0)
+                .filter(|pct| *pct > self.cfg.drop_pct);
+            let reason = match (floor, drop_pct) {
+                (Some(floor), _) => format!("below the {} floor", pool.token_out.label(floor)),
+                (None, Some(pct)) => format!("down {:.0}% within an hour", pct),
+                (None, None) => {
+                    self.alerted.lock().unwrap().remove(&i);
+                    continue;
+                }
+            };
+            if self.alerted.lock().unwrap().insert(i) {
+                alerts.push(format!(
+                    "{} holds {} of liquidity, {}; arbitrage on it is unlikely",
+                    pool.label(),
+                    pool.token_out.label(format!("{:.2}", liquidity)),
+                    reason
+                ));
+            }
+        }
+
+        if let Some(floor) = self.cfg.floor {
+            let mut liquid_pools: BTreeMap<(&str, Option<&str>), usize> = self
+                .pools
+                .iter()
+                .map(|pool| ((pool.instance.as_str(), pool.pair.as_deref()), 0))
+                .collect();
+            for (i, liquidity) in depths {
+                let pool = &self.pools[i];
+                if liquidity >= floor {
+                    *liquid_pools
+                        .entry((pool.instance.as_str(), pool.pair.as_deref()))
+                        .or_default() += 1;
+                }
+            }
+            for ((instance, pair), liquid) in liquid_pools {
+                if let Some(reason) =
+                    SUSPENSIONS.liquid(instance, pair, liquid >= 2, now, self.cfg.dead_after)
+                {
+                    alerts.push(format!(
+                        "[{}] Suspending {}: {}; reactivate it with POST /api/control/pairs/reactivate",
+                        instance,
+                        pair.unwrap_or("the pair"),
+                        reason
+                    ));
+                }
+            }
+        }
+        Ok(alerts)
+    }
+}
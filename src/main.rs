@@ -1,282 +1,919 @@
 //! Polygon arbitrage bot with web dashboard
 
+use actix_files::{Files, NamedFile};
+use actix_web::dev::{fn_service, Service, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::DefaultHeaders;
+use actix_web::{get, post, put, web, App, HttpRequest, HttpResponse, HttpServer, Responder};
 use anyhow::Context;
 use chrono::Utc;
-use dotenv::dotenv;
-use ethers::prelude::*;
-use ethers::providers::{Http, Middleware, Provider};
-use once_cell::sync::Lazy;
+use clap::Parser;
+use ethers::providers::Provider;
+use ethers::signers::Signer;
+use futures::future::{ready, Either, Ready};
+use futures::stream;
+use polygon_arb_bot::alerts::{AlertConfig, AlertRouter, Severity, Telegram};
+use polygon_arb_bot::amounts;
+use polygon_arb_bot::api::ApiOpportunity;
+use polygon_arb_bot::audit::{self, AuditFilter};
+use polygon_arb_bot::auth::{self, AuthConfig, Caller, Scope};
+use polygon_arb_bot::buffer::StorageEvent;
+use polygon_arb_bot::candles::{self, CandleParams};
+use polygon_arb_bot::clock::{Clock, SystemClock};
+use polygon_arb_bot::consensus::{self, DeviationParams};
+use polygon_arb_bot::correlation;
+use polygon_arb_bot::db::{self, opportunity_from_row, Opportunity, TopBy, OPPORTUNITY_COLUMNS};
+use polygon_arb_bot::diagnostics;
+use polygon_arb_bot::events::{self, EventParams};
+use polygon_arb_bot::execution::Executor;
+use polygon_arb_bot::export::Exporter;
+use polygon_arb_bot::format::Precision;
+use polygon_arb_bot::gas;
+use polygon_arb_bot::heads::NewHeads;
+use polygon_arb_bot::heatmap::{self, HeatmapParams};
+use polygon_arb_bot::influx::InfluxSink;
+use polygon_arb_bot::jobs::JOBS;
+use polygon_arb_bot::leadlag::{self, LeadLagParams};
+use polygon_arb_bot::liquidity::{LiquidityConfig, LiquidityWatch};
+use polygon_arb_bot::maintenance::{self, RollupParams};
+use polygon_arb_bot::metrics::METRICS;
+use polygon_arb_bot::migrate;
+use polygon_arb_bot::paper;
+use polygon_arb_bot::planner::Coalescer;
+use polygon_arb_bot::portfolio::Portfolio;
+use polygon_arb_bot::prices::LivePrices;
+use polygon_arb_bot::quotecheck::QUOTE_CHECKS;
+use polygon_arb_bot::rejections::{self, RejectionParams};
+use polygon_arb_bot::rpc::{FailoverClient, RetryPolicy};
+use polygon_arb_bot::schedule;
+use polygon_arb_bot::share::ShareLinks;
+use polygon_arb_bot::sink::NdjsonSink;
+use polygon_arb_bot::spreads::{self, SpreadParams};
+use polygon_arb_bot::strategy::{Strategies, Window};
+use polygon_arb_bot::summary::{self, SummaryParams};
+use polygon_arb_bot::supervisor;
+use polygon_arb_bot::suspension::SUSPENSIONS;
+use polygon_arb_bot::topup::{GasTopUp, TopUpConfig};
+use polygon_arb_bot::watch::{self, WalletWatcher};
+use polygon_arb_bot::{query, report, units, ArbDetector, Config};
 use rusqlite::{params, Connection};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::Deserialize;
 use std::env;
+use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tokio::time::sleep;
-use actix_web::{get, web, App, HttpResponse, HttpServer, Responder};
-use actix_files::Files;
-
-abigen!(
-    TokenSwapCalculator,
-    r#"[ function getAmountsOut(uint256 amountIn, address[] memory path) external view returns (uint256[] memory amounts) ]"#
-);
-
-abigen!(
-    ERC20,
-    r#"[ function decimals() external view returns (uint8) ]"#
-);
-
-#[derive(Debug, Deserialize, Clone)]
-struct Config {
-    rpc_url: String,
-    dex_a_router: Address,
-    dex_b_router: Address,
-    token_in: Address,
-    token_out: Address,
-    trade_size_wei: U256,
-    min_profit_usdc: f64,
-    poll_interval_secs: u64,
-    simulated_gas_usdc: f64,
-    database_path: String,
-}
-
-impl Config {
-    pub fn from_env() -> anyhow::Result<Self> {
-        dotenv().ok();
-        Ok(Self {
-            rpc_url: env::var("RPC_URL")?,
-            dex_a_router: env::var("DEX_A_ROUTER")?.parse::<Address>()?,
-            dex_b_router: env::var("DEX_B_ROUTER")?.parse::<Address>()?,
-            token_in: env::var("TOKEN_IN")?.parse::<Address>()?,
-            token_out: env::var("TOKEN_OUT")?.parse::<Address>()?,
-            trade_size_wei: U256::from(env::var("TRADE_SIZE_WEI")?.parse::<u128>()?),
-            min_profit_usdc: env::var("MIN_PROFIT_USDC")?.parse::<f64>()?,
-            poll_interval_secs: env::var("POLL_INTERVAL_SECS")?.parse::<u64>()?,
-            simulated_gas_usdc: env::var("SIMULATED_GAS_USDC")?.parse::<f64>()?,
-            database_path: env::var("DATABASE_PATH")?,
-        })
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+mod cli;
+
+use cli::{Cli, Command, DbCommand, KeysCommand};
+
+/// Rows read per page of `/opportunities.ndjson`.
+const NDJSON_PAGE: usize = 1000;
+
+/// Rows of `/opportunities` without `limit`, and the most one page may hold.
+const DEFAULT_PAGE_LIMIT: usize = 100;
+const MAX_PAGE_LIMIT: usize = 1000;
+
+/// Longest the detectors and open connections get to finish on shutdown.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(30);
+
+/// Opportunities held for a slow `/stream` client before it skips some.
+const STREAM_BUFFER: usize = 256;
+
+/// Longest `/stream` stays silent; proxies drop idle connections.
+const STREAM_KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// Every detector of this process, for the endpoints that list or drive them.
+type Detectors = Vec<Arc<ArbDetector<Provider<FailoverClient>>>>;
+
+/// Wallet of live execution, as served to `/portfolio`.
+type LivePortfolio = Portfolio<Provider<FailoverClient>>;
+
+/// `WHERE` clause of the `OpportunityFilter` parameters, bound as ?1-?6.
+const OPPORTUNITY_FILTER: &str = "(?1 IS NULL OR strategy = ?1)
+     AND (?2 IS NULL OR instance = ?2) AND (?3 IS NULL OR pair = ?3)
+     AND (?4 IS NULL OR roi_bps >= ?4) AND (?5 IS NULL OR timestamp >= ?5)
+     AND (?6 IS NULL OR profit >= ?6)";
+
+#[derive(Deserialize, Clone)]
+struct OpportunityFilter {
+    strategy: Option<String>,
+    instance: Option<String>,
+    pair: Option<String>,
+    min_roi_bps: Option<f64>,
+    /// RFC 3339 timestamp; rows stored at or after it.
+    since: Option<String>,
+    min_profit: Option<f64>,
+    /// Paging of `/opportunities`; the NDJSON download ignores them.
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+impl OpportunityFilter {
+    /// Rewrites `since` in the stored timestamp format, so it compares as text.
+    fn normalized(mut self) -> Result<Self, String> {
+        if let Some(since) = &self.since {
+            let since = chrono::DateTime::parse_from_rfc3339(since)
+                .map_err(|e| format!("Invalid since: {}", e))?;
+            self.since = Some(since.with_timezone(&Utc).to_rfc3339());
+        }
+        Ok(self)
     }
 }
 
-static DECIMALS_CACHE: Lazy<Mutex<HashMap<Address, u8>>> =
-    Lazy::new(|| Mutex::new(HashMap::new()));
+#[derive(Deserialize)]
+struct TopParams {
+    /// Look-back window, e.g. `24h` or `7d` (default 24h).
+    window: Option<String>,
+    /// How many to return (default 10, at most 100).
+    n: Option<usize>,
+    by: Option<TopBy>,
+    instance: Option<String>,
+    pair: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PaperParams {
+    instance: Option<String>,
+}
 
-#[derive(Serialize)]
-struct Opportunity {
-    timestamp: String,
-    dex_buy: String,
-    dex_sell: String,
-    amount_in: String,
-    amount_out_buy: String,
-    amount_out_sell: String,
-    profit: f64,
+#[derive(Deserialize, Clone)]
+struct StreamParams {
+    instance: Option<String>,
+    pair: Option<String>,
+}
+
+/// Selects detectors by instance and pair; every one when unset.
+#[derive(Deserialize)]
+struct DetectorParams {
+    instance: Option<String>,
+    pair: Option<String>,
+}
+
+impl DetectorParams {
+    fn select<'a>(
+        &self,
+        detectors: &'a Detectors,
+    ) -> Vec<&'a Arc<ArbDetector<Provider<FailoverClient>>>> {
+        detectors
+            .iter()
+            .filter(|d| {
+                self.instance
+                    .as_deref()
+                    .is_none_or(|i| d.config().instance == i)
+            })
+            .filter(|d| self.pair.is_none() || d.pair() == self.pair.as_deref())
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct LogLevelBody {
+    filter: String,
+}
+
+#[derive(Deserialize)]
+struct StrategyBody {
+    enabled: Option<bool>,
+    /// `HH:MM-HH:MM` in UTC; an empty string removes the window.
+    window: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ShareParams {
+    expires: Option<i64>,
+    sig: Option<String>,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    env_logger::init();
+    diagnostics::init_logging();
 
-    let cfg = Config::from_env().context("Failed to read config from .env")?;
-    log::info!(
-        "Starting Polygon Arb Bot | Poll every {}s | Min profit {} USDC",
-        cfg.poll_interval_secs,
-        cfg.min_profit_usdc
-    );
+    let cli = Cli::parse();
+    let config_file = cli
+        .config
+        .clone()
+        .or_else(|| Some(PathBuf::from("config.toml")).filter(|p| p.exists()));
+    let cfg = match &config_file {
+        Some(path) => Config::from_file(path)
+            .with_context(|| format!("Failed to read config from {}", path.display()))?,
+        None => Config::from_env().context("Failed to read config from .env")?,
+    };
 
-    let provider = Arc::new(
-        Provider::<Http>::try_from(cfg.rpc_url.as_str())?.interval(Duration::from_millis(500)),
-    );
+    if let Some(command) = cli.command {
+        let conn = Connection::open(&cfg.database_path)?;
+        db::init_db(&conn)?;
+        return run_command(command, &conn).await;
+    }
+
+    let instances = Config::instances()?;
+
+    let rpc = FailoverClient::new(
+        &cfg.rpc_urls(),
+        cfg.rpc_public_failover,
+        Duration::from_secs(cfg.rpc_request_timeout_secs),
+        RetryPolicy {
+            max_attempts: cfg.rpc_retry_attempts,
+            base_delay: Duration::from_millis(cfg.rpc_retry_base_ms),
+            jitter: Duration::from_millis(cfg.rpc_retry_jitter_ms),
+        },
+    )?;
+    {
+        let rpc = rpc.clone();
+        let interval = Duration::from_secs(cfg.rpc_health_interval_secs);
+        supervisor::spawn("rpc_health", move || {
+            rpc.clone().run_health_checks(interval)
+        });
+    }
+    let provider = Arc::new(Provider::new(rpc.clone()).interval(Duration::from_millis(500)));
 
     let conn = Arc::new(Mutex::new(Connection::open(&cfg.database_path)?));
-    init_db(&conn.lock().unwrap())?;
+    db::init_db(&conn.lock().unwrap())?;
 
-    let dex_a_router = TokenSwapCalculator::new(cfg.dex_a_router, Arc::clone(&provider));
-    let dex_b_router = TokenSwapCalculator::new(cfg.dex_b_router, Arc::clone(&provider));
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+    let links = Arc::new(ShareLinks::new(
+        &cfg.public_base_url,
+        cfg.share_link_secret.as_deref(),
+        cfg.share_link_ttl_secs,
+    ));
 
-    let decimals_in = get_decimals_cached(Arc::clone(&provider), cfg.token_in)
-        .await
-        .unwrap_or(18u8);
-    let decimals_out = get_decimals_cached(Arc::clone(&provider), cfg.token_out)
-        .await
-        .unwrap_or(18u8);
+    let sink = match &cfg.ndjson_output {
+        Some(target) => {
+            Some(Arc::new(NdjsonSink::open(target).with_context(|| {
+                format!("Failed to open NDJSON output {}", target)
+            })?))
+        }
+        None => None,
+    };
 
-    // Spawn background bot loop
-    let cfg_clone = cfg.clone();
-    let conn_clone = Arc::clone(&conn);
-    tokio::spawn(async move {
-        loop {
-            if let Err(e) = run_cycle(
-                &cfg_clone,
-                &conn_clone,
-                &dex_a_router,
-                &dex_b_router,
-                decimals_in as u32,
-                decimals_out as u32,
+    let influx = cfg.influx_url.as_deref().map(|url| {
+        Arc::new(InfluxSink::spawn(
+            url,
+            &cfg.influx_org,
+            &cfg.influx_bucket,
+            cfg.influx_token.clone(),
+        ))
+    });
+
+    let strategies = Arc::new(Strategies::from_config(
+        &cfg.disabled_strategies,
+        &cfg.strategy_windows,
+    )?);
+
+    let alerts = match &cfg.alert_rules {
+        Some(rules) => {
+            let telegram = cfg
+                .telegram_bot_token
+                .clone()
+                .zip(cfg.telegram_chat_id.clone())
+                .map(|(bot_token, chat_id)| Telegram {
+                    bot_token,
+                    chat_id,
+                    cooldown: Duration::from_secs(cfg.telegram_cooldown_secs),
+                });
+            let alert_cfg = AlertConfig {
+                rules,
+                severity_profits: cfg.alert_severity_profits,
+                telegram,
+                webhook_url: cfg.alert_webhook_url.clone(),
+            };
+            Some(Arc::new(
+                AlertRouter::spawn(alert_cfg, Arc::clone(&links)).context("Invalid ALERT_RULES")?,
+            ))
+        }
+        None => None,
+    };
+
+    let heads = cfg.rpc_ws_url.as_deref().map(NewHeads::spawn);
+
+    let pair_cfgs = instances
+        .iter()
+        .map(Config::pair_configs)
+        .collect::<anyhow::Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    // One detector has nobody to share its batches with.
+    let coalescer = if pair_cfgs.len() > 1 && cfg.multicall_coalesce_ms > 0 {
+        Some(Arc::new(Coalescer::new(
+            Arc::clone(&provider),
+            cfg.multicall_coalesce_ms,
+            cfg.multicall_batch_size,
+        )?))
+    } else {
+        None
+    };
+
+    // Shared by every detector, so trades from one wallet never overlap.
+    let executor = match cfg.execution_signer()? {
+        Some(signer) => Some(Arc::new(
+            Executor::new(
+                (*provider).clone(),
+                signer,
+                cfg.execution_slippage_bps,
+                Duration::from_secs(cfg.execution_confirm_timeout_secs),
+            )
+            .await?,
+        )),
+        None => None,
+    };
+    let portfolio = match &executor {
+        Some(executor) => Some(Arc::new(
+            Portfolio::new(
+                Arc::clone(&provider),
+                executor.address(),
+                &pair_cfgs,
+                clock.now(),
             )
             .await
-            {
-                log::error!("Error in arbitrage loop: {:?}", e);
-            }
-            sleep(Duration::from_secs(cfg_clone.poll_interval_secs)).await;
+            .context("Could not read the starting wallet balances")?,
+        )),
+        None => None,
+    };
+    let liquidity = Arc::new(
+        LiquidityWatch::new(
+            Arc::clone(&provider),
+            &pair_cfgs,
+            LiquidityConfig {
+                floor: cfg.liquidity_floor,
+                drop_pct: cfg.liquidity_drop_pct,
+                dead_after: chrono::Duration::seconds(cfg.pair_dead_after_secs as i64),
+            },
+        )
+        .await?,
+    );
+
+    // Every stored opportunity of every detector, for `/stream` clients.
+    let (live, _) = broadcast::channel::<Opportunity>(STREAM_BUFFER);
+    let live_prices = Arc::new(LivePrices::default());
+    let mut detectors: Detectors = Vec::new();
+    let mut detector_tasks = Vec::new();
+    let shutdown = CancellationToken::new();
+    for pair_cfg in pair_cfgs {
+        let task = match &pair_cfg.pair {
+            Some(pair) => format!("detector:{}:{}", pair_cfg.instance, pair),
+            None => format!("detector:{}", pair_cfg.instance),
+        };
+        let mut detector = ArbDetector::new(pair_cfg, &provider, &conn, &links).await?;
+        detector.with_strategies(Arc::clone(&strategies));
+        detector.with_live_prices(Arc::clone(&live_prices));
+        detector.with_shutdown(shutdown.clone());
+        if let Some(sink) = &sink {
+            detector.with_sink(Arc::clone(sink));
         }
-    });
+        if let Some(influx) = &influx {
+            detector.with_influx(Arc::clone(influx));
+        }
+        if let Some(heads) = &heads {
+            detector.with_new_heads(heads.clone());
+        }
+        if let Some(coalescer) = &coalescer {
+            detector.with_coalescer(Arc::clone(coalescer));
+        }
+        if let Some(executor) = &executor {
+            detector.with_executor(Arc::clone(executor));
+        }
+        let tx = live.clone();
+        // Only fails while no client is connected.
+        detector.on_opportunity(move |opp| drop(tx.send(opp.clone())));
+        if let Some(alerts) = &alerts {
+            let router = Arc::clone(alerts);
+            detector.on_opportunity(move |opp| router.route(opp));
+            let alerts = Arc::clone(alerts);
+            detector.on_storage(move |event| {
+                let severity = match event {
+                    StorageEvent::Failing { .. } => Severity::High,
+                    StorageEvent::Recovered { .. } => Severity::Low,
+                };
+                alerts.notify(severity, &event.to_string())
+            });
+        }
+        // Spawn background bot loop, restarted if it panics
+        let detector = Arc::new(detector);
+        detectors.push(Arc::clone(&detector));
+        detector_tasks.push(supervisor::spawn(task, move || {
+            let detector = Arc::clone(&detector);
+            async move { detector.run_cycles().await }
+        }));
+    }
+
+    if let Some(wallet) = cfg.watch_wallet {
+        let provider = Arc::clone(&provider);
+        let conn = Arc::clone(&conn);
+        let token_in_info = amounts::load(Arc::clone(&provider), cfg.token_in).await?;
+        let poll_interval = Duration::from_secs(cfg.poll_interval_secs);
+        let (token_in, token_out) = (cfg.token_in, cfg.token_out);
+        let clock = Arc::clone(&clock);
+        JOBS.register(
+            "wallet_watcher",
+            format!("every {}s", poll_interval.as_secs()),
+        );
+        supervisor::spawn("wallet_watcher", move || {
+            let mut watcher =
+                WalletWatcher::new(Arc::clone(&provider), wallet, token_in, token_out);
+            let conn = Arc::clone(&conn);
+            let clock = Arc::clone(&clock);
+            let token_in_info = token_in_info.clone();
+            async move {
+                loop {
+                    let polled = watcher.poll().await;
+                    let now = clock.now();
+                    JOBS.finished("wallet_watcher", now, &polled);
+                    match polled {
+                        Ok(swaps) => {
+                            for swap in swaps {
+                                let bps = swap.spread_bps(&token_in_info);
+                                log::info!(
+                                "Watched {:?} captured {:.1} bps in {:?} (block {}, token_out leg: {})",
+                                wallet,
+                                bps,
+                                swap.tx_hash,
+                                swap.block_number,
+                                swap.touched_out
+                            );
+                                if let Err(e) =
+                                    watch::record(&conn.lock().unwrap(), now, wallet, &swap, bps)
+                                {
+                                    log::error!("Failed to record watched swap: {:?}", e);
+                                }
+                                if let Some(gas_used) = swap.gas_used.filter(|_| swap.touched_out) {
+                                    if let Err(e) = gas::record(
+                                        &conn.lock().unwrap(),
+                                        now,
+                                        token_in,
+                                        token_out,
+                                        gas_used.as_u64(),
+                                    ) {
+                                        log::error!("Failed to record pair gas: {:?}", e);
+                                    }
+                                }
+                            }
+                        }
+                        Err(e) => log::error!("Error watching {:?}: {:?}", wallet, e),
+                    }
+                    JOBS.scheduled(
+                        "wallet_watcher",
+                        clock.now() + chrono::Duration::seconds(poll_interval.as_secs() as i64),
+                    );
+                    clock.sleep(poll_interval).await;
+                }
+            }
+        });
+    }
+
+    if let Some(dir) = cfg.report_dir.clone() {
+        let conn = Arc::clone(&conn);
+        schedule::spawn(
+            Arc::clone(&clock),
+            "report",
+            cfg.report_cron.clone(),
+            move |now| {
+                let written = report::write_report(&conn.lock().unwrap(), Path::new(&dir), now);
+                if let Ok(path) = &written {
+                    log::info!("Weekly report written to {}", path.display());
+                }
+                ready(written.map(drop))
+            },
+        );
+    }
+
+    if let Some(days) = cfg.retention_days {
+        let conn = Arc::clone(&conn);
+        schedule::spawn(
+            Arc::clone(&clock),
+            "retention",
+            cfg.retention_cron.clone(),
+            move |now| {
+                let pruned = maintenance::prune(&conn.lock().unwrap(), days, now);
+                if let Ok(n) = pruned {
+                    log::info!("Pruned {} rows older than {} days", n, days);
+                }
+                ready(pruned.map(drop))
+            },
+        );
+    }
+
+    if let Some(hours) = cfg.downsample_after_hours {
+        let conn = Arc::clone(&conn);
+        let hourly_after = chrono::Duration::days(cfg.downsample_hourly_after_days as i64);
+        schedule::spawn(
+            Arc::clone(&clock),
+            "downsample",
+            cfg.downsample_cron.clone(),
+            move |now| {
+                let downsampled = maintenance::downsample(
+                    &mut conn.lock().unwrap(),
+                    now,
+                    chrono::Duration::hours(hours as i64),
+                    hourly_after,
+                );
+                if let Ok(d) = &downsampled {
+                    log::info!(
+                        "Downsampled {} price samples into 1-minute bars and {} 1-minute bars into 1-hour bars",
+                        d.samples,
+                        d.minute_bars
+                    );
+                }
+                ready(downsampled.map(drop))
+            },
+        );
+    }
+
+    {
+        let conn = Arc::clone(&conn);
+        schedule::spawn(
+            Arc::clone(&clock),
+            "rollup",
+            cfg.rollup_cron.clone(),
+            move |now| ready(maintenance::rollup(&conn.lock().unwrap(), now).map(drop)),
+        );
+    }
+
+    if let Some(url) = cfg.heartbeat_url.clone() {
+        let client = reqwest::Client::new();
+        schedule::spawn(
+            Arc::clone(&clock),
+            "heartbeat",
+            cfg.heartbeat_cron.clone(),
+            move |_| {
+                let (client, url) = (client.clone(), url.clone());
+                async move { maintenance::heartbeat(&client, &url).await }
+            },
+        );
+    }
+
+    let gas_signer = cfg.gas_signer()?;
+    if let Some(wallet) = cfg.gas_wallet.or(gas_signer.as_ref().map(Signer::address)) {
+        let top_up = Arc::new(GasTopUp::new(
+            Arc::clone(&provider),
+            TopUpConfig {
+                wallet,
+                floor_pol: cfg.gas_floor_pol,
+                token: cfg.gas_top_up_token,
+                amount: cfg.gas_top_up_amount,
+                router: cfg.dexes[0].1,
+                wrapped_native: cfg.native_token,
+                signer: gas_signer,
+            },
+        ));
+        let (conn, alerts) = (Arc::clone(&conn), alerts.clone());
+        schedule::spawn(
+            Arc::clone(&clock),
+            "gas_top_up",
+            cfg.gas_check_cron.clone(),
+            move |now| {
+                let (top_up, conn, alerts) =
+                    (Arc::clone(&top_up), Arc::clone(&conn), alerts.clone());
+                async move {
+                    if let Some(message) = top_up.check(&conn, now).await? {
+                        log::warn!("{}", message);
+                        if let Some(alerts) = &alerts {
+                            alerts.notify(Severity::High, &message);
+                        }
+                    }
+                    Ok(())
+                }
+            },
+        );
+    }
+
+    {
+        let (conn, alerts) = (Arc::clone(&conn), alerts.clone());
+        schedule::spawn(
+            Arc::clone(&clock),
+            "liquidity_watch",
+            cfg.liquidity_check_cron.clone(),
+            move |now| {
+                let (liquidity, conn, alerts) =
+                    (Arc::clone(&liquidity), Arc::clone(&conn), alerts.clone());
+                async move {
+                    for message in liquidity.check(&conn, now).await? {
+                        log::warn!("{}", message);
+                        if let Some(alerts) = &alerts {
+                            alerts.notify(Severity::High, &message);
+                        }
+                    }
+                    Ok(())
+                }
+            },
+        );
+    }
+
+    if let Some(url) = cfg.export_url.clone() {
+        let (token, mutation) = (
+            cfg.export_token.clone(),
+            cfg.export_graphql_mutation.clone(),
+        );
+        let conn = Arc::clone(&conn);
+        let clock = Arc::clone(&clock);
+        let interval = Duration::from_secs(cfg.export_interval_secs);
+        JOBS.register("exporter", format!("every {}s", interval.as_secs()));
+        supervisor::spawn("exporter", move || {
+            let exporter = Exporter::new(&url, token.clone(), mutation.clone());
+            let (url, conn, clock) = (url.clone(), Arc::clone(&conn), Arc::clone(&clock));
+            async move {
+                loop {
+                    let exported = exporter.export(&conn).await;
+                    JOBS.finished("exporter", clock.now(), &exported);
+                    match exported {
+                        Ok(0) => {}
+                        Ok(n) => log::info!("Exported {} opportunities to {}", n, url),
+                        Err(e) => log::error!("Failed to export opportunities: {:?}", e),
+                    }
+                    JOBS.scheduled(
+                        "exporter",
+                        clock.now() + chrono::Duration::seconds(interval.as_secs() as i64),
+                    );
+                    clock.sleep(interval).await;
+                }
+            }
+        });
+    }
 
     // --- ✅ FIXED FOR RENDER: dynamic port + 0.0.0.0 binding ---
     let port = env::var("PORT").unwrap_or_else(|_| "8080".to_string());
     let bind_address = format!("0.0.0.0:{}", port);
-    println!("Starting web server on {}", bind_address);
+    log::info!("Starting web server on {}", bind_address);
+    let read_only_api = cfg.read_only_api;
+    let api_auth = cfg.api_auth;
+    let frontend_dir = cfg.frontend_dir.clone();
+    let precision = cfg.precision;
+    let detectors = Arc::new(detectors);
+    let checkpoint_conn = Arc::clone(&conn);
+    if read_only_api {
+        log::info!("Read-only API mode: control and admin routes are disabled");
+    }
 
-    HttpServer::new(move || {
+    let server = HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(Arc::clone(&conn)))
-            .service(index)
-            .service(get_opportunities)
+            .app_data(web::Data::from(Arc::clone(&links)))
+            .app_data(web::Data::from(Arc::clone(&clock)))
+            .app_data(web::Data::new(AuthConfig { enabled: api_auth }))
+            .app_data(web::Data::new(precision))
+            .app_data(web::Data::from(Arc::clone(&strategies)))
+            .app_data(web::Data::new(rpc.clone()))
+            .app_data(web::Data::from(Arc::clone(&detectors)))
+            .app_data(web::Data::new(live.clone()))
+            .app_data(web::Data::from(Arc::clone(&live_prices)))
+            .configure(|c| {
+                if let Some(portfolio) = &portfolio {
+                    c.app_data(web::Data::from(Arc::clone(portfolio)));
+                }
+            })
+            .service(
+                web::scope("/api/v1")
+                    .wrap(DefaultHeaders::new().add((API_VERSION_HEADER, API_VERSION)))
+                    .wrap_fn(negotiate_version)
+                    .configure(|c| api_routes(c, read_only_api)),
+            )
+            .service(
+                web::scope("/api")
+                    .wrap(
+                        DefaultHeaders::new()
+                            .add((API_VERSION_HEADER, API_VERSION))
+                            .add(("Deprecation", "true"))
+                            .add(("Link", "</api/v1/>; rel=\"successor-version\"")),
+                    )
+                    .wrap_fn(negotiate_version)
+                    .configure(|c| api_routes(c, read_only_api)),
+            )
+            .service(get_metrics)
             .service(Files::new("/static", "./static"))
+            .configure(|c| match &frontend_dir {
+                Some(dir) => {
+                    c.service(frontend_files(dir));
+                }
+                None => {
+                    c.service(index);
+                }
+            })
+            .default_service(web::to(spa_fallback))
+            .wrap_fn(tag_request)
     })
     .bind(bind_address)?
-    .run()
-    .await?;
+    .disable_signals()
+    .shutdown_timeout(SHUTDOWN_GRACE.as_secs())
+    .run();
 
+    let handle = server.handle();
+    let stop = shutdown.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        log::info!("Shutting down: finishing in-flight cycles and requests");
+        stop.cancel();
+        handle.stop(true).await;
+    });
+    server.await?;
+
+    shutdown.cancel();
+    if tokio::time::timeout(SHUTDOWN_GRACE, futures::future::join_all(detector_tasks))
+        .await
+        .is_err()
+    {
+        log::warn!(
+            "Detectors did not stop within {}s, exiting anyway",
+            SHUTDOWN_GRACE.as_secs()
+        );
+    }
+    // Folds the WAL back into the database file, if the database uses one.
+    if let Err(e) =
+        checkpoint_conn
+            .lock()
+            .unwrap()
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+    {
+        log::error!("SQLite checkpoint on shutdown failed: {}", e);
+    }
+    log::info!("Shutdown complete");
     Ok(())
 }
 
-// ----- Database -----
-fn init_db(conn: &Connection) -> anyhow::Result<()> {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS opportunities (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            timestamp TEXT NOT NULL,
-            dex_buy TEXT NOT NULL,
-            dex_sell TEXT NOT NULL,
-            amount_in TEXT NOT NULL,
-            amount_out_buy TEXT NOT NULL,
-            amount_out_sell TEXT NOT NULL,
-            profit REAL NOT NULL
-        )",
-        [],
-    )?;
-    Ok(())
+/// Resolves on Ctrl-C, or on SIGTERM (what service managers and containers
+/// send) on Unix.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    if let Ok(mut term) = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+    {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = term.recv() => {}
+        }
+        return;
+    }
+    let _ = tokio::signal::ctrl_c().await;
 }
 
-fn insert_opportunity(
-    conn: &Arc<Mutex<Connection>>,
-    dex_buy: &str,
-    dex_sell: &str,
-    amount_in: f64,
-    amount_out_buy: f64,
-    amount_out_sell: f64,
-    profit: f64,
-) -> anyhow::Result<()> {
-    let ts = Utc::now().to_rfc3339();
-    conn.lock().unwrap().execute(
-        "INSERT INTO opportunities (timestamp, dex_buy, dex_sell, amount_in, amount_out_buy, amount_out_sell, profit) 
-         VALUES (?1,?2,?3,?4,?5,?6,?7)",
-        params![ts, dex_buy, dex_sell, amount_in, amount_out_buy, amount_out_sell, profit],
-    )?;
+// ----- CLI -----
+async fn run_command(command: Command, conn: &Connection) -> anyhow::Result<()> {
+    match command {
+        Command::Keys { action } => match action {
+            KeysCommand::Create { name, scopes } => {
+                let key = auth::create_key(conn, &name, &scopes)?;
+                println!(
+                    "Created API key '{}'. Store it now, it will not be shown again:",
+                    name
+                );
+                println!("{}", key);
+            }
+            KeysCommand::Revoke { name } => {
+                let revoked = auth::revoke_key(conn, &name)?;
+                println!("Revoked {} key(s) named '{}'", revoked, name);
+            }
+            KeysCommand::List => {
+                for key in auth::list_keys(conn)? {
+                    let scopes: Vec<&str> = key.scopes.iter().map(Scope::as_str).collect();
+                    println!(
+                        "{}\t{}\t{}\tcreated {}{}",
+                        key.id,
+                        key.name,
+                        scopes.join(","),
+                        key.created_at,
+                        key.revoked_at
+                            .map(|t| format!("\trevoked {}", t))
+                            .unwrap_or_default()
+                    );
+                }
+            }
+        },
+        Command::Report { out } => {
+            let html = report::generate(conn, Utc::now())?;
+            match out {
+                Some(path) => std::fs::write(path, html)?,
+                None => println!("{}", html),
+            }
+        }
+        Command::Query { filter, last } => {
+            let filter = query::parse(&filter).context("Invalid filter")?;
+            for opp in query::opportunities(conn, &filter, last)? {
+                println!(
+                    "{}\t{}\t{}\t{}\t{} -> {}\tin {}\tprofit {}{}",
+                    opp.id,
+                    opp.timestamp,
+                    opp.pair.as_deref().unwrap_or("-"),
+                    opp.strategy,
+                    opp.dex_buy,
+                    opp.dex_sell,
+                    opp.amount_in,
+                    opp.profit_exact
+                        .map_or_else(|| opp.profit.to_string(), |profit| profit.to_string()),
+                    opp.roi_bps
+                        .map(|bps| format!("\troi {:.1} bps", bps))
+                        .unwrap_or_default()
+                );
+            }
+        }
+        Command::Db { action } => match action {
+            DbCommand::Migrate { to } => {
+                for table in migrate::to_postgres(conn, &to).await? {
+                    println!("{}\t{} rows", table.table, table.rows);
+                }
+                println!("Migration complete, row counts verified");
+            }
+        },
+    }
     Ok(())
 }
 
-// ----- Helpers -----
-fn u256_to_f64(value: U256, decimals: u32) -> f64 {
-    let mut v = value.as_u128() as f64;
-    v /= 10f64.powi(decimals as i32);
-    v
+// ----- Web endpoints -----
+const API_VERSION_HEADER: &str = "API-Version";
+const API_VERSION: &str = "1";
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// JSON endpoints, mounted under `/api/v1` and under the deprecated `/api`.
+fn api_routes(cfg: &mut web::ServiceConfig, read_only: bool) {
+    cfg.service(get_opportunities)
+        .service(get_opportunities_ndjson)
+        .service(stream_opportunities)
+        .service(ws_prices)
+        .service(get_top_opportunities)
+        .service(get_opportunity)
+        .service(get_events)
+        .service(get_summary)
+        .service(get_heatmap)
+        .service(get_rollups)
+        .service(get_rejections)
+        .service(get_leadlag)
+        .service(get_spreads)
+        .service(get_candles)
+        .service(get_deviations)
+        .service(get_strategies)
+        .service(get_paper)
+        .service(get_pairs)
+        .service(get_state)
+        .service(get_jobs)
+        .service(get_dexes);
+    if !read_only {
+        control_routes(cfg);
+    }
 }
 
-async fn get_decimals_cached<M: Middleware + 'static>(
-    provider: Arc<M>,
-    token: Address,
-) -> Option<u8> {
-    {
-        let cache = DECIMALS_CACHE.lock().unwrap();
-        if let Some(&d) = cache.get(&token) {
-            return Some(d);
+/// Runs the request under a request ID (see `correlation`) and echoes it in
+/// `X-Request-Id`.
+fn tag_request<S>(
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = Result<ServiceResponse, actix_web::Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse, Error = actix_web::Error>,
+{
+    let id = correlation::request_id(
+        req.headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok()),
+    );
+    let res = correlation::scope(id.clone(), srv.call(req));
+    async move {
+        let mut res = res.await?;
+        if let Ok(value) = HeaderValue::from_str(&id) {
+            res.headers_mut()
+                .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
         }
+        Ok(res)
     }
-    let erc20 = ERC20::new(token, Arc::clone(&provider));
-    match erc20.decimals().call().await {
-        Ok(d) => {
-            let mut cache = DECIMALS_CACHE.lock().unwrap();
-            cache.insert(token, d);
-            Some(d)
-        }
-        Err(_) => None,
-    }
-}
-
-// ----- Bot cycle -----
-async fn run_cycle<M: Middleware + 'static>(
-    cfg: &Config,
-    conn: &Arc<Mutex<Connection>>,
-    dex_a_router: &TokenSwapCalculator<M>,
-    dex_b_router: &TokenSwapCalculator<M>,
-    decimals_in: u32,
-    decimals_out: u32,
-) -> anyhow::Result<()> {
-    let path = vec![cfg.token_in, cfg.token_out];
-
-    let a_amounts = dex_a_router
-        .get_amounts_out(cfg.trade_size_wei, path.clone())
-        .call()
-        .await?;
-    let b_amounts = dex_b_router
-        .get_amounts_out(cfg.trade_size_wei, path.clone())
-        .call()
-        .await?;
-
-    let dex_a_amount_out = a_amounts.last().cloned().unwrap_or_else(U256::zero);
-    let dex_b_amount_out = b_amounts.last().cloned().unwrap_or_else(U256::zero);
-
-    let trade_size_f = u256_to_f64(cfg.trade_size_wei, decimals_in);
-    let price_a = u256_to_f64(dex_a_amount_out, decimals_out) / trade_size_f;
-    let price_b = u256_to_f64(dex_b_amount_out, decimals_out) / trade_size_f;
-
-    log::info!("Prices: A = {:.4} | B = {:.4}", price_a * trade_size_f, price_b * trade_size_f);
-
-    if price_b > price_a {
-        let profit = (price_b - price_a) * trade_size_f - cfg.simulated_gas_usdc;
-        if profit > cfg.min_profit_usdc {
-            log::info!(
-                "Arb Opportunity: Buy on DEX A @ {:.4}, Sell on DEX B @ {:.4} → Profit: {:.4} USDC",
-                price_a * trade_size_f,
-                price_b * trade_size_f,
-                profit
-            );
-            insert_opportunity(
-                conn,
-                "A",
-                "B",
-                trade_size_f,
-                u256_to_f64(dex_a_amount_out, decimals_out),
-                u256_to_f64(dex_b_amount_out, decimals_out),
-                profit,
-            )?;
-        }
-    } else if price_a > price_b {
-        let profit = (price_a - price_b) * trade_size_f - cfg.simulated_gas_usdc;
-        if profit > cfg.min_profit_usdc {
-            log::info!(
-                "Arb Opportunity: Buy on DEX B @ {:.4}, Sell on DEX A @ {:.4} → Profit: {:.4} USDC",
-                price_b * trade_size_f,
-                price_a * trade_size_f,
-                profit
-            );
-            insert_opportunity(
-                conn,
-                "B",
-                "A",
-                trade_size_f,
-                u256_to_f64(dex_a_amount_out, decimals_out),
-                u256_to_f64(dex_b_amount_out, decimals_out),
-                profit,
-            )?;
+}
+
+/// Rejects requests whose `API-Version` header asks for a version this server
+/// does not speak; without the header the current version is served.
+fn negotiate_version<S>(
+    req: ServiceRequest,
+    srv: &S,
+) -> Either<S::Future, Ready<Result<ServiceResponse, actix_web::Error>>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse, Error = actix_web::Error>,
+{
+    let requested = req
+        .headers()
+        .get(API_VERSION_HEADER)
+        .map(|v| v.to_str().unwrap_or_default().trim().to_string());
+    match requested {
+        Some(version) if version != API_VERSION => {
+            let res = HttpResponse::NotAcceptable().json(serde_json::json!({
+                "error": format!("unsupported API version {:?}", version),
+                "supported": [API_VERSION],
+            }));
+            Either::Right(ready(Ok(req.into_response(res))))
         }
+        _ => Either::Left(srv.call(req)),
     }
+}
 
-    Ok(())
+/// Routes that change state or are meant for the operator only. None of them
+/// are registered in read-only mode, so they cannot be reached publicly.
+fn control_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(share_opportunity)
+        .service(get_audit_log)
+        .service(get_log_level)
+        .service(set_log_level)
+        .service(update_strategy)
+        .service(run_once)
+        .service(reactivate_pairs)
+        .service(get_portfolio);
 }
 
-// ----- Web endpoints -----
 #[get("/")]
 async fn index() -> impl Responder {
     HttpResponse::Ok()
@@ -284,30 +921,894 @@ async fn index() -> impl Responder {
         .body(include_str!("../static/landing.html"))
 }
 
+/// History-mode routing for the bundled landing page: any unknown non-API
+/// path renders it, so client-side routes survive a reload.
+async fn spa_fallback(req: HttpRequest) -> HttpResponse {
+    if req.path().starts_with("/api/") {
+        return HttpResponse::NotFound().json(serde_json::json!({ "error": "not found" }));
+    }
+    HttpResponse::Ok()
+        .content_type("text/html")
+        .body(include_str!("../static/landing.html"))
+}
+
+/// Serves a built SPA from `dir`, falling back to its `index.html` for unknown
+/// non-API paths.
+fn frontend_files(dir: &str) -> Files {
+    let index_html = std::path::Path::new(dir).join("index.html");
+    Files::new("/", dir)
+        .index_file("index.html")
+        .default_handler(fn_service(move |req: ServiceRequest| {
+            let index_html = index_html.clone();
+            async move {
+                let (req, _) = req.into_parts();
+                let res = if req.path().starts_with("/api/") {
+                    HttpResponse::NotFound().json(serde_json::json!({ "error": "not found" }))
+                } else {
+                    NamedFile::open_async(index_html).await?.into_response(&req)
+                };
+                Ok::<_, actix_web::Error>(ServiceResponse::new(req, res))
+            }
+        }))
+}
+
+/// Newest opportunities first, a page at a time (`limit`, default 100, at
+/// most 1000, and `offset`). `X-Total-Count` carries the number of rows the
+/// filters match.
 #[get("/opportunities")]
-async fn get_opportunities(conn: web::Data<Arc<Mutex<Connection>>>) -> impl Responder {
-    let conn = conn.lock().unwrap();
-    let mut stmt = conn
-        .prepare(
-            "SELECT timestamp, dex_buy, dex_sell, amount_in, amount_out_buy, amount_out_sell, profit 
-             FROM opportunities ORDER BY id DESC",
-        )
-        .unwrap();
+async fn get_opportunities(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    precision: web::Data<Precision>,
+    filter: web::Query<OpportunityFilter>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    let filter = match filter.into_inner().normalized() {
+        Ok(filter) => filter,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    match opportunity_list(&conn.lock().unwrap(), &filter) {
+        Ok((total, rows)) => HttpResponse::Ok()
+            .insert_header(("X-Total-Count", total.to_string()))
+            .json(
+                rows.iter()
+                    .map(|opp| ApiOpportunity::from(opp).with_display(&precision))
+                    .collect::<Vec<_>>(),
+            ),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
 
+/// Count of the rows matching `filter` and the requested page of them.
+fn opportunity_list(
+    conn: &Connection,
+    filter: &OpportunityFilter,
+) -> rusqlite::Result<(i64, Vec<Opportunity>)> {
+    let bound: [&dyn rusqlite::ToSql; 6] = [
+        &filter.strategy,
+        &filter.instance,
+        &filter.pair,
+        &filter.min_roi_bps,
+        &filter.since,
+        &filter.min_profit,
+    ];
+    let total = conn.query_row(
+        &format!(
+            "SELECT COUNT(*) FROM opportunities WHERE {}",
+            OPPORTUNITY_FILTER
+        ),
+        &bound[..],
+        |row| row.get(0),
+    )?;
+    let limit = filter
+        .limit
+        .unwrap_or(DEFAULT_PAGE_LIMIT)
+        .min(MAX_PAGE_LIMIT);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM opportunities WHERE {} ORDER BY id DESC LIMIT {} OFFSET {}",
+        OPPORTUNITY_COLUMNS,
+        OPPORTUNITY_FILTER,
+        limit,
+        filter.offset.unwrap_or(0)
+    ))?;
     let rows = stmt
-        .query_map([], |row| {
-            Ok(Opportunity {
-                timestamp: row.get(0)?,
-                dex_buy: row.get(1)?,
-                dex_sell: row.get(2)?,
-                amount_in: row.get(3)?,
-                amount_out_buy: row.get(4)?,
-                amount_out_sell: row.get(5)?,
-                profit: row.get(6)?,
+        .query_map(&bound[..], opportunity_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((total, rows))
+}
+
+/// One page of the NDJSON download: the opportunities matching `filter`
+/// below `before`, newest first.
+fn opportunity_page(
+    conn: &Connection,
+    filter: &OpportunityFilter,
+    before: Option<i64>,
+) -> rusqlite::Result<Vec<Opportunity>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM opportunities WHERE {} AND (?7 IS NULL OR id < ?7)
+         ORDER BY id DESC LIMIT ?8",
+        OPPORTUNITY_COLUMNS, OPPORTUNITY_FILTER
+    ))?;
+    let rows = stmt
+        .query_map(
+            params![
+                filter.strategy,
+                filter.instance,
+                filter.pair,
+                filter.min_roi_bps,
+                filter.since,
+                filter.min_profit,
+                before,
+                NDJSON_PAGE as i64
+            ],
+            opportunity_from_row,
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// The history as newline-delimited JSON, same filters as `/opportunities`.
+/// Rows are read in pages of `NDJSON_PAGE` by id only as the client consumes
+/// the body, so memory stays flat however large the table is and a slow
+/// client holds back the reads instead of buffering them.
+#[get("/opportunities.ndjson")]
+async fn get_opportunities_ndjson(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    precision: web::Data<Precision>,
+    filter: web::Query<OpportunityFilter>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    let conn: Arc<Mutex<Connection>> = (**conn).clone();
+    let precision = **precision;
+    let filter = match filter.into_inner().normalized() {
+        Ok(filter) => filter,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let pages = stream::unfold(Some(None), move |cursor| {
+        let conn = Arc::clone(&conn);
+        let filter = filter.clone();
+        async move {
+            // `None` once a short page has shown the table is exhausted.
+            let before = cursor?;
+            let page = match opportunity_page(&conn.lock().unwrap(), &filter, before) {
+                Ok(page) => page,
+                Err(e) => {
+                    log::error!("NDJSON download failed after id {:?}: {}", before, e);
+                    return Some((Err(actix_web::error::ErrorInternalServerError(e)), None));
+                }
+            };
+            if page.is_empty() {
+                return None;
+            }
+            let next = (page.len() == NDJSON_PAGE).then(|| page.last().map(|opp| opp.id));
+            let mut body = Vec::new();
+            for opp in page {
+                if let Err(e) = serde_json::to_writer(
+                    &mut body,
+                    &ApiOpportunity::from(&opp).with_display(&precision),
+                ) {
+                    return Some((Err(actix_web::error::ErrorInternalServerError(e)), None));
+                }
+                body.push(b'\n');
+            }
+            Some((Ok::<_, actix_web::Error>(web::Bytes::from(body)), next))
+        }
+    });
+    HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(pages)
+}
+
+/// Opportunities as Server-Sent Events while they are stored, one
+/// `opportunity` event each (`id:` is the row id), optionally only those of
+/// `instance`/`pair`. Nothing stored before the client connected is replayed.
+#[get("/stream")]
+async fn stream_opportunities(
+    caller: Caller,
+    live: web::Data<broadcast::Sender<Opportunity>>,
+    precision: web::Data<Precision>,
+    params: web::Query<StreamParams>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    let precision = **precision;
+    let params = params.into_inner();
+    let events = stream::unfold(live.subscribe(), move |mut rx| {
+        let params = params.clone();
+        async move {
+            loop {
+                let opp = match tokio::time::timeout(STREAM_KEEPALIVE, rx.recv()).await {
+                    Ok(Ok(opp)) => opp,
+                    Ok(Err(broadcast::error::RecvError::Lagged(missed))) => {
+                        log::warn!("Stream client lagged, skipped {} opportunities", missed);
+                        continue;
+                    }
+                    Ok(Err(broadcast::error::RecvError::Closed)) => return None,
+                    Err(_) => return Some((Ok(web::Bytes::from_static(b": keep-alive\n\n")), rx)),
+                };
+                if params.instance.as_ref().is_some_and(|i| *i != opp.instance)
+                    || params.pair.is_some() && opp.pair != params.pair
+                {
+                    continue;
+                }
+                let event = match serde_json::to_string(
+                    &ApiOpportunity::from(&opp).with_display(&precision),
+                ) {
+                    Ok(data) => format!("event: opportunity\nid: {}\ndata: {}\n\n", opp.id, data),
+                    Err(e) => {
+                        return Some((Err(actix_web::error::ErrorInternalServerError(e)), rx))
+                    }
+                };
+                return Some((Ok::<_, actix_web::Error>(web::Bytes::from(event)), rx));
+            }
+        }
+    });
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(events)
+}
+
+/// Per-DEX prices over a WebSocket, one JSON text message per snapshot: the
+/// latest of every detector on connect, then each cycle's as it is quoted.
+#[get("/ws/prices")]
+async fn ws_prices(
+    caller: Caller,
+    req: HttpRequest,
+    body: web::Payload,
+    prices: web::Data<LivePrices>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    let (response, mut session, mut incoming) = match actix_ws::handle(&req, body) {
+        Ok(ws) => ws,
+        Err(e) => return e.error_response(),
+    };
+    let (latest, mut rx) = prices.subscribe();
+    actix_web::rt::spawn(async move {
+        for snapshot in latest {
+            let Ok(text) = serde_json::to_string(&snapshot) else {
+                continue;
+            };
+            if session.text(text).await.is_err() {
+                return;
+            }
+        }
+        loop {
+            tokio::select! {
+                update = rx.recv() => match update {
+                    Ok(snapshot) => {
+                        let Ok(text) = serde_json::to_string(&snapshot) else { continue };
+                        if session.text(text).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        log::warn!("Price feed client lagged, skipped {} snapshots", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                msg = incoming.recv() => match msg {
+                    Some(Ok(actix_ws::Message::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            return;
+                        }
+                    }
+                    Some(Ok(actix_ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                },
+            }
+        }
+        let _ = session.close(None).await;
+    });
+    response
+}
+
+/// Leaderboard of the best opportunities in a window, e.g.
+/// `/opportunities/top?window=24h&n=10&by=roi`.
+#[get("/opportunities/top")]
+async fn get_top_opportunities(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    clock: web::Data<dyn Clock>,
+    precision: web::Data<Precision>,
+    params: web::Query<TopParams>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    let window = match params.window.as_deref() {
+        Some(window) => match units::parse_duration(window, Duration::from_secs(3600)) {
+            Ok(window) => window,
+            Err(e) => return HttpResponse::BadRequest().body(format!("Invalid window: {}", e)),
+        },
+        None => Duration::from_secs(24 * 3600),
+    };
+    let Ok(window) = chrono::Duration::from_std(window) else {
+        return HttpResponse::BadRequest().body("Invalid window: too long");
+    };
+    match db::top_opportunities(
+        &conn.lock().unwrap(),
+        clock.now() - window,
+        params.by.unwrap_or_default(),
+        params.n.unwrap_or(10).min(100),
+        params.instance.as_deref(),
+        params.pair.as_deref(),
+    ) {
+        Ok(top) => HttpResponse::Ok().json(
+            top.into_iter()
+                .map(|opp| ApiOpportunity::from(&opp).with_display(&precision))
+                .collect::<Vec<_>>(),
+        ),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Read-only detail view. A valid `expires` + `sig` pair from a share link is
+/// accepted in place of any other credential; a bad or expired one is refused.
+#[get("/opportunities/{id}")]
+async fn get_opportunity(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    links: web::Data<ShareLinks>,
+    clock: web::Data<dyn Clock>,
+    precision: web::Data<Precision>,
+    path: web::Path<i64>,
+    share: web::Query<ShareParams>,
+) -> impl Responder {
+    let id = path.into_inner();
+    if let (Some(expires), Some(sig)) = (share.expires, &share.sig) {
+        if !links.verify(id, expires, sig, clock.now().timestamp()) {
+            return HttpResponse::Forbidden().body("Invalid or expired share link");
+        }
+    } else if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+
+    let conn = conn.lock().unwrap();
+    match conn.query_row(
+        &format!(
+            "SELECT {} FROM opportunities WHERE id = ?1",
+            OPPORTUNITY_COLUMNS
+        ),
+        params![id],
+        opportunity_from_row,
+    ) {
+        Ok(opp) => HttpResponse::Ok().json(ApiOpportunity::from(&opp).with_display(&precision)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => HttpResponse::NotFound().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[post("/opportunities/{id}/share")]
+async fn share_opportunity(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    links: web::Data<ShareLinks>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<i64>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Control) {
+        return e.error_response();
+    }
+    let id = path.into_inner();
+    let Some((url, expires)) = links.share_link(id, clock.now().timestamp()) else {
+        return HttpResponse::NotImplemented().body("SHARE_LINK_SECRET is not configured");
+    };
+    let payload = serde_json::json!({ "id": id, "expires": expires });
+    if let Err(e) = audit::record(
+        &conn.lock().unwrap(),
+        &caller,
+        "share_opportunity",
+        &payload,
+    ) {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+    HttpResponse::Ok().json(serde_json::json!({ "url": url, "expires": expires }))
+}
+
+#[post("/control/run-once")]
+async fn run_once(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    detectors: web::Data<Detectors>,
+    params: web::Query<DetectorParams>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Control) {
+        return e.error_response();
+    }
+    let selected = params.select(&detectors);
+    if selected.is_empty() {
+        return HttpResponse::NotFound().body("no detector matches instance and pair");
+    }
+    let payload = serde_json::json!({ "instance": params.instance, "pair": params.pair });
+    if let Err(e) = audit::record(&conn.lock().unwrap(), &caller, "run_once", &payload) {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+    let reports = futures::future::join_all(selected.iter().map(|d| d.run_once())).await;
+    HttpResponse::Ok().json(reports)
+}
+
+/// Lifts the suspension of the matching dead pairs; their detectors resume
+/// with the next cycle.
+#[post("/control/pairs/reactivate")]
+async fn reactivate_pairs(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    clock: web::Data<dyn Clock>,
+    detectors: web::Data<Detectors>,
+    params: web::Query<DetectorParams>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Control) {
+        return e.error_response();
+    }
+    let selected = params.select(&detectors);
+    if selected.is_empty() {
+        return HttpResponse::NotFound().body("no detector matches instance and pair");
+    }
+    let now = clock.now();
+    let reactivated: Vec<_> = selected
+        .iter()
+        .filter(|d| SUSPENSIONS.reactivate(&d.config().instance, d.pair(), now))
+        .map(|d| serde_json::json!({ "instance": d.config().instance, "pair": d.pair() }))
+        .collect();
+    for pair in &reactivated {
+        log::info!("Reactivated {}", pair);
+    }
+    let payload = serde_json::json!({ "instance": params.instance, "pair": params.pair });
+    if let Err(e) = audit::record(&conn.lock().unwrap(), &caller, "reactivate_pairs", &payload) {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+    HttpResponse::Ok().json(serde_json::json!({ "reactivated": reactivated }))
+}
+
+#[get("/admin/audit")]
+async fn get_audit_log(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    filter: web::Query<AuditFilter>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Admin) {
+        return e.error_response();
+    }
+    match audit::list(&conn.lock().unwrap(), &filter) {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[get("/admin/log-level")]
+async fn get_log_level(caller: Caller) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Admin) {
+        return e.error_response();
+    }
+    HttpResponse::Ok().json(serde_json::json!({ "filter": diagnostics::log_filter() }))
+}
+
+/// Swaps the `RUST_LOG`-style filter, e.g. `{"filter": "info,polygon_arb_bot::planner=debug"}`.
+#[put("/admin/log-level")]
+async fn set_log_level(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    body: web::Json<LogLevelBody>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Admin) {
+        return e.error_response();
+    }
+    let previous = diagnostics::log_filter();
+    diagnostics::set_log_filter(&body.filter);
+    log::warn!(
+        "Log filter changed from {:?} to {:?}",
+        previous,
+        body.filter
+    );
+    let payload = serde_json::json!({ "from": previous, "to": body.filter });
+    if let Err(e) = audit::record(&conn.lock().unwrap(), &caller, "set_log_level", &payload) {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+    HttpResponse::Ok().json(serde_json::json!({ "filter": body.filter }))
+}
+
+/// Simulated balances and cumulative P&L of paper trading.
+#[get("/paper")]
+async fn get_paper(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    params: web::Query<PaperParams>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    match paper::summary(&conn.lock().unwrap(), params.instance.as_deref()) {
+        Ok(summary) => HttpResponse::Ok().json(summary),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Every monitored pair with its DEXes and whether it is suspended as dead.
+#[get("/pairs")]
+async fn get_pairs(caller: Caller, detectors: web::Data<Detectors>) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    let pairs: Vec<_> = detectors
+        .iter()
+        .map(|d| {
+            let cfg = d.config();
+            serde_json::json!({
+                "instance": cfg.instance,
+                "pair": d.pair(),
+                "token_in": cfg.token_in,
+                "token_out": cfg.token_out,
+                "dexes": d.dex_names(),
+                "status": SUSPENSIONS.status(&cfg.instance, d.pair()),
             })
         })
-        .unwrap();
+        .collect();
+    HttpResponse::Ok().json(pairs)
+}
+
+/// Wallet holdings, inventory drift and realized P&L of live execution.
+/// Admin only and not served with `READ_ONLY_API`, since it exposes the
+/// execution wallet.
+#[get("/portfolio")]
+async fn get_portfolio(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    portfolio: Option<web::Data<LivePortfolio>>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Admin) {
+        return e.error_response();
+    }
+    let Some(portfolio) = portfolio else {
+        return HttpResponse::NotFound().body("Live execution is not enabled");
+    };
+    match portfolio.view(&conn).await {
+        Ok(view) => HttpResponse::Ok().json(view),
+        Err(e) => HttpResponse::InternalServerError().body(format!("{:#}", e)),
+    }
+}
+
+#[get("/strategies")]
+async fn get_strategies(caller: Caller, strategies: web::Data<Strategies>) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    HttpResponse::Ok().json(strategies.list())
+}
 
-    let data: Vec<_> = rows.map(|r| r.unwrap()).collect();
-    HttpResponse::Ok().json(data)
+/// Toggles a strategy or changes its window, e.g. `{"window": "13:00-21:00"}`.
+#[put("/strategies/{name}")]
+async fn update_strategy(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    strategies: web::Data<Strategies>,
+    path: web::Path<String>,
+    body: web::Json<StrategyBody>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Control) {
+        return e.error_response();
+    }
+    let window = match body.window.as_deref() {
+        None => None,
+        Some("") => Some(None),
+        Some(spec) => match Window::parse(spec) {
+            Ok(window) => Some(Some(window)),
+            Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+        },
+    };
+    let name = path.into_inner();
+    let Some(state) = strategies.update(&name, body.enabled, window) else {
+        return HttpResponse::NotFound().body(format!("unknown strategy {}", name));
+    };
+    log::warn!(
+        "Strategy {} is now {} (window: {})",
+        state.name,
+        if state.enabled { "enabled" } else { "disabled" },
+        state
+            .window
+            .map(|w| w.to_string())
+            .unwrap_or_else(|| "always".to_string())
+    );
+    let payload =
+        serde_json::json!({ "name": name, "enabled": body.enabled, "window": body.window });
+    if let Err(e) = audit::record(&conn.lock().unwrap(), &caller, "update_strategy", &payload) {
+        return HttpResponse::InternalServerError().body(e.to_string());
+    }
+    HttpResponse::Ok().json(state)
+}
+
+#[get("/events")]
+async fn get_events(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    clock: web::Data<dyn Clock>,
+    params: web::Query<EventParams>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    match events::load(
+        &conn.lock().unwrap(),
+        clock.now(),
+        params.hours.unwrap_or(24),
+        params.gap_secs.unwrap_or(60),
+        params.instance.as_deref(),
+    ) {
+        Ok(events) => HttpResponse::Ok().json(events),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[get("/stats/heatmap")]
+async fn get_heatmap(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    clock: web::Data<dyn Clock>,
+    params: web::Query<HeatmapParams>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    match heatmap::load(
+        &conn.lock().unwrap(),
+        clock.now(),
+        params.days.unwrap_or(28),
+        params.instance.as_deref(),
+    ) {
+        Ok(cells) => HttpResponse::Ok().json(cells),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Opportunities per day or week, e.g. `/stats?group=week&days=90`.
+#[get("/stats")]
+async fn get_summary(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    clock: web::Data<dyn Clock>,
+    params: web::Query<SummaryParams>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    match summary::load(
+        &conn.lock().unwrap(),
+        clock.now(),
+        params.group.unwrap_or_default(),
+        params.days.unwrap_or(30),
+        params.instance.as_deref(),
+    ) {
+        Ok(periods) => HttpResponse::Ok().json(periods),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[get("/stats/rollups")]
+async fn get_rollups(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    clock: web::Data<dyn Clock>,
+    params: web::Query<RollupParams>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    match maintenance::load_rollups(
+        &conn.lock().unwrap(),
+        clock.now(),
+        params.days.unwrap_or(30),
+        params.instance.as_deref(),
+    ) {
+        Ok(rollups) => HttpResponse::Ok().json(rollups),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[get("/stats/rejections")]
+async fn get_rejections(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    clock: web::Data<dyn Clock>,
+    params: web::Query<RejectionParams>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    match rejections::counts(
+        &conn.lock().unwrap(),
+        clock.now(),
+        params.hours.unwrap_or(24),
+        params.instance.as_deref(),
+    ) {
+        Ok(counts) => HttpResponse::Ok().json(counts),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[get("/stats/leadlag")]
+async fn get_leadlag(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    clock: web::Data<dyn Clock>,
+    params: web::Query<LeadLagParams>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    match leadlag::load(
+        &conn.lock().unwrap(),
+        clock.now(),
+        params.hours.unwrap_or(24),
+        params.max_lag.unwrap_or(5),
+        params.instance.as_deref(),
+    ) {
+        Ok(pairs) => HttpResponse::Ok().json(pairs),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Spread of every cycle in a window, e.g.
+/// `/spreads?pair=WETH/USDC&from=2024-05-01T00:00:00Z&to=2024-05-02T00:00:00Z`.
+#[get("/spreads")]
+async fn get_spreads(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    clock: web::Data<dyn Clock>,
+    params: web::Query<SpreadParams>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    let (from, to) = match params.window(clock.now()) {
+        Ok(window) => window,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    match spreads::load(
+        &conn.lock().unwrap(),
+        from,
+        to,
+        params.pair.as_deref(),
+        params.instance.as_deref(),
+        params
+            .limit
+            .unwrap_or(spreads::DEFAULT_LIMIT)
+            .clamp(1, spreads::MAX_LIMIT),
+    ) {
+        Ok(points) => HttpResponse::Ok().json(points),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// `/candles?pair=WETH/USDC&dex=QuickSwap&interval=5m`.
+#[get("/candles")]
+async fn get_candles(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    clock: web::Data<dyn Clock>,
+    params: web::Query<CandleParams>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    let limit = params
+        .limit
+        .unwrap_or(candles::DEFAULT_LIMIT)
+        .clamp(1, candles::MAX_LIMIT);
+    let window = params
+        .interval_secs()
+        .and_then(|secs| Ok((secs, params.window(clock.now(), secs, limit)?)));
+    let (interval_secs, (from, to)) = match window {
+        Ok(window) => window,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let conn = conn.lock().unwrap();
+    if params.instance.is_none() {
+        match candles::instances(&conn, &params.dex, &params.pair, from, to) {
+            Ok(instances) if instances.len() > 1 => {
+                return HttpResponse::BadRequest().body(format!(
+                    "{} on {} was recorded by several instances ({}); set instance",
+                    params.pair,
+                    params.dex,
+                    instances.join(", ")
+                ))
+            }
+            Ok(_) => {}
+            Err(e) => return HttpResponse::InternalServerError().body(e.to_string()),
+        }
+    }
+    match candles::load(
+        &conn,
+        &params.dex,
+        &params.pair,
+        params.instance.as_deref(),
+        interval_secs,
+        from,
+        to,
+        limit,
+    ) {
+        Ok(candles) => HttpResponse::Ok().json(candles),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+#[get("/stats/deviations")]
+async fn get_deviations(
+    caller: Caller,
+    conn: web::Data<Arc<Mutex<Connection>>>,
+    clock: web::Data<dyn Clock>,
+    params: web::Query<DeviationParams>,
+) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    match consensus::load(
+        &conn.lock().unwrap(),
+        clock.now(),
+        params.hours.unwrap_or(24),
+        params.instance.as_deref(),
+    ) {
+        Ok(deviations) => HttpResponse::Ok().json(deviations),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Runtime state of the process, currently the RPC endpoints and which one
+/// is in use.
+#[get("/state")]
+async fn get_state(caller: Caller, rpc: web::Data<FailoverClient>) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    HttpResponse::Ok().json(serde_json::json!({ "rpc": rpc.state() }))
+}
+
+/// Every detector's DEXes with the check of their router quotes against
+/// the pool reserves (`quote_check`, unset until reserves were read).
+#[get("/dexes")]
+async fn get_dexes(caller: Caller, detectors: web::Data<Detectors>) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    let dexes: Vec<_> = detectors
+        .iter()
+        .flat_map(|detector| {
+            let instance = &detector.config().instance;
+            detector.dex_names().into_iter().map(move |dex| {
+                serde_json::json!({
+                    "instance": instance,
+                    "pair": detector.pair(),
+                    "dex": dex,
+                    "quote_check": QUOTE_CHECKS.get(instance, detector.pair(), dex),
+                })
+            })
+        })
+        .collect();
+    HttpResponse::Ok().json(dexes)
+}
+
+#[get("/jobs")]
+async fn get_jobs(caller: Caller) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    HttpResponse::Ok().json(JOBS.list())
+}
+
+#[get("/metrics")]
+async fn get_metrics(caller: Caller) -> impl Responder {
+    if let Err(e) = caller.require(Scope::Read) {
+        return e.error_response();
+    }
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(METRICS.render())
 }
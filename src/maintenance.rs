@@ -0,0 +1,403 @@
+//! Scheduled housekeeping: retention pruning, downsampling, daily rollups and
+//! heartbeats
+//!
+//! Raw price samples, rejections, cycle timings and liquidity samples grow
+//! with every block or check, so rows older than `RETENTION_DAYS` are
+//! deleted. Opportunities are kept, and their per-day totals are rolled up
+//! into `daily_rollups` so long-range charts don't have to scan the raw table.
+//!
+//! With `DOWNSAMPLE_AFTER_HOURS`, price samples older than that are folded
+//! into 1-minute bars (open, high, low and close of each DEX's price in
+//! `price_bars`, and of each pair's cycle spread in `spread_bars`) before the
+//! raw rows are deleted; 1-minute bars older than
+//! `DOWNSAMPLE_HOURLY_AFTER_DAYS` are folded into 1-hour bars the same way.
+//! Bars are never pruned.
+
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Tables pruned by [`prune`], all timestamped in `timestamp`.
+const PRUNED_TABLES: &[&str] = &[
+    "price_samples",
+    "rejections",
+    "cycle_timings",
+    "liquidity_samples",
+];
+
+/// Days re-aggregated by each rollup; today's row is refreshed until the day
+/// is over, yesterday's picks up rows flushed after midnight.
+const ROLLUP_DAYS: i64 = 2;
+
+#[derive(Deserialize)]
+pub struct RollupParams {
+    /// Look-back window in days (default 30).
+    pub days: Option<u32>,
+    /// Restrict to one bot instance; all instances when unset.
+    pub instance: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DailyRollup {
+    pub day: String,
+    pub instance: String,
+    pub pair: Option<String>,
+    pub strategy: String,
+    pub opportunities: i64,
+    pub total_profit: f64,
+    pub max_profit: f64,
+    pub total_profit_usd: Option<f64>,
+}
+
+/// Deletes raw rows older than `days`; returns the number of rows removed.
+pub fn prune(conn: &Connection, days: u32, now: DateTime<Utc>) -> anyhow::Result<usize> {
+    let cutoff = (now - Duration::days(days as i64)).to_rfc3339();
+    let mut removed = 0;
+    for table in PRUNED_TABLES {
+        removed += conn.execute(
+            &format!("DELETE FROM {} WHERE timestamp < ?1", table),
+            params![cutoff],
+        )?;
+    }
+    Ok(removed)
+}
+
+/// Open, high, low and close of a series within one bar.
+#[derive(Debug, Clone, Copy)]
+struct Ohlc {
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    samples: i64,
+}
+
+impl Ohlc {
+    fn new(value: f64) -> Self {
+        Self {
+            open: value,
+            high: value,
+            low: value,
+            close: value,
+            samples: 1,
+        }
+    }
+
+    /// Extends the bar with `later`, which starts after it.
+    fn extend(&mut self, later: Ohlc) {
+        self.high = self.high.max(later.high);
+        self.low = self.low.min(later.low);
+        self.close = later.close;
+        self.samples += later.samples;
+    }
+}
+
+/// Bars keyed by (bucket, instance, pair or `''`, DEX or `''` for spreads).
+type Bars = BTreeMap<(String, String, String, String), Ohlc>;
+
+/// (min, max, DEXes) of each cycle, by bucket, instance, pair and block.
+type Cycles = BTreeMap<(String, String, String, i64), (f64, f64, usize)>;
+
+/// Bar resolution, with the SQL expression of the bucket a `timestamp` or a
+/// finer `bucket` falls in.
+#[derive(Debug, Clone, Copy)]
+enum Resolution {
+    Minute,
+    Hour,
+}
+
+impl Resolution {
+    fn label(self) -> &'static str {
+        match self {
+            Resolution::Minute => "1m",
+            Resolution::Hour => "1h",
+        }
+    }
+
+    fn bucket(self, column: &str) -> String {
+        match self {
+            Resolution::Minute => format!("substr({column}, 1, 16) || ':00+00:00'"),
+            Resolution::Hour => format!("substr({column}, 1, 13) || ':00:00+00:00'"),
+        }
+    }
+
+    /// Start of the bucket `at` falls in; only older rows are folded so no
+    /// bar is written before it is complete.
+    fn start(self, at: DateTime<Utc>) -> String {
+        match self {
+            Resolution::Minute => at.format("%Y-%m-%dT%H:%M:00+00:00").to_string(),
+            Resolution::Hour => at.format("%Y-%m-%dT%H:00:00+00:00").to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Downsampled {
+    /// Raw price samples folded into 1-minute bars and deleted.
+    pub samples: usize,
+    /// 1-minute bars folded into 1-hour bars and deleted.
+    pub minute_bars: usize,
+}
+
+/// Folds price samples older than `raw_after` into 1-minute bars and
+/// 1-minute bars older than `minute_after` into 1-hour bars, deleting what
+/// was folded. Bars that already exist are extended, so samples flushed late
+/// still count.
+pub fn downsample(
+    conn: &mut Connection,
+    now: DateTime<Utc>,
+    raw_after: Duration,
+    minute_after: Duration,
+) -> anyhow::Result<Downsampled> {
+    let tx = conn.transaction()?;
+    let raw_cutoff = Resolution::Minute.start(now - raw_after);
+    let (prices, spreads) = sample_bars(&tx, &raw_cutoff)?;
+    write_bars(&tx, "price_bars", Resolution::Minute, &prices)?;
+    write_bars(&tx, "spread_bars", Resolution::Minute, &spreads)?;
+    let samples = tx.execute(
+        "DELETE FROM price_samples WHERE timestamp < ?1",
+        params![raw_cutoff],
+    )?;
+
+    let minute_cutoff = Resolution::Hour.start(now - minute_after);
+    let mut minute_bars = 0;
+    for table in ["price_bars", "spread_bars"] {
+        let bars = hour_bars(&tx, table, &minute_cutoff)?;
+        write_bars(&tx, table, Resolution::Hour, &bars)?;
+        minute_bars += tx.execute(
+            &format!("DELETE FROM {table} WHERE resolution = ?1 AND bucket < ?2"),
+            params![Resolution::Minute.label(), minute_cutoff],
+        )?;
+    }
+    tx.commit()?;
+    Ok(Downsampled {
+        samples,
+        minute_bars,
+    })
+}
+
+/// 1-minute price and spread bars of the samples before `cutoff`. A cycle's
+/// spread is its highest over its lowest DEX price, as in `/spreads`, and
+/// only counts when at least two DEXes quoted.
+fn sample_bars(conn: &Connection, cutoff: &str) -> anyhow::Result<(Bars, Bars)> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, instance, COALESCE(pair, ''), dex, block_number, price
+         FROM price_samples WHERE timestamp < ?1 AND price > 0
+         ORDER BY timestamp, block_number, id",
+        Resolution::Minute.bucket("timestamp")
+    ))?;
+    let rows = stmt.query_map(params![cutoff], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, i64>(4)?,
+            row.get::<_, f64>(5)?,
+        ))
+    })?;
+    let mut prices = Bars::new();
+    let mut cycles = Cycles::new();
+    for row in rows {
+        let (bucket, instance, pair, dex, block, price) = row?;
+        let cycle = cycles
+            .entry((bucket.clone(), instance.clone(), pair.clone(), block))
+            .or_insert((price, price, 0));
+        *cycle = (cycle.0.min(price), cycle.1.max(price), cycle.2 + 1);
+        prices
+            .entry((bucket, instance, pair, dex))
+            .and_modify(|bar| bar.extend(Ohlc::new(price)))
+            .or_insert_with(|| Ohlc::new(price));
+    }
+
+    let mut spreads = Bars::new();
+    for ((bucket, instance, pair, _), (min, max, dexes)) in cycles {
+        if dexes < 2 {
+            continue;
+        }
+        let spread = Ohlc::new((max - min) / min * 10_000.0);
+        spreads
+            .entry((bucket, instance, pair, String::new()))
+            .and_modify(|bar| bar.extend(spread))
+            .or_insert(spread);
+    }
+    Ok((prices, spreads))
+}
+
+/// 1-hour bars of the 1-minute bars of `table` before `cutoff`.
+fn hour_bars(conn: &Connection, table: &str, cutoff: &str) -> anyhow::Result<Bars> {
+    let dex = if table == "price_bars" { "dex" } else { "''" };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, instance, pair, {dex}, open, high, low, close, samples
+         FROM {table} WHERE resolution = ?1 AND bucket < ?2
+         ORDER BY bucket",
+        Resolution::Hour.bucket("bucket")
+    ))?;
+    let rows = stmt.query_map(params![Resolution::Minute.label(), cutoff], |row| {
+        Ok((
+            (
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ),
+            Ohlc {
+                open: row.get(4)?,
+                high: row.get(5)?,
+                low: row.get(6)?,
+                close: row.get(7)?,
+                samples: row.get(8)?,
+            },
+        ))
+    })?;
+    let mut bars = Bars::new();
+    for row in rows {
+        let (key, bar) = row?;
+        bars.entry(key)
+            .and_modify(|hour| hour.extend(bar))
+            .or_insert(bar);
+    }
+    Ok(bars)
+}
+
+/// Upserts `bars` into `table`; an existing bar is extended by the new one.
+fn write_bars(
+    conn: &Connection,
+    table: &str,
+    resolution: Resolution,
+    bars: &Bars,
+) -> anyhow::Result<()> {
+    let (dex_column, dex_value) = if table == "price_bars" {
+        (", dex", ", ?5")
+    } else {
+        ("", "")
+    };
+    let mut stmt = conn.prepare(&format!(
+        "INSERT INTO {table} (resolution, bucket, instance, pair{dex_column}, open, high, low, close, samples)
+         VALUES (?1, ?2, ?3, ?4{dex_value}, ?6, ?7, ?8, ?9, ?10)
+         ON CONFLICT (resolution, bucket, instance, pair{dex_column}) DO UPDATE SET
+            high = MAX(high, excluded.high),
+            low = MIN(low, excluded.low),
+            close = excluded.close,
+            samples = samples + excluded.samples"
+    ))?;
+    for ((bucket, instance, pair, dex), bar) in bars {
+        let dex = (!dex.is_empty()).then_some(dex);
+        stmt.execute(params![
+            resolution.label(),
+            bucket,
+            instance,
+            pair,
+            dex,
+            bar.open,
+            bar.high,
+            bar.low,
+            bar.close,
+            bar.samples
+        ])?;
+    }
+    Ok(())
+}
+
+/// Recomputes the rollups of the last [`ROLLUP_DAYS`] days; returns the
+/// number of rows written.
+pub fn rollup(conn: &Connection, now: DateTime<Utc>) -> anyhow::Result<usize> {
+    let since = (now - Duration::days(ROLLUP_DAYS - 1))
+        .format("%Y-%m-%d")
+        .to_string();
+    Ok(conn.execute(
+        "INSERT OR REPLACE INTO daily_rollups
+            (day, instance, pair, strategy, opportunities, total_profit, max_profit, total_profit_usd)
+         SELECT substr(timestamp, 1, 10) AS day, instance, COALESCE(pair, ''), strategy,
+                COUNT(*), SUM(profit), MAX(profit), SUM(profit_usd)
+         FROM opportunities WHERE timestamp >= ?1
+         GROUP BY day, instance, COALESCE(pair, ''), strategy",
+        params![since],
+    )?)
+}
+
+/// Daily rollups of the last `days` days, oldest first.
+pub fn load_rollups(
+    conn: &Connection,
+    now: DateTime<Utc>,
+    days: u32,
+    instance: Option<&str>,
+) -> anyhow::Result<Vec<DailyRollup>> {
+    let since = (now - Duration::days(days as i64))
+        .format("%Y-%m-%d")
+        .to_string();
+    let mut stmt = conn.prepare(
+        "SELECT day, instance, pair, strategy, opportunities, total_profit, max_profit,
+                total_profit_usd
+         FROM daily_rollups WHERE day >= ?1 AND (?2 IS NULL OR instance = ?2)
+         ORDER BY day, instance, pair, strategy",
+    )?;
+    let rows = stmt
+        .query_map(params![since, instance], |row| {
+            Ok(DailyRollup {
+                day: row.get(0)?,
+                instance: row.get(1)?,
+                pair: row
+                    .get::<_, String>(2)
+                    .map(|p| (!p.is_empty()).then_some(p))?,
+                strategy: row.get(3)?,
+                opportunities: row.get(4)?,
+                total_profit: row.get(5)?,
+                max_profit: row.get(6)?,
+                total_profit_usd: row.get(7)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Pings an uptime monitor (healthchecks.io style `GET`).
+pub async fn heartbeat(client: &reqwest::Client, url: &str) -> anyhow::Result<()> {
+    client.get(url).send().await?.error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, MockClock};
+    use crate::db::{init_db, insert_price_samples, PriceSample};
+    use chrono::TimeZone;
+
+    fn sample() -> PriceSample {
+        PriceSample {
+            dex: "QuickSwap".to_string(),
+            price: 1.0,
+            deviation_bps: None,
+        }
+    }
+
+    fn count(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM price_samples", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn prunes_rows_once_they_age_past_retention() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap());
+        insert_price_samples(&conn, "main", None, 1, &[sample()], clock.now()).unwrap();
+        clock.advance(std::time::Duration::from_secs(2 * 86_400));
+        insert_price_samples(&conn, "main", None, 2, &[sample()], clock.now()).unwrap();
+
+        clock.advance(std::time::Duration::from_secs(86_400));
+        assert_eq!(prune(&conn, 7, clock.now()).unwrap(), 0);
+        assert_eq!(count(&conn), 2);
+
+        // Day 8: only the first sample is more than a week old.
+        clock.advance(std::time::Duration::from_secs(5 * 86_400));
+        assert_eq!(prune(&conn, 7, clock.now()).unwrap(), 1);
+        assert_eq!(count(&conn), 1);
+
+        clock.advance(std::time::Duration::from_secs(2 * 86_400));
+        assert_eq!(prune(&conn, 7, clock.now()).unwrap(), 1);
+        assert_eq!(count(&conn), 0);
+    }
+}
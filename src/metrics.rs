@@ -0,0 +1,109 @@
+//! Process-wide counters, rendered in Prometheus text format at `/metrics`
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub struct Metrics {
+    pub cycles_total: AtomicU64,
+    pub cycles_failed: AtomicU64,
+    /// Cycles dropped because their quotes were older than `MAX_QUOTE_AGE_SECS`.
+    pub stale_cycles: AtomicU64,
+    /// Quotes skipped because the per-block RPC budget was exhausted.
+    pub quotes_over_budget: AtomicU64,
+    /// V3 fee-tier quotes left out between full sweeps (see `dex::v3::TierCache`).
+    pub v3_tiers_skipped: AtomicU64,
+    /// RPC requests sent again after a transient failure (see `rpc::RetryPolicy`).
+    pub rpc_retries: AtomicU64,
+    /// Background tasks restarted by the supervisor after a panic.
+    pub task_panics: AtomicU64,
+    pub db_write_failures: AtomicU64,
+    /// Queued rows dropped because the database stayed down past the buffer.
+    pub db_rows_dropped: AtomicU64,
+}
+
+pub static METRICS: Metrics = Metrics {
+    cycles_total: AtomicU64::new(0),
+    cycles_failed: AtomicU64::new(0),
+    stale_cycles: AtomicU64::new(0),
+    quotes_over_budget: AtomicU64::new(0),
+    v3_tiers_skipped: AtomicU64::new(0),
+    rpc_retries: AtomicU64::new(0),
+    task_panics: AtomicU64::new(0),
+    db_write_failures: AtomicU64::new(0),
+    db_rows_dropped: AtomicU64::new(0),
+};
+
+pub fn inc(counter: &AtomicU64) {
+    add(counter, 1);
+}
+
+pub fn add(counter: &AtomicU64, n: u64) {
+    counter.fetch_add(n, Ordering::Relaxed);
+}
+
+impl Metrics {
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        counter(
+            &mut out,
+            "arb_cycles_total",
+            "Detection cycles run",
+            &self.cycles_total,
+        );
+        counter(
+            &mut out,
+            "arb_cycles_failed_total",
+            "Detection cycles that returned an error",
+            &self.cycles_failed,
+        );
+        counter(
+            &mut out,
+            "arb_stale_cycles_total",
+            "Detection cycles discarded because quotes were too old",
+            &self.stale_cycles,
+        );
+        counter(
+            &mut out,
+            "arb_quotes_over_budget_total",
+            "Quotes skipped because the per-block RPC budget was exhausted",
+            &self.quotes_over_budget,
+        );
+        counter(
+            &mut out,
+            "arb_v3_tiers_skipped_total",
+            "V3 fee-tier quotes left out because another tier quoted best",
+            &self.v3_tiers_skipped,
+        );
+        counter(
+            &mut out,
+            "arb_rpc_retries_total",
+            "RPC requests retried after a transient failure",
+            &self.rpc_retries,
+        );
+        counter(
+            &mut out,
+            "arb_task_panics_total",
+            "Background tasks restarted after a panic",
+            &self.task_panics,
+        );
+        counter(
+            &mut out,
+            "arb_db_write_failures_total",
+            "Batched database writes that failed and were kept for retry",
+            &self.db_write_failures,
+        );
+        counter(
+            &mut out,
+            "arb_db_rows_dropped_total",
+            "Buffered rows dropped because the database stayed unavailable",
+            &self.db_rows_dropped,
+        );
+        out
+    }
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: &AtomicU64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+}
@@ -0,0 +1,227 @@
+//! Copy of the SQLite database into Postgres
+//!
+//! `polygon_arb_bot db migrate --to postgres://…` creates every table of the
+//! local database in the target and copies its rows, one transaction per
+//! table. Column types follow the SQLite declarations (INTEGER → BIGINT,
+//! REAL → DOUBLE PRECISION, BLOB → BYTEA, anything else → TEXT). Target tables
+//! must be empty, and row counts are compared afterwards; any mismatch fails
+//! the command. The connection is made without TLS, so add
+//! `sslmode=disable` or tunnel to servers that require it.
+
+use anyhow::Context;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::NoTls;
+
+/// Rows read from SQLite per round trip.
+const CHUNK: i64 = 5_000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum PgType {
+    BigInt,
+    Double,
+    Bytea,
+    Text,
+}
+
+impl PgType {
+    /// SQLite type affinity rules, reduced to the types the schema uses.
+    fn from_sqlite(decl: &str) -> Self {
+        let decl = decl.to_ascii_uppercase();
+        if decl.contains("INT") {
+            PgType::BigInt
+        } else if decl.contains("REAL") || decl.contains("FLOA") || decl.contains("DOUB") {
+            PgType::Double
+        } else if decl.contains("BLOB") {
+            PgType::Bytea
+        } else {
+            PgType::Text
+        }
+    }
+
+    fn sql(self) -> &'static str {
+        match self {
+            PgType::BigInt => "BIGINT",
+            PgType::Double => "DOUBLE PRECISION",
+            PgType::Bytea => "BYTEA",
+            PgType::Text => "TEXT",
+        }
+    }
+
+    /// Converts a SQLite value to this column type, coercing where SQLite's
+    /// dynamic typing stored something else.
+    fn convert(self, value: Value) -> anyhow::Result<Box<dyn ToSql + Sync + Send>> {
+        Ok(match (self, value) {
+            (PgType::BigInt, Value::Null) => Box::new(None::<i64>),
+            (PgType::Double, Value::Null) => Box::new(None::<f64>),
+            (PgType::Bytea, Value::Null) => Box::new(None::<Vec<u8>>),
+            (PgType::Text, Value::Null) => Box::new(None::<String>),
+            (PgType::BigInt, Value::Integer(v)) => Box::new(v),
+            (PgType::BigInt, Value::Real(v)) => Box::new(v as i64),
+            (PgType::BigInt, Value::Text(v)) => Box::new(v.trim().parse::<i64>()?),
+            (PgType::Double, Value::Integer(v)) => Box::new(v as f64),
+            (PgType::Double, Value::Real(v)) => Box::new(v),
+            (PgType::Double, Value::Text(v)) => Box::new(v.trim().parse::<f64>()?),
+            (PgType::Bytea, Value::Blob(v)) => Box::new(v),
+            (PgType::Bytea, Value::Text(v)) => Box::new(v.into_bytes()),
+            (PgType::Text, Value::Integer(v)) => Box::new(v.to_string()),
+            (PgType::Text, Value::Real(v)) => Box::new(v.to_string()),
+            (PgType::Text, Value::Text(v)) => Box::new(v),
+            (PgType::Text, Value::Blob(v)) => Box::new(String::from_utf8(v)?),
+            (ty, value) => anyhow::bail!("cannot store {:?} as {}", value, ty.sql()),
+        })
+    }
+}
+
+struct Column {
+    name: String,
+    ty: PgType,
+    /// Position in the primary key (1-based), 0 when not part of it.
+    pk: i64,
+}
+
+/// Rows copied per table.
+pub struct TableReport {
+    pub table: String,
+    pub rows: i64,
+}
+
+pub async fn to_postgres(sqlite: &Connection, url: &str) -> anyhow::Result<Vec<TableReport>> {
+    let (mut client, connection) = tokio_postgres::connect(url, NoTls)
+        .await
+        .context("Failed to connect to Postgres")?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            log::error!("Postgres connection error: {}", e);
+        }
+    });
+
+    let mut reports = Vec::new();
+    for table in tables(sqlite)? {
+        let columns = columns(sqlite, &table)?;
+        client
+            .batch_execute(&create_table(&table, &columns))
+            .await
+            .with_context(|| format!("Failed to create {}", table))?;
+        let existing: i64 = client
+            .query_one(format!("SELECT COUNT(*) FROM {}", table).as_str(), &[])
+            .await?
+            .get(0);
+        if existing > 0 {
+            anyhow::bail!("{} already has {} rows in the target", table, existing);
+        }
+
+        let names: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
+        let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("${}", i)).collect();
+        let tx = client.transaction().await?;
+        let insert = tx
+            .prepare(&format!(
+                "INSERT INTO {} ({}) VALUES ({})",
+                table,
+                names.join(", "),
+                placeholders.join(", ")
+            ))
+            .await?;
+        let mut select = sqlite.prepare(&format!(
+            "SELECT rowid, {} FROM {} WHERE rowid > ?1 ORDER BY rowid LIMIT ?2",
+            names.join(", "),
+            table
+        ))?;
+        let mut last_rowid = 0i64;
+        loop {
+            let rows = select
+                .query_map([last_rowid, CHUNK], |row| {
+                    let rowid: i64 = row.get(0)?;
+                    let values = (1..=columns.len())
+                        .map(|i| row.get::<_, Value>(i))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok((rowid, values))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            let Some(&(rowid, _)) = rows.last() else {
+                break;
+            };
+            last_rowid = rowid;
+            for (_, values) in rows {
+                let params = columns
+                    .iter()
+                    .zip(values)
+                    .map(|(column, value)| {
+                        column
+                            .ty
+                            .convert(value)
+                            .with_context(|| format!("{}.{}", table, column.name))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                let refs: Vec<&(dyn ToSql + Sync)> = params
+                    .iter()
+                    .map(|p| p.as_ref() as &(dyn ToSql + Sync))
+                    .collect();
+                tx.execute(&insert, &refs).await?;
+            }
+        }
+        tx.commit().await?;
+
+        let source: i64 =
+            sqlite.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                row.get(0)
+            })?;
+        let copied: i64 = client
+            .query_one(format!("SELECT COUNT(*) FROM {}", table).as_str(), &[])
+            .await?
+            .get(0);
+        if source != copied {
+            anyhow::bail!(
+                "{}: {} rows in SQLite but {} in Postgres",
+                table,
+                source,
+                copied
+            );
+        }
+        reports.push(TableReport {
+            table,
+            rows: copied,
+        });
+    }
+    Ok(reports)
+}
+
+fn tables(conn: &Connection) -> anyhow::Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'
+         ORDER BY name",
+    )?;
+    let tables = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(tables)
+}
+
+fn columns(conn: &Connection, table: &str) -> anyhow::Result<Vec<Column>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = stmt
+        .query_map([], |row| {
+            Ok(Column {
+                name: row.get(1)?,
+                ty: PgType::from_sqlite(&row.get::<_, String>(2)?),
+                pk: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(columns)
+}
+
+fn create_table(table: &str, columns: &[Column]) -> String {
+    let mut defs: Vec<String> = columns
+        .iter()
+        .map(|c| format!("{} {}", c.name, c.ty.sql()))
+        .collect();
+    let mut pk: Vec<&Column> = columns.iter().filter(|c| c.pk > 0).collect();
+    pk.sort_by_key(|c| c.pk);
+    if !pk.is_empty() {
+        let names: Vec<&str> = pk.iter().map(|c| c.name.as_str()).collect();
+        defs.push(format!("PRIMARY KEY ({})", names.join(", ")));
+    }
+    format!("CREATE TABLE IF NOT EXISTS {} ({})", table, defs.join(", "))
+}
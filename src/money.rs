@@ -0,0 +1,185 @@
+//! Exact signed token amounts
+//!
+//! A [`Money`] is a raw `I256` count of a token's smallest unit together with
+//! the token's decimals, so round-trip profits are computed on the quoted
+//! integers instead of on `f64`s that have already lost the low digits of an
+//! 18-decimal amount. Conversion to `f64` only happens for ratios and USD
+//! pricing; stored and served profits keep the exact decimal string.
+
+use crate::units;
+use ethers::types::{I256, U256};
+use serde::{Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Neg, Sub};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Money {
+    raw: I256,
+    decimals: u32,
+}
+
+impl Money {
+    pub fn zero(decimals: u32) -> Self {
+        Self {
+            raw: I256::zero(),
+            decimals,
+        }
+    }
+
+    /// `value` raw units of a token with `decimals`.
+    pub fn from_units(value: U256, decimals: u32) -> Self {
+        Self {
+            raw: I256::from_raw(value),
+            decimals,
+        }
+    }
+
+    /// Parses a signed decimal such as `-0.25`; see [`units::parse_units`].
+    pub fn parse(amount: &str, decimals: u32) -> anyhow::Result<Self> {
+        let amount = amount.trim();
+        let (negative, digits) = match amount.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, amount),
+        };
+        let value = Self::from_units(units::parse_units(digits, decimals)?, decimals);
+        Ok(if negative { -value } else { value })
+    }
+
+    /// Nearest amount to `value`, for config values and prices that are
+    /// only known as `f64`.
+    pub fn from_f64(value: f64, decimals: u32) -> Self {
+        Self::parse(&format!("{:.*}", decimals as usize, value), decimals)
+            .unwrap_or_else(|_| Self::zero(decimals))
+    }
+
+    /// Value of this token_in amount at `amount_out` per `amount_in` raw
+    /// units, in token_out with `decimals_out`. Truncates towards zero; a
+    /// zero `amount_in` gives zero.
+    pub fn convert(self, amount_out: U256, amount_in: U256, decimals_out: u32) -> Self {
+        if amount_in.is_zero() {
+            return Self::zero(decimals_out);
+        }
+        Self {
+            raw: self.raw * I256::from_raw(amount_out) / I256::from_raw(amount_in),
+            decimals: decimals_out,
+        }
+    }
+
+    /// Same value with `decimals`, truncating digits that no longer fit.
+    pub fn rescale(self, decimals: u32) -> Self {
+        let raw = match decimals.cmp(&self.decimals) {
+            Ordering::Equal => self.raw,
+            Ordering::Greater => self.raw * pow10(decimals - self.decimals),
+            Ordering::Less => self.raw / pow10(self.decimals - decimals),
+        };
+        Self { raw, decimals }
+    }
+
+    pub fn decimals(&self) -> u32 {
+        self.decimals
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.raw.is_positive()
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let value = units::to_f64(self.raw.unsigned_abs(), self.decimals);
+        if self.raw.is_negative() {
+            -value
+        } else {
+            value
+        }
+    }
+
+    /// Both operands at the larger of their decimals.
+    fn aligned(self, other: Self) -> (I256, I256, u32) {
+        let decimals = self.decimals.max(other.decimals);
+        (
+            self.rescale(decimals).raw,
+            other.rescale(decimals).raw,
+            decimals,
+        )
+    }
+}
+
+fn pow10(exp: u32) -> I256 {
+    I256::from_raw(U256::exp10(exp as usize))
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        let (a, b, decimals) = self.aligned(other);
+        Money {
+            raw: a + b,
+            decimals,
+        }
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Money) -> Money {
+        let (a, b, decimals) = self.aligned(other);
+        Money {
+            raw: a - b,
+            decimals,
+        }
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money {
+            raw: -self.raw,
+            decimals: self.decimals,
+        }
+    }
+}
+
+impl PartialEq for Money {
+    fn eq(&self, other: &Money) -> bool {
+        let (a, b, _) = self.aligned(*other);
+        a == b
+    }
+}
+
+impl Eq for Money {}
+
+impl PartialOrd for Money {
+    fn partial_cmp(&self, other: &Money) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Money {
+    fn cmp(&self, other: &Money) -> Ordering {
+        let (a, b, _) = self.aligned(*other);
+        a.cmp(&b)
+    }
+}
+
+/// Exact decimal without trailing zeros, e.g. `-0.000000000000000001`.
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = units::format_units(self.raw.unsigned_abs(), self.decimals);
+        if self.raw.is_negative() {
+            write!(f, "-{}", digits)
+        } else {
+            f.write_str(&digits)
+        }
+    }
+}
+
+/// Serialized as the exact decimal string; JSON numbers would round it.
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
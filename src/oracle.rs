@@ -0,0 +1,102 @@
+//! Chainlink sanity check of DEX prices
+//!
+//! With `ORACLE_CHECK` every two-DEX opportunity's prices are compared with
+//! the rate the Chainlink USD feeds of its tokens imply (token_in/USD over
+//! token_out/USD). The feeds of the well-known tokens are built in (MATIC/USD
+//! for WPOL); `ORACLE_FEEDS` adds or replaces them. The leg furthest from the
+//! oracle is stored as `oracle_deviation`, and one beyond
+//! `ORACLE_MAX_DEVIATION_BPS` is flagged in the log: a pool that far off is
+//! more likely stale or trading a different token than a real opportunity.
+//! Feeds older than `USD_FEED_MAX_AGE_SECS` are not used.
+
+use crate::contracts::AggregatorV3;
+use crate::tokens;
+use crate::usd;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use ethers::providers::Middleware;
+use ethers::types::Address;
+use std::sync::Arc;
+
+struct Feed<M> {
+    aggregator: AggregatorV3<M>,
+    decimals: u8,
+}
+
+impl<M: Middleware + 'static> Feed<M> {
+    async fn load(client: &Arc<M>, address: Address) -> anyhow::Result<Self> {
+        let aggregator = AggregatorV3::new(address, Arc::clone(client));
+        let decimals = aggregator
+            .decimals()
+            .call()
+            .await
+            .with_context(|| format!("Failed to load Chainlink feed {:?}", address))?;
+        Ok(Self {
+            aggregator,
+            decimals,
+        })
+    }
+}
+
+/// The USD feeds of one pair's tokens.
+pub struct Oracle<M> {
+    token_in: Feed<M>,
+    token_out: Feed<M>,
+    max_age_secs: i64,
+}
+
+/// `token`'s feed: from `overrides` (token, feed), else the built-in one.
+pub fn feed_of(token: Address, overrides: &[(Address, Address)]) -> Option<Address> {
+    overrides
+        .iter()
+        .find(|(t, _)| *t == token)
+        .map(|(_, feed)| *feed)
+        .or_else(|| tokens::by_address(token).and_then(|t| t.usd_feed()))
+}
+
+impl<M: Middleware + 'static> Oracle<M> {
+    /// `None` when either token has no known feed.
+    pub async fn new(
+        client: Arc<M>,
+        token_in: Address,
+        token_out: Address,
+        overrides: &[(Address, Address)],
+        max_age_secs: u64,
+    ) -> anyhow::Result<Option<Self>> {
+        let (Some(feed_in), Some(feed_out)) =
+            (feed_of(token_in, overrides), feed_of(token_out, overrides))
+        else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            token_in: Feed::load(&client, feed_in).await?,
+            token_out: Feed::load(&client, feed_out).await?,
+            max_age_secs: max_age_secs as i64,
+        }))
+    }
+
+    /// token_out per token_in as the feeds price them.
+    pub async fn price(&self, now: DateTime<Utc>) -> anyhow::Result<f64> {
+        let (usd_in, usd_out) = futures::future::try_join(
+            usd::read_feed(
+                &self.token_in.aggregator,
+                self.token_in.decimals,
+                now,
+                self.max_age_secs,
+            ),
+            usd::read_feed(
+                &self.token_out.aggregator,
+                self.token_out.decimals,
+                now,
+                self.max_age_secs,
+            ),
+        )
+        .await?;
+        Ok(usd_in / usd_out)
+    }
+}
+
+/// `dex` price relative to the `oracle` price, in basis points.
+pub fn deviation_bps(dex: f64, oracle: f64) -> f64 {
+    (dex - oracle) / oracle * 10_000.0
+}
@@ -0,0 +1,219 @@
+//! Paper trading
+//!
+//! With `EXECUTION_MODE=paper` every two-DEX opportunity is "executed"
+//! against simulated balances kept in SQLite instead of on chain. Each
+//! instance starts with `PAPER_START_BALANCE` of `token_in`; a fill sells the
+//! trade size at the quote less `PAPER_SLIPPAGE_BPS`, buys back at the
+//! scaled quote less the same slippage and pays the cycle's gas estimate in
+//! `token_out`. Fills the balance cannot cover are skipped. Every fill is
+//! stored in `paper_trades` with its P&L and the running total, which
+//! `/api/paper` serves to the dashboard.
+
+use crate::money::Money;
+use chrono::{DateTime, Utc};
+use ethers::types::{Address, U256};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+/// Points of the cumulative P&L series served at most.
+const SERIES_POINTS: usize = 500;
+
+/// A detected round trip to fill on paper.
+pub struct PaperOrder {
+    pub instance: String,
+    pub pair: Option<String>,
+    pub cycle_id: Option<String>,
+    pub dex_sell: String,
+    pub dex_buy: String,
+    pub token_in: Address,
+    pub token_out: Address,
+    pub decimals_in: u32,
+    pub decimals_out: u32,
+    pub amount_in: U256,
+    /// Quoted token_out of the selling leg.
+    pub sell_quote: U256,
+    /// Quoted token_in bought back with `sell_quote`.
+    pub back_quote: U256,
+    /// Gas of the round trip, in token_out.
+    pub gas: Money,
+}
+
+/// A simulated fill.
+#[derive(Debug, Clone, Copy)]
+pub struct PaperFill {
+    pub amount_back: Money,
+    /// Net P&L in token_out, gas included.
+    pub pnl: Money,
+    pub cumulative_pnl: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PaperBalance {
+    pub instance: String,
+    pub token: String,
+    pub balance: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PnlPoint {
+    pub timestamp: String,
+    pub cumulative_pnl: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PaperSummary {
+    pub trades: i64,
+    pub pnl: f64,
+    pub balances: Vec<PaperBalance>,
+    /// Running P&L after each of the latest fills, oldest first.
+    pub series: Vec<PnlPoint>,
+}
+
+fn less_slippage(amount: U256, slippage_bps: u32) -> U256 {
+    amount * U256::from(10_000u32.saturating_sub(slippage_bps)) / U256::from(10_000u32)
+}
+
+fn balance(
+    conn: &Connection,
+    instance: &str,
+    token: Address,
+    decimals: u32,
+) -> anyhow::Result<Option<Money>> {
+    let stored: Option<String> = conn
+        .query_row(
+            "SELECT balance FROM paper_balances WHERE instance = ?1 AND token = ?2",
+            params![instance, format!("{:?}", token)],
+            |row| row.get(0),
+        )
+        .optional()?;
+    stored.map(|b| Money::parse(&b, decimals)).transpose()
+}
+
+fn set_balance(
+    conn: &Connection,
+    instance: &str,
+    token: Address,
+    balance: Money,
+) -> anyhow::Result<()> {
+    conn.execute(
+        "INSERT INTO paper_balances (instance, token, balance) VALUES (?1, ?2, ?3)
+         ON CONFLICT(instance, token) DO UPDATE SET balance = excluded.balance",
+        params![instance, format!("{:?}", token), balance.to_string()],
+    )?;
+    Ok(())
+}
+
+/// Fills `order` against the instance's simulated balances, seeding
+/// `token_in` with `start_balance` on first use. `None` when the balance
+/// cannot cover the trade.
+pub fn fill(
+    conn: &mut Connection,
+    now: DateTime<Utc>,
+    order: &PaperOrder,
+    start_balance: U256,
+    slippage_bps: u32,
+) -> anyhow::Result<Option<PaperFill>> {
+    let tx = conn.transaction()?;
+    let (instance, decimals_in, decimals_out) = (
+        order.instance.as_str(),
+        order.decimals_in,
+        order.decimals_out,
+    );
+    let balance_in = balance(&tx, instance, order.token_in, decimals_in)?
+        .unwrap_or_else(|| Money::from_units(start_balance, decimals_in));
+    let balance_out = balance(&tx, instance, order.token_out, decimals_out)?
+        .unwrap_or_else(|| Money::zero(decimals_out));
+    let amount_in = Money::from_units(order.amount_in, decimals_in);
+    if balance_in < amount_in || order.sell_quote.is_zero() {
+        return Ok(None);
+    }
+
+    let sell_out = less_slippage(order.sell_quote, slippage_bps);
+    let back = less_slippage(order.back_quote * sell_out / order.sell_quote, slippage_bps);
+    let amount_back = Money::from_units(back, decimals_in);
+    let gain = amount_back - amount_in;
+    let pnl = gain.convert(sell_out, order.amount_in, decimals_out) - order.gas;
+    set_balance(&tx, instance, order.token_in, balance_in + gain)?;
+    set_balance(&tx, instance, order.token_out, balance_out - order.gas)?;
+
+    let previous: f64 = tx.query_row(
+        "SELECT COALESCE(SUM(pnl), 0) FROM paper_trades WHERE instance = ?1",
+        params![instance],
+        |row| row.get(0),
+    )?;
+    let cumulative_pnl = previous + pnl.to_f64();
+    tx.execute(
+        "INSERT INTO paper_trades (timestamp, instance, pair, cycle_id, dex_sell, dex_buy, amount_in, amount_back, gas, pnl, cumulative_pnl)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            now.to_rfc3339(),
+            instance,
+            order.pair,
+            order.cycle_id,
+            order.dex_sell,
+            order.dex_buy,
+            amount_in.to_string(),
+            amount_back.to_string(),
+            order.gas.to_string(),
+            pnl.to_f64(),
+            cumulative_pnl
+        ],
+    )?;
+    tx.commit()?;
+    Ok(Some(PaperFill {
+        amount_back,
+        pnl,
+        cumulative_pnl,
+    }))
+}
+
+/// Balances, trade count and P&L of one instance, or of all of them.
+pub fn summary(conn: &Connection, instance: Option<&str>) -> anyhow::Result<PaperSummary> {
+    let (trades, pnl) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(pnl), 0) FROM paper_trades WHERE ?1 IS NULL OR instance = ?1",
+        params![instance],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let balances = conn
+        .prepare(
+            "SELECT instance, token, balance FROM paper_balances
+             WHERE ?1 IS NULL OR instance = ?1 ORDER BY instance, token",
+        )?
+        .query_map(params![instance], |row| {
+            Ok(PaperBalance {
+                instance: row.get(0)?,
+                token: row.get(1)?,
+                balance: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    // Across instances the running total is summed here rather than read
+    // from the per-instance column.
+    let mut series = conn
+        .prepare(
+            "SELECT timestamp, pnl FROM paper_trades WHERE ?1 IS NULL OR instance = ?1
+             ORDER BY id DESC LIMIT ?2",
+        )?
+        .query_map(params![instance, SERIES_POINTS as i64], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    series.reverse();
+    let mut running = pnl - series.iter().map(|(_, p)| p).sum::<f64>();
+    let series = series
+        .into_iter()
+        .map(|(timestamp, p)| {
+            running += p;
+            PnlPoint {
+                timestamp,
+                cumulative_pnl: running,
+            }
+        })
+        .collect();
+    Ok(PaperSummary {
+        trades,
+        pnl,
+        balances,
+        series,
+    })
+}
@@ -0,0 +1,316 @@
+//! Per-block quote planning
+//!
+//! Each cycle expands the (pair, dex, trade size) matrix into quote requests.
+//! The planner packs them into as few Multicall3 `aggregate3` batches as the
+//! batch size allows, sends the batches concurrently and refuses to spend more
+//! than the configured number of RPC calls on a single block.
+//!
+//! When several pairs or instances run, a shared [`Coalescer`] merges the
+//! batches every detector sends for the same block during a short window
+//! (`MULTICALL_COALESCE_MS`) into shared `aggregate3` calls, so one block costs
+//! one eth_call for all of them instead of one per detector.
+
+use crate::contracts::Multicall3;
+use crate::dex::{self, BlockRef, DexQuoter, Quote};
+use crate::metrics::{self, METRICS};
+use crate::rpc;
+use ethers::providers::Middleware;
+use ethers::types::{Address, BlockId, BlockNumber, Bytes, U256};
+use futures::future::join_all;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Multicall3 is deployed at the same address on every EVM chain.
+pub const MULTICALL3: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+
+type Call3 = (Address, bool, Bytes);
+type Returned = Vec<(bool, Bytes)>;
+type Coalesced = (Result<Returned, String>, Vec<String>);
+
+async fn aggregate3<M: Middleware + 'static>(
+    multicall: &Multicall3<M>,
+    calls: Vec<Call3>,
+    block: u64,
+) -> anyhow::Result<Returned> {
+    let at = BlockId::Number(BlockNumber::Number(block.into()));
+    Ok(multicall
+        .method::<_, Returned>("aggregate3", calls)?
+        .block(at)
+        .call()
+        .await?)
+}
+
+/// Calls queued for one block, waiting to be sent together.
+struct PendingCalls {
+    /// Tells the detector that opened the window whether it still owns it.
+    id: u64,
+    calls: Vec<Call3>,
+    /// (offset into `calls`, number of calls, where their results and the
+    /// RPC hosts that served them go)
+    waiters: Vec<(usize, usize, oneshot::Sender<Coalesced>)>,
+}
+
+/// Merges `aggregate3` calls from several detectors quoting the same block.
+pub struct Coalescer<M> {
+    multicall: Multicall3<M>,
+    window: Duration,
+    max_calls: usize,
+    pending: Mutex<(u64, HashMap<u64, PendingCalls>)>,
+}
+
+impl<M: Middleware + 'static> Coalescer<M> {
+    pub fn new(client: Arc<M>, window_ms: u64, max_calls: usize) -> anyhow::Result<Self> {
+        Ok(Self {
+            multicall: Multicall3::new(MULTICALL3.parse::<Address>()?, client),
+            window: Duration::from_millis(window_ms),
+            max_calls: max_calls.max(1),
+            pending: Mutex::new((0, HashMap::new())),
+        })
+    }
+
+    /// Queues `calls` for `block` and returns their results once the shared
+    /// batch was sent. The first caller for a block waits out the window and
+    /// sends the batch; a batch reaching `max_calls` is sent at once.
+    async fn call(&self, calls: Vec<Call3>, block: u64) -> anyhow::Result<Returned> {
+        let (tx, rx) = oneshot::channel();
+        let (opened, full) = {
+            let mut guard = self.pending.lock().unwrap();
+            let (next_id, pending) = &mut *guard;
+            let mut opened = None;
+            let batch = pending.entry(block).or_insert_with(|| {
+                *next_id += 1;
+                opened = Some(*next_id);
+                PendingCalls {
+                    id: *next_id,
+                    calls: Vec::new(),
+                    waiters: Vec::new(),
+                }
+            });
+            batch.waiters.push((batch.calls.len(), calls.len(), tx));
+            batch.calls.extend(calls);
+            let full = if batch.calls.len() >= self.max_calls {
+                pending.remove(&block)
+            } else {
+                None
+            };
+            (opened, full)
+        };
+
+        if let Some(batch) = full {
+            self.send(batch, block).await;
+        } else if let Some(id) = opened {
+            tokio::time::sleep(self.window).await;
+            let batch = {
+                let mut guard = self.pending.lock().unwrap();
+                match guard.1.get(&block) {
+                    Some(batch) if batch.id == id => guard.1.remove(&block),
+                    _ => None,
+                }
+            };
+            if let Some(batch) = batch {
+                self.send(batch, block).await;
+            }
+        }
+
+        match rx.await {
+            Ok((result, served)) => {
+                // Sent from whichever detector's task sent the batch.
+                for host in &served {
+                    rpc::note_served(host);
+                }
+                result.map_err(anyhow::Error::msg)
+            }
+            Err(_) => anyhow::bail!("coalesced multicall batch was dropped"),
+        }
+    }
+
+    async fn send(&self, batch: PendingCalls, block: u64) {
+        log::debug!(
+            "Sending {} coalesced quotes from {} batches for block {}",
+            batch.calls.len(),
+            batch.waiters.len(),
+            block
+        );
+        let (result, served) = rpc::track(async {
+            let result = aggregate3(&self.multicall, batch.calls, block).await;
+            (result, rpc::served())
+        })
+        .await;
+        for (offset, len, tx) in batch.waiters {
+            let slice = match &result {
+                Ok(returned) => Ok(returned.iter().skip(offset).take(len).cloned().collect()),
+                Err(e) => Err(format!("{:#}", e)),
+            };
+            // Only fails when the waiting detector is gone.
+            let _ = tx.send((slice, served.clone()));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct QuoteRequest {
+    /// Index into the DEX list the plan is executed against.
+    pub dex: usize,
+    pub token_in: Address,
+    pub token_out: Address,
+    pub amount_in: U256,
+}
+
+pub struct Planner<M> {
+    client: Arc<M>,
+    multicall: Multicall3<M>,
+    coalescer: Option<Arc<Coalescer<M>>>,
+    max_calls_per_batch: usize,
+    rpc_budget_per_block: usize,
+    /// (block number, RPC calls already spent on it)
+    spent: Mutex<(u64, usize)>,
+}
+
+impl<M: Middleware + 'static> Planner<M> {
+    pub fn new(
+        client: Arc<M>,
+        max_calls_per_batch: usize,
+        rpc_budget_per_block: usize,
+    ) -> anyhow::Result<Self> {
+        let multicall = Multicall3::new(MULTICALL3.parse::<Address>()?, Arc::clone(&client));
+        Ok(Self {
+            client,
+            multicall,
+            coalescer: None,
+            max_calls_per_batch: max_calls_per_batch.max(1),
+            rpc_budget_per_block,
+            spent: Mutex::new((0, 0)),
+        })
+    }
+
+    /// Sends batches through `coalescer`, shared with the other detectors.
+    pub fn with_coalescer(&mut self, coalescer: Arc<Coalescer<M>>) {
+        self.coalescer = Some(coalescer);
+    }
+
+    /// Takes up to `wanted` RPC calls from the budget of `block`.
+    fn reserve(&self, block: BlockRef, wanted: usize) -> usize {
+        let mut spent = self.spent.lock().unwrap();
+        if spent.0 != block.number {
+            *spent = (block.number, 0);
+        }
+        let granted = wanted.min(self.rpc_budget_per_block.saturating_sub(spent.1));
+        spent.1 += granted;
+        granted
+    }
+
+    /// Quotes every request at `block`. The result has one entry per request,
+    /// in order; requests that failed or did not fit the budget are `None`.
+    pub async fn execute(
+        &self,
+        dexes: &[Box<dyn DexQuoter>],
+        requests: &[QuoteRequest],
+        block: BlockRef,
+    ) -> Vec<Option<Quote>> {
+        let batches: Vec<&[QuoteRequest]> = requests.chunks(self.max_calls_per_batch).collect();
+        let granted = self.reserve(block, batches.len());
+        if granted < batches.len() {
+            let skipped: usize = batches[granted..].iter().map(|b| b.len()).sum();
+            metrics::add(&METRICS.quotes_over_budget, skipped as u64);
+            log::warn!(
+                "RPC budget for block {} exhausted, skipping {} quotes",
+                block.number,
+                skipped
+            );
+        }
+
+        let results = join_all(
+            batches[..granted]
+                .iter()
+                .map(|batch| self.execute_batch(dexes, batch, block)),
+        )
+        .await;
+
+        let mut quotes: Vec<Option<Quote>> = results.into_iter().flatten().collect();
+        quotes.resize(requests.len(), None);
+        quotes
+    }
+
+    async fn execute_batch(
+        &self,
+        dexes: &[Box<dyn DexQuoter>],
+        batch: &[QuoteRequest],
+        block: BlockRef,
+    ) -> Vec<Option<Quote>> {
+        // A lone request is cheaper as a plain eth_call than wrapped in
+        // aggregate3, unless it can share a call with other detectors.
+        if let ([req], None) = (batch, &self.coalescer) {
+            let d = dexes[req.dex].as_ref();
+            return match dex::quote(
+                self.client.as_ref(),
+                d,
+                req.token_in,
+                req.token_out,
+                req.amount_in,
+                block,
+            )
+            .await
+            {
+                Ok(quote) => vec![Some(quote)],
+                Err(e) => {
+                    log::warn!("Quote from DEX {} failed: {:?}", d.name(), e);
+                    vec![None]
+                }
+            };
+        }
+
+        // Requests a venue can't quote get no quote and no call.
+        let mut calls: Vec<(Address, bool, Bytes)> = Vec::with_capacity(batch.len());
+        let mut callable = Vec::with_capacity(batch.len());
+        for req in batch {
+            let d = dexes[req.dex].as_ref();
+            match d.quote_call(req.token_in, req.token_out, req.amount_in) {
+                Ok(call) => {
+                    calls.push((call.target, true, call.calldata));
+                    callable.push(true);
+                }
+                Err(e) => {
+                    log::warn!("DEX {} cannot quote: {:?}", d.name(), e);
+                    callable.push(false);
+                }
+            }
+        }
+        if calls.is_empty() {
+            return vec![None; batch.len()];
+        }
+        let returned = match &self.coalescer {
+            Some(coalescer) => coalescer.call(calls, block.number).await,
+            None => aggregate3(&self.multicall, calls, block.number).await,
+        };
+        let returned = match returned {
+            Ok(returned) => returned,
+            Err(e) => {
+                log::warn!("Multicall batch of {} quotes failed: {:?}", batch.len(), e);
+                return vec![None; batch.len()];
+            }
+        };
+
+        let mut returned = returned.into_iter();
+        batch
+            .iter()
+            .zip(callable)
+            .map(|(req, callable)| {
+                let (success, data) = callable.then(|| returned.next()).flatten()?;
+                let d = dexes[req.dex].as_ref();
+                if !success {
+                    log::warn!("Quote from DEX {} reverted", d.name());
+                    return None;
+                }
+                match d.decode_quote(&data) {
+                    Ok(amount_out) => Some(Quote { amount_out, block }),
+                    Err(e) => {
+                        log::warn!("Could not decode quote from DEX {}: {:?}", d.name(), e);
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,227 @@
+//! Wallet holdings of live execution, served at `/portfolio`
+//!
+//! With `EXECUTION_MODE=live` every pair trades from the same wallet. The
+//! portfolio reads that wallet's balance of every pair token, values it in
+//! USD (stablecoins at par, any other token quoted into USDC on the first
+//! configured router), compares it with the balances read at startup, i.e.
+//! the inventory drift the trades left behind, and sums the realized P&L of
+//! each pair's trades from `executions`.
+
+use crate::amounts::{self, TokenInfo};
+use crate::config::Config;
+use crate::contracts::ERC20;
+use crate::tokens;
+use crate::uniswap;
+use chrono::{DateTime, Utc};
+use ethers::providers::Middleware;
+use ethers::types::{Address, U256};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Serialize)]
+pub struct Holding {
+    pub token: Address,
+    pub symbol: Option<String>,
+    pub balance: String,
+    pub start_balance: String,
+    /// `balance - start_balance`; negative when trades used inventory up.
+    pub drift: String,
+    pub usd_price: Option<f64>,
+    pub value_usd: Option<f64>,
+    pub drift_usd: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct RealizedPnl {
+    pub instance: String,
+    pub pair: Option<String>,
+    pub trades: i64,
+    pub filled: i64,
+    /// Trades left holding token_out; their value shows up as drift.
+    pub partial: i64,
+    /// token_in bought back over token_in sold, summed over filled trades.
+    pub pnl: f64,
+    pub pnl_usd: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct PortfolioView {
+    pub wallet: Address,
+    /// When the start balances were read.
+    pub since: String,
+    pub holdings: Vec<Holding>,
+    /// Unset when a holding could not be priced.
+    pub total_usd: Option<f64>,
+    pub drift_usd: Option<f64>,
+    pub realized: Vec<RealizedPnl>,
+    pub realized_usd: Option<f64>,
+}
+
+pub struct Portfolio<M> {
+    client: Arc<M>,
+    wallet: Address,
+    /// Router quoting non-stable tokens into USDC.
+    router: Option<Address>,
+    /// Every pair token with its balance at startup.
+    start: Vec<(TokenInfo, U256)>,
+    since: DateTime<Utc>,
+    /// token_in of each `(instance, pair)`, to value realized P&L.
+    token_in: BTreeMap<(String, Option<String>), Address>,
+}
+
+impl<M: Middleware + 'static> Portfolio<M> {
+    /// Reads the start balances of `wallet` for every token of `pairs`.
+    pub async fn new(
+        client: Arc<M>,
+        wallet: Address,
+        pairs: &[Config],
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Self> {
+        let mut addresses: Vec<Address> = Vec::new();
+        for cfg in pairs {
+            for token in [cfg.token_in, cfg.token_out] {
+                if !addresses.contains(&token) {
+                    addresses.push(token);
+                }
+            }
+        }
+        let mut start = Vec::with_capacity(addresses.len());
+        for address in addresses {
+            let info = amounts::load(Arc::clone(&client), address).await?;
+            let balance = balance_of(&client, address, wallet).await?;
+            start.push((info, balance));
+        }
+        Ok(Self {
+            router: pairs
+                .first()
+                .and_then(|cfg| cfg.dexes.first())
+                .map(|(_, router)| *router),
+            token_in: pairs
+                .iter()
+                .map(|cfg| ((cfg.instance.clone(), cfg.pair.clone()), cfg.token_in))
+                .collect(),
+            client,
+            wallet,
+            start,
+            since: now,
+        })
+    }
+
+    pub async fn view(&self, conn: &Mutex<Connection>) -> anyhow::Result<PortfolioView> {
+        let mut holdings = Vec::with_capacity(self.start.len());
+        let mut prices: BTreeMap<Address, Option<f64>> = BTreeMap::new();
+        for (token, start) in &self.start {
+            let balance = balance_of(&self.client, token.address, self.wallet).await?;
+            let usd_price = self.usd_price(token).await;
+            prices.insert(token.address, usd_price);
+            let drift = token.money(balance) - token.money(*start);
+            holdings.push(Holding {
+                token: token.address,
+                symbol: token.symbol.clone(),
+                balance: token.decimal(balance),
+                start_balance: token.decimal(*start),
+                drift: drift.to_string(),
+                usd_price,
+                value_usd: usd_price.map(|p| p * token.to_f64(balance)),
+                drift_usd: usd_price.map(|p| p * drift.to_f64()),
+            });
+        }
+
+        let mut realized = realized_pnl(&conn.lock().unwrap())?;
+        for pnl in &mut realized {
+            let price = self
+                .token_in
+                .get(&(pnl.instance.clone(), pnl.pair.clone()))
+                .and_then(|token| prices.get(token).copied().flatten());
+            pnl.pnl_usd = price.map(|p| p * pnl.pnl);
+        }
+        Ok(PortfolioView {
+            wallet: self.wallet,
+            since: self.since.to_rfc3339(),
+            total_usd: holdings.iter().map(|h| h.value_usd).sum(),
+            drift_usd: holdings.iter().map(|h| h.drift_usd).sum(),
+            realized_usd: realized.iter().map(|r| r.pnl_usd).sum(),
+            holdings,
+            realized,
+        })
+    }
+
+    /// USD per whole `token`; `None` when it can't be quoted.
+    async fn usd_price(&self, token: &TokenInfo) -> Option<f64> {
+        if tokens::is_stablecoin(token.address) {
+            return Some(1.0);
+        }
+        let usdc = tokens::by_name("USDC")?.address();
+        let one = U256::exp10(token.decimals as usize);
+        match uniswap::get_price(
+            Arc::clone(&self.client),
+            self.router?,
+            token.address,
+            usdc,
+            one,
+        )
+        .await
+        {
+            Ok(price) => Some(price.per_unit()),
+            Err(e) => {
+                log::warn!("Could not price {} in USDC: {:#}", token.label(""), e);
+                None
+            }
+        }
+    }
+}
+
+async fn balance_of<M: Middleware + 'static>(
+    client: &Arc<M>,
+    token: Address,
+    wallet: Address,
+) -> anyhow::Result<U256> {
+    Ok(ERC20::new(token, Arc::clone(client))
+        .balance_of(wallet)
+        .call()
+        .await?)
+}
+
+/// Trades and realized P&L per instance and pair, from `executions`.
+fn realized_pnl(conn: &Connection) -> anyhow::Result<Vec<RealizedPnl>> {
+    let mut stmt = conn.prepare(
+        "SELECT instance, pair, status, amount_in, realized_in FROM executions
+         ORDER BY instance, pair",
+    )?;
+    let rows = stmt.query_map(params![], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, Option<String>>(4)?,
+        ))
+    })?;
+    let mut by_pair: BTreeMap<(String, Option<String>), RealizedPnl> = BTreeMap::new();
+    for row in rows {
+        let (instance, pair, status, amount_in, realized_in) = row?;
+        let entry = by_pair
+            .entry((instance.clone(), pair.clone()))
+            .or_insert_with(|| RealizedPnl {
+                instance,
+                pair,
+                trades: 0,
+                filled: 0,
+                partial: 0,
+                pnl: 0.0,
+                pnl_usd: None,
+            });
+        entry.trades += 1;
+        match status.as_str() {
+            "filled" => entry.filled += 1,
+            "partial" => entry.partial += 1,
+            _ => {}
+        }
+        if let (Some(realized), Ok(sold)) = (realized_in, amount_in.parse::<f64>()) {
+            entry.pnl += realized.parse::<f64>().unwrap_or(sold) - sold;
+        }
+    }
+    Ok(by_pair.into_values().collect())
+}
@@ -0,0 +1,61 @@
+//! Live per-DEX prices for `/ws/prices`
+//!
+//! Every cycle a detector built with [`ArbDetector::with_live_prices`]
+//! publishes the prices it quoted at the primary trade size (the samples
+//! stored in `price_samples`), whether or not they made an opportunity.
+//! Subscribers get the latest snapshot of every instance and pair first, then
+//! each new one as it is published.
+//!
+//! [`ArbDetector::with_live_prices`]: crate::ArbDetector::with_live_prices
+
+use crate::db::PriceSample;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+/// Snapshots held for a slow subscriber before it skips some.
+const SUBSCRIBER_BUFFER: usize = 256;
+
+/// Prices one detector quoted in one cycle.
+#[derive(Serialize, Clone, Debug)]
+pub struct PriceSnapshot {
+    pub instance: String,
+    pub pair: Option<String>,
+    pub timestamp: String,
+    pub block: u64,
+    pub cycle_id: Option<String>,
+    pub prices: Vec<PriceSample>,
+    /// Highest over lowest price, in basis points; unset when a DEX quoted zero.
+    pub spread_bps: Option<f64>,
+}
+
+/// Latest snapshot per instance and pair, shared by every detector.
+pub struct LivePrices {
+    latest: Mutex<BTreeMap<(String, Option<String>), PriceSnapshot>>,
+    tx: broadcast::Sender<PriceSnapshot>,
+}
+
+impl Default for LivePrices {
+    fn default() -> Self {
+        Self {
+            latest: Mutex::new(BTreeMap::new()),
+            tx: broadcast::channel(SUBSCRIBER_BUFFER).0,
+        }
+    }
+}
+
+impl LivePrices {
+    pub fn publish(&self, snapshot: PriceSnapshot) {
+        let mut latest = self.latest.lock().unwrap();
+        // Only fails when nobody is subscribed.
+        let _ = self.tx.send(snapshot.clone());
+        latest.insert((snapshot.instance.clone(), snapshot.pair.clone()), snapshot);
+    }
+
+    /// The latest snapshots, and every one published after them.
+    pub fn subscribe(&self) -> (Vec<PriceSnapshot>, broadcast::Receiver<PriceSnapshot>) {
+        let latest = self.latest.lock().unwrap();
+        (latest.values().cloned().collect(), self.tx.subscribe())
+    }
+}
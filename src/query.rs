@@ -0,0 +1,260 @@
+//! Filter expressions over stored opportunities
+//!
+//! `polygon_arb_bot query "profit > 5 AND pair = 'WETH/USDC'" --last 20` compiles
+//! its filter into a parameterised SQL `WHERE` clause, so quick inspections need
+//! neither `sqlite3` nor the HTTP API. The grammar is deliberately small:
+//!
+//! ```text
+//! expr   := and ("OR" and)*
+//! and    := unary ("AND" unary)*
+//! unary  := "NOT" unary | "(" expr ")" | field op value | field "IS" ["NOT"] "NULL"
+//! op     := "=" | "!=" | "<>" | "<" | "<=" | ">" | ">=" | "LIKE"
+//! value  := number | 'string'
+//! ```
+//!
+//! Fields are the columns of `opportunities`; keywords are case-insensitive.
+//! Amounts stored as exact decimal strings compare numerically against
+//! numbers.
+
+use crate::db::{opportunity_from_row, Opportunity, OPPORTUNITY_COLUMNS};
+use anyhow::bail;
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f64),
+    Str(String),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+/// A compiled filter: the `WHERE` clause and its bound values.
+#[derive(Debug)]
+pub struct Filter {
+    pub sql: String,
+    pub params: Vec<Value>,
+}
+
+fn tokenize(input: &str) -> anyhow::Result<Vec<Token>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::Open);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::Close);
+                i += 1;
+            }
+            '\'' => {
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        // A doubled quote is a literal quote, as in SQL.
+                        Some('\'') if chars.get(i + 1) == Some(&'\'') => {
+                            value.push('\'');
+                            i += 2;
+                        }
+                        Some('\'') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&c) => {
+                            value.push(c);
+                            i += 1;
+                        }
+                        None => bail!("unterminated string '{}", value),
+                    }
+                }
+                tokens.push(Token::Str(value));
+            }
+            '=' | '!' | '<' | '>' => {
+                let next = chars.get(i + 1).copied();
+                let (op, len) = match (c, next) {
+                    ('!', Some('=')) | ('<', Some('>')) => ("!=", 2),
+                    ('<', Some('=')) => ("<=", 2),
+                    ('>', Some('=')) => (">=", 2),
+                    ('=', _) => ("=", 1),
+                    ('<', _) => ("<", 1),
+                    ('>', _) => (">", 1),
+                    _ => bail!("unexpected '!' at {}", i),
+                };
+                tokens.push(Token::Op(op));
+                i += len;
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let number = text
+                    .parse::<f64>()
+                    .map_err(|_| anyhow::anyhow!("invalid number {}", text))?;
+                tokens.push(Token::Number(number));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            c => bail!("unexpected '{}' at {}", c, i),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    params: Vec<Value>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Consumes the keyword when it comes next.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Ident(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expr(&mut self) -> anyhow::Result<String> {
+        let mut sql = self.and()?;
+        while self.keyword("OR") {
+            sql = format!("{} OR {}", sql, self.and()?);
+        }
+        Ok(sql)
+    }
+
+    fn and(&mut self) -> anyhow::Result<String> {
+        let mut sql = self.unary()?;
+        while self.keyword("AND") {
+            sql = format!("{} AND {}", sql, self.unary()?);
+        }
+        Ok(sql)
+    }
+
+    fn unary(&mut self) -> anyhow::Result<String> {
+        if self.keyword("NOT") {
+            return Ok(format!("NOT {}", self.unary()?));
+        }
+        match self.next() {
+            Some(Token::Open) => {
+                let inner = self.expr()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(format!("({})", inner)),
+                    _ => bail!("missing ')'"),
+                }
+            }
+            Some(Token::Ident(field)) => self.comparison(&field),
+            Some(token) => bail!("expected a field, found {:?}", token),
+            None => bail!("expected a field, found the end of the filter"),
+        }
+    }
+
+    fn comparison(&mut self, field: &str) -> anyhow::Result<String> {
+        let field = column(field)?;
+        if self.keyword("IS") {
+            let not = if self.keyword("NOT") { " NOT" } else { "" };
+            if !self.keyword("NULL") {
+                bail!("expected NULL after {} IS{}", field, not);
+            }
+            return Ok(format!("{} IS{} NULL", field, not));
+        }
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            Some(Token::Ident(word)) if word.eq_ignore_ascii_case("LIKE") => "LIKE",
+            other => bail!("expected an operator after {}, found {:?}", field, other),
+        };
+        let (lhs, value) = match self.next() {
+            // Amounts are stored as exact decimal strings.
+            Some(Token::Number(n)) if op != "LIKE" => {
+                (format!("CAST({} AS REAL)", field), Value::Real(n))
+            }
+            Some(Token::Str(s)) => (field.to_string(), Value::Text(s)),
+            other => bail!("expected a value after {} {}, found {:?}", field, op, other),
+        };
+        self.params.push(value);
+        Ok(format!("{} {} ?{}", lhs, op, self.params.len()))
+    }
+}
+
+/// The `opportunities` column named `field`, ignoring case.
+fn column(field: &str) -> anyhow::Result<&'static str> {
+    OPPORTUNITY_COLUMNS
+        .split(", ")
+        .find(|c| c.eq_ignore_ascii_case(field))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "unknown field '{}'; fields are {}",
+                field,
+                OPPORTUNITY_COLUMNS
+            )
+        })
+}
+
+/// Compiles a filter expression; an empty one matches everything.
+pub fn parse(input: &str) -> anyhow::Result<Filter> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Ok(Filter {
+            sql: "1".to_string(),
+            params: Vec::new(),
+        });
+    }
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        params: Vec::new(),
+    };
+    let sql = parser.expr()?;
+    if let Some(token) = parser.peek() {
+        bail!("unexpected {:?} after a complete filter", token);
+    }
+    Ok(Filter {
+        sql,
+        params: parser.params,
+    })
+}
+
+/// The `last` most recent opportunities matching `filter`, newest first.
+pub fn opportunities(
+    conn: &Connection,
+    filter: &Filter,
+    last: usize,
+) -> anyhow::Result<Vec<Opportunity>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM opportunities WHERE {} ORDER BY id DESC LIMIT {}",
+        OPPORTUNITY_COLUMNS, filter.sql, last
+    ))?;
+    let rows = stmt
+        .query_map(params_from_iter(filter.params.iter()), opportunity_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
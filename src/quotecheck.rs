@@ -0,0 +1,90 @@
+//! Router quotes checked against local constant-product math, served at `/api/dexes`
+//!
+//! Whenever a round trip's V2 pool reserves are read, each leg's router quote
+//! is compared with the output the reserves give at `V2_FEE_BPS`. A router
+//! answering more than `QUOTE_MISMATCH_BPS` away from that (a fee-on-transfer
+//! token, a custom router or pair fee) flags the DEX on the pair: its quotes,
+//! and the opportunities built on them, are not what the pool would pay.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QuoteCheck {
+    pub checks: u64,
+    pub mismatches: u64,
+    /// Router over local output of the last check, in basis points; negative
+    /// when the router returns less than the pool math.
+    pub last_deviation_bps: f64,
+    /// Largest absolute deviation seen.
+    pub worst_deviation_bps: f64,
+    /// Whether the last check exceeded `QUOTE_MISMATCH_BPS`.
+    pub flagged: bool,
+    pub last_checked: Option<String>,
+    pub last_mismatch: Option<String>,
+}
+
+/// (instance, pair, DEX) a check is kept under.
+type CheckKey = (String, Option<String>, String);
+
+/// Checks per instance, pair and DEX.
+pub struct QuoteChecks {
+    checks: Mutex<BTreeMap<CheckKey, QuoteCheck>>,
+}
+
+pub static QUOTE_CHECKS: QuoteChecks = QuoteChecks {
+    checks: Mutex::new(BTreeMap::new()),
+};
+
+impl QuoteChecks {
+    /// Records a check of `dex`; returns whether it just became flagged.
+    pub fn record(
+        &self,
+        instance: &str,
+        pair: Option<&str>,
+        dex: &str,
+        deviation_bps: f64,
+        threshold_bps: f64,
+        at: DateTime<Utc>,
+    ) -> bool {
+        let mut checks = self.checks.lock().unwrap();
+        let check = checks
+            .entry((
+                instance.to_string(),
+                pair.map(str::to_string),
+                dex.to_string(),
+            ))
+            .or_default();
+        let was_flagged = check.flagged;
+        check.checks += 1;
+        check.last_deviation_bps = deviation_bps;
+        check.worst_deviation_bps = check.worst_deviation_bps.max(deviation_bps.abs());
+        check.flagged = deviation_bps.abs() > threshold_bps;
+        check.last_checked = Some(at.to_rfc3339());
+        if check.flagged {
+            check.mismatches += 1;
+            check.last_mismatch = Some(at.to_rfc3339());
+        }
+        check.flagged && !was_flagged
+    }
+
+    /// Checks of `dex` on the pair, if it was checked yet.
+    pub fn get(&self, instance: &str, pair: Option<&str>, dex: &str) -> Option<QuoteCheck> {
+        self.checks
+            .lock()
+            .unwrap()
+            .get(&(
+                instance.to_string(),
+                pair.map(str::to_string),
+                dex.to_string(),
+            ))
+            .cloned()
+    }
+}
+
+/// `quoted` relative to `local`, in basis points; `None` when `local` is zero.
+pub fn deviation_bps(quoted: f64, local: f64) -> Option<f64> {
+    (local > 0.0).then(|| (quoted - local) / local * 10_000.0)
+}
@@ -0,0 +1,113 @@
+//! Why candidate spreads were not stored as opportunities
+//!
+//! Every candidate the detector drops is written to `rejections` with its
+//! reason, so `/api/stats/rejections` can show which filter does the work when
+//! tuning thresholds.
+
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// Clears `MIN_PROFIT_USDC` before gas, but not after it.
+    GasTooHigh,
+    /// Does not clear `MIN_PROFIT_USDC` even before gas.
+    BelowThreshold,
+    /// Fewer than two DEXes could quote the trade size (or the way back).
+    ThinLiquidity,
+    /// Quotes were older than `MAX_QUOTE_AGE_SECS` when compared.
+    StaleQuotes,
+    /// Clears `MIN_PROFIT_USDC` but returns less than `MIN_ROI_BPS` on notional.
+    BelowRoi,
+    /// Found while the detector was warming up after a start or RPC failover.
+    WarmingUp,
+}
+
+impl Reason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Reason::GasTooHigh => "gas_too_high",
+            Reason::BelowThreshold => "below_threshold",
+            Reason::ThinLiquidity => "thin_liquidity",
+            Reason::StaleQuotes => "stale_quotes",
+            Reason::BelowRoi => "below_roi",
+            Reason::WarmingUp => "warming_up",
+        }
+    }
+}
+
+/// The candidate that was dropped, when there was one to price.
+pub struct Candidate<'a> {
+    pub dex_buy: &'a str,
+    pub dex_sell: &'a str,
+    pub amount_in: f64,
+    /// Profit before gas in USDC.
+    pub gross_profit: f64,
+}
+
+#[derive(Deserialize)]
+pub struct RejectionParams {
+    /// Look-back window in hours (default 24).
+    pub hours: Option<u32>,
+    pub instance: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RejectionCount {
+    pub reason: String,
+    pub count: i64,
+    pub avg_gross_profit: Option<f64>,
+    pub max_gross_profit: Option<f64>,
+}
+
+pub fn record(
+    conn: &Connection,
+    now: DateTime<Utc>,
+    instance: &str,
+    reason: Reason,
+    candidate: Option<&Candidate>,
+    cycle_id: Option<&str>,
+) -> anyhow::Result<()> {
+    conn.execute(
+        "INSERT INTO rejections (timestamp, instance, reason, dex_buy, dex_sell, amount_in, gross_profit, cycle_id)
+         VALUES (?1,?2,?3,?4,?5,?6,?7,?8)",
+        params![
+            now.to_rfc3339(),
+            instance,
+            reason.as_str(),
+            candidate.map(|c| c.dex_buy),
+            candidate.map(|c| c.dex_sell),
+            candidate.map(|c| c.amount_in),
+            candidate.map(|c| c.gross_profit),
+            cycle_id
+        ],
+    )?;
+    Ok(())
+}
+
+/// Rejections per reason in the window, most frequent first.
+pub fn counts(
+    conn: &Connection,
+    now: DateTime<Utc>,
+    hours: u32,
+    instance: Option<&str>,
+) -> anyhow::Result<Vec<RejectionCount>> {
+    let since = (now - Duration::hours(hours as i64)).to_rfc3339();
+    let mut stmt = conn.prepare(
+        "SELECT reason, COUNT(*), AVG(gross_profit), MAX(gross_profit) FROM rejections
+         WHERE timestamp >= ?1 AND (?2 IS NULL OR instance = ?2)
+         GROUP BY reason ORDER BY COUNT(*) DESC",
+    )?;
+    let counts = stmt
+        .query_map(params![since, instance], |row| {
+            Ok(RejectionCount {
+                reason: row.get(0)?,
+                count: row.get(1)?,
+                avg_gross_profit: row.get(2)?,
+                max_gross_profit: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(counts)
+}
@@ -0,0 +1,214 @@
+//! Weekly HTML summary report
+//!
+//! Summarises the last seven days of opportunities (totals per pair, top
+//! opportunities, daily USD profit curve and DEX attribution) into a standalone HTML page that is
+//! dropped into `REPORT_DIR` on `REPORT_CRON` (Mondays 00:00 UTC by default).
+
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+const TOP_N: usize = 10;
+
+pub fn generate(conn: &Connection, now: DateTime<Utc>) -> anyhow::Result<String> {
+    let since = (now - Duration::days(7)).to_rfc3339();
+
+    // Profit is in each pair's token_out, so it is only summed per pair;
+    // across pairs the report adds up `profit_usd`.
+    let (count, total_usd): (i64, Option<f64>) = conn.query_row(
+        "SELECT COUNT(*), SUM(profit_usd) FROM opportunities WHERE timestamp >= ?1",
+        params![since],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT pair, COUNT(*), SUM(profit), MAX(profit) FROM opportunities
+         WHERE timestamp >= ?1 GROUP BY pair ORDER BY pair",
+    )?;
+    let pairs = stmt
+        .query_map(params![since], |row| {
+            Ok((
+                row.get::<_, Option<String>>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, f64>(2)?,
+                row.get::<_, f64>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT timestamp, strategy, pair, dex_buy, dex_sell, profit FROM opportunities
+         WHERE timestamp >= ?1 ORDER BY profit_usd IS NULL, profit_usd DESC, profit DESC LIMIT ?2",
+    )?;
+    let top = stmt
+        .query_map(params![since, TOP_N as i64], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, f64>(5)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT substr(timestamp, 1, 10) AS day, COUNT(*), COALESCE(SUM(profit_usd), 0)
+         FROM opportunities WHERE timestamp >= ?1 GROUP BY day ORDER BY day",
+    )?;
+    let daily = stmt
+        .query_map(params![since], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, f64>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT pair, dex_buy, dex_sell, COUNT(*), SUM(profit) FROM opportunities
+         WHERE timestamp >= ?1 GROUP BY pair, dex_buy, dex_sell ORDER BY pair, SUM(profit) DESC",
+    )?;
+    let attribution = stmt
+        .query_map(params![since], |row| {
+            Ok((
+                row.get::<_, Option<String>>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, f64>(4)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut html = String::new();
+    writeln!(
+        html,
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Weekly Arbitrage Report</title>\
+         <style>body{{font-family:sans-serif;margin:2em}}table{{border-collapse:collapse}}\
+         td,th{{border:1px solid #ccc;padding:4px 8px;text-align:right}}</style></head><body>"
+    )?;
+    writeln!(
+        html,
+        "<h1>Weekly Arbitrage Report</h1><p>{} to {}</p>",
+        &since[..10],
+        now.format("%Y-%m-%d")
+    )?;
+    match total_usd {
+        Some(total) => writeln!(
+            html,
+            "<h2>Summary</h2><p>{} opportunities, total profit {:.2} USD (rows with a USD price)</p>",
+            count, total
+        )?,
+        None => writeln!(html, "<h2>Summary</h2><p>{} opportunities</p>", count)?,
+    }
+    writeln!(
+        html,
+        "<table><tr><th>Pair</th><th>Count</th><th>Total profit</th><th>Best</th></tr>"
+    )?;
+    for (pair, n, total, max) in &pairs {
+        let unit = profit_unit(pair.as_deref());
+        writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{:.4} {}</td><td>{:.4} {}</td></tr>",
+            escape(pair.as_deref().unwrap_or("-")),
+            n,
+            total,
+            escape(unit),
+            max,
+            escape(unit)
+        )?;
+    }
+    writeln!(html, "</table>")?;
+
+    writeln!(html, "<h2>Daily Profit (USD)</h2>")?;
+    let peak = daily
+        .iter()
+        .map(|d| d.2)
+        .fold(0.0, f64::max)
+        .max(f64::EPSILON);
+    writeln!(
+        html,
+        "<svg width=\"{}\" height=\"120\">",
+        daily.len().max(1) * 60
+    )?;
+    for (i, (day, _, profit)) in daily.iter().enumerate() {
+        let h = (profit / peak * 100.0).max(0.0);
+        writeln!(
+            html,
+            "<rect x=\"{}\" y=\"{:.1}\" width=\"40\" height=\"{:.1}\" fill=\"#2ecc71\"><title>{} {:.2} USD</title></rect>\
+             <text x=\"{}\" y=\"118\" font-size=\"10\">{}</text>",
+            i * 60,
+            100.0 - h,
+            h,
+            day,
+            profit,
+            i * 60,
+            &day[5..]
+        )?;
+    }
+    writeln!(html, "</svg>")?;
+
+    writeln!(
+        html,
+        "<h2>Top {} Opportunities</h2><table><tr><th>Time</th><th>Strategy</th><th>Pair</th><th>Buy</th><th>Sell</th><th>Profit</th></tr>",
+        TOP_N
+    )?;
+    for (ts, strategy, pair, buy, sell, profit) in &top {
+        writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.4} {}</td></tr>",
+            escape(ts),
+            escape(strategy),
+            escape(pair.as_deref().unwrap_or("-")),
+            escape(buy),
+            escape(sell),
+            profit,
+            escape(profit_unit(pair.as_deref()))
+        )?;
+    }
+    writeln!(html, "</table>")?;
+
+    writeln!(
+        html,
+        "<h2>DEX Attribution</h2><table><tr><th>Pair</th><th>Buy</th><th>Sell</th><th>Count</th><th>Profit</th></tr>"
+    )?;
+    for (pair, buy, sell, n, profit) in &attribution {
+        writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.4} {}</td></tr>",
+            escape(pair.as_deref().unwrap_or("-")),
+            escape(buy),
+            escape(sell),
+            n,
+            profit,
+            escape(profit_unit(pair.as_deref()))
+        )?;
+    }
+    writeln!(html, "</table></body></html>")?;
+
+    Ok(html)
+}
+
+/// Writes the report to `dir/weekly-report-YYYY-MM-DD.html` and returns the path.
+pub fn write_report(conn: &Connection, dir: &Path, now: DateTime<Utc>) -> anyhow::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("weekly-report-{}.html", now.format("%Y-%m-%d")));
+    std::fs::write(&path, generate(conn, now)?)?;
+    Ok(path)
+}
+
+/// Token profit of `pair` (`WETH/USDC`) is counted in: its second symbol.
+fn profit_unit(pair: Option<&str>) -> &str {
+    pair.and_then(|pair| pair.split_once('/'))
+        .map_or("token_out", |(_, token_out)| token_out)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
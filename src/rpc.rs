@@ -0,0 +1,460 @@
+//! RPC failover across configured and public Polygon endpoints
+//!
+//! Requests go to one active endpoint. A health checker probes every endpoint
+//! each `RPC_HEALTH_INTERVAL_SECS` with `eth_blockNumber`: an endpoint that
+//! fails, times out or lags more than `MAX_BLOCK_LAG` blocks behind the best
+//! one is down. The first healthy endpoint of `RPC_URL` (which may list
+//! several) then `RPC_FALLBACK_URLS` is used; only when all of them are down
+//! does the fastest healthy entry of [`PUBLIC_RPCS`] take over (unless
+//! `RPC_PUBLIC_FAILOVER=false`). A request failing at the transport level,
+//! timing out after `RPC_REQUEST_TIMEOUT_SECS` or rate limited marks its
+//! endpoint down and is retried on the next one, until every endpoint was
+//! tried; the next probe brings a recovered endpoint back. The active
+//! endpoint and each endpoint's request and failure counts are exposed at
+//! `/api/state`, with URLs in their errors cut down to the host.
+//!
+//! A request that still fails transiently, e.g. because every endpoint timed
+//! out or the node hit an internal error, is sent again under the
+//! [`RetryPolicy`] (`RPC_RETRY_ATTEMPTS`, `RPC_RETRY_BASE_MS` doubling per
+//! retry, plus up to `RPC_RETRY_JITTER_MS`), so one hiccup does not cost a
+//! detection cycle. Transactions are never resent.
+//!
+//! A task running under [`track`] notes which endpoints answered its
+//! requests ([`served`]), so each detection cycle can record the providers
+//! its opportunities were quoted from.
+
+use crate::metrics::{self, METRICS};
+use async_trait::async_trait;
+use chrono::Utc;
+use ethers::core::rand::{thread_rng, Rng};
+use ethers::providers::{Http, HttpClientError, JsonRpcClient};
+use ethers::types::U64;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Public Polygon PoS endpoints used when every configured endpoint is down.
+pub const PUBLIC_RPCS: &[&str] = &[
+    "https://polygon-rpc.com",
+    "https://polygon-bor-rpc.publicnode.com",
+    "https://rpc.ankr.com/polygon",
+    "https://polygon.llamarpc.com",
+    "https://1rpc.io/matic",
+    "https://polygon.drpc.org",
+];
+
+tokio::task_local! {
+    /// Hosts that answered requests of the task, in first-use order.
+    static SERVED: RefCell<Vec<String>>;
+}
+
+/// Runs `fut` noting the endpoints that answer its requests; see [`served`].
+pub async fn track<F: Future>(fut: F) -> F::Output {
+    SERVED.scope(RefCell::new(Vec::new()), fut).await
+}
+
+/// Hosts that answered the tracked task's requests so far; empty outside
+/// [`track`].
+pub fn served() -> Vec<String> {
+    SERVED
+        .try_with(|hosts| hosts.borrow().clone())
+        .unwrap_or_default()
+}
+
+/// [`served`] comma-separated, or unset when no request was answered.
+pub fn served_by() -> Option<String> {
+    let hosts = served();
+    (!hosts.is_empty()).then(|| hosts.join(","))
+}
+
+/// Notes `host` as having answered a request of the tracked task, e.g. for a
+/// request another task sent on its behalf. Does nothing outside [`track`].
+pub fn note_served(host: &str) {
+    let _ = SERVED.try_with(|hosts| {
+        let mut hosts = hosts.borrow_mut();
+        if !hosts.iter().any(|h| h == host) {
+            hosts.push(host.to_string());
+        }
+    });
+}
+
+/// Times any client switched its active endpoint since start.
+static SWITCHES: AtomicU64 = AtomicU64::new(0);
+
+/// Endpoint switches of every [`FailoverClient`] since start; a change tells
+/// a detector its quotes now come from another node.
+pub fn endpoint_switches() -> u64 {
+    SWITCHES.load(Ordering::Relaxed)
+}
+
+/// Blocks an endpoint may trail the highest reported head and still be used.
+const MAX_BLOCK_LAG: u64 = 5;
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Methods that broadcast a transaction; resending one is not a retry.
+const SEND_METHODS: &[&str] = &["eth_sendRawTransaction", "eth_sendTransaction"];
+
+/// How often and how long apart a transiently failed request is sent again.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Tries per request, the first included; 1 disables retries.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled before each further one.
+    pub base_delay: Duration,
+    /// Up to this much is added to each delay at random, so detectors
+    /// sharing an endpoint don't retry in lockstep.
+    pub jitter: Duration,
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `retry` (from 1).
+    fn delay(&self, retry: u32) -> Duration {
+        let backoff = self.base_delay * 2u32.pow(retry.saturating_sub(1).min(16));
+        backoff + self.jitter.mul_f64(thread_rng().gen::<f64>())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Health {
+    pub healthy: bool,
+    pub latency_ms: Option<f64>,
+    pub block: Option<u64>,
+    pub error: Option<String>,
+    pub checked_at: Option<String>,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    /// Scheme and host only; paths often carry API keys.
+    host: String,
+    client: Http,
+    public: bool,
+    health: Mutex<Health>,
+    requests: AtomicU64,
+    /// Requests that failed over to another endpoint.
+    failures: AtomicU64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EndpointState {
+    pub host: String,
+    pub public: bool,
+    pub active: bool,
+    pub requests: u64,
+    pub failures: u64,
+    #[serde(flatten)]
+    pub health: Health,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RpcState {
+    pub active: String,
+    pub endpoints: Vec<EndpointState>,
+}
+
+#[derive(Debug)]
+struct Pool {
+    endpoints: Vec<Endpoint>,
+    active: AtomicUsize,
+    retry: RetryPolicy,
+}
+
+/// JSON-RPC transport that routes every request to the active endpoint.
+/// Clones share the same endpoints and health.
+#[derive(Debug, Clone)]
+pub struct FailoverClient {
+    pool: Arc<Pool>,
+}
+
+impl FailoverClient {
+    /// `configured` in order of preference, followed by [`PUBLIC_RPCS`] when
+    /// `public_failover` is set. Starts on the first configured endpoint.
+    pub fn new(
+        configured: &[String],
+        public_failover: bool,
+        request_timeout: Duration,
+        retry: RetryPolicy,
+    ) -> anyhow::Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(request_timeout)
+            .build()?;
+        let public = PUBLIC_RPCS
+            .iter()
+            .filter(|_| public_failover)
+            .filter(|url| !configured.iter().any(|c| c.trim_end_matches('/') == **url));
+        let mut endpoints = Vec::new();
+        for (url, public) in configured
+            .iter()
+            .map(|url| (url.as_str(), false))
+            .chain(public.map(|url| (*url, true)))
+        {
+            endpoints.push(Endpoint {
+                host: url_host(url),
+                client: Http::new_with_client(url.parse::<reqwest::Url>()?, http.clone()),
+                public,
+                health: Mutex::new(Health::default()),
+                requests: AtomicU64::new(0),
+                failures: AtomicU64::new(0),
+            });
+        }
+        if endpoints.is_empty() {
+            anyhow::bail!("no RPC endpoint configured");
+        }
+        Ok(Self {
+            pool: Arc::new(Pool {
+                endpoints,
+                active: AtomicUsize::new(0),
+                retry,
+            }),
+        })
+    }
+
+    /// Probes every endpoint and switches to the preferred healthy one.
+    pub async fn check(&self) {
+        let probes = self.pool.endpoints.iter().map(|endpoint| async move {
+            let timer = Instant::now();
+            let result = tokio::time::timeout(
+                PROBE_TIMEOUT,
+                endpoint.client.request::<_, U64>("eth_blockNumber", ()),
+            )
+            .await;
+            let latency_ms = timer.elapsed().as_secs_f64() * 1000.0;
+            match result {
+                Ok(Ok(block)) => Ok((block.as_u64(), latency_ms)),
+                Ok(Err(e)) => Err(redact_urls(&e.to_string())),
+                Err(_) => Err(format!("no answer within {}s", PROBE_TIMEOUT.as_secs())),
+            }
+        });
+        let results = futures::future::join_all(probes).await;
+        let head = results
+            .iter()
+            .filter_map(|r| r.as_ref().ok().map(|(block, _)| *block))
+            .max()
+            .unwrap_or_default();
+        let checked_at = Utc::now().to_rfc3339();
+        for (endpoint, result) in self.pool.endpoints.iter().zip(results) {
+            let health = match result {
+                Ok((block, latency_ms)) => Health {
+                    healthy: block + MAX_BLOCK_LAG >= head,
+                    latency_ms: Some(latency_ms),
+                    block: Some(block),
+                    error: (block + MAX_BLOCK_LAG < head)
+                        .then(|| format!("{} blocks behind", head - block)),
+                    checked_at: Some(checked_at.clone()),
+                },
+                Err(error) => Health {
+                    healthy: false,
+                    latency_ms: None,
+                    block: None,
+                    error: Some(error),
+                    checked_at: Some(checked_at.clone()),
+                },
+            };
+            *endpoint.health.lock().unwrap() = health;
+        }
+        self.select();
+    }
+
+    /// Probes forever; must be spawned inside the Tokio runtime.
+    pub async fn run_health_checks(self, interval: Duration) {
+        loop {
+            self.check().await;
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    pub fn state(&self) -> RpcState {
+        let active = self.pool.active.load(Ordering::Relaxed);
+        RpcState {
+            active: self.pool.endpoints[active].host.clone(),
+            endpoints: self
+                .pool
+                .endpoints
+                .iter()
+                .enumerate()
+                .map(|(i, endpoint)| EndpointState {
+                    host: endpoint.host.clone(),
+                    public: endpoint.public,
+                    active: i == active,
+                    requests: endpoint.requests.load(Ordering::Relaxed),
+                    failures: endpoint.failures.load(Ordering::Relaxed),
+                    health: endpoint.health.lock().unwrap().clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// First healthy configured endpoint, else the fastest healthy public one.
+    /// Keeps the current endpoint when nothing is healthy.
+    fn select(&self) -> usize {
+        let endpoints = &self.pool.endpoints;
+        let health: Vec<Health> = endpoints
+            .iter()
+            .map(|e| e.health.lock().unwrap().clone())
+            .collect();
+        let configured = (0..endpoints.len()).find(|&i| !endpoints[i].public && health[i].healthy);
+        let public = || {
+            (0..endpoints.len())
+                .filter(|&i| endpoints[i].public && health[i].healthy)
+                .min_by(|&a, &b| {
+                    let latency = |i: usize| health[i].latency_ms.unwrap_or(f64::MAX);
+                    latency(a).total_cmp(&latency(b))
+                })
+        };
+        let current = self.pool.active.load(Ordering::Relaxed);
+        let Some(next) = configured.or_else(public) else {
+            return current;
+        };
+        if next != current {
+            self.pool.active.store(next, Ordering::Relaxed);
+            SWITCHES.fetch_add(1, Ordering::Relaxed);
+            log::warn!(
+                "Switching RPC endpoint from {} to {}",
+                endpoints[current].host,
+                endpoints[next].host
+            );
+        }
+        next
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for FailoverClient {
+    type Error = HttpClientError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let params = serde_json::to_value(params).map_err(|err| HttpClientError::SerdeJson {
+            err,
+            text: String::new(),
+        })?;
+        let retry = self.pool.retry;
+        let mut attempt = 1;
+        loop {
+            match self.send(method, &params).await {
+                Err(e)
+                    if attempt < retry.max_attempts
+                        && transient(&e)
+                        && !SEND_METHODS.contains(&method) =>
+                {
+                    let delay = retry.delay(attempt);
+                    log::debug!(
+                        "RPC {} failed ({}), retry {}/{} in {}ms",
+                        method,
+                        redact_urls(&e.to_string()),
+                        attempt,
+                        retry.max_attempts - 1,
+                        delay.as_millis()
+                    );
+                    metrics::inc(&METRICS.rpc_retries);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl FailoverClient {
+    /// Sends the request to the active endpoint, failing over to the next
+    /// one until every endpoint was tried.
+    async fn send<R: DeserializeOwned + Send>(
+        &self,
+        method: &str,
+        params: &serde_json::Value,
+    ) -> Result<R, HttpClientError> {
+        let mut tried = Vec::new();
+        let mut current = self.pool.active.load(Ordering::Relaxed);
+        loop {
+            let endpoint = &self.pool.endpoints[current];
+            endpoint.requests.fetch_add(1, Ordering::Relaxed);
+            match endpoint.client.request(method, params).await {
+                Err(e) if fails_over(&e) => {
+                    endpoint.failures.fetch_add(1, Ordering::Relaxed);
+                    {
+                        let mut health = endpoint.health.lock().unwrap();
+                        health.healthy = false;
+                        health.error = Some(redact_urls(&e.to_string()));
+                    }
+                    tried.push(current);
+                    let next = self.select();
+                    if tried.contains(&next) {
+                        return Err(e);
+                    }
+                    current = next;
+                }
+                result => {
+                    if result.is_ok() {
+                        note_served(&endpoint.host);
+                    }
+                    return result;
+                }
+            }
+        }
+    }
+}
+
+/// Whether `err` says the endpoint, not the request, is at fault: no answer
+/// (including a timeout) or a rate limit.
+fn fails_over(err: &HttpClientError) -> bool {
+    let rate_limited = |text: &str| {
+        let text = text.to_lowercase();
+        text.contains("429") || text.contains("too many requests") || text.contains("rate limit")
+    };
+    match err {
+        HttpClientError::ReqwestError(_) => true,
+        HttpClientError::JsonRpcError(e) => {
+            e.code == 429 || e.code == -32005 || rate_limited(&e.message)
+        }
+        HttpClientError::SerdeJson { text, .. } => rate_limited(text),
+    }
+}
+
+/// Whether sending `err`'s request again may succeed: the endpoint was at
+/// fault, or the node failed internally or had not caught up to the block.
+fn transient(err: &HttpClientError) -> bool {
+    fails_over(err)
+        || matches!(err, HttpClientError::JsonRpcError(e)
+            if e.code == -32603 || e.message.contains("header not found"))
+}
+
+/// Scheme and host of `url`, without credentials, path or query.
+pub fn url_host(url: &str) -> String {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("", url));
+    let authority = rest.split(['/', '?']).next().unwrap_or_default();
+    let host = authority.rsplit('@').next().unwrap_or_default();
+    if scheme.is_empty() {
+        host.to_string()
+    } else {
+        format!("{}://{}", scheme, host)
+    }
+}
+
+/// `text` with every URL in it cut down to [`url_host`], for error messages
+/// that are served or logged: providers put API keys in the path, and
+/// reqwest names the URL of a failed request.
+pub fn redact_urls(text: &str) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(sep) = rest.find("://") {
+        let start = rest[..sep]
+            .trim_end_matches(|c: char| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+            .len();
+        let end = rest[sep..]
+            .find(|c: char| c.is_whitespace() || matches!(c, ')' | '"' | '\'' | '>' | ','))
+            .map_or(rest.len(), |i| sep + i);
+        redacted.push_str(&rest[..start]);
+        redacted.push_str(&url_host(&rest[start..end]));
+        rest = &rest[end..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
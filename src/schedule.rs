@@ -0,0 +1,211 @@
+//! In-process job scheduler driven by cron expressions
+//!
+//! Expressions have the usual five fields, `minute hour day-of-month month
+//! day-of-week`, evaluated in UTC. Each field takes `*`, a value, a range
+//! `a-b`, a step `*/n` or `a-b/n`, or a comma-separated list of those; day of
+//! week runs from 0 (Sunday) to 6, with 7 also meaning Sunday. As in cron,
+//! when both day fields are restricted a day matching either one runs.
+//! Scheduled jobs report their runs to [`JOBS`].
+
+use crate::clock::Clock;
+use crate::jobs::JOBS;
+use crate::supervisor;
+use anyhow::Context;
+use chrono::{DateTime, Datelike, Duration, Timelike, Utc};
+use serde::Deserialize;
+use std::future::Future;
+use std::sync::Arc;
+
+/// Days searched for the next run before an expression is treated as never
+/// matching (e.g. `0 0 30 2 *`).
+const SEARCH_DAYS: i64 = 4 * 366;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Cron {
+    expr: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    pub fn parse(expr: &str) -> anyhow::Result<Self> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            anyhow::bail!("cron expression {:?} must have five fields", expr);
+        };
+        let field = |spec: &str, min: u32, max: u32, name: &str| {
+            parse_field(spec, min, max)
+                .with_context(|| format!("invalid {} field {:?} in {:?}", name, spec, expr))
+        };
+        let mut weekdays = field(weekday, 0, 7, "day-of-week")?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            expr: expr.to_string(),
+            minutes: field(minute, 0, 59, "minute")?,
+            hours: field(hour, 0, 23, "hour")?,
+            days: field(day, 1, 31, "day-of-month")?,
+            months: field(month, 1, 12, "month")?,
+            weekdays,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+
+    /// First whole minute strictly after `after` that matches, if any.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let mut day = start.date_naive();
+        for _ in 0..SEARCH_DAYS {
+            if self.matches_day(day) {
+                let first_minute = if day == start.date_naive() {
+                    start.hour() * 60 + start.minute()
+                } else {
+                    0
+                };
+                let next = (first_minute..24 * 60)
+                    .find(|m| bit(self.hours, m / 60) && bit(self.minutes, m % 60));
+                if let Some(m) = next {
+                    return Some(day.and_hms_opt(m / 60, m % 60, 0)?.and_utc());
+                }
+            }
+            day = day.succ_opt()?;
+        }
+        None
+    }
+
+    fn matches_day(&self, day: chrono::NaiveDate) -> bool {
+        if !bit(self.months, day.month()) {
+            return false;
+        }
+        let dom = bit(self.days, day.day());
+        let dow = bit(self.weekdays, day.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => dom,
+            (true, false) => dow,
+            (false, false) => dom || dow,
+        }
+    }
+}
+
+impl std::fmt::Display for Cron {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.expr)
+    }
+}
+
+fn bit(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Bit set of the values in `min..=max` selected by one cron field.
+fn parse_field(spec: &str, min: u32, max: u32) -> anyhow::Result<u64> {
+    let mut set = 0u64;
+    for part in spec.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>()?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            anyhow::bail!("step must be positive");
+        }
+        let (lo, hi) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((lo, hi)) => (lo.parse::<u32>()?, hi.parse::<u32>()?),
+                // `5/15` runs from 5 to the end of the range.
+                None if step > 1 => (range.parse::<u32>()?, max),
+                None => {
+                    let v = range.parse::<u32>()?;
+                    (v, v)
+                }
+            },
+        };
+        if lo < min || hi > max || lo > hi {
+            anyhow::bail!("{} is outside {}-{}", part, min, max);
+        }
+        for v in (lo..=hi).step_by(step as usize) {
+            set |= 1 << v;
+        }
+    }
+    Ok(set)
+}
+
+/// [`run`] under the [`supervisor`], restarted with a fresh copy of `job`
+/// after a panic.
+pub fn spawn<F, Fut>(clock: Arc<dyn Clock>, name: &'static str, cron: Cron, job: F)
+where
+    F: FnMut(DateTime<Utc>) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    supervisor::spawn(name, move || {
+        run(Arc::clone(&clock), name, cron.clone(), job.clone())
+    });
+}
+
+/// Runs `job` at every time `cron` matches, forever; must be spawned inside
+/// the Tokio runtime. Failures are logged and the job waits for its next run.
+pub async fn run<F, Fut>(clock: Arc<dyn Clock>, name: &'static str, cron: Cron, mut job: F)
+where
+    F: FnMut(DateTime<Utc>) -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    log::info!("Scheduled {} at '{}'", name, cron);
+    JOBS.register(name, cron.to_string());
+    loop {
+        let now = clock.now();
+        let Some(next) = cron.next_after(now) else {
+            log::warn!("Schedule '{}' of {} never matches, job stopped", cron, name);
+            return;
+        };
+        JOBS.scheduled(name, next);
+        clock.sleep((next - now).to_std().unwrap_or_default()).await;
+        let result = job(next).await;
+        JOBS.finished(name, clock.now(), &result);
+        if let Err(e) = result {
+            log::error!("Scheduled job {} failed: {:?}", name, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn runs_at_each_match_of_a_mock_clock() {
+        let at = |h, m| Utc.with_ymd_and_hms(2024, 1, 1, h, m, 0).unwrap();
+        let clock = Arc::new(MockClock::new(at(0, 7)));
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let job = run(
+            Arc::clone(&clock) as Arc<dyn Clock>,
+            "mock_clock_test",
+            Cron::parse("*/15 * * * *").unwrap(),
+            move |now| {
+                let _ = tx.send(now);
+                async { Ok(()) }
+            },
+        );
+        let first_three = async {
+            let mut runs = Vec::new();
+            for _ in 0..3 {
+                runs.push(rx.recv().await.unwrap());
+            }
+            runs
+        };
+        let runs = tokio::select! {
+            runs = first_three => runs,
+            _ = job => unreachable!("the schedule always matches"),
+        };
+        assert_eq!(runs, [at(0, 15), at(0, 30), at(0, 45)]);
+    }
+}
@@ -0,0 +1,87 @@
+//! NDJSON output of opportunities and price samples
+//!
+//! With `NDJSON_OUTPUT` set, every stored opportunity and price sample is also
+//! written as one JSON object per line to stdout (`-`) or to a file / named
+//! pipe, for shell pipelines and log shippers. Lines carry a `type` field
+//! (`opportunity` or `price_sample`).
+
+use crate::api::ApiOpportunity;
+use crate::db::{Opportunity, PriceSample};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+pub struct NdjsonSink {
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Line<'a> {
+    Opportunity(Box<ApiOpportunity>),
+    PriceSample {
+        timestamp: String,
+        instance: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pair: Option<&'a str>,
+        block_number: u64,
+        dex: &'a str,
+        price: f64,
+        deviation_bps: Option<f64>,
+    },
+}
+
+impl NdjsonSink {
+    /// `-` is stdout; anything else is opened for appending (a FIFO blocks
+    /// until a reader attaches).
+    pub fn open(target: &str) -> io::Result<Self> {
+        let out: Box<dyn Write + Send> = if target == "-" {
+            Box::new(io::stdout())
+        } else {
+            Box::new(OpenOptions::new().create(true).append(true).open(target)?)
+        };
+        Ok(Self {
+            out: Mutex::new(out),
+        })
+    }
+
+    pub fn opportunity(&self, opp: &Opportunity) {
+        self.write(&Line::Opportunity(Box::new(opp.into())));
+    }
+
+    pub fn price_samples(
+        &self,
+        now: DateTime<Utc>,
+        instance: &str,
+        pair: Option<&str>,
+        block_number: u64,
+        samples: &[PriceSample],
+    ) {
+        let timestamp = now.to_rfc3339();
+        for sample in samples {
+            self.write(&Line::PriceSample {
+                timestamp: timestamp.clone(),
+                instance,
+                pair,
+                block_number,
+                dex: &sample.dex,
+                price: sample.price,
+                deviation_bps: sample.deviation_bps,
+            });
+        }
+    }
+
+    /// A broken pipe must not stop detection, so failures are only logged.
+    fn write(&self, line: &Line) {
+        let mut out = self.out.lock().unwrap();
+        let written = serde_json::to_writer(&mut *out, line)
+            .map_err(io::Error::from)
+            .and_then(|_| out.write_all(b"\n"))
+            .and_then(|_| out.flush());
+        if let Err(e) = written {
+            log::warn!("Failed to write NDJSON line: {}", e);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Historical spread series from the per-cycle price samples
+//!
+//! Every cycle stores each DEX's price in `price_samples` (kept for
+//! `RETENTION_DAYS`), whether or not an opportunity triggered. The spread of
+//! a cycle is its highest over its lowest DEX price, so the series can be
+//! charted for any window the samples still cover.
+
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+/// Points of `/spreads` without `limit`, and the most one request may get.
+pub const DEFAULT_LIMIT: usize = 1000;
+pub const MAX_LIMIT: usize = 10_000;
+
+#[derive(Deserialize)]
+pub struct SpreadParams {
+    /// Token pair, e.g. `WETH/USDC`; every pair when unset.
+    pub pair: Option<String>,
+    pub instance: Option<String>,
+    /// RFC 3339 bounds; the last 24 hours up to now when unset.
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpreadPoint {
+    pub timestamp: String,
+    pub block: i64,
+    pub instance: String,
+    pub pair: Option<String>,
+    /// DEXes that quoted in the cycle.
+    pub dexes: i64,
+    pub min_price: f64,
+    pub max_price: f64,
+    pub spread_bps: f64,
+}
+
+impl SpreadParams {
+    /// `from` and `to`, defaulting to the 24 hours up to `now`.
+    pub fn window(&self, now: DateTime<Utc>) -> Result<(DateTime<Utc>, DateTime<Utc>), String> {
+        let bound = |value: &Option<String>, name: &str| {
+            value
+                .as_deref()
+                .map(|v| {
+                    DateTime::parse_from_rfc3339(v)
+                        .map(|t| t.with_timezone(&Utc))
+                        .map_err(|e| format!("Invalid {}: {}", name, e))
+                })
+                .transpose()
+        };
+        let to = bound(&self.to, "to")?.unwrap_or(now);
+        let from = bound(&self.from, "from")?.unwrap_or(to - Duration::hours(24));
+        if from > to {
+            return Err("from is after to".to_string());
+        }
+        Ok((from, to))
+    }
+}
+
+/// Spread of every cycle between `from` and `to`, oldest first. At most
+/// `limit` points, the latest ones when the window holds more.
+pub fn load(
+    conn: &Connection,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    pair: Option<&str>,
+    instance: Option<&str>,
+    limit: usize,
+) -> anyhow::Result<Vec<SpreadPoint>> {
+    let mut stmt = conn.prepare(
+        "SELECT timestamp, block_number, instance, pair, COUNT(*), MIN(price), MAX(price)
+         FROM price_samples
+         WHERE timestamp >= ?1 AND timestamp <= ?2 AND price > 0
+           AND (?3 IS NULL OR pair = ?3) AND (?4 IS NULL OR instance = ?4)
+         GROUP BY instance, pair, block_number, timestamp
+         ORDER BY timestamp DESC LIMIT ?5",
+    )?;
+    let rows = stmt.query_map(
+        params![
+            from.to_rfc3339(),
+            to.to_rfc3339(),
+            pair,
+            instance,
+            limit as i64
+        ],
+        |row| {
+            let min_price: f64 = row.get(5)?;
+            let max_price: f64 = row.get(6)?;
+            Ok(SpreadPoint {
+                timestamp: row.get(0)?,
+                block: row.get(1)?,
+                instance: row.get(2)?,
+                pair: row.get(3)?,
+                dexes: row.get(4)?,
+                min_price,
+                max_price,
+                spread_bps: (max_price - min_price) / min_price * 10_000.0,
+            })
+        },
+    )?;
+    let mut points = rows.collect::<Result<Vec<_>, _>>()?;
+    points.reverse();
+    Ok(points)
+}
@@ -0,0 +1,61 @@
+//! Rolling spread statistics
+//!
+//! Tracks an EMA and a rolling mean / standard deviation of the cross-DEX
+//! spread (in bps), so an unusual spread can be told apart from normal noise.
+
+use std::collections::VecDeque;
+
+pub struct SpreadStats {
+    window: usize,
+    alpha: f64,
+    ema: Option<f64>,
+    samples: VecDeque<f64>,
+}
+
+impl SpreadStats {
+    pub fn new(window: usize) -> Self {
+        let window = window.max(2);
+        Self {
+            window,
+            alpha: 2.0 / (window as f64 + 1.0),
+            ema: None,
+            samples: VecDeque::with_capacity(window),
+        }
+    }
+
+    pub fn push(&mut self, spread_bps: f64) {
+        self.ema = Some(match self.ema {
+            Some(ema) => ema + self.alpha * (spread_bps - ema),
+            None => spread_bps,
+        });
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(spread_bps);
+    }
+
+    pub fn ema(&self) -> Option<f64> {
+        self.ema
+    }
+
+    pub fn sample_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// True once the rolling window is full.
+    pub fn is_warm(&self) -> bool {
+        self.samples.len() == self.window
+    }
+
+    /// Standard score of `spread_bps` against the current window, once warm.
+    pub fn z_score(&self, spread_bps: f64) -> Option<f64> {
+        if !self.is_warm() {
+            return None;
+        }
+        let n = self.samples.len() as f64;
+        let mean = self.samples.iter().sum::<f64>() / n;
+        let var = self.samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n;
+        let std = var.sqrt();
+        (std > 0.0).then(|| (spread_bps - mean) / std)
+    }
+}
@@ -0,0 +1,154 @@
+//! Per-strategy switches and active time windows
+//!
+//! Each strategy can be turned off (`DISABLED_STRATEGIES`) or limited to a
+//! daily UTC window (`STRATEGY_WINDOWS=basis=13:00-21:00`), e.g. to run a
+//! strategy only during high-volume hours. Both can be changed at runtime
+//! with `PUT /api/strategies/{name}`. A window whose end is before its
+//! start wraps past midnight.
+
+use anyhow::Context;
+use chrono::{DateTime, NaiveTime, Utc};
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::sync::RwLock;
+
+/// Strategies the detector runs, by the name stored with their opportunities.
+pub const STRATEGIES: [&str; 3] = ["two_dex", "basis", "triangular"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Window {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl Window {
+    /// Parses `HH:MM-HH:MM`.
+    pub fn parse(spec: &str) -> anyhow::Result<Self> {
+        let (start, end) = spec
+            .split_once('-')
+            .with_context(|| format!("window {:?} is not HH:MM-HH:MM", spec))?;
+        let time = |t: &str| {
+            NaiveTime::parse_from_str(t.trim(), "%H:%M")
+                .with_context(|| format!("invalid time {:?} in window {:?}", t, spec))
+        };
+        Ok(Self {
+            start: time(start)?,
+            end: time(end)?,
+        })
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+impl Serialize for Window {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct StrategyState {
+    pub name: &'static str,
+    pub enabled: bool,
+    /// Daily UTC window the strategy runs in; always when unset.
+    pub window: Option<Window>,
+}
+
+impl StrategyState {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.enabled && self.window.iter().all(|w| w.contains(now.time()))
+    }
+}
+
+pub struct Strategies {
+    states: RwLock<Vec<StrategyState>>,
+}
+
+impl Default for Strategies {
+    /// Every strategy enabled around the clock.
+    fn default() -> Self {
+        Self {
+            states: RwLock::new(
+                STRATEGIES
+                    .iter()
+                    .map(|&name| StrategyState {
+                        name,
+                        enabled: true,
+                        window: None,
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Strategies {
+    /// `disabled` lists strategy names, `windows` holds `name=HH:MM-HH:MM` entries.
+    pub fn from_config(disabled: &[String], windows: &[String]) -> anyhow::Result<Self> {
+        let strategies = Self::default();
+        for name in disabled {
+            strategies
+                .update(name, Some(false), None)
+                .with_context(|| format!("unknown strategy {:?} in DISABLED_STRATEGIES", name))?;
+        }
+        for entry in windows {
+            let (name, spec) = entry.split_once('=').with_context(|| {
+                format!("STRATEGY_WINDOWS entry {:?} is not name=window", entry)
+            })?;
+            let window = Window::parse(spec)?;
+            strategies
+                .update(name.trim(), None, Some(Some(window)))
+                .with_context(|| format!("unknown strategy {:?} in STRATEGY_WINDOWS", name))?;
+        }
+        Ok(strategies)
+    }
+
+    pub fn is_active(&self, name: &str, now: DateTime<Utc>) -> bool {
+        self.states
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|s| s.name == name)
+            .all(|s| s.is_active(now))
+    }
+
+    pub fn list(&self) -> Vec<StrategyState> {
+        self.states.read().unwrap().clone()
+    }
+
+    /// Changes what is given (`Some(None)` clears the window) and returns the
+    /// new state, or `None` for an unknown strategy.
+    pub fn update(
+        &self,
+        name: &str,
+        enabled: Option<bool>,
+        window: Option<Option<Window>>,
+    ) -> Option<StrategyState> {
+        let mut states = self.states.write().unwrap();
+        let state = states.iter_mut().find(|s| s.name == name)?;
+        if let Some(enabled) = enabled {
+            state.enabled = enabled;
+        }
+        if let Some(window) = window {
+            state.window = window;
+        }
+        Some(state.clone())
+    }
+}
@@ -0,0 +1,135 @@
+//! Daily or weekly summary of stored opportunities, served at `/stats`
+//!
+//! Per period: how many opportunities were found, their average, largest and
+//! total (hypothetical, as if every one had been traded) profit, and which
+//! pair and which buy → sell DEX direction contributed the most profit.
+//! Weeks start on Monday and are labelled with that day.
+
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    #[default]
+    Day,
+    Week,
+}
+
+impl Period {
+    /// SQL expression of the period a row's `timestamp` falls in.
+    fn key(self) -> &'static str {
+        match self {
+            Period::Day => "substr(timestamp, 1, 10)",
+            Period::Week => "date(substr(timestamp, 1, 10), 'weekday 0', '-6 days')",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SummaryParams {
+    /// `day` (default) or `week`.
+    pub group: Option<Period>,
+    /// Look-back window in days (default 30).
+    pub days: Option<u32>,
+    pub instance: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PeriodSummary {
+    /// First day of the period, `YYYY-MM-DD`.
+    pub period: String,
+    pub opportunities: i64,
+    pub avg_profit: f64,
+    pub max_profit: f64,
+    /// Sum of every opportunity's profit, as if all had been traded.
+    pub total_profit: f64,
+    pub total_profit_usd: Option<f64>,
+    pub best_pair: Option<String>,
+    pub best_pair_profit: Option<f64>,
+    /// `buy DEX → sell DEX` with the largest total profit.
+    pub best_direction: Option<String>,
+    pub best_direction_profit: Option<f64>,
+}
+
+/// Summaries of the last `days` days, oldest period first.
+pub fn load(
+    conn: &Connection,
+    now: DateTime<Utc>,
+    group: Period,
+    days: u32,
+    instance: Option<&str>,
+) -> anyhow::Result<Vec<PeriodSummary>> {
+    let since = (now - Duration::days(days as i64)).to_rfc3339();
+    let key = group.key();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {key} AS period, COUNT(*), AVG(profit), MAX(profit), SUM(profit), SUM(profit_usd)
+         FROM opportunities WHERE timestamp >= ?1 AND (?2 IS NULL OR instance = ?2)
+         GROUP BY period ORDER BY period"
+    ))?;
+    let mut summaries = stmt
+        .query_map(params![since, instance], |row| {
+            Ok(PeriodSummary {
+                period: row.get(0)?,
+                opportunities: row.get(1)?,
+                avg_profit: row.get(2)?,
+                max_profit: row.get(3)?,
+                total_profit: row.get(4)?,
+                total_profit_usd: row.get(5)?,
+                best_pair: None,
+                best_pair_profit: None,
+                best_direction: None,
+                best_direction_profit: None,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let pairs = best_per_period(conn, key, "COALESCE(pair, '')", &since, instance)?;
+    let directions = best_per_period(conn, key, "dex_buy || ' → ' || dex_sell", &since, instance)?;
+    for summary in &mut summaries {
+        if let Some((pair, profit)) = pairs.get(&summary.period) {
+            summary.best_pair = (!pair.is_empty()).then(|| pair.clone());
+            summary.best_pair_profit = Some(*profit);
+        }
+        if let Some((direction, profit)) = directions.get(&summary.period) {
+            summary.best_direction = Some(direction.clone());
+            summary.best_direction_profit = Some(*profit);
+        }
+    }
+    Ok(summaries)
+}
+
+/// Value of `group_expr` with the largest total profit in each period.
+fn best_per_period(
+    conn: &Connection,
+    key: &str,
+    group_expr: &str,
+    since: &str,
+    instance: Option<&str>,
+) -> anyhow::Result<BTreeMap<String, (String, f64)>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {key} AS period, {group_expr} AS grouped, SUM(profit)
+         FROM opportunities WHERE timestamp >= ?1 AND (?2 IS NULL OR instance = ?2)
+         GROUP BY period, grouped"
+    ))?;
+    let mut best: BTreeMap<String, (String, f64)> = BTreeMap::new();
+    let rows = stmt.query_map(params![since, instance], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, f64>(2)?,
+        ))
+    })?;
+    for row in rows {
+        let (period, grouped, profit) = row?;
+        match best.get(&period) {
+            Some((_, top)) if *top >= profit => {}
+            _ => {
+                best.insert(period, (grouped, profit));
+            }
+        }
+    }
+    Ok(best)
+}
@@ -0,0 +1,60 @@
+//! Panic isolation for background tasks
+//!
+//! A panicking task would otherwise end silently while the web server keeps
+//! answering. [`spawn`] runs a task built by a factory, catches its panic,
+//! logs it under the task's name, counts it in `arb_task_panics_total` and
+//! builds and starts a fresh task after a backoff that doubles up to
+//! `MAX_BACKOFF` (reset once a task ran for `HEALTHY_RUN`).
+
+use crate::metrics::{self, METRICS};
+use futures::FutureExt;
+use std::any::Any;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// A task that ran this long before panicking restarts after `MIN_BACKOFF`.
+const HEALTHY_RUN: Duration = Duration::from_secs(300);
+
+/// Spawns `task()` and restarts it whenever it panics. A task that returns
+/// normally is not restarted.
+pub fn spawn<F, Fut>(name: impl Into<String>, mut task: F) -> JoinHandle<()>
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let name = name.into();
+    tokio::spawn(async move {
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            let started = Instant::now();
+            let Err(panic) = AssertUnwindSafe(task()).catch_unwind().await else {
+                log::info!("Task {} finished", name);
+                return;
+            };
+            metrics::inc(&METRICS.task_panics);
+            if started.elapsed() >= HEALTHY_RUN {
+                backoff = MIN_BACKOFF;
+            }
+            log::error!(
+                "Task {} panicked: {}; restarting in {}s",
+                name,
+                panic_message(&*panic),
+                backoff.as_secs()
+            );
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    })
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("non-string panic payload")
+}
@@ -0,0 +1,147 @@
+//! Suspension of dead pairs, listed at `/pairs`
+//!
+//! A pair is alive while its cycles get a nonzero quote from at least two
+//! DEXes and, with `LIQUIDITY_FLOOR`, while at least two of its pools hold
+//! that much liquidity. Once either has not been the case for
+//! `PAIR_DEAD_AFTER_SECS`, the pair is suspended: its detector stops running
+//! cycles until the pair is reactivated through
+//! `POST /control/pairs/reactivate`.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PairStatus {
+    pub suspended: bool,
+    pub suspended_at: Option<String>,
+    pub reason: Option<String>,
+    /// Last cycle with nonzero quotes from two DEXes, or when the pair was
+    /// first seen or reactivated.
+    pub last_quoted: String,
+    /// Last liquidity check with two pools above the floor, likewise.
+    pub last_liquid: String,
+}
+
+struct Entry {
+    last_quoted: DateTime<Utc>,
+    last_liquid: DateTime<Utc>,
+    suspended: Option<(DateTime<Utc>, String)>,
+}
+
+impl Entry {
+    fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            last_quoted: now,
+            last_liquid: now,
+            suspended: None,
+        }
+    }
+}
+
+/// Liveness per instance and pair.
+pub struct Suspensions {
+    pairs: Mutex<BTreeMap<(String, Option<String>), Entry>>,
+}
+
+pub static SUSPENSIONS: Suspensions = Suspensions {
+    pairs: Mutex::new(BTreeMap::new()),
+};
+
+impl Suspensions {
+    /// Records whether a cycle of the pair quoted on two DEXes; returns the
+    /// reason when this suspends it. `dead_after` of zero never suspends.
+    pub fn quoted(
+        &self,
+        instance: &str,
+        pair: Option<&str>,
+        alive: bool,
+        now: DateTime<Utc>,
+        dead_after: Duration,
+    ) -> Option<String> {
+        self.observe(instance, pair, now, dead_after, |entry| {
+            if alive {
+                entry.last_quoted = now;
+            }
+            (entry.last_quoted, "no nonzero quotes from two DEXes")
+        })
+    }
+
+    /// Records whether two of the pair's pools held the liquidity floor;
+    /// returns the reason when this suspends it.
+    pub fn liquid(
+        &self,
+        instance: &str,
+        pair: Option<&str>,
+        alive: bool,
+        now: DateTime<Utc>,
+        dead_after: Duration,
+    ) -> Option<String> {
+        self.observe(instance, pair, now, dead_after, |entry| {
+            if alive {
+                entry.last_liquid = now;
+            }
+            (
+                entry.last_liquid,
+                "fewer than two pools above the liquidity floor",
+            )
+        })
+    }
+
+    fn observe(
+        &self,
+        instance: &str,
+        pair: Option<&str>,
+        now: DateTime<Utc>,
+        dead_after: Duration,
+        update: impl FnOnce(&mut Entry) -> (DateTime<Utc>, &'static str),
+    ) -> Option<String> {
+        let mut pairs = self.pairs.lock().unwrap();
+        let entry = pairs
+            .entry((instance.to_string(), pair.map(str::to_string)))
+            .or_insert_with(|| Entry::new(now));
+        let (since, what) = update(entry);
+        if dead_after <= Duration::zero() || entry.suspended.is_some() || now - since < dead_after {
+            return None;
+        }
+        let reason = format!("{} since {}", what, since.to_rfc3339());
+        entry.suspended = Some((now, reason.clone()));
+        Some(reason)
+    }
+
+    pub fn is_suspended(&self, instance: &str, pair: Option<&str>) -> bool {
+        self.pairs
+            .lock()
+            .unwrap()
+            .get(&(instance.to_string(), pair.map(str::to_string)))
+            .is_some_and(|entry| entry.suspended.is_some())
+    }
+
+    /// Lifts the pair's suspension and restarts its dead period; returns
+    /// whether it was suspended.
+    pub fn reactivate(&self, instance: &str, pair: Option<&str>, now: DateTime<Utc>) -> bool {
+        let mut pairs = self.pairs.lock().unwrap();
+        let entry = pairs
+            .entry((instance.to_string(), pair.map(str::to_string)))
+            .or_insert_with(|| Entry::new(now));
+        let was_suspended = entry.suspended.is_some();
+        *entry = Entry::new(now);
+        was_suspended
+    }
+
+    /// Status of the pair, if any of its cycles or checks ran yet.
+    pub fn status(&self, instance: &str, pair: Option<&str>) -> Option<PairStatus> {
+        self.pairs
+            .lock()
+            .unwrap()
+            .get(&(instance.to_string(), pair.map(str::to_string)))
+            .map(|entry| PairStatus {
+                suspended: entry.suspended.is_some(),
+                suspended_at: entry.suspended.as_ref().map(|(at, _)| at.to_rfc3339()),
+                reason: entry.suspended.as_ref().map(|(_, reason)| reason.clone()),
+                last_quoted: entry.last_quoted.to_rfc3339(),
+                last_liquid: entry.last_liquid.to_rfc3339(),
+            })
+    }
+}
@@ -0,0 +1,111 @@
+//! Well-known Polygon PoS tokens
+//!
+//! Lets configuration name amounts in token units (`1000 USDC`) without an
+//! RPC round trip for `decimals()`, and knows each token's Chainlink USD feed.
+
+use ethers::types::Address;
+
+pub struct Token {
+    pub symbol: &'static str,
+    /// Other names the token goes by.
+    pub aliases: &'static [&'static str],
+    pub address: &'static str,
+    pub decimals: u32,
+    /// Chainlink USD aggregator of the token (MATIC/USD for WPOL, BTC/USD
+    /// for WBTC).
+    pub usd_feed: Option<&'static str>,
+}
+
+pub const KNOWN_TOKENS: &[Token] = &[
+    Token {
+        symbol: "WETH",
+        aliases: &["ETH"],
+        address: "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619",
+        decimals: 18,
+        usd_feed: Some("0xF9680D99D6C9589e2a93a78A04A279e509205945"),
+    },
+    Token {
+        symbol: "WPOL",
+        aliases: &["WMATIC", "POL", "MATIC"],
+        address: "0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270",
+        decimals: 18,
+        usd_feed: Some("0xAB594600376Ec9fD91F8e885dADF0CE036862dE0"),
+    },
+    Token {
+        symbol: "USDC",
+        aliases: &[],
+        address: "0x3c499c542cEF5E3811e1192ce70d8cC03d5c3359",
+        decimals: 6,
+        usd_feed: Some("0xfE4A8cc5b5B2366C1B58Bea3858e81843581b2F7"),
+    },
+    Token {
+        symbol: "USDC.e",
+        aliases: &["USDC"],
+        address: "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174",
+        decimals: 6,
+        usd_feed: Some("0xfE4A8cc5b5B2366C1B58Bea3858e81843581b2F7"),
+    },
+    Token {
+        symbol: "USDT",
+        aliases: &[],
+        address: "0xc2132D05D31c914a87C6611C10748AEb04B58e8F",
+        decimals: 6,
+        usd_feed: Some("0x0A6513e40db6EB1b165753AD52E80663aeA50545"),
+    },
+    Token {
+        symbol: "DAI",
+        aliases: &[],
+        address: "0x8f3Cf7ad23Cd3CaDbD9735AFf958023239c6A063",
+        decimals: 18,
+        usd_feed: Some("0x4746DeC9e833A82EC7C2C1356372CcF2cfcD2F3D"),
+    },
+    Token {
+        symbol: "WBTC",
+        aliases: &["BTC"],
+        address: "0x1BFD67037B42Cf73acF2047067bd4F2C47D9BfD6",
+        decimals: 8,
+        usd_feed: Some("0xc907E116054Ad103354f2D350FD2514433D57F6f"),
+    },
+];
+
+impl Token {
+    pub fn address(&self) -> Address {
+        self.address
+            .parse()
+            .expect("known token addresses are valid")
+    }
+
+    pub fn usd_feed(&self) -> Option<Address> {
+        self.usd_feed
+            .map(|feed| feed.parse().expect("known feed addresses are valid"))
+    }
+
+    /// Whether `name` is the symbol or an alias, ignoring case.
+    pub fn is_named(&self, name: &str) -> bool {
+        std::iter::once(&self.symbol)
+            .chain(self.aliases)
+            .any(|n| n.eq_ignore_ascii_case(name))
+    }
+}
+
+pub fn by_address(address: Address) -> Option<&'static Token> {
+    KNOWN_TOKENS.iter().find(|t| t.address() == address)
+}
+
+/// Symbol of a known token, the address otherwise.
+pub fn label(address: Address) -> String {
+    by_address(address).map_or_else(|| format!("{:?}", address), |t| t.symbol.to_string())
+}
+
+/// First token whose symbol or alias is `name`.
+pub fn by_name(name: &str) -> Option<&'static Token> {
+    KNOWN_TOKENS.iter().find(|t| t.is_named(name))
+}
+
+/// Symbols of the USD stablecoins, valued at one USD without a quote.
+pub const STABLECOINS: &[&str] = &["USDC", "USDC.e", "USDT", "DAI"];
+
+/// Whether `address` is one of the known [`STABLECOINS`].
+pub fn is_stablecoin(address: Address) -> bool {
+    by_address(address).is_some_and(|t| STABLECOINS.contains(&t.symbol))
+}
@@ -0,0 +1,192 @@
+//! Gas wallet low-watermark alerts and opt-in top-ups
+//!
+//! On `GAS_CHECK_CRON` the POL balance of `GAS_WALLET` is compared with
+//! `GAS_FLOOR_POL`. When it drops below, an alert suggests swapping
+//! `GAS_TOP_UP_AMOUNT` of `GAS_TOP_UP_TOKEN` into POL on the first DEX, with
+//! the quoted output. Only with `GAS_TOP_UP_AUTO` and `GAS_TOP_UP_PRIVATE_KEY`
+//! is the swap sent (`swapExactTokensForETH` with a 1% slippage guard,
+//! approving the router first when needed). Each dip below the floor alerts
+//! once, and every suggestion or swap is recorded in `gas_top_ups`.
+
+use crate::contracts::{UniswapV2Router, ERC20};
+use crate::{uniswap, units};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use ethers::middleware::SignerMiddleware;
+use ethers::providers::Middleware;
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, U256};
+use rusqlite::{params, Connection};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Seconds a sent swap stays valid.
+const DEADLINE_SECS: i64 = 300;
+
+pub struct TopUpConfig {
+    pub wallet: Address,
+    pub floor_pol: f64,
+    /// Stable token swapped into POL.
+    pub token: Address,
+    /// Raw units of `token` per top-up; alerts carry no suggestion without it.
+    pub amount: Option<U256>,
+    pub router: Address,
+    pub wrapped_native: Address,
+    /// Signs the swap when auto top-up is on; only suggestions are made without it.
+    pub signer: Option<LocalWallet>,
+}
+
+pub struct GasTopUp<M> {
+    client: Arc<M>,
+    cfg: TopUpConfig,
+    /// Set once the current dip below the floor was handled.
+    handled: AtomicBool,
+}
+
+impl<M: Middleware + Clone + 'static> GasTopUp<M> {
+    pub fn new(client: Arc<M>, cfg: TopUpConfig) -> Self {
+        Self {
+            client,
+            cfg,
+            handled: AtomicBool::new(false),
+        }
+    }
+
+    /// Checks the balance and returns the alert to send, if it just fell
+    /// below the floor.
+    pub async fn check(
+        &self,
+        conn: &std::sync::Mutex<Connection>,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Option<String>> {
+        let balance = self.client.get_balance(self.cfg.wallet, None).await?;
+        let balance_pol = units::to_f64(balance, 18);
+        if balance_pol >= self.cfg.floor_pol {
+            self.handled.store(false, Ordering::Relaxed);
+            return Ok(None);
+        }
+        if self.handled.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+
+        let mut message = format!(
+            "Gas wallet {:?} holds {:.4} POL, below the {} POL floor",
+            self.cfg.wallet, balance_pol, self.cfg.floor_pol
+        );
+        let Some(amount) = self.cfg.amount else {
+            self.handled.store(true, Ordering::Relaxed);
+            return Ok(Some(message));
+        };
+        let path = vec![self.cfg.token, self.cfg.wrapped_native];
+        let price = uniswap::get_price(
+            Arc::clone(&self.client),
+            self.cfg.router,
+            self.cfg.token,
+            self.cfg.wrapped_native,
+            amount,
+        )
+        .await?;
+        let expected = price.amount_out;
+        let (amount_f, expected_f) = (
+            price.token_in.decimal(amount),
+            price.token_out.decimal(expected),
+        );
+
+        let (status, tx_hash) = match &self.cfg.signer {
+            Some(signer) => match self.swap(signer, amount, expected, path, now).await {
+                Ok(tx_hash) => {
+                    message += &format!(
+                        "; swapped {} {:?} for ~{} POL in {}",
+                        amount_f, self.cfg.token, expected_f, tx_hash
+                    );
+                    ("sent", Some(tx_hash))
+                }
+                Err(e) => {
+                    message += &format!("; top-up swap failed: {:#}", e);
+                    ("failed", None)
+                }
+            },
+            None => {
+                message += &format!(
+                    "; suggest swapping {} {:?} for ~{} POL on {:?}",
+                    amount_f, self.cfg.token, expected_f, self.cfg.router
+                );
+                ("suggested", None)
+            }
+        };
+        record(
+            &conn.lock().unwrap(),
+            now,
+            self.cfg.wallet,
+            balance_pol,
+            &amount_f,
+            &expected_f,
+            status,
+            tx_hash.as_deref(),
+        )?;
+        // A failed swap is retried on the next check.
+        self.handled.store(status != "failed", Ordering::Relaxed);
+        Ok(Some(message))
+    }
+
+    /// Sends the swap and returns its transaction hash once mined.
+    async fn swap(
+        &self,
+        signer: &LocalWallet,
+        amount: U256,
+        expected: U256,
+        path: Vec<Address>,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<String> {
+        let chain_id = self.client.get_chainid().await?.as_u64();
+        let signer = signer.clone().with_chain_id(chain_id);
+        let from = signer.address();
+        let client = Arc::new(SignerMiddleware::new((*self.client).clone(), signer));
+
+        let token = ERC20::new(self.cfg.token, Arc::clone(&client));
+        if token.allowance(from, self.cfg.router).call().await? < amount {
+            let approve = token.approve(self.cfg.router, amount);
+            let pending = approve.send().await?;
+            pending.await?.context("approval was dropped")?;
+        }
+
+        let router = UniswapV2Router::new(self.cfg.router, client);
+        let min_out = expected * 99 / 100;
+        let deadline = U256::from(now.timestamp() + DEADLINE_SECS);
+        let swap =
+            router.swap_exact_tokens_for_eth(amount, min_out, path, self.cfg.wallet, deadline);
+        let pending = swap.send().await?;
+        let receipt = pending.await?.context("top-up swap was dropped")?;
+        if receipt.status != Some(1u64.into()) {
+            anyhow::bail!("top-up swap {:?} reverted", receipt.transaction_hash);
+        }
+        Ok(format!("{:?}", receipt.transaction_hash))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn record(
+    conn: &Connection,
+    now: DateTime<Utc>,
+    wallet: Address,
+    balance_pol: f64,
+    amount_in: &str,
+    expected_pol: &str,
+    status: &str,
+    tx_hash: Option<&str>,
+) -> anyhow::Result<()> {
+    conn.execute(
+        "INSERT INTO gas_top_ups (timestamp, wallet, balance_pol, amount_in, expected_pol, status, tx_hash)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            now.to_rfc3339(),
+            format!("{:?}", wallet),
+            balance_pol,
+            amount_in,
+            expected_pol,
+            status,
+            tx_hash
+        ],
+    )?;
+    Ok(())
+}
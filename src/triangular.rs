@@ -0,0 +1,46 @@
+//! Triangular arbitrage within a single DEX
+//!
+//! For every V2 router and every ordered pair (B, C) of `TRIANGULAR_TOKENS`,
+//! the cycle `token_in`→B→C→`token_in` is quoted with one multi-hop
+//! `getAmountsOut` call at `TRADE_SIZE_WEI`. The best cycle of a block is
+//! judged like a two-DEX round trip: its `token_in` surplus, valued at the
+//! pair's best quote, net of gas. Opportunities are stored with the
+//! `triangular` strategy.
+
+use crate::dex::v2::V2Route;
+use crate::dex::DexQuoter;
+use crate::tokens;
+use ethers::types::Address;
+
+/// Short label of a token: its symbol when known, its address otherwise.
+/// One route per V2 venue of `dexes` and ordered pair of distinct `tokens`
+/// other than `base`, named `<venue> via <B>/<C>`.
+pub fn routes(dexes: &[Box<dyn DexQuoter>], base: Address, tokens: &[Address]) -> Vec<V2Route> {
+    let mut hops: Vec<Address> = Vec::new();
+    for &token in tokens {
+        if token != base && !hops.contains(&token) {
+            hops.push(token);
+        }
+    }
+    let mut routes = Vec::new();
+    for dex in dexes {
+        let Some(router) = dex.v2_router() else {
+            continue;
+        };
+        for &b in &hops {
+            for &c in hops.iter().filter(|&&c| c != b) {
+                routes.push(V2Route::new(
+                    format!(
+                        "{} via {}/{}",
+                        dex.name(),
+                        tokens::label(b),
+                        tokens::label(c)
+                    ),
+                    router,
+                    vec![b, c],
+                ));
+            }
+        }
+    }
+    routes
+}
@@ -1,44 +1,72 @@
-use ethers::abi::Abi;
-use ethers::contract::Contract;
-use ethers::providers::Provider;
-use ethers::types::{Address, U256};
-use std::sync::Arc;
-
-// Uniswap V2 ABI (only getAmountsOut function is needed)
-const UNISWAP_V2_ABI: &str = r#"[{
-    "name": "getAmountsOut",
-    "type": "function",
-    "stateMutability": "view",
-    "inputs": [
-        {"name": "amountIn", "type": "uint256"},
-        {"name": "path", "type": "address[]"}
-    ],
-    "outputs": [
-        {"name": "", "type": "uint256[]"}
-    ]
-}]"#;
-
-pub async fn get_price(
-    provider: &Provider<ethers::providers::Http>,
-    router: Address,
-    token_in: Address,
-    token_out: Address,
-    amount_in: U256,
-) -> anyhow::Result<f64> {
-    let client = Arc::new(provider.clone());
-    let abi: Abi = serde_json::from_str(UNISWAP_V2_ABI)?;
-    let contract = Contract::new(router, abi, client);
-
-    let path = vec![token_in, token_out];
-    let amounts: Vec<U256> = contract
-        .method::<_, Vec<U256>>("getAmountsOut", (amount_in, path))?
-        .call()
-        .await?;
-
-    // amount_out is in smallest unit (wei, USDC 6 decimals etc.)
-    let amount_out = amounts[1];
-    let price = amount_out.as_u128() as f64 / 1e6; // assuming token_out = USDC (6 decimals)
-
-    Ok(price)
-}
-
+//! One-off V2 router quotes with token metadata
+//!
+//! [`get_price`] asks a router's `getAmountsOut` directly, outside the
+//! per-block planner, and returns a [`Price`] that carries each token's
+//! [`TokenInfo`], so callers never assume a token like USDC. [`get_path_price`]
+//! quotes a multi-hop path the same way, e.g. token_out → WETH → USDC to
+//! value a token with no direct stablecoin pool.
+
+use crate::amounts::{self, TokenInfo};
+use crate::contracts::UniswapV2Router;
+use ethers::providers::Middleware;
+use ethers::types::{Address, U256};
+use std::sync::Arc;
+
+/// A quoted trade of `amount_in` token_in for `amount_out` token_out.
+#[derive(Debug, Clone)]
+pub struct Price {
+    pub token_in: TokenInfo,
+    pub token_out: TokenInfo,
+    pub amount_in: U256,
+    pub amount_out: U256,
+}
+
+impl Price {
+    /// Token_out per whole token_in at this size.
+    pub fn per_unit(&self) -> f64 {
+        let amount_in = self.token_in.to_f64(self.amount_in);
+        if amount_in == 0.0 {
+            return 0.0;
+        }
+        self.token_out.to_f64(self.amount_out) / amount_in
+    }
+}
+
+pub async fn get_price<M: Middleware + 'static>(
+    client: Arc<M>,
+    router: Address,
+    token_in: Address,
+    token_out: Address,
+    amount_in: U256,
+) -> anyhow::Result<Price> {
+    get_path_price(client, router, &[token_in, token_out], amount_in).await
+}
+
+/// Quote of `amount_in` of the first token of `path` swapped hop by hop into
+/// the last one.
+pub async fn get_path_price<M: Middleware + 'static>(
+    client: Arc<M>,
+    router: Address,
+    path: &[Address],
+    amount_in: U256,
+) -> anyhow::Result<Price> {
+    let (Some(&token_in), Some(&token_out)) = (path.first(), path.last()) else {
+        anyhow::bail!("empty swap path");
+    };
+    anyhow::ensure!(path.len() >= 2, "swap path needs at least two tokens");
+    let contract = UniswapV2Router::new(router, Arc::clone(&client));
+    let amount_out = contract
+        .get_amounts_out(amount_in, path.to_vec())
+        .call()
+        .await?
+        .last()
+        .copied()
+        .unwrap_or_default();
+
+    Ok(Price {
+        token_in: amounts::load(Arc::clone(&client), token_in).await?,
+        token_out: amounts::load(client, token_out).await?,
+        amount_in,
+        amount_out,
+    })
+}
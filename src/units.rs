@@ -0,0 +1,198 @@
+//! Conversions between raw token amounts and decimal units
+//!
+//! Work on the full `U256` range for any number of decimals by going through
+//! the exact decimal representation instead of `u128`.
+
+use anyhow::bail;
+use ethers::types::U256;
+use std::time::Duration;
+
+/// Exact decimal string of `value` scaled down by `decimals`, without
+/// trailing zeros (`1500000` with 6 decimals is `"1.5"`).
+pub fn format_units(value: U256, decimals: u32) -> String {
+    let digits = value.to_string();
+    let decimals = decimals as usize;
+    if decimals == 0 {
+        return digits;
+    }
+    let padded = format!("{:0>width$}", digits, width = decimals + 1);
+    let (int, frac) = padded.split_at(padded.len() - decimals);
+    let frac = frac.trim_end_matches('0');
+    if frac.is_empty() {
+        int.to_string()
+    } else {
+        format!("{}.{}", int, frac)
+    }
+}
+
+/// Parses a non-negative decimal amount into raw units. More fractional
+/// digits than `decimals` or a result above `U256::MAX` is an error.
+pub fn parse_units(amount: &str, decimals: u32) -> anyhow::Result<U256> {
+    let amount = amount.trim();
+    let (int, frac) = amount.split_once('.').unwrap_or((amount, ""));
+    if int.is_empty() && frac.is_empty() {
+        bail!("empty amount");
+    }
+    if !int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit()) {
+        bail!("invalid amount {:?}", amount);
+    }
+    if frac.len() > decimals as usize {
+        bail!(
+            "amount {:?} has more than {} decimal places",
+            amount,
+            decimals
+        );
+    }
+    let digits = format!(
+        "{}{}{}",
+        int,
+        frac,
+        "0".repeat(decimals as usize - frac.len())
+    );
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Ok(U256::zero());
+    }
+    U256::from_dec_str(digits).map_err(|_| anyhow::anyhow!("amount {:?} overflows U256", amount))
+}
+
+/// Nearest `f64` to `value` scaled down by `decimals`.
+pub fn to_f64(value: U256, decimals: u32) -> f64 {
+    format_units(value, decimals)
+        .parse()
+        .expect("format_units yields a valid decimal")
+}
+
+/// Parses a duration such as `15s`, `500ms`, `2m`, `1h` or `1d` (decimals
+/// allowed, e.g. `1.5s`); a bare number is taken in `bare` units.
+pub fn parse_duration(value: &str, bare: Duration) -> anyhow::Result<Duration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let Ok(number) = number.parse::<f64>() else {
+        bail!("invalid duration {:?}", value);
+    };
+    let unit = match unit.trim() {
+        "" => bare,
+        "ms" => Duration::from_millis(1),
+        "s" | "sec" | "secs" => Duration::from_secs(1),
+        "m" | "min" | "mins" => Duration::from_secs(60),
+        "h" => Duration::from_secs(3600),
+        "d" => Duration::from_secs(86_400),
+        other => bail!("unknown duration unit {:?} (ms, s, m, h, d)", other),
+    };
+    Duration::try_from_secs_f64(unit.as_secs_f64() * number)
+        .map_err(|_| anyhow::anyhow!("duration {:?} is out of range", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DECIMALS: [u32; 4] = [0, 6, 18, 77];
+
+    /// Deterministic xorshift64 stream, so failures reproduce.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// Values of every magnitude: 1 to 4 random 64-bit limbs, shifted
+        /// down by a random number of bits.
+        fn u256(&mut self) -> U256 {
+            let limbs = (self.next() % 4 + 1) as usize;
+            let mut words = [0u64; 4];
+            for word in words.iter_mut().take(limbs) {
+                *word = self.next();
+            }
+            U256(words) >> (self.next() % 64) as usize
+        }
+    }
+
+    fn assert_round_trip(value: U256, decimals: u32) {
+        let formatted = format_units(value, decimals);
+        let parsed = parse_units(&formatted, decimals)
+            .unwrap_or_else(|e| panic!("{} at {} decimals: {:?}", formatted, decimals, e));
+        assert_eq!(parsed, value, "{} at {} decimals", formatted, decimals);
+    }
+
+    #[test]
+    fn round_trips_random_values() {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..2000 {
+            let value = rng.u256();
+            for decimals in DECIMALS {
+                assert_round_trip(value, decimals);
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_edge_values() {
+        let above_u128 = U256::from(u128::MAX) + 1;
+        let edges = [
+            U256::zero(),
+            U256::one(),
+            U256::from(u128::MAX),
+            above_u128,
+            above_u128 * 1000 + 7,
+            U256::MAX - 1,
+            U256::MAX,
+        ];
+        for value in edges {
+            for decimals in DECIMALS {
+                assert_round_trip(value, decimals);
+            }
+        }
+    }
+
+    #[test]
+    fn formats_without_trailing_zeros() {
+        assert_eq!(format_units(U256::from(1_500_000), 6), "1.5");
+        assert_eq!(format_units(U256::from(1_000_000), 6), "1");
+        assert_eq!(format_units(U256::from(5), 18), "0.000000000000000005");
+        assert_eq!(format_units(U256::zero(), 77), "0");
+        assert_eq!(
+            format_units(U256::MAX, 77),
+            "1.15792089237316195423570985008687907853269984665640564039457584007913129639935"
+        );
+    }
+
+    #[test]
+    fn rejects_values_above_u256() {
+        let max = U256::MAX.to_string();
+        assert!(parse_units(&format!("{}0", max), 0).is_err());
+        assert!(parse_units(&max, 1).is_err());
+        assert_eq!(parse_units(&max, 0).unwrap(), U256::MAX);
+    }
+
+    #[test]
+    fn rejects_malformed_amounts() {
+        assert!(parse_units("", 6).is_err());
+        assert!(parse_units(".", 6).is_err());
+        assert!(parse_units("-1", 6).is_err());
+        assert!(parse_units("1.2345678", 6).is_err());
+        assert!(parse_units("1e6", 6).is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_range_durations() {
+        let hour = Duration::from_secs(3600);
+        assert_eq!(
+            parse_duration("1.5s", hour).unwrap(),
+            Duration::from_millis(1500)
+        );
+        assert_eq!(
+            parse_duration("2", hour).unwrap(),
+            Duration::from_secs(7200)
+        );
+        assert!(parse_duration("99999999999999999999d", hour).is_err());
+    }
+}
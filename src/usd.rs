@@ -0,0 +1,237 @@
+//! USD value of profits, with a CEX ticker as failover for the oracle
+//!
+//! Profits are computed in `token_out`. With `USD_PRICE_FEED` (a Chainlink
+//! token_out/USD aggregator) they are also converted to USD. When the feed
+//! errors, reports a non-positive answer or has not updated for
+//! `USD_FEED_MAX_AGE_SECS`, or is not set, the price is quoted along
+//! `USD_PRICE_ROUTE` (token_out → … → a stablecoin taken at par, on the first
+//! DEX's router), and failing that taken from `USD_FALLBACK_TICKER`
+//! (`binance:USDCUSDT` or `coinbase:USDC-USD`). Opportunities store the source
+//! next to the converted value.
+
+use crate::amounts::TokenInfo;
+use crate::contracts::AggregatorV3;
+use crate::uniswap;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use ethers::providers::Middleware;
+use ethers::types::{Address, U256};
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// USD per `token_out` and where it came from.
+#[derive(Debug, Clone, Copy)]
+pub struct UsdPrice {
+    pub price: f64,
+    pub source: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Exchange {
+    Binance,
+    Coinbase,
+}
+
+pub struct CexTicker {
+    exchange: Exchange,
+    symbol: String,
+}
+
+impl CexTicker {
+    /// Parses `binance:SYMBOL` or `coinbase:BASE-QUOTE`.
+    pub fn parse(spec: &str) -> anyhow::Result<Self> {
+        let (exchange, symbol) = spec
+            .split_once(':')
+            .with_context(|| format!("ticker {:?} is not exchange:symbol", spec))?;
+        let exchange = match exchange.trim().to_ascii_lowercase().as_str() {
+            "binance" => Exchange::Binance,
+            "coinbase" => Exchange::Coinbase,
+            other => anyhow::bail!("unsupported exchange {:?} (binance, coinbase)", other),
+        };
+        Ok(Self {
+            exchange,
+            symbol: symbol.trim().to_string(),
+        })
+    }
+
+    async fn price(&self, http: &reqwest::Client) -> anyhow::Result<UsdPrice> {
+        #[derive(Deserialize)]
+        struct BinanceTicker {
+            price: String,
+        }
+        #[derive(Deserialize)]
+        struct CoinbaseSpot {
+            data: CoinbaseAmount,
+        }
+        #[derive(Deserialize)]
+        struct CoinbaseAmount {
+            amount: String,
+        }
+
+        let (price, source) = match self.exchange {
+            Exchange::Binance => {
+                let url = format!(
+                    "https://api.binance.com/api/v3/ticker/price?symbol={}",
+                    self.symbol
+                );
+                let ticker: BinanceTicker = http
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                (ticker.price, "binance")
+            }
+            Exchange::Coinbase => {
+                let url = format!("https://api.coinbase.com/v2/prices/{}/spot", self.symbol);
+                let spot: CoinbaseSpot = http
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                (spot.data.amount, "coinbase")
+            }
+        };
+        Ok(UsdPrice {
+            price: price.parse()?,
+            source,
+        })
+    }
+}
+
+/// One token_out quoted on a V2 router along a path ending in a stablecoin.
+pub struct DexRoute<M> {
+    client: Arc<M>,
+    router: Address,
+    /// token_out first.
+    path: Vec<Address>,
+    /// One whole token_out.
+    one: U256,
+}
+
+impl<M: Middleware + 'static> DexRoute<M> {
+    /// Routes `token_out` through `hops`, the last of which is the stablecoin.
+    pub fn new(client: Arc<M>, router: Address, token_out: &TokenInfo, hops: &[Address]) -> Self {
+        Self {
+            client,
+            router,
+            path: std::iter::once(token_out.address)
+                .chain(hops.iter().copied())
+                .collect(),
+            one: U256::exp10(token_out.decimals as usize),
+        }
+    }
+
+    async fn price(&self) -> anyhow::Result<UsdPrice> {
+        let price =
+            uniswap::get_path_price(Arc::clone(&self.client), self.router, &self.path, self.one)
+                .await?
+                .per_unit();
+        if price <= 0.0 {
+            anyhow::bail!("route quoted {}", price);
+        }
+        Ok(UsdPrice {
+            price,
+            source: "dex",
+        })
+    }
+}
+
+pub struct UsdPricer<M> {
+    feed: Option<(AggregatorV3<M>, u8)>,
+    route: Option<DexRoute<M>>,
+    ticker: Option<CexTicker>,
+    max_age_secs: i64,
+    http: reqwest::Client,
+}
+
+impl<M: Middleware + 'static> UsdPricer<M> {
+    pub async fn new(
+        client: Arc<M>,
+        feed: Option<Address>,
+        route: Option<DexRoute<M>>,
+        ticker: Option<CexTicker>,
+        max_age_secs: u64,
+    ) -> anyhow::Result<Self> {
+        let feed = match feed {
+            Some(address) => {
+                let feed = AggregatorV3::new(address, client);
+                let decimals = feed
+                    .decimals()
+                    .call()
+                    .await
+                    .context("Failed to load USD price feed")?;
+                Some((feed, decimals))
+            }
+            None => None,
+        };
+        Ok(Self {
+            feed,
+            route,
+            ticker,
+            max_age_secs: max_age_secs as i64,
+            http: reqwest::Client::new(),
+        })
+    }
+
+    /// Oracle price when it is healthy, else the DEX route's quote, else the
+    /// CEX ticker.
+    pub async fn price(&self, now: DateTime<Utc>) -> anyhow::Result<UsdPrice> {
+        if let Some(feed) = &self.feed {
+            match self.oracle_price(feed, now).await {
+                Ok(price) => return Ok(price),
+                Err(e) if self.route.is_some() || self.ticker.is_some() => {
+                    log::warn!("USD price feed unavailable, using a fallback: {:?}", e)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        if let Some(route) = &self.route {
+            match route.price().await {
+                Ok(price) => return Ok(price),
+                Err(e) if self.ticker.is_some() => {
+                    log::warn!("USD price route failed, using CEX ticker: {:?}", e)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        match &self.ticker {
+            Some(ticker) => ticker.price(&self.http).await,
+            None => anyhow::bail!("no USD price source configured"),
+        }
+    }
+
+    async fn oracle_price(
+        &self,
+        (feed, decimals): &(AggregatorV3<M>, u8),
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<UsdPrice> {
+        Ok(UsdPrice {
+            price: read_feed(feed, *decimals, now, self.max_age_secs).await?,
+            source: "chainlink",
+        })
+    }
+}
+
+/// Latest answer of a Chainlink aggregator reporting with `decimals`; fails
+/// when it is older than `max_age_secs` or not positive.
+pub async fn read_feed<M: Middleware + 'static>(
+    feed: &AggregatorV3<M>,
+    decimals: u8,
+    now: DateTime<Utc>,
+    max_age_secs: i64,
+) -> anyhow::Result<f64> {
+    let (_, answer, _, updated_at, _) = feed.latest_round_data().call().await?;
+    let age = now.timestamp() - updated_at.low_u64() as i64;
+    if age > max_age_secs {
+        anyhow::bail!("feed last updated {}s ago", age);
+    }
+    let price = answer.as_i128() as f64 / 10f64.powi(decimals as i32);
+    if price <= 0.0 {
+        anyhow::bail!("feed answered {}", price);
+    }
+    Ok(price)
+}
@@ -0,0 +1,61 @@
+//! Warm-up after start and RPC failover
+//!
+//! Right after a start, or once the RPC client switched to another endpoint,
+//! quotes come from cold caches and baselines and one bad quote can look like
+//! a large spread. Opportunities are therefore only stored, alerted and
+//! executed once `WARMUP_CYCLES` consecutive cycles quoted at least two DEXes;
+//! until then they are rejected as `warming_up`. A failed cycle or another
+//! endpoint switch starts the count over.
+
+use crate::rpc;
+
+pub struct Warmup {
+    cycles: u32,
+    consistent: u32,
+    switches: u64,
+}
+
+impl Warmup {
+    pub fn new(cycles: u32) -> Self {
+        Self {
+            cycles,
+            consistent: 0,
+            switches: rpc::endpoint_switches(),
+        }
+    }
+
+    /// Counts one cycle, `consistent` when it quoted enough DEXes; returns
+    /// whether its opportunities may go out.
+    pub fn observe(&mut self, instance: &str, consistent: bool) -> bool {
+        let switches = rpc::endpoint_switches();
+        if switches != self.switches {
+            self.switches = switches;
+            if self.cycles > 0 {
+                log::warn!(
+                    "[{}] RPC endpoint switched; warming up for {} cycles",
+                    instance,
+                    self.cycles
+                );
+            }
+            self.consistent = 0;
+        }
+        if !consistent {
+            self.consistent = 0;
+            return self.cycles == 0;
+        }
+        self.consistent = self.consistent.saturating_add(1);
+        if self.consistent == self.cycles {
+            log::info!(
+                "[{}] Warmed up after {} consistent cycles",
+                instance,
+                self.cycles
+            );
+        }
+        self.consistent >= self.cycles
+    }
+
+    /// Starts the count over, e.g. after a failed cycle.
+    pub fn reset(&mut self) {
+        self.consistent = 0;
+    }
+}
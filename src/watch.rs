@@ -0,0 +1,154 @@
+//! Watch-only tracking of another wallet's arbitrage
+//!
+//! Scans each new block for transactions sent by (or to) the watched address
+//! and reads the ERC20 `Transfer` logs of the configured pair to work out what
+//! the wallet put in and got back. Round trips on `token_in` are logged with
+//! the spread they captured and stored in `watched_swaps`, so our own
+//! detections can be benchmarked against a known competitor.
+
+use crate::amounts::TokenInfo;
+use chrono::{DateTime, Utc};
+use ethers::providers::Middleware;
+use ethers::types::{Address, Transaction, TransactionReceipt, H256, U256, U64};
+use ethers::utils::keccak256;
+use once_cell::sync::Lazy;
+use rusqlite::{params, Connection};
+use std::sync::Arc;
+
+static TRANSFER_TOPIC: Lazy<H256> =
+    Lazy::new(|| H256::from(keccak256("Transfer(address,address,uint256)")));
+
+/// Blocks scanned per poll at most; the watcher skips ahead after downtime.
+const MAX_BLOCKS_PER_POLL: u64 = 20;
+
+pub struct WatchedSwap {
+    pub tx_hash: H256,
+    pub block_number: u64,
+    /// `token_in` the wallet sent out.
+    pub sent: U256,
+    /// `token_in` the wallet got back.
+    pub received: U256,
+    /// Whether `token_out` moved through the wallet in the same transaction.
+    pub touched_out: bool,
+    pub gas_used: Option<U256>,
+}
+
+impl WatchedSwap {
+    /// Net return on the amount sent, in basis points.
+    pub fn spread_bps(&self, token_in: &TokenInfo) -> f64 {
+        let sent = token_in.to_f64(self.sent);
+        let received = token_in.to_f64(self.received);
+        (received - sent) / sent * 10_000.0
+    }
+}
+
+pub struct WalletWatcher<M> {
+    client: Arc<M>,
+    wallet: Address,
+    token_in: Address,
+    token_out: Address,
+    last_block: Option<u64>,
+}
+
+impl<M: Middleware + 'static> WalletWatcher<M> {
+    pub fn new(client: Arc<M>, wallet: Address, token_in: Address, token_out: Address) -> Self {
+        Self {
+            client,
+            wallet,
+            token_in,
+            token_out,
+            last_block: None,
+        }
+    }
+
+    /// Returns the wallet's round trips on the configured pair since the last poll.
+    pub async fn poll(&mut self) -> anyhow::Result<Vec<WatchedSwap>> {
+        let latest = self.client.get_block_number().await?.as_u64();
+        let from = match self.last_block {
+            Some(last) => (last + 1).max(latest.saturating_sub(MAX_BLOCKS_PER_POLL - 1)),
+            None => latest,
+        };
+        let mut swaps = Vec::new();
+        for number in from..=latest {
+            let Some(block) = self.client.get_block_with_txs(number).await? else {
+                continue;
+            };
+            for tx in block.transactions.iter().filter(|tx| self.is_watched(tx)) {
+                let Some(receipt) = self.client.get_transaction_receipt(tx.hash).await? else {
+                    continue;
+                };
+                if receipt.status != Some(U64::from(1)) {
+                    continue;
+                }
+                if let Some(swap) = self.decode(number, &receipt) {
+                    swaps.push(swap);
+                }
+            }
+        }
+        self.last_block = Some(latest);
+        Ok(swaps)
+    }
+
+    fn is_watched(&self, tx: &Transaction) -> bool {
+        tx.from == self.wallet || tx.to == Some(self.wallet)
+    }
+
+    fn decode(&self, block_number: u64, receipt: &TransactionReceipt) -> Option<WatchedSwap> {
+        let mut sent = U256::zero();
+        let mut received = U256::zero();
+        let mut touched_out = false;
+        for log in &receipt.logs {
+            if log.topics.len() != 3 || log.topics[0] != *TRANSFER_TOPIC {
+                continue;
+            }
+            let from = Address::from(log.topics[1]);
+            let to = Address::from(log.topics[2]);
+            if from != self.wallet && to != self.wallet {
+                continue;
+            }
+            if log.address == self.token_out {
+                touched_out = true;
+            } else if log.address == self.token_in {
+                let amount = U256::from_big_endian(&log.data);
+                if from == self.wallet {
+                    sent += amount;
+                } else {
+                    received += amount;
+                }
+            }
+        }
+        (!sent.is_zero() && !received.is_zero()).then_some(WatchedSwap {
+            tx_hash: receipt.transaction_hash,
+            block_number,
+            sent,
+            received,
+            touched_out,
+            gas_used: receipt.gas_used,
+        })
+    }
+}
+
+pub fn record(
+    conn: &Connection,
+    now: DateTime<Utc>,
+    wallet: Address,
+    swap: &WatchedSwap,
+    spread_bps: f64,
+) -> anyhow::Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO watched_swaps
+            (timestamp, block_number, wallet, tx_hash, sent, received, touched_out, spread_bps)
+         VALUES (?1,?2,?3,?4,?5,?6,?7,?8)",
+        params![
+            now.to_rfc3339(),
+            swap.block_number as i64,
+            format!("{:?}", wallet),
+            format!("{:?}", swap.tx_hash),
+            swap.sent.to_string(),
+            swap.received.to_string(),
+            swap.touched_out,
+            spread_bps
+        ],
+    )?;
+    Ok(())
+}